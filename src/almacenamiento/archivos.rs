@@ -0,0 +1,293 @@
+use std::{fs::{self, File, OpenOptions}, io::{self, Read, Seek, SeekFrom, Write}, path::{Path, PathBuf}};
+
+use super::Almacenamiento;
+
+/// Almacenamiento que guarda cada clave en un archivo `<clave>.json` dentro de un directorio.
+/// El directorio se crea la primera vez que se escribe.
+///
+/// Cada escritura se hace primero en `<clave>.json.tmp` y despues se renombra sobre el archivo
+/// final, asi un corte a mitad de escritura nunca deja un JSON a medias. La version anterior
+/// queda en `<clave>.json.bak` y se usa como respaldo al leer.
+///
+/// Los registros de lineas se guardan en `<clave>.jsonl`, agregando al final del archivo
+#[derive(Debug, Clone)]
+pub struct AlmacenamientoArchivos {
+    directorio: PathBuf,
+    // Si es true se hace fsync del archivo y del directorio antes de dar por terminada la escritura
+    fsync: bool,
+}
+
+impl AlmacenamientoArchivos {
+    pub fn new(directorio: impl Into<PathBuf>) -> Self {
+        AlmacenamientoArchivos::new_con_fsync(directorio, false)
+    }
+
+    pub fn new_con_fsync(directorio: impl Into<PathBuf>, fsync: bool) -> Self {
+        AlmacenamientoArchivos {
+            directorio: directorio.into(),
+            fsync
+        }
+    }
+
+    pub fn directorio(&self) -> &Path {
+        &self.directorio
+    }
+
+    pub fn ruta(&self, clave: &str) -> PathBuf {
+        self.directorio.join(format!("{}.json", clave))
+    }
+
+    pub fn ruta_respaldo(&self, clave: &str) -> PathBuf {
+        self.directorio.join(format!("{}.json.bak", clave))
+    }
+
+    pub fn ruta_lineas(&self, clave: &str) -> PathBuf {
+        self.directorio.join(format!("{}.jsonl", clave))
+    }
+
+    fn ruta_temporal(&self, clave: &str) -> PathBuf {
+        self.directorio.join(format!("{}.json.tmp", clave))
+    }
+
+    /// Escribe en el temporal y lo renombra sobre el archivo final. Con `rotar` el archivo actual
+    /// pasa a ser el respaldo; si no, se reemplaza y el respaldo queda como estaba
+    fn escribir_rotando(&self, clave: &str, contenido: &str, rotar: bool) -> io::Result<()> {
+        fs::create_dir_all(&self.directorio)?;
+
+        let temporal = self.ruta_temporal(clave);
+        escribir_temporal(&temporal, contenido, self.fsync)?;
+
+        let ruta = self.ruta(clave);
+        if rotar && ruta.exists() {
+            fs::rename(&ruta, self.ruta_respaldo(clave))?;
+        }
+        fs::rename(&temporal, &ruta)?;
+
+        if self.fsync {
+            self.sincronizar_directorio()?;
+        }
+        Ok(())
+    }
+
+    fn sincronizar_directorio(&self) -> io::Result<()> {
+        // En windows no se puede abrir un directorio como archivo
+        if cfg!(unix) {
+            File::open(&self.directorio)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+fn escribir_temporal(temporal: &Path, contenido: &str, fsync: bool) -> io::Result<()> {
+    let mut file = File::create(temporal)?;
+    file.write_all(contenido.as_bytes())?;
+    if fsync {
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Escribe el archivo en `<ruta>.tmp` y lo renombra sobre `ruta`, asi un corte a mitad de
+/// escritura deja el archivo anterior (o ninguno) en lugar de uno a medias
+pub fn escribir_atomico(ruta: &Path, contenido: &str) -> io::Result<()> {
+    let mut temporal = ruta.as_os_str().to_owned();
+    temporal.push(".tmp");
+    let temporal = PathBuf::from(temporal);
+    escribir_temporal(&temporal, contenido, false)?;
+    fs::rename(&temporal, ruta)
+}
+
+/// Largo del archivo sin la ultima linea si no termina en un salto de linea
+fn largo_lineas_completas(file: &mut File, largo: u64) -> io::Result<u64> {
+    if largo == 0 {
+        return Ok(0);
+    }
+    let mut ultimo = [0u8; 1];
+    file.seek(SeekFrom::Start(largo - 1))?;
+    file.read_exact(&mut ultimo)?;
+    if ultimo[0] == b'\n' {
+        return Ok(largo);
+    }
+    let mut contenido = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut contenido)?;
+    Ok(contenido.iter().rposition(|&byte| byte == b'\n').map_or(0, |fin| fin as u64 + 1))
+}
+
+fn leer_si_existe(ruta: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(ruta) {
+        Ok(contenido) => Ok(Some(contenido)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl Almacenamiento for AlmacenamientoArchivos {
+    fn leer(&self, clave: &str) -> io::Result<Option<String>> {
+        leer_si_existe(&self.ruta(clave))
+    }
+
+    fn leer_respaldo(&self, clave: &str) -> io::Result<Option<String>> {
+        leer_si_existe(&self.ruta_respaldo(clave))
+    }
+
+    fn escribir(&self, clave: &str, contenido: &str) -> io::Result<()> {
+        self.escribir_rotando(clave, contenido, true)
+    }
+
+    fn escribir_conservando_respaldo(&self, clave: &str, contenido: &str) -> io::Result<()> {
+        self.escribir_rotando(clave, contenido, false)
+    }
+
+    fn agregar_linea(&self, clave: &str, linea: &str) -> io::Result<()> {
+        fs::create_dir_all(&self.directorio)?;
+
+        let mut file = OpenOptions::new().create(true).read(true).append(true).open(self.ruta_lineas(clave))?;
+        let mut largo = file.metadata()?.len();
+        // Una ultima linea sin salto de linea es una escritura que se corto a la mitad. Se recorta
+        // antes de agregar para que la nueva linea no quede pegada a ella
+        let completo = largo_lineas_completas(&mut file, largo)?;
+        if completo < largo {
+            file.set_len(completo)?;
+            largo = completo;
+        }
+        // Si la escritura falla a la mitad se recorta lo que se llego a escribir, asi la linea
+        // no queda a medias en el registro
+        if let Err(e) = file.write_all(format!("{}\n", linea).as_bytes()) {
+            let _ = file.set_len(largo);
+            return Err(e);
+        }
+        if self.fsync {
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    fn leer_lineas(&self, clave: &str) -> io::Result<Vec<String>> {
+        let contenido = leer_si_existe(&self.ruta_lineas(clave))?.unwrap_or_default();
+        // Una ultima linea sin salto de linea es una escritura que se corto a la mitad y se ignora.
+        // El archivo no se modifica al leer, se recorta al agregar la proxima linea
+        let largo_completo = contenido.rfind('\n').map_or(0, |fin| fin + 1);
+        Ok(contenido[..largo_completo].lines().map(|linea| linea.to_string()).collect())
+    }
+
+    fn vaciar_lineas(&self, clave: &str) -> io::Result<()> {
+        match fs::remove_file(self.ruta_lineas(clave)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::almacenamiento::{escribir_json, leer_json};
+
+    fn directorio_temporal(nombre: &str) -> PathBuf {
+        let directorio = std::env::temp_dir().join(format!("rust-unlp-{}-{}", nombre, std::process::id()));
+        let _ = fs::remove_dir_all(&directorio);
+        directorio
+    }
+
+    #[test]
+    fn test_almacenamiento_archivos() {
+        let directorio = directorio_temporal("archivos");
+        let almacenamiento = AlmacenamientoArchivos::new(&directorio);
+        assert_eq!(almacenamiento.leer("datos").unwrap(), None);
+
+        almacenamiento.escribir("datos", "[1,2,3]").unwrap();
+        assert_eq!(almacenamiento.ruta("datos"), directorio.join("datos.json"));
+        assert_eq!(fs::read_to_string(directorio.join("datos.json")).unwrap(), "[1,2,3]");
+        assert_eq!(almacenamiento.leer("datos").unwrap(), Some("[1,2,3]".to_string()));
+        assert!(!directorio.join("datos.json.tmp").exists());
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn test_almacenamiento_archivos_respaldo() {
+        let directorio = directorio_temporal("respaldo");
+        let almacenamiento = AlmacenamientoArchivos::new_con_fsync(&directorio, true);
+        assert_eq!(almacenamiento.leer_respaldo("datos").unwrap(), None);
+
+        almacenamiento.escribir("datos", "[1]").unwrap();
+        almacenamiento.escribir("datos", "[1,2]").unwrap();
+        almacenamiento.escribir("datos", "[1,2,3]").unwrap();
+        assert_eq!(almacenamiento.leer("datos").unwrap(), Some("[1,2,3]".to_string()));
+        assert_eq!(almacenamiento.leer_respaldo("datos").unwrap(), Some("[1,2]".to_string()));
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn test_almacenamiento_archivos_lineas() {
+        let directorio = directorio_temporal("lineas");
+        let almacenamiento = AlmacenamientoArchivos::new(&directorio);
+        assert!(almacenamiento.leer_lineas("registro").unwrap().is_empty());
+
+        almacenamiento.agregar_linea("registro", "1").unwrap();
+        almacenamiento.agregar_linea("registro", "2").unwrap();
+        assert_eq!(fs::read_to_string(directorio.join("registro.jsonl")).unwrap(), "1\n2\n");
+
+        // Simula un corte a mitad de la escritura de la tercera linea
+        OpenOptions::new().append(true).open(almacenamiento.ruta_lineas("registro")).unwrap().write_all(b"{\"a").unwrap();
+        assert_eq!(almacenamiento.leer_lineas("registro").unwrap(), vec!["1", "2"]);
+        assert_eq!(fs::read_to_string(directorio.join("registro.jsonl")).unwrap(), "1\n2\n{\"a");
+        almacenamiento.agregar_linea("registro", "3").unwrap();
+        assert_eq!(fs::read_to_string(directorio.join("registro.jsonl")).unwrap(), "1\n2\n3\n");
+        assert_eq!(almacenamiento.leer_lineas("registro").unwrap(), vec!["1", "2", "3"]);
+
+        almacenamiento.vaciar_lineas("registro").unwrap();
+        almacenamiento.vaciar_lineas("registro").unwrap();
+        assert!(almacenamiento.leer_lineas("registro").unwrap().is_empty());
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn test_almacenamiento_archivos_no_rota_corrupto() {
+        let directorio = directorio_temporal("no-rota");
+        let almacenamiento = AlmacenamientoArchivos::new(&directorio);
+        escribir_json(&almacenamiento, "datos", &vec![1u32, 2]).unwrap();
+        escribir_json(&almacenamiento, "datos", &vec![3u32]).unwrap();
+        fs::write(almacenamiento.ruta("datos"), "[3,").unwrap();
+
+        // El respaldo es lo unico valido, guardar de nuevo no lo pisa con el archivo corrupto
+        escribir_json(&almacenamiento, "datos", &vec![4u32]).unwrap();
+        assert_eq!(fs::read_to_string(almacenamiento.ruta_respaldo("datos")).unwrap(), r#"{"version":1,"datos":[1,2]}"#);
+        assert_eq!(leer_json::<Vec<u32>>(&almacenamiento, "datos").unwrap(), Some(vec![4]));
+        assert!(!directorio.join("datos.json.tmp").exists());
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn test_escribir_atomico() {
+        let directorio = directorio_temporal("atomico");
+        fs::create_dir_all(&directorio).unwrap();
+        let ruta = directorio.join("reporte.csv");
+        escribir_atomico(&ruta, "a,b\n").unwrap();
+        escribir_atomico(&ruta, "a,b\n1,2\n").unwrap();
+        assert_eq!(fs::read_to_string(&ruta).unwrap(), "a,b\n1,2\n");
+        assert!(!directorio.join("reporte.csv.tmp").exists());
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn test_almacenamiento_archivos_corte_en_rotacion() {
+        // Si el proceso se corta despues de mover el archivo al respaldo pero antes de renombrar el
+        // temporal, el archivo principal no existe y los datos se recuperan del respaldo
+        let directorio = directorio_temporal("corte");
+        let almacenamiento = AlmacenamientoArchivos::new(&directorio);
+        almacenamiento.escribir("datos", "[1,2]").unwrap();
+        fs::rename(almacenamiento.ruta("datos"), almacenamiento.ruta_respaldo("datos")).unwrap();
+        fs::write(directorio.join("datos.json.tmp"), "[1,2,").unwrap();
+
+        let leidos: Vec<u32> = leer_json(&almacenamiento, "datos").unwrap().unwrap();
+        assert_eq!(leidos, vec![1, 2]);
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+}
@@ -0,0 +1,224 @@
+use std::{fmt::{Display, Formatter}, io};
+use serde::de::DeserializeOwned;
+
+use super::{leer_json_con_respaldo, leer_json_lineas, Almacenamiento, Lectura, Versionado};
+
+/// Motivo por el que no se pudieron cargar los datos guardados de un gestor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorCarga {
+    /// No hay nada guardado con la clave
+    NoEncontrado(String),
+    /// Lo guardado no es valido y tampoco se pudo recuperar del respaldo
+    Corrupto(String),
+    /// Lo guardado no es valido pero el respaldo si, la politica no permite usarlo
+    Recuperable(String),
+    /// Hay mas elementos guardados que los que admite el gestor
+    SobreCapacidad { guardados: usize, capacidad: usize },
+    /// Fallo la lectura en si (permisos, disco, etc.)
+    Io(String),
+}
+
+/// Que hacer cuando no hay datos guardados o no se pueden leer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccionCarga {
+    Fallar,
+    EmpezarVacio,
+}
+
+/// Que hacer cuando hay mas datos guardados que la capacidad del gestor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccionCapacidad {
+    Fallar,
+    /// Descarta todo lo guardado y empieza vacio
+    Descartar,
+    /// Se queda con los primeros elementos hasta llenar la capacidad
+    Truncar,
+}
+
+/// Politica para cada caso en el que los datos guardados no se pueden usar tal cual.
+/// Por defecto, si no hay nada guardado se empieza vacio y cualquier otro problema es un error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoliticaCarga {
+    pub no_encontrado: AccionCarga,
+    pub corrupto: AccionCarga,
+    pub sobre_capacidad: AccionCapacidad,
+    /// Si lo guardado esta corrupto y hay un respaldo valido, usarlo en lugar de aplicar `corrupto`
+    pub usar_respaldo: bool,
+}
+
+impl Default for PoliticaCarga {
+    fn default() -> Self {
+        PoliticaCarga {
+            no_encontrado: AccionCarga::EmpezarVacio,
+            corrupto: AccionCarga::Fallar,
+            sobre_capacidad: AccionCapacidad::Fallar,
+            usar_respaldo: false,
+        }
+    }
+}
+
+impl PoliticaCarga {
+    /// Falla ante cualquier problema, incluso si no hay nada guardado
+    pub fn estricta() -> Self {
+        PoliticaCarga {
+            no_encontrado: AccionCarga::Fallar,
+            ..PoliticaCarga::default()
+        }
+    }
+
+    /// La que usan los constructores `new`, que no devuelven errores: usa el respaldo si lo guardado esta
+    /// corrupto y se queda con lo que entra en la capacidad. Solo falla si lo guardado esta corrupto y no
+    /// hay un respaldo valido
+    pub fn compatible() -> Self {
+        PoliticaCarga {
+            sobre_capacidad: AccionCapacidad::Truncar,
+            usar_respaldo: true,
+            ..PoliticaCarga::default()
+        }
+    }
+
+    /// Aplica la politica a una coleccion que tiene un limite de elementos
+    pub fn ajustar_capacidad<T>(&self, mut elementos: Vec<T>, capacidad: usize) -> Result<Vec<T>, ErrorCarga> {
+        if elementos.len() <= capacidad {
+            return Ok(elementos);
+        }
+
+        match self.sobre_capacidad {
+            AccionCapacidad::Fallar => Err(ErrorCarga::SobreCapacidad { guardados: elementos.len(), capacidad }),
+            AccionCapacidad::Descartar => Ok(Vec::with_capacity(capacidad)),
+            AccionCapacidad::Truncar => {
+                elementos.truncate(capacidad);
+                Ok(elementos)
+            }
+        }
+    }
+}
+
+/// Lee el JSON guardado con la clave aplicando la politica cuando no existe o esta corrupto
+pub fn cargar_json<T: DeserializeOwned + Default + Versionado>(almacenamiento: &dyn Almacenamiento, clave: &str, politica: &PoliticaCarga) -> Result<T, ErrorCarga> {
+    match leer_con_politica(almacenamiento, clave, politica)? {
+        Some(valor) => Ok(valor),
+        None => match politica.no_encontrado {
+            AccionCarga::Fallar => Err(ErrorCarga::NoEncontrado(clave.to_string())),
+            AccionCarga::EmpezarVacio => Ok(T::default()),
+        },
+    }
+}
+
+/// Como `cargar_json` pero devuelve None si no hay nada guardado, sin importar la politica.
+/// Si esta corrupto y la politica es empezar vacio tambien devuelve None
+pub fn cargar_json_opcional<T: DeserializeOwned + Versionado>(almacenamiento: &dyn Almacenamiento, clave: &str, politica: &PoliticaCarga) -> Result<Option<T>, ErrorCarga> {
+    leer_con_politica(almacenamiento, clave, politica)
+}
+
+/// Lee la clave y decide con la politica que hacer si lo guardado esta corrupto. None es que no hay
+/// nada guardado o que se empieza vacio
+fn leer_con_politica<T: DeserializeOwned + Versionado>(almacenamiento: &dyn Almacenamiento, clave: &str, politica: &PoliticaCarga) -> Result<Option<T>, ErrorCarga> {
+    match leer_json_con_respaldo(almacenamiento, clave) {
+        Ok(Some(Lectura::Principal(valor))) | Ok(Some(Lectura::Respaldo { valor, error: None })) => Ok(Some(valor)),
+        Ok(Some(Lectura::Respaldo { valor, error: Some(error) })) => {
+            if politica.usar_respaldo {
+                return Ok(Some(valor));
+            }
+            match politica.corrupto {
+                AccionCarga::Fallar => Err(ErrorCarga::Recuperable(format!("{}: {}", clave, error))),
+                AccionCarga::EmpezarVacio => Ok(None),
+            }
+        },
+        Ok(None) => Ok(None),
+        Err(e) => error_de_lectura(clave, e, politica).map(|_| None),
+    }
+}
+
+/// Lee todas las lineas del registro guardado con la clave aplicando la politica si alguna esta corrupta
+pub fn cargar_json_lineas<T: DeserializeOwned + Versionado>(almacenamiento: &dyn Almacenamiento, clave: &str, politica: &PoliticaCarga) -> Result<Vec<T>, ErrorCarga> {
+    match leer_json_lineas(almacenamiento, clave) {
+        Ok(lineas) => Ok(lineas),
+        Err(e) => error_de_lectura(clave, e, politica).map(|_| Vec::new()),
+    }
+}
+
+fn error_de_lectura(clave: &str, error: io::Error, politica: &PoliticaCarga) -> Result<(), ErrorCarga> {
+    if error.kind() != io::ErrorKind::InvalidData {
+        return Err(ErrorCarga::Io(format!("{}: {}", clave, error)));
+    }
+
+    match politica.corrupto {
+        AccionCarga::Fallar => Err(ErrorCarga::Corrupto(format!("{}: {}", clave, error))),
+        AccionCarga::EmpezarVacio => Ok(()),
+    }
+}
+
+impl Display for ErrorCarga {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorCarga::NoEncontrado(clave) => write!(f, "No hay datos guardados para {}", clave),
+            ErrorCarga::Corrupto(detalle) => write!(f, "Datos corruptos en {}", detalle),
+            ErrorCarga::Recuperable(detalle) => write!(f, "Datos corruptos con un respaldo valido en {}", detalle),
+            ErrorCarga::SobreCapacidad { guardados, capacidad } => {
+                write!(f, "Hay {} elementos guardados pero la capacidad es {}", guardados, capacidad)
+            }
+            ErrorCarga::Io(detalle) => write!(f, "Error de lectura en {}", detalle),
+        }
+    }
+}
+
+impl std::error::Error for ErrorCarga {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::almacenamiento::{escribir_json, AlmacenamientoMemoria};
+
+    #[test]
+    fn test_cargar_json_no_encontrado() {
+        let almacenamiento = AlmacenamientoMemoria::new();
+        let numeros: Vec<u32> = cargar_json(&almacenamiento, "numeros", &PoliticaCarga::default()).unwrap();
+        assert!(numeros.is_empty());
+
+        let error = cargar_json::<Vec<u32>>(&almacenamiento, "numeros", &PoliticaCarga::estricta()).unwrap_err();
+        assert_eq!(error, ErrorCarga::NoEncontrado("numeros".to_string()));
+    }
+
+    #[test]
+    fn test_cargar_json_corrupto() {
+        let almacenamiento = AlmacenamientoMemoria::new();
+        almacenamiento.escribir("numeros", "[1,").unwrap();
+
+        let error = cargar_json::<Vec<u32>>(&almacenamiento, "numeros", &PoliticaCarga::default()).unwrap_err();
+        assert!(matches!(error, ErrorCarga::Corrupto(_)));
+
+        let politica = PoliticaCarga { corrupto: AccionCarga::EmpezarVacio, ..PoliticaCarga::default() };
+        let numeros: Vec<u32> = cargar_json(&almacenamiento, "numeros", &politica).unwrap();
+        assert!(numeros.is_empty());
+    }
+
+    #[test]
+    fn test_cargar_json_recuperable() {
+        let almacenamiento = AlmacenamientoMemoria::new();
+        escribir_json(&almacenamiento, "numeros", &vec![1u32, 2]).unwrap();
+        almacenamiento.escribir("numeros", "[1,").unwrap();
+
+        let error = cargar_json::<Vec<u32>>(&almacenamiento, "numeros", &PoliticaCarga::default()).unwrap_err();
+        assert!(matches!(error, ErrorCarga::Recuperable(_)));
+
+        let politica = PoliticaCarga { usar_respaldo: true, ..PoliticaCarga::default() };
+        let numeros: Vec<u32> = cargar_json(&almacenamiento, "numeros", &politica).unwrap();
+        assert_eq!(numeros, vec![1, 2]);
+        let politica = PoliticaCarga { corrupto: AccionCarga::EmpezarVacio, ..PoliticaCarga::default() };
+        assert!(cargar_json_opcional::<Vec<u32>>(&almacenamiento, "numeros", &politica).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_ajustar_capacidad() {
+        let mut politica = PoliticaCarga::default();
+        assert_eq!(politica.ajustar_capacidad(vec![1, 2], 2), Ok(vec![1, 2]));
+        assert_eq!(politica.ajustar_capacidad(vec![1, 2, 3], 2), Err(ErrorCarga::SobreCapacidad { guardados: 3, capacidad: 2 }));
+
+        politica.sobre_capacidad = AccionCapacidad::Descartar;
+        assert_eq!(politica.ajustar_capacidad(vec![1, 2, 3], 2), Ok(vec![]));
+
+        politica.sobre_capacidad = AccionCapacidad::Truncar;
+        assert_eq!(politica.ajustar_capacidad(vec![1, 2, 3], 2), Ok(vec![1, 2]));
+    }
+}
@@ -0,0 +1,29 @@
+use std::{fs, path::PathBuf, rc::Rc};
+
+use super::{Almacenamiento, AlmacenamientoMemoria};
+
+/// Carga los archivos guardados con versiones anteriores del formato para probar que se siguen leyendo.
+/// Por cada subdirectorio de `fixtures/<directorio>/` (uno por version) devuelve el nombre de la
+/// version y un almacenamiento en memoria con cada archivo `<clave>.json` guardado con su clave
+pub fn cargar_fixtures(directorio: &str) -> Vec<(String, Rc<dyn Almacenamiento>)> {
+    let raiz = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures").join(directorio);
+    let mut versiones: Vec<PathBuf> = fs::read_dir(&raiz)
+        .unwrap_or_else(|e| panic!("No se pudo leer {}: {}", raiz.display(), e))
+        .map(|entrada| entrada.unwrap().path())
+        .filter(|ruta| ruta.is_dir())
+        .collect();
+    versiones.sort();
+
+    versiones.into_iter().map(|version| {
+        let almacenamiento = AlmacenamientoMemoria::new();
+        for entrada in fs::read_dir(&version).unwrap() {
+            let ruta = entrada.unwrap().path();
+            if ruta.extension().is_some_and(|extension| extension == "json") {
+                let clave = ruta.file_stem().unwrap().to_string_lossy();
+                almacenamiento.escribir(&clave, &fs::read_to_string(&ruta).unwrap()).unwrap();
+            }
+        }
+        let nombre = version.file_name().unwrap().to_string_lossy().to_string();
+        (nombre, Rc::new(almacenamiento) as Rc<dyn Almacenamiento>)
+    }).collect()
+}
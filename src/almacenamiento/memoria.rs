@@ -0,0 +1,73 @@
+use std::{cell::RefCell, collections::HashMap, io};
+
+use super::Almacenamiento;
+
+/// Almacenamiento que guarda todo en memoria, los datos se pierden al liberarlo.
+/// Pensado para los tests, donde cada test usa el suyo sin tocar el disco.
+/// Al igual que el de archivos, conserva la version anterior de cada clave como respaldo
+#[derive(Debug, Default)]
+pub struct AlmacenamientoMemoria {
+    datos: RefCell<HashMap<String, String>>,
+    respaldos: RefCell<HashMap<String, String>>,
+    lineas: RefCell<HashMap<String, Vec<String>>>,
+}
+
+impl AlmacenamientoMemoria {
+    pub fn new() -> Self {
+        AlmacenamientoMemoria::default()
+    }
+}
+
+impl Almacenamiento for AlmacenamientoMemoria {
+    fn leer(&self, clave: &str) -> io::Result<Option<String>> {
+        Ok(self.datos.borrow().get(clave).cloned())
+    }
+
+    fn leer_respaldo(&self, clave: &str) -> io::Result<Option<String>> {
+        Ok(self.respaldos.borrow().get(clave).cloned())
+    }
+
+    fn escribir(&self, clave: &str, contenido: &str) -> io::Result<()> {
+        let anterior = self.datos.borrow_mut().insert(clave.to_string(), contenido.to_string());
+        if let Some(anterior) = anterior {
+            self.respaldos.borrow_mut().insert(clave.to_string(), anterior);
+        }
+        Ok(())
+    }
+
+    fn escribir_conservando_respaldo(&self, clave: &str, contenido: &str) -> io::Result<()> {
+        self.datos.borrow_mut().insert(clave.to_string(), contenido.to_string());
+        Ok(())
+    }
+
+    fn agregar_linea(&self, clave: &str, linea: &str) -> io::Result<()> {
+        self.lineas.borrow_mut().entry(clave.to_string()).or_default().push(linea.to_string());
+        Ok(())
+    }
+
+    fn leer_lineas(&self, clave: &str) -> io::Result<Vec<String>> {
+        Ok(self.lineas.borrow().get(clave).cloned().unwrap_or_default())
+    }
+
+    fn vaciar_lineas(&self, clave: &str) -> io::Result<()> {
+        self.lineas.borrow_mut().remove(clave);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_almacenamiento_memoria() {
+        let almacenamiento = AlmacenamientoMemoria::new();
+        assert_eq!(almacenamiento.leer("datos").unwrap(), None);
+
+        almacenamiento.escribir("datos", "uno").unwrap();
+        almacenamiento.escribir("datos", "dos").unwrap();
+        assert_eq!(almacenamiento.leer("datos").unwrap(), Some("dos".to_string()));
+        assert_eq!(almacenamiento.leer_respaldo("datos").unwrap(), Some("uno".to_string()));
+        assert_eq!(almacenamiento.leer("otros").unwrap(), None);
+    }
+}
@@ -0,0 +1,205 @@
+#![allow(dead_code)]
+use std::io;
+use serde::{de::{DeserializeOwned, IgnoredAny}, Serialize};
+
+mod archivos;
+mod carga;
+#[cfg(test)]
+pub mod fixtures;
+mod memoria;
+pub mod sqlite;
+mod versiones;
+
+pub use archivos::{escribir_atomico, AlmacenamientoArchivos};
+pub use carga::{cargar_json, cargar_json_lineas, cargar_json_opcional, AccionCapacidad, AccionCarga, ErrorCarga, PoliticaCarga};
+pub use memoria::AlmacenamientoMemoria;
+pub use versiones::{Migracion, Migraciones, Versionado};
+
+/// Lugar donde los gestores guardan sus datos serializados, cada conjunto de datos se
+/// identifica con una clave (por ejemplo el nombre del gestor)
+pub trait Almacenamiento {
+    /// Devuelve el contenido guardado con la clave, o None si nunca se guardo nada
+    fn leer(&self, clave: &str) -> io::Result<Option<String>>;
+
+    /// Devuelve la version anterior del contenido guardado con la clave, si el almacenamiento
+    /// guarda respaldos
+    fn leer_respaldo(&self, _clave: &str) -> io::Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Reemplaza el contenido guardado con la clave
+    fn escribir(&self, clave: &str, contenido: &str) -> io::Result<()>;
+
+    /// Reemplaza el contenido guardado con la clave sin pasar el actual al respaldo. Se usa cuando el
+    /// actual esta corrupto, para no pisar el respaldo que todavia es valido
+    fn escribir_conservando_respaldo(&self, clave: &str, contenido: &str) -> io::Result<()> {
+        self.escribir(clave, contenido)
+    }
+
+    /// Agrega una linea al final del registro guardado con la clave, sin reescribir las anteriores
+    fn agregar_linea(&self, clave: &str, linea: &str) -> io::Result<()>;
+
+    /// Devuelve las lineas completas del registro guardado con la clave, en el orden en que se agregaron
+    fn leer_lineas(&self, clave: &str) -> io::Result<Vec<String>>;
+
+    /// Borra todas las lineas del registro guardado con la clave
+    fn vaciar_lineas(&self, clave: &str) -> io::Result<()>;
+}
+
+/// Contenido leido de una clave y de donde salio
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lectura<T> {
+    Principal(T),
+    /// No se pudo usar el contenido principal y se leyo el respaldo. `error` es por que el principal
+    /// no se pudo leer, o None si faltaba (un corte en medio de la rotacion)
+    Respaldo { valor: T, error: Option<String> },
+}
+
+/// Lee el JSON guardado con la clave, lo lleva a la version actual del tipo y lo deserializa.
+/// Si el contenido falta se usa el respaldo. Si esta corrupto devuelve un error de tipo `InvalidData`,
+/// aunque haya un respaldo valido; para recuperarlo usar `leer_json_con_respaldo`
+pub fn leer_json<T: DeserializeOwned + Versionado>(almacenamiento: &dyn Almacenamiento, clave: &str) -> io::Result<Option<T>> {
+    match leer_json_con_respaldo(almacenamiento, clave)? {
+        Some(Lectura::Principal(valor)) | Some(Lectura::Respaldo { valor, error: None }) => Ok(Some(valor)),
+        Some(Lectura::Respaldo { error: Some(error), .. }) => {
+            Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} (hay un respaldo valido)", error)))
+        },
+        None => Ok(None),
+    }
+}
+
+/// Como `leer_json` pero si el contenido principal esta corrupto y el respaldo es valido devuelve
+/// el respaldo, indicando que se recupero. Si tampoco hay un respaldo valido devuelve un error de
+/// tipo `InvalidData`
+pub fn leer_json_con_respaldo<T: DeserializeOwned + Versionado>(almacenamiento: &dyn Almacenamiento, clave: &str) -> io::Result<Option<Lectura<T>>> {
+    let error = match almacenamiento.leer(clave)? {
+        Some(contenido) => match decodificar(&contenido) {
+            Ok(valor) => return Ok(Some(Lectura::Principal(valor))),
+            Err(e) => Some(e),
+        },
+        None => None,
+    };
+
+    match (almacenamiento.leer_respaldo(clave)?, error) {
+        (Some(respaldo), error) => match decodificar(&respaldo) {
+            Ok(valor) => Ok(Some(Lectura::Respaldo { valor, error })),
+            Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, error.unwrap_or(e))),
+        },
+        (None, Some(error)) => Err(io::Error::new(io::ErrorKind::InvalidData, error)),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Serializa el valor como JSON dentro de un sobre con su version y lo guarda con la clave.
+/// Si lo guardado no es JSON valido (por ejemplo quedo a medias) no pasa al respaldo, asi no se
+/// pierde el ultimo valido. Solo se revisa la sintaxis, sin migrar ni deserializar los datos
+pub fn escribir_json<T: Serialize + Versionado>(almacenamiento: &dyn Almacenamiento, clave: &str, valor: &T) -> io::Result<()> {
+    let serializado = versiones::ensobrar(valor)?;
+    match almacenamiento.leer(clave)? {
+        Some(actual) if serde_json::from_str::<IgnoredAny>(&actual).is_err() => almacenamiento.escribir_conservando_respaldo(clave, &serializado),
+        _ => almacenamiento.escribir(clave, &serializado),
+    }
+}
+
+/// Agrega el valor dentro de un sobre con su version como una linea de JSON (formato JSON Lines)
+/// al registro de la clave
+pub fn agregar_json_linea<T: Serialize + Versionado>(almacenamiento: &dyn Almacenamiento, clave: &str, valor: &T) -> io::Result<()> {
+    let serializado = versiones::ensobrar(valor)?;
+    almacenamiento.agregar_linea(clave, &serializado)
+}
+
+/// Lee cada linea del registro de la clave, la lleva a la version actual del tipo y la deserializa.
+/// Cada linea se migra por separado, asi un registro puede tener lineas de distintas versiones.
+/// Si alguna linea no es JSON valido devuelve un error de tipo `InvalidData`
+pub fn leer_json_lineas<T: DeserializeOwned + Versionado>(almacenamiento: &dyn Almacenamiento, clave: &str) -> io::Result<Vec<T>> {
+    almacenamiento.leer_lineas(clave)?.iter().enumerate()
+        .map(|(i, linea)| decodificar(linea)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("linea {}: {}", i + 1, e))))
+        .collect()
+}
+
+fn decodificar<T: DeserializeOwned + Versionado>(contenido: &str) -> Result<T, String> {
+    let valor = serde_json::from_str(contenido).map_err(|e| e.to_string())?;
+    let (version, datos) = versiones::abrir_sobre(valor)?;
+    let datos = T::migraciones().migrar(datos, version)?;
+    serde_json::from_value(datos).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Versionado for Vec<u32> {}
+    impl Versionado for u32 {}
+
+    #[test]
+    fn test_leer_escribir_json() {
+        let almacenamiento = AlmacenamientoMemoria::new();
+        assert!(leer_json::<Vec<u32>>(&almacenamiento, "numeros").unwrap().is_none());
+
+        escribir_json(&almacenamiento, "numeros", &vec![1, 2, 3]).unwrap();
+        let leidos: Vec<u32> = leer_json(&almacenamiento, "numeros").unwrap().unwrap();
+        assert_eq!(leidos, vec![1, 2, 3]);
+        assert_eq!(almacenamiento.leer("numeros").unwrap().unwrap(), r#"{"version":1,"datos":[1,2,3]}"#);
+    }
+
+    #[test]
+    fn test_leer_json_invalido() {
+        let almacenamiento = AlmacenamientoMemoria::new();
+        almacenamiento.escribir("numeros", "[1, 2,").unwrap();
+
+        let error = leer_json::<Vec<u32>>(&almacenamiento, "numeros").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_json_lineas() {
+        let almacenamiento = AlmacenamientoMemoria::new();
+        assert!(leer_json_lineas::<u32>(&almacenamiento, "registro").unwrap().is_empty());
+
+        agregar_json_linea(&almacenamiento, "registro", &1).unwrap();
+        agregar_json_linea(&almacenamiento, "registro", &2).unwrap();
+        assert_eq!(leer_json_lineas::<u32>(&almacenamiento, "registro").unwrap(), vec![1, 2]);
+        assert_eq!(almacenamiento.leer_lineas("registro").unwrap()[0], r#"{"version":1,"datos":1}"#);
+
+        // Las lineas guardadas sin sobre se leen como version 1
+        almacenamiento.agregar_linea("registro", "3").unwrap();
+        assert_eq!(leer_json_lineas::<u32>(&almacenamiento, "registro").unwrap(), vec![1, 2, 3]);
+
+        almacenamiento.agregar_linea("registro", "{").unwrap();
+        let error = leer_json_lineas::<u32>(&almacenamiento, "registro").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        almacenamiento.vaciar_lineas("registro").unwrap();
+        assert!(leer_json_lineas::<u32>(&almacenamiento, "registro").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_leer_json_usa_respaldo() {
+        let almacenamiento = AlmacenamientoMemoria::new();
+        escribir_json(&almacenamiento, "numeros", &vec![1, 2]).unwrap();
+        almacenamiento.escribir("numeros", "[1, 2,").unwrap();
+
+        // Se avisa que el principal esta corrupto aunque el respaldo se pueda usar
+        let error = leer_json::<Vec<u32>>(&almacenamiento, "numeros").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        match leer_json_con_respaldo::<Vec<u32>>(&almacenamiento, "numeros").unwrap() {
+            Some(Lectura::Respaldo { valor, error }) => {
+                assert_eq!(valor, vec![1, 2]);
+                assert!(error.is_some());
+            },
+            _ => panic!("se esperaba el respaldo"),
+        }
+
+        // Guardar sobre el principal corrupto no pisa el respaldo
+        escribir_json(&almacenamiento, "numeros", &vec![3]).unwrap();
+        assert_eq!(leer_json::<Vec<u32>>(&almacenamiento, "numeros").unwrap(), Some(vec![3]));
+        assert_eq!(almacenamiento.leer_respaldo("numeros").unwrap().unwrap(), r#"{"version":1,"datos":[1,2]}"#);
+
+        // Con el respaldo tambien roto no hay nada que recuperar
+        almacenamiento.escribir("numeros", "{").unwrap();
+        almacenamiento.escribir("numeros", "{").unwrap();
+        let error = leer_json_con_respaldo::<Vec<u32>>(&almacenamiento, "numeros").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
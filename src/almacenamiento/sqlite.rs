@@ -0,0 +1,75 @@
+use std::io;
+
+use rusqlite::{types::Type, Connection};
+
+use crate::fecha::Fecha;
+
+/// Ruta especial que abre una base de datos que vive solo en memoria
+pub const EN_MEMORIA: &str = ":memory:";
+
+/// Abre la base de datos SQLite en la ruta indicada, creando el archivo si no existe.
+/// Con `EN_MEMORIA` la base se pierde al cerrar la conexion
+pub fn abrir_sqlite(ruta: &str) -> io::Result<Connection> {
+    let conexion = if ruta == EN_MEMORIA {
+        Connection::open_in_memory()
+    } else {
+        Connection::open(ruta)
+    }.map_err(error_sqlite)?;
+
+    conexion.pragma_update(None, "foreign_keys", true).map_err(error_sqlite)?;
+    Ok(conexion)
+}
+
+/// Convierte un error de SQLite al tipo de error que usan los gestores
+pub fn error_sqlite(error: rusqlite::Error) -> io::Error {
+    io::Error::other(error.to_string())
+}
+
+/// Escribe la fecha como `aaaa-mm-dd`, asi el orden de los textos es el mismo que el de las fechas
+pub fn fecha_a_sql(fecha: &Fecha) -> String {
+    fecha.formatear("%Y-%m-%d")
+}
+
+/// Lee una fecha guardada con `fecha_a_sql`
+pub fn fecha_desde_sql(texto: &str) -> rusqlite::Result<Fecha> {
+    Fecha::parse_con_formato(texto, "%Y-%m-%d")
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(e)))
+}
+
+/// Error para una columna de texto cuyo valor no corresponde a ninguna variante conocida
+pub fn valor_invalido(columna: usize, texto: &str) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(columna, Type::Text, format!("valor invalido: {}", texto).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_abrir_sqlite_en_memoria() {
+        let conexion = abrir_sqlite(EN_MEMORIA).unwrap();
+        let claves_foraneas: bool = conexion.pragma_query_value(None, "foreign_keys", |fila| fila.get(0)).unwrap();
+        assert!(claves_foraneas);
+    }
+
+    #[test]
+    fn test_abrir_sqlite_archivo() {
+        let ruta = std::env::temp_dir().join(format!("test_abrir_sqlite_archivo-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&ruta);
+        let ruta = ruta.to_str().unwrap();
+
+        abrir_sqlite(ruta).unwrap().execute_batch("CREATE TABLE t (x INTEGER); INSERT INTO t VALUES (1);").unwrap();
+        let cantidad: u32 = abrir_sqlite(ruta).unwrap().query_row("SELECT COUNT(*) FROM t", [], |fila| fila.get(0)).unwrap();
+        assert_eq!(cantidad, 1);
+        std::fs::remove_file(ruta).unwrap();
+    }
+
+    #[test]
+    fn test_fecha_sql() {
+        let fecha = Fecha::new(5, 3, 2024);
+        assert_eq!(fecha_a_sql(&fecha), "2024-03-05");
+        assert_eq!(fecha_desde_sql("2024-03-05").unwrap(), fecha);
+        assert!(fecha_a_sql(&Fecha::new(9, 12, 2023)) < fecha_a_sql(&fecha));
+        assert!(fecha_desde_sql("no es fecha").is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Transforma los datos guardados en una version al formato de la version siguiente
+pub type Migracion = fn(Value) -> Result<Value, String>;
+
+/// Registro de migraciones de un formato de datos. La version 1 es el formato original y
+/// cada migracion agregada lleva de la ultima version a la siguiente
+#[derive(Debug, Clone, Default)]
+pub struct Migraciones {
+    pasos: Vec<Migracion>,
+}
+
+/// Tipo que se guarda dentro de un sobre con version, conoce las migraciones de sus formatos anteriores.
+/// Si el formato nunca cambio alcanza con `impl Versionado for Tipo {}`
+pub trait Versionado {
+    fn migraciones() -> Migraciones {
+        Migraciones::new()
+    }
+}
+
+/// Forma en la que se guardan los datos: `{"version": n, "datos": ...}`
+#[derive(Serialize)]
+struct Sobre<'a, T> {
+    version: u32,
+    datos: &'a T,
+}
+
+impl Migraciones {
+    pub fn new() -> Self {
+        Migraciones::default()
+    }
+
+    /// Registra la migracion de la version actual a la siguiente
+    pub fn agregar(mut self, migracion: Migracion) -> Self {
+        self.pasos.push(migracion);
+        self
+    }
+
+    pub fn version_actual(&self) -> u32 {
+        self.pasos.len() as u32 + 1
+    }
+
+    /// Aplica en orden las migraciones necesarias para llevar los datos a la version actual
+    pub fn migrar(&self, mut datos: Value, desde: u32) -> Result<Value, String> {
+        if desde == 0 || desde > self.version_actual() {
+            return Err(format!("version {} no soportada, la actual es {}", desde, self.version_actual()));
+        }
+
+        for (i, migracion) in self.pasos.iter().enumerate().skip(desde as usize - 1) {
+            datos = migracion(datos).map_err(|e| format!("migracion de la version {} a la {}: {}", i + 1, i + 2, e))?;
+        }
+        Ok(datos)
+    }
+}
+
+/// Arma el sobre con la version actual del tipo
+pub(super) fn ensobrar<T: Serialize + Versionado>(datos: &T) -> serde_json::Result<String> {
+    serde_json::to_string(&Sobre { version: T::migraciones().version_actual(), datos })
+}
+
+/// Separa la version de los datos. Lo guardado antes de que existiera el sobre se toma como version 1
+pub(super) fn abrir_sobre(valor: Value) -> Result<(u32, Value), String> {
+    match valor {
+        Value::Object(mut campos) if es_sobre(&campos) => {
+            let version = campos["version"].as_u64()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or_else(|| format!("version invalida: {}", campos["version"]))?;
+            Ok((version, campos.remove("datos").unwrap_or(Value::Null)))
+        }
+        valor => Ok((1, valor)),
+    }
+}
+
+fn es_sobre(campos: &Map<String, Value>) -> bool {
+    campos.len() == 2 && campos.contains_key("version") && campos.contains_key("datos")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn renombrar_nombre(mut datos: Value) -> Result<Value, String> {
+        for elemento in datos.as_array_mut().ok_or("se esperaba una lista")? {
+            let nombre = elemento.as_object_mut().and_then(|o| o.remove("nombre")).ok_or("falta el nombre")?;
+            elemento["titulo"] = nombre;
+        }
+        Ok(datos)
+    }
+
+    fn agregar_anio(mut datos: Value) -> Result<Value, String> {
+        for elemento in datos.as_array_mut().ok_or("se esperaba una lista")? {
+            elemento["anio"] = json!(2024);
+        }
+        Ok(datos)
+    }
+
+    #[test]
+    fn test_migrar() {
+        let migraciones = Migraciones::new().agregar(renombrar_nombre).agregar(agregar_anio);
+        assert_eq!(migraciones.version_actual(), 3);
+
+        let v1 = json!([{"nombre": "a"}]);
+        assert_eq!(migraciones.migrar(v1, 1).unwrap(), json!([{"titulo": "a", "anio": 2024}]));
+
+        let v2 = json!([{"titulo": "b"}]);
+        assert_eq!(migraciones.migrar(v2, 2).unwrap(), json!([{"titulo": "b", "anio": 2024}]));
+
+        let v3 = json!([{"titulo": "c", "anio": 2020}]);
+        assert_eq!(migraciones.migrar(v3.clone(), 3).unwrap(), v3);
+    }
+
+    #[test]
+    fn test_migrar_errores() {
+        let migraciones = Migraciones::new().agregar(renombrar_nombre);
+        assert!(migraciones.migrar(json!([]), 0).is_err());
+        assert!(migraciones.migrar(json!([]), 3).is_err());
+
+        let error = migraciones.migrar(json!([{"titulo": "a"}]), 1).unwrap_err();
+        assert!(error.contains("de la version 1 a la 2"));
+    }
+
+    #[test]
+    fn test_abrir_sobre() {
+        assert_eq!(abrir_sobre(json!({"version": 2, "datos": [1]})).unwrap(), (2, json!([1])));
+        // Datos guardados sin sobre
+        assert_eq!(abrir_sobre(json!([1, 2])).unwrap(), (1, json!([1, 2])));
+        assert_eq!(abrir_sobre(json!({"123": {"BTC": 1.0}})).unwrap(), (1, json!({"123": {"BTC": 1.0}})));
+        assert!(abrir_sobre(json!({"version": "dos", "datos": [1]})).is_err());
+    }
+}
@@ -0,0 +1,446 @@
+#![allow(dead_code)]
+use std::{cmp::Ordering, fmt::{self, Display, Formatter}};
+
+use serde::{Deserialize, Serialize};
+
+/// Cantidad de decimales de las monedas fiat
+const DECIMALES_FIAT: u32 = 2;
+/// Cantidad de decimales de las criptomonedas (la unidad menor del bitcoin es el satoshi)
+const DECIMALES_CRIPTO: u32 = 8;
+
+/// Moneda de un monto: su codigo y la cantidad de decimales de su unidad menor
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Moneda {
+    codigo: String,
+    decimales: u32,
+}
+
+/// Monto de dinero guardado como un entero de unidades menores (centavos, satoshis) de su moneda,
+/// asi las cuentas son exactas. Las operaciones que pueden desbordar o mezclar monedas devuelven
+/// un Result y las que tienen que redondear reciben el `Redondeo` a usar
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Dinero {
+    unidades: i64,
+    #[serde(flatten)]
+    moneda: Moneda,
+}
+
+/// Porcentaje con dos decimales, guardado en centesimas de punto (10,5% son 1050)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Porcentaje {
+    centesimas: i64,
+}
+
+/// Como redondear cuando el resultado no entra justo en la unidad menor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Redondeo {
+    /// Descarta los decimales que sobran
+    Truncar,
+    /// Hacia el menor valor
+    Piso,
+    /// Hacia el mayor valor
+    Techo,
+    /// Al mas cercano, la mitad se aleja del cero
+    MitadArriba,
+    /// Al mas cercano, la mitad va al par (redondeo bancario)
+    MitadPar,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorDinero {
+    MonedasDistintas(String, String),
+    Desborde,
+    DivisionPorCero,
+    Formato(String),
+}
+
+impl Display for ErrorDinero {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorDinero::MonedasDistintas(una, otra) => write!(f, "no se pueden operar montos en {} y en {}", una, otra),
+            ErrorDinero::Desborde => write!(f, "el resultado no entra en un monto"),
+            ErrorDinero::DivisionPorCero => write!(f, "division por cero"),
+            ErrorDinero::Formato(texto) => write!(f, "monto invalido: {}", texto),
+        }
+    }
+}
+
+impl std::error::Error for ErrorDinero {}
+
+impl Moneda {
+    pub fn new(codigo: &str, decimales: u32) -> Self {
+        Moneda { codigo: codigo.to_string(), decimales }
+    }
+
+    /// Peso argentino
+    pub fn ars() -> Self {
+        Moneda::new("ARS", DECIMALES_FIAT)
+    }
+
+    /// Dolar estadounidense
+    pub fn usd() -> Self {
+        Moneda::new("USD", DECIMALES_FIAT)
+    }
+
+    pub fn cripto(prefijo: &str) -> Self {
+        Moneda::new(prefijo, DECIMALES_CRIPTO)
+    }
+
+    pub fn codigo(&self) -> &str {
+        &self.codigo
+    }
+
+    pub fn decimales(&self) -> u32 {
+        self.decimales
+    }
+
+    /// Cantidad de unidades menores en una unidad de la moneda
+    fn escala(&self) -> i128 {
+        10i128.pow(self.decimales)
+    }
+}
+
+impl Dinero {
+    pub fn new(unidades: i64, moneda: Moneda) -> Self {
+        Dinero { unidades, moneda }
+    }
+
+    pub fn cero(moneda: Moneda) -> Self {
+        Dinero::new(0, moneda)
+    }
+
+    /// Lee un monto escrito en unidades de la moneda, como `"12.5"` o `"-3"`. Es un error si tiene
+    /// mas decimales de los que admite la moneda
+    pub fn desde_decimal(texto: &str, moneda: Moneda) -> Result<Self, ErrorDinero> {
+        let error = || ErrorDinero::Formato(texto.to_string());
+        let (negativo, numero) = match texto.strip_prefix('-') {
+            Some(resto) => (true, resto),
+            None => (false, texto),
+        };
+        let (entero, decimales) = numero.split_once('.').unwrap_or((numero, ""));
+        let son_digitos = |parte: &str| parte.chars().all(|c| c.is_ascii_digit());
+        if entero.is_empty() || !son_digitos(entero) || !son_digitos(decimales) || decimales.len() > moneda.decimales as usize {
+            return Err(error());
+        }
+
+        let decimales = format!("{:0<ancho$}", decimales, ancho = moneda.decimales as usize);
+        let unidades: i128 = format!("{}{}", entero, decimales).parse().map_err(|_| error())?;
+        let unidades = i64::try_from(if negativo { -unidades } else { unidades }).map_err(|_| ErrorDinero::Desborde)?;
+        Ok(Dinero::new(unidades, moneda))
+    }
+
+    /// Convierte un monto guardado como numero de punto flotante, pensado para leer los datos
+    /// guardados antes de que existiera este tipo
+    pub fn desde_f64(valor: f64, moneda: Moneda, redondeo: Redondeo) -> Result<Self, ErrorDinero> {
+        let escalado = valor * moneda.escala() as f64;
+        let redondeado = match redondeo {
+            Redondeo::Truncar => escalado.trunc(),
+            Redondeo::Piso => escalado.floor(),
+            Redondeo::Techo => escalado.ceil(),
+            Redondeo::MitadArriba => escalado.round(),
+            Redondeo::MitadPar => escalado.round_ties_even(),
+        };
+
+        if !redondeado.is_finite() {
+            Err(ErrorDinero::Formato(valor.to_string()))
+        } else if redondeado < i64::MIN as f64 || redondeado >= i64::MAX as f64 {
+            Err(ErrorDinero::Desborde)
+        } else {
+            Ok(Dinero::new(redondeado as i64, moneda))
+        }
+    }
+
+    /// El monto con todos los decimales de la moneda y sin el codigo, se puede volver a leer con `desde_decimal`
+    pub fn a_decimal(&self) -> String {
+        let signo = if self.unidades < 0 { "-" } else { "" };
+        let unidades = (self.unidades as i128).abs();
+        let escala = self.moneda.escala();
+
+        if self.moneda.decimales == 0 {
+            format!("{}{}", signo, unidades)
+        } else {
+            format!("{}{}.{:0ancho$}", signo, unidades / escala, unidades % escala, ancho = self.moneda.decimales as usize)
+        }
+    }
+
+    pub fn unidades(&self) -> i64 {
+        self.unidades
+    }
+
+    pub fn moneda(&self) -> &Moneda {
+        &self.moneda
+    }
+
+    pub fn es_cero(&self) -> bool {
+        self.unidades == 0
+    }
+
+    pub fn es_negativo(&self) -> bool {
+        self.unidades < 0
+    }
+
+    pub fn sumar(&self, otro: &Dinero) -> Result<Dinero, ErrorDinero> {
+        self.misma_moneda(otro)?;
+        let unidades = self.unidades.checked_add(otro.unidades).ok_or(ErrorDinero::Desborde)?;
+        Ok(Dinero::new(unidades, self.moneda.clone()))
+    }
+
+    pub fn restar(&self, otro: &Dinero) -> Result<Dinero, ErrorDinero> {
+        self.misma_moneda(otro)?;
+        let unidades = self.unidades.checked_sub(otro.unidades).ok_or(ErrorDinero::Desborde)?;
+        Ok(Dinero::new(unidades, self.moneda.clone()))
+    }
+
+    pub fn multiplicar(&self, factor: i64) -> Result<Dinero, ErrorDinero> {
+        let unidades = self.unidades.checked_mul(factor).ok_or(ErrorDinero::Desborde)?;
+        Ok(Dinero::new(unidades, self.moneda.clone()))
+    }
+
+    /// Devuelve el porcentaje del monto, por ejemplo el 10% de 2,55 es 0,255 y se redondea
+    pub fn multiplicar_porcentaje(&self, porcentaje: Porcentaje, redondeo: Redondeo) -> Result<Dinero, ErrorDinero> {
+        let unidades = dividir(self.unidades as i128 * porcentaje.centesimas as i128, Porcentaje::ESCALA, redondeo)?;
+        Ok(Dinero::new(unidades, self.moneda.clone()))
+    }
+
+    /// Resta al monto el porcentaje indicado, redondeando el descuento
+    pub fn descontar(&self, porcentaje: Porcentaje, redondeo: Redondeo) -> Result<Dinero, ErrorDinero> {
+        self.restar(&self.multiplicar_porcentaje(porcentaje, redondeo)?)
+    }
+
+    /// Cuanto de la moneda `destino` se obtiene con este monto, si una unidad de `destino` cuesta `precio`
+    pub fn convertir(&self, precio: &Dinero, destino: &Moneda, redondeo: Redondeo) -> Result<Dinero, ErrorDinero> {
+        self.misma_moneda(precio)?;
+        let unidades = dividir(self.unidades as i128 * destino.escala(), precio.unidades as i128, redondeo)?;
+        Ok(Dinero::new(unidades, destino.clone()))
+    }
+
+    /// Cuanto vale este monto en la moneda de `precio`, si una unidad de este monto cuesta `precio`
+    pub fn valuar(&self, precio: &Dinero, redondeo: Redondeo) -> Result<Dinero, ErrorDinero> {
+        let unidades = dividir(self.unidades as i128 * precio.unidades as i128, self.moneda.escala(), redondeo)?;
+        Ok(Dinero::new(unidades, precio.moneda.clone()))
+    }
+
+    /// Parte de este monto que corresponde a `parte` de `total`, por ejemplo el costo de vender
+    /// 0,3 BTC de un lote de 1 BTC que costo este monto
+    pub fn prorratear(&self, parte: &Dinero, total: &Dinero, redondeo: Redondeo) -> Result<Dinero, ErrorDinero> {
+        parte.misma_moneda(total)?;
+        let unidades = dividir(self.unidades as i128 * parte.unidades as i128, total.unidades as i128, redondeo)?;
+        Ok(Dinero::new(unidades, self.moneda.clone()))
+    }
+
+    fn misma_moneda(&self, otro: &Dinero) -> Result<(), ErrorDinero> {
+        if self.moneda == otro.moneda {
+            Ok(())
+        } else {
+            Err(ErrorDinero::MonedasDistintas(self.moneda.codigo.clone(), otro.moneda.codigo.clone()))
+        }
+    }
+}
+
+impl Porcentaje {
+    const ESCALA: i128 = 10_000;
+
+    /// Porcentaje entero, `Porcentaje::new(10)` es el 10%
+    pub const fn new(porcentaje: i64) -> Self {
+        Porcentaje { centesimas: porcentaje * 100 }
+    }
+
+    pub const fn desde_centesimas(centesimas: i64) -> Self {
+        Porcentaje { centesimas }
+    }
+
+    pub fn centesimas(&self) -> i64 {
+        self.centesimas
+    }
+}
+
+/// Divide enteros redondeando el cociente segun el modo pedido
+fn dividir(numerador: i128, denominador: i128, redondeo: Redondeo) -> Result<i64, ErrorDinero> {
+    if denominador == 0 {
+        return Err(ErrorDinero::DivisionPorCero);
+    }
+
+    let cociente = numerador / denominador;
+    let resto = numerador % denominador;
+    let negativo = (numerador < 0) != (denominador < 0);
+    let alejar = if negativo { -1 } else { 1 };
+
+    let resultado = if resto == 0 {
+        cociente
+    } else {
+        match redondeo {
+            Redondeo::Truncar => cociente,
+            Redondeo::Piso => if negativo { cociente - 1 } else { cociente },
+            Redondeo::Techo => if negativo { cociente } else { cociente + 1 },
+            Redondeo::MitadArriba | Redondeo::MitadPar => match (2 * resto.abs()).cmp(&denominador.abs()) {
+                Ordering::Less => cociente,
+                Ordering::Greater => cociente + alejar,
+                Ordering::Equal if redondeo == Redondeo::MitadPar && cociente % 2 == 0 => cociente,
+                Ordering::Equal => cociente + alejar,
+            },
+        }
+    };
+    i64::try_from(resultado).map_err(|_| ErrorDinero::Desborde)
+}
+
+/// Solo se pueden comparar montos de la misma moneda
+impl PartialOrd for Dinero {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if self.moneda == other.moneda {
+            Some(self.unidades.cmp(&other.unidades))
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for Moneda {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.codigo)
+    }
+}
+
+impl Display for Dinero {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.a_decimal(), self.moneda)
+    }
+}
+
+impl Display for Porcentaje {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let signo = if self.centesimas < 0 { "-" } else { "" };
+        write!(f, "{}{}.{:02}%", signo, self.centesimas.abs() / 100, self.centesimas.abs() % 100)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ars(unidades: i64) -> Dinero {
+        Dinero::new(unidades, Moneda::ars())
+    }
+
+    #[test]
+    fn test_desde_decimal() {
+        assert_eq!(Dinero::desde_decimal("12.5", Moneda::ars()), Ok(ars(1250)));
+        assert_eq!(Dinero::desde_decimal("-0.05", Moneda::ars()), Ok(ars(-5)));
+        assert_eq!(Dinero::desde_decimal("7", Moneda::ars()), Ok(ars(700)));
+        assert_eq!(Dinero::desde_decimal("0.00000001", Moneda::cripto("BTC")), Ok(Dinero::new(1, Moneda::cripto("BTC"))));
+        assert_eq!(Dinero::desde_decimal("1.234", Moneda::ars()), Err(ErrorDinero::Formato("1.234".to_string())));
+        assert!(Dinero::desde_decimal("", Moneda::ars()).is_err());
+        assert!(Dinero::desde_decimal(".5", Moneda::ars()).is_err());
+        assert!(Dinero::desde_decimal("1,5", Moneda::ars()).is_err());
+        assert_eq!(Dinero::desde_decimal("99999999999999999999", Moneda::ars()), Err(ErrorDinero::Desborde));
+    }
+
+    #[test]
+    fn test_desde_f64() {
+        assert_eq!(Dinero::desde_f64(0.1 + 0.2, Moneda::ars(), Redondeo::MitadArriba), Ok(ars(30)));
+        assert_eq!(Dinero::desde_f64(0.01, Moneda::cripto("BTC"), Redondeo::MitadArriba), Ok(Dinero::new(1_000_000, Moneda::cripto("BTC"))));
+        assert_eq!(Dinero::desde_f64(1.239, Moneda::ars(), Redondeo::Truncar), Ok(ars(123)));
+        assert!(Dinero::desde_f64(f64::NAN, Moneda::ars(), Redondeo::Truncar).is_err());
+        assert_eq!(Dinero::desde_f64(1e30, Moneda::ars(), Redondeo::Truncar), Err(ErrorDinero::Desborde));
+    }
+
+    #[test]
+    fn test_sumar_restar() {
+        assert_eq!(ars(1050).sumar(&ars(-50)), Ok(ars(1000)));
+        assert_eq!(ars(1050).restar(&ars(2000)), Ok(ars(-950)));
+        assert_eq!(ars(i64::MAX).sumar(&ars(1)), Err(ErrorDinero::Desborde));
+        assert_eq!(ars(i64::MIN).restar(&ars(1)), Err(ErrorDinero::Desborde));
+        assert_eq!(ars(1).sumar(&Dinero::new(1, Moneda::usd())), Err(ErrorDinero::MonedasDistintas("ARS".to_string(), "USD".to_string())));
+    }
+
+    #[test]
+    fn test_multiplicar() {
+        assert_eq!(ars(1000).multiplicar(3), Ok(ars(3000)));
+        assert_eq!(ars(i64::MAX).multiplicar(2), Err(ErrorDinero::Desborde));
+    }
+
+    #[test]
+    fn test_multiplicar_porcentaje() {
+        // El 10% de 2,55 es 0,255
+        assert_eq!(ars(255).multiplicar_porcentaje(Porcentaje::new(10), Redondeo::Truncar), Ok(ars(25)));
+        assert_eq!(ars(255).multiplicar_porcentaje(Porcentaje::new(10), Redondeo::Techo), Ok(ars(26)));
+        assert_eq!(ars(255).multiplicar_porcentaje(Porcentaje::new(10), Redondeo::MitadArriba), Ok(ars(26)));
+        assert_eq!(ars(255).multiplicar_porcentaje(Porcentaje::new(10), Redondeo::MitadPar), Ok(ars(26)));
+        assert_eq!(ars(-255).multiplicar_porcentaje(Porcentaje::new(10), Redondeo::Piso), Ok(ars(-26)));
+        assert_eq!(ars(10000).multiplicar_porcentaje(Porcentaje::desde_centesimas(1050), Redondeo::Truncar), Ok(ars(1050)));
+        assert_eq!(ars(10000).descontar(Porcentaje::new(15), Redondeo::MitadArriba), Ok(ars(8500)));
+        assert_eq!(ars(i64::MAX).multiplicar_porcentaje(Porcentaje::new(200), Redondeo::Truncar), Err(ErrorDinero::Desborde));
+    }
+
+    #[test]
+    fn test_redondeos() {
+        assert_eq!(dividir(25, 10, Redondeo::Truncar), Ok(2));
+        assert_eq!(dividir(25, 10, Redondeo::Piso), Ok(2));
+        assert_eq!(dividir(25, 10, Redondeo::Techo), Ok(3));
+        assert_eq!(dividir(25, 10, Redondeo::MitadArriba), Ok(3));
+        assert_eq!(dividir(25, 10, Redondeo::MitadPar), Ok(2));
+        assert_eq!(dividir(35, 10, Redondeo::MitadPar), Ok(4));
+        assert_eq!(dividir(-25, 10, Redondeo::Truncar), Ok(-2));
+        assert_eq!(dividir(-25, 10, Redondeo::Piso), Ok(-3));
+        assert_eq!(dividir(-25, 10, Redondeo::Techo), Ok(-2));
+        assert_eq!(dividir(-25, 10, Redondeo::MitadArriba), Ok(-3));
+        assert_eq!(dividir(-25, 10, Redondeo::MitadPar), Ok(-2));
+        assert_eq!(dividir(24, -10, Redondeo::MitadArriba), Ok(-2));
+        assert_eq!(dividir(1, 0, Redondeo::Truncar), Err(ErrorDinero::DivisionPorCero));
+    }
+
+    #[test]
+    fn test_convertir_y_valuar() {
+        let btc = Moneda::cripto("BTC");
+        let precio = ars(5_000_000); // 50000 ARS por BTC
+
+        let comprado = ars(50000).convertir(&precio, &btc, Redondeo::Truncar).unwrap();
+        assert_eq!(comprado, Dinero::new(1_000_000, btc.clone())); // 0,01 BTC
+        assert_eq!(comprado.valuar(&precio, Redondeo::Truncar), Ok(ars(50000)));
+
+        // 1 ARS a 3 ARS por unidad no da justo
+        let tercio = ars(100).convertir(&ars(300), &btc, Redondeo::Truncar).unwrap();
+        assert_eq!(tercio.unidades(), 33_333_333);
+        assert_eq!(ars(100).convertir(&ars(0), &btc, Redondeo::Truncar), Err(ErrorDinero::DivisionPorCero));
+        assert!(ars(100).convertir(&Dinero::new(1, Moneda::usd()), &btc, Redondeo::Truncar).is_err());
+    }
+
+    #[test]
+    fn test_prorratear() {
+        let btc = |unidades| Dinero::new(unidades, Moneda::cripto("BTC"));
+        // 0,3 de un lote de 1 BTC que costo 100,00
+        assert_eq!(ars(10000).prorratear(&btc(30_000_000), &btc(100_000_000), Redondeo::MitadPar), Ok(ars(3000)));
+        assert_eq!(ars(100).prorratear(&btc(1), &btc(3), Redondeo::MitadPar), Ok(ars(33)));
+        assert_eq!(ars(100).prorratear(&btc(2), &btc(3), Redondeo::MitadPar), Ok(ars(67)));
+        assert_eq!(ars(100).prorratear(&btc(1), &btc(0), Redondeo::MitadPar), Err(ErrorDinero::DivisionPorCero));
+        assert!(ars(100).prorratear(&btc(1), &ars(3), Redondeo::MitadPar).is_err());
+    }
+
+    #[test]
+    fn test_comparar() {
+        assert!(ars(100) > ars(99));
+        assert!(ars(-1) < ars(0));
+        assert_eq!(ars(1).partial_cmp(&Dinero::new(1, Moneda::usd())), None);
+        // Entre monedas distintas ninguna comparacion es verdadera
+        assert!(!ars(1).ge(&Dinero::new(1, Moneda::usd())));
+        assert!(!ars(1).lt(&Dinero::new(1, Moneda::usd())));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(ars(123456).to_string(), "1234.56 ARS");
+        assert_eq!(ars(-5).to_string(), "-0.05 ARS");
+        assert_eq!(Dinero::new(1, Moneda::cripto("BTC")).to_string(), "0.00000001 BTC");
+        assert_eq!(Dinero::new(7, Moneda::new("JPY", 0)).to_string(), "7 JPY");
+        assert_eq!(ars(i64::MIN).to_string(), "-92233720368547758.08 ARS");
+        assert_eq!(Porcentaje::desde_centesimas(1050).to_string(), "10.50%");
+        assert_eq!(ars(-5).a_decimal(), "-0.05");
+        assert_eq!(Dinero::desde_decimal(&ars(123456).a_decimal(), Moneda::ars()), Ok(ars(123456)));
+    }
+
+    #[test]
+    fn test_serializar() {
+        let json = serde_json::to_string(&ars(1250)).unwrap();
+        assert_eq!(json, r#"{"unidades":1250,"codigo":"ARS","decimales":2}"#);
+        assert_eq!(serde_json::from_str::<Dinero>(&json).unwrap(), ars(1250));
+    }
+}
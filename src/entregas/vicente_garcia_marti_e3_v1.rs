@@ -1,114 +1,7 @@
 // Nombre: Vicente García Martí | DNI: 46.645.435 | Discord: Vicen621
 use std::collections::HashMap;
-use chrono::{Datelike, Local};
-
-#[derive(Debug, Clone, Eq, PartialEq)]
-pub struct Fecha {
-    day: u32,
-    month: u32,
-    year: i32,
-}
-
-impl Default for Fecha {
-    fn default() -> Self {
-        Fecha::now()
-    }
-}
-
-impl Fecha {
-    pub fn now() -> Self {
-        let now = Local::now();
-        Fecha {
-            day: now.day(),
-            month: now.month(),
-            year: now.year(),
-        }
-    }
-
-    pub fn new(day: u32, month: u32, year: i32) -> Self {
-        Fecha {
-            day,
-            month,
-            year
-        }
-    }
-
-    pub fn to_string(&self) -> String {
-        let mut result = String::new();
-        result.push_str(&format!("{}/{}/{}", self.day, self.month, self.year));
-        result
-    }
-
-    pub fn equals(&self, other: &Fecha) -> bool {
-        self.to_string() == other.to_string()
-    }
-
-    pub fn es_fecha_valida(&self) -> bool {
-        self.day <= self.obtener_dias_para_mes() && self.day > 0
-    }
-
-    pub fn es_bisiesto(&self) -> bool {
-        self.year % 4 == 0
-    }
-
-    /// Devuelve la cantidad de dias que tiene el mes actual
-    fn obtener_dias_para_mes(&self) -> u32 {
-        if self.month > 12 || self.month < 1 {
-            return 0;
-        }
-
-        const DIAS_POR_MES: [u32; 12] = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
-        let dias = DIAS_POR_MES[(self.month - 1) as usize];
-        // bool as u32 = if true { 1 } else { 0 }
-        dias + (self.month == 2 && self.es_bisiesto()) as u32
-    }
-
-    pub fn sumar_dias(&mut self, dias: u32) {
-        let mut dias_restantes = dias;
-        while dias_restantes > 0 {
-            let dias_en_mes = self.obtener_dias_para_mes();
-            // Se suma 1 ya que tengo que contar el dia actual
-            let dias_hasta_fin_de_mes = dias_en_mes - self.day + 1;
-
-            if dias_hasta_fin_de_mes > dias_restantes {
-                self.day += dias_restantes;
-                dias_restantes = 0;
-            } else {
-                dias_restantes -= dias_hasta_fin_de_mes;
-                self.month += 1;
-                if self.month > 12 {
-                    self.month = 1;
-                    self.year += 1;
-                }
-                self.day = 1;
-            }
-        }
-    }
-
-    pub fn restar_dias(&mut self, dias: u32) {
-        let mut dias_restantes = dias;
-        while dias_restantes > 0 {
-            if self.day > dias_restantes {
-                self.day -= dias_restantes;
-                dias_restantes = 0;
-            } else {
-                dias_restantes -= self.day;
-                self.month -= 1;
-                if self.month == 0 {
-                    self.month = 12;
-                    self.year -= 1;
-                }
-                self.day = self.obtener_dias_para_mes();
-            }
-        }
-    }
-
-    pub fn es_mayor(&self, una_fecha: &Fecha) -> bool {
-        (self.year > una_fecha.year) || 
-            (self.year == una_fecha.year && self.month > una_fecha.month) || 
-            (self.year == una_fecha.year && self.month == una_fecha.month && self.day > una_fecha.day)
-    }
-}
+use crate::dinero::{Dinero, ErrorDinero, Moneda};
+use crate::fecha::Fecha;
 
 struct StreamingRust {
     usuarios: Vec<Usuario>,
@@ -374,8 +267,8 @@ impl Suscripcion {
         self.estado = EstadoSuscripcion::Inactiva;
     }
 
-    fn costo(&self) -> f64 {
-        self.tipo_suscripcion.costo() * self.duracion_meses as f64
+    fn costo(&self) -> Result<Dinero, ErrorDinero> {
+        self.tipo_suscripcion.costo().multiplicar(self.duracion_meses as i64)
     }
 
     fn esta_activa(&self) -> bool {
@@ -407,21 +300,22 @@ impl PartialEq for TipoPack {
 }
 
 impl TipoPack {
-    fn costo(&self) -> f64 {
+    fn costo(&self) -> Dinero {
         match self {
-            TipoPack::Futbol => 10.0,
-            TipoPack::EstrenosExclusivos(_) => 20.0,
-            TipoPack::Familiar(_) => 30.0,
+            TipoPack::Futbol => Dinero::new(1000, Moneda::ars()),
+            TipoPack::EstrenosExclusivos(_) => Dinero::new(2000, Moneda::ars()),
+            TipoPack::Familiar(_) => Dinero::new(3000, Moneda::ars()),
         }
     }
 }
 
 impl TipoSuscripcion {
-    fn costo(&self) -> f64 {
+    /// Costo mensual de la suscripcion
+    fn costo(&self) -> Dinero {
         match self {
-            TipoSuscripcion::Basic => 10.0,
-            TipoSuscripcion::Clasic => 20.0,
-            TipoSuscripcion::Super => 30.0,
+            TipoSuscripcion::Basic => Dinero::new(1000, Moneda::ars()),
+            TipoSuscripcion::Clasic => Dinero::new(2000, Moneda::ars()),
+            TipoSuscripcion::Super => Dinero::new(3000, Moneda::ars()),
         }
     }
 }
@@ -429,11 +323,12 @@ impl TipoSuscripcion {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::{Datelike, Local};
 
     #[test]
     fn test_subscripcion_cost() {
         let subscripcion = Suscripcion::new(TipoSuscripcion::Basic, 3, 1);
-        assert_eq!(subscripcion.costo(), 30.0);
+        assert_eq!(subscripcion.costo(), Ok(Dinero::new(3000, Moneda::ars())));
     }
 
     #[test]
@@ -606,9 +501,9 @@ mod tests {
 
     #[test]
     fn test_tipo_pack_costo() {
-        assert_eq!(TipoPack::Futbol.costo(), 10.0);
-        assert_eq!(TipoPack::EstrenosExclusivos(vec![]).costo(), 20.0);
-        assert_eq!(TipoPack::Familiar([0, 0, 0, 0, 0]).costo(), 30.0);
+        assert_eq!(TipoPack::Futbol.costo(), Dinero::new(1000, Moneda::ars()));
+        assert_eq!(TipoPack::EstrenosExclusivos(vec![]).costo(), Dinero::new(2000, Moneda::ars()));
+        assert_eq!(TipoPack::Familiar([0, 0, 0, 0, 0]).costo(), Dinero::new(3000, Moneda::ars()));
     }
 
     #[test]
@@ -698,8 +593,8 @@ mod tests {
     fn test_now() {
         let fecha = Fecha::now();
         let now = Local::now();
-        assert_eq!(fecha.day, now.day());
-        assert_eq!(fecha.month, now.month());
-        assert_eq!(fecha.year, now.year());
+        assert_eq!(fecha.dia(), now.day());
+        assert_eq!(fecha.mes(), now.month());
+        assert_eq!(fecha.anio(), now.year());
     }
 }
\ No newline at end of file
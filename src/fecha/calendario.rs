@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+
+use super::{DiaSemana, Duracion, ErrorParseoFecha, Fecha};
+
+/// Calendario de dias habiles: los sabados, domingos y feriados no son habiles.
+/// Los feriados pueden ser fechas puntuales, fechas que se repiten todos los anios
+/// o reglas que calculan los feriados moviles de cada anio
+#[derive(Clone, Default)]
+pub struct Calendario {
+    feriados: HashSet<Fecha>,
+    // (dia, mes)
+    feriados_fijos: HashSet<(u32, u32)>,
+    feriados_moviles: Vec<fn(i32) -> Vec<Fecha>>,
+}
+
+impl Calendario {
+    /// Crea un calendario sin feriados, solo los fines de semana no son habiles
+    pub fn new() -> Self {
+        Calendario::default()
+    }
+
+    /// Crea un calendario con los feriados nacionales de Argentina (inamovibles, trasladables,
+    /// carnaval y semana santa)
+    pub fn argentina() -> Self {
+        let mut calendario = Calendario::new();
+        const FIJOS: [(u32, u32); 9] = [(1, 1), (24, 3), (2, 4), (1, 5), (25, 5), (20, 6), (9, 7), (8, 12), (25, 12)];
+        for (day, month) in FIJOS {
+            calendario.agregar_feriado_fijo(day, month);
+        }
+        calendario.agregar_feriado_movil(feriados_pascua_argentina);
+        calendario.agregar_feriado_movil(feriados_trasladables_argentina);
+        calendario
+    }
+
+    /// Agrega un feriado para una fecha puntual
+    pub fn agregar_feriado(&mut self, fecha: Fecha) {
+        self.feriados.insert(fecha);
+    }
+
+    /// Agrega un feriado que se repite todos los anios en el mismo dia y mes
+    pub fn agregar_feriado_fijo(&mut self, day: u32, month: u32) {
+        self.feriados_fijos.insert((day, month));
+    }
+
+    /// Agrega una regla que devuelve los feriados moviles de un anio
+    pub fn agregar_feriado_movil(&mut self, regla: fn(i32) -> Vec<Fecha>) {
+        self.feriados_moviles.push(regla);
+    }
+
+    /// Agrega como feriados las fechas del texto, una por linea, en cualquiera de los
+    /// formatos aceptados por `Fecha::from_str`. Las lineas vacias o que empiezan con `#` se ignoran
+    pub fn cargar_feriados(&mut self, texto: &str) -> Result<(), ErrorParseoFecha> {
+        for linea in texto.lines().map(str::trim) {
+            if linea.is_empty() || linea.starts_with('#') {
+                continue;
+            }
+            self.agregar_feriado(linea.parse()?);
+        }
+        Ok(())
+    }
+
+    pub fn es_feriado(&self, fecha: &Fecha) -> bool {
+        self.feriados.contains(fecha)
+            || self.feriados_fijos.contains(&(fecha.day, fecha.month))
+            || self.feriados_moviles.iter().any(|regla| regla(fecha.year).contains(fecha))
+    }
+
+    pub fn es_dia_habil(&self, fecha: &Fecha) -> bool {
+        !fecha.es_fin_de_semana() && !self.es_feriado(fecha)
+    }
+
+    /// Devuelve todos los feriados del anio ordenados
+    pub fn feriados_del_anio(&self, year: i32) -> Vec<Fecha> {
+        Fecha::rango(&Fecha::new(1, 1, year), &Fecha::new(31, 12, year))
+            .filter(|fecha| self.es_feriado(fecha))
+            .collect()
+    }
+
+    /// Devuelve la misma fecha si es habil, o el primer dia habil posterior
+    pub fn siguiente_dia_habil(&self, fecha: &Fecha) -> Fecha {
+        let mut ret = fecha.clone();
+        while !self.es_dia_habil(&ret) {
+            ret.sumar_dias(1);
+        }
+        ret
+    }
+
+    /// Suma la cantidad de dias habiles indicada, salteando fines de semana y feriados
+    pub fn sumar_dias_habiles(&self, fecha: &Fecha, dias: u32) -> Fecha {
+        let mut ret = fecha.clone();
+        let mut dias_restantes = dias;
+        while dias_restantes > 0 {
+            ret.sumar_dias(1);
+            if self.es_dia_habil(&ret) {
+                dias_restantes -= 1;
+            }
+        }
+        ret
+    }
+}
+
+/// Carnaval (lunes y martes) y Viernes Santo
+fn feriados_pascua_argentina(year: i32) -> Vec<Fecha> {
+    let pascua = Fecha::domingo_de_pascua(year);
+    vec![pascua.clone() - Duracion::dias(48), pascua.clone() - Duracion::dias(47), pascua - Duracion::dias(2)]
+}
+
+/// Feriados trasladables (Ley 27.399): si caen martes o miercoles se mueven al lunes anterior,
+/// si caen jueves o viernes al lunes siguiente
+fn feriados_trasladables_argentina(year: i32) -> Vec<Fecha> {
+    const TRASLADABLES: [(u32, u32); 4] = [(17, 6), (17, 8), (12, 10), (20, 11)];
+    TRASLADABLES.iter().map(|(day, month)| {
+        let fecha = Fecha::new(*day, *month, year);
+        match fecha.dia_de_la_semana() {
+            DiaSemana::Martes => fecha - Duracion::dias(1),
+            DiaSemana::Miercoles => fecha - Duracion::dias(2),
+            DiaSemana::Jueves => fecha + Duracion::dias(4),
+            DiaSemana::Viernes => fecha + Duracion::dias(3),
+            _ => fecha,
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calendario_argentina() {
+        let calendario = Calendario::argentina();
+        // Inamovibles
+        assert!(calendario.es_feriado(&Fecha::new(25, 5, 2024)));
+        assert!(calendario.es_feriado(&Fecha::new(9, 7, 2030)));
+        // Carnaval y Viernes Santo 2024
+        assert!(calendario.es_feriado(&Fecha::new(12, 2, 2024)));
+        assert!(calendario.es_feriado(&Fecha::new(13, 2, 2024)));
+        assert!(calendario.es_feriado(&Fecha::new(29, 3, 2024)));
+        // 17/8/2024 fue sabado, no se traslada
+        assert!(calendario.es_feriado(&Fecha::new(17, 8, 2024)));
+        // 20/11/2024 fue miercoles, se traslada al lunes 18
+        assert!(calendario.es_feriado(&Fecha::new(18, 11, 2024)));
+        assert!(!calendario.es_feriado(&Fecha::new(20, 11, 2024)));
+        assert!(!calendario.es_feriado(&Fecha::new(21, 5, 2024)));
+    }
+
+    #[test]
+    fn test_calendario_configurable() {
+        let mut calendario = Calendario::new();
+        assert!(calendario.feriados_del_anio(2024).is_empty());
+
+        calendario.agregar_feriado_fijo(15, 9);
+        assert!(calendario.cargar_feriados("# feriados locales\n2024-03-05\n\n7/10/2024").is_ok());
+        assert_eq!(calendario.feriados_del_anio(2024), vec![Fecha::new(5, 3, 2024), Fecha::new(15, 9, 2024), Fecha::new(7, 10, 2024)]);
+        assert!(calendario.cargar_feriados("30/02/2024").is_err());
+    }
+
+    #[test]
+    fn test_sumar_dias_habiles() {
+        let calendario = Calendario::argentina();
+        // Viernes 17/5/2024 + 1 dia habil = lunes 20/5
+        assert_eq!(calendario.sumar_dias_habiles(&Fecha::new(17, 5, 2024), 1), Fecha::new(20, 5, 2024));
+        // Viernes 22/3/2024 + 3: lunes 25, martes 26, miercoles 27 (el 24/3 es domingo)
+        assert_eq!(calendario.sumar_dias_habiles(&Fecha::new(22, 3, 2024), 3), Fecha::new(27, 3, 2024));
+        // Miercoles 27/3/2024 + 3: jueves 28, lunes 1/4 (29/3 es viernes santo) y miercoles 3/4 (2/4 es feriado)
+        assert_eq!(calendario.sumar_dias_habiles(&Fecha::new(27, 3, 2024), 3), Fecha::new(3, 4, 2024));
+        assert_eq!(calendario.sumar_dias_habiles(&Fecha::new(27, 3, 2024), 0), Fecha::new(27, 3, 2024));
+        assert_eq!(calendario.siguiente_dia_habil(&Fecha::new(19, 5, 2024)), Fecha::new(20, 5, 2024));
+    }
+}
@@ -0,0 +1,292 @@
+use std::{cmp::Ordering, fmt::{Display, Formatter}, hash::{Hash, Hasher}, ops::{Add, Sub}, str::FromStr};
+use chrono::{DateTime, Datelike, Local, Timelike};
+use serde::{Deserialize, Serialize};
+
+use super::{Duracion, ErrorParseoFecha, Fecha};
+
+/// Fecha con hora del dia y diferencia con UTC (en minutos).
+/// Dos FechaHora son iguales si representan el mismo instante, aunque tengan distinto offset.
+/// Se serializa con los campos de la fecha al mismo nivel que los de la hora, por lo que
+/// una Fecha serializada se puede leer como una FechaHora a las 00:00:00 UTC
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FechaHora {
+    #[serde(flatten)]
+    pub(super) fecha: Fecha,
+    #[serde(default)]
+    hora: u32,
+    #[serde(default)]
+    minuto: u32,
+    #[serde(default)]
+    segundo: u32,
+    #[serde(default)]
+    offset_minutos: i32,
+}
+
+impl Default for FechaHora {
+    fn default() -> Self {
+        FechaHora::now()
+    }
+}
+
+impl FechaHora {
+    pub fn now() -> Self {
+        let now = Local::now();
+        FechaHora {
+            fecha: Fecha::new(now.day(), now.month(), now.year()),
+            hora: now.hour(),
+            minuto: now.minute(),
+            segundo: now.second(),
+            offset_minutos: now.offset().local_minus_utc() / 60,
+        }
+    }
+
+    /// Crea una FechaHora en UTC
+    pub fn new(fecha: Fecha, hora: u32, minuto: u32, segundo: u32) -> Self {
+        FechaHora::new_con_offset(fecha, hora, minuto, segundo, 0)
+    }
+
+    /// Crea una FechaHora con la diferencia con UTC indicada en minutos (ej: -180 para Argentina)
+    pub fn new_con_offset(fecha: Fecha, hora: u32, minuto: u32, segundo: u32, offset_minutos: i32) -> Self {
+        FechaHora {
+            fecha,
+            hora,
+            minuto,
+            segundo,
+            offset_minutos
+        }
+    }
+
+    pub fn es_fecha_hora_valida(&self) -> bool {
+        self.fecha.es_fecha_valida() && self.hora < 24 && self.minuto < 60 && self.segundo < 60
+            && self.offset_minutos.abs() < 24 * 60
+    }
+
+    pub fn fecha(&self) -> &Fecha {
+        &self.fecha
+    }
+
+    pub fn hora(&self) -> u32 {
+        self.hora
+    }
+
+    pub fn minuto(&self) -> u32 {
+        self.minuto
+    }
+
+    pub fn segundo(&self) -> u32 {
+        self.segundo
+    }
+
+    pub fn offset_minutos(&self) -> i32 {
+        self.offset_minutos
+    }
+
+    /// Suma (o resta si es negativa) la cantidad de segundos indicada, manteniendo el offset
+    pub fn sumar_segundos(&mut self, segundos: i64) {
+        let local = self.segundos_locales() + segundos;
+        let dias = local.div_euclid(86400);
+        let segundos_del_dia = local.rem_euclid(86400) as u32;
+        self.fecha = Fecha::desde_dias_epoca(dias);
+        self.hora = segundos_del_dia / 3600;
+        self.minuto = segundos_del_dia % 3600 / 60;
+        self.segundo = segundos_del_dia % 60;
+    }
+
+    pub fn sumar_minutos(&mut self, minutos: i64) {
+        self.sumar_segundos(minutos * 60);
+    }
+
+    pub fn sumar_horas(&mut self, horas: i64) {
+        self.sumar_segundos(horas * 3600);
+    }
+
+    /// Devuelve la cantidad de segundos desde self hasta otra, negativa si otra es anterior
+    pub fn segundos_entre(&self, otra: &FechaHora) -> i64 {
+        otra.timestamp() - self.timestamp()
+    }
+
+    /// Devuelve el mismo instante expresado con otro offset
+    pub fn con_offset(&self, offset_minutos: i32) -> FechaHora {
+        let mut ret = self.clone();
+        ret.sumar_minutos((offset_minutos - self.offset_minutos) as i64);
+        ret.offset_minutos = offset_minutos;
+        ret
+    }
+
+    pub fn a_utc(&self) -> FechaHora {
+        self.con_offset(0)
+    }
+
+    /// Segundos transcurridos desde el 1/1/1970 00:00:00 UTC
+    pub fn timestamp(&self) -> i64 {
+        self.segundos_locales() - self.offset_minutos as i64 * 60
+    }
+
+    fn segundos_locales(&self) -> i64 {
+        self.fecha.dias_desde_epoca() * 86400 + (self.hora * 3600 + self.minuto * 60 + self.segundo) as i64
+    }
+}
+
+impl From<FechaHora> for Fecha {
+    fn from(fecha_hora: FechaHora) -> Self {
+        fecha_hora.fecha
+    }
+}
+
+impl From<Fecha> for FechaHora {
+    fn from(fecha: Fecha) -> Self {
+        FechaHora::new(fecha, 0, 0, 0)
+    }
+}
+
+impl Display for FechaHora {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let signo = if self.offset_minutos < 0 { '-' } else { '+' };
+        let offset = self.offset_minutos.abs();
+        write!(f, "{} {:02}:{:02}:{:02}{}{:02}:{:02}", self.fecha, self.hora, self.minuto, self.segundo, signo, offset / 60, offset % 60)
+    }
+}
+
+/// Acepta fechas con hora en RFC 3339, como `2024-03-05T10:00:00-03:00` o `2024-03-05T13:00:00Z`
+impl FromStr for FechaHora {
+    type Err = ErrorParseoFecha;
+
+    fn from_str(texto: &str) -> Result<Self, Self::Err> {
+        let fecha_hora = DateTime::parse_from_rfc3339(texto.trim())
+            .map_err(|e| ErrorParseoFecha::Formato(format!("{}: {}", texto, e)))?;
+        let fecha = Fecha::new(fecha_hora.day(), fecha_hora.month(), fecha_hora.year());
+        let offset_minutos = fecha_hora.offset().local_minus_utc() / 60;
+        Ok(FechaHora::new_con_offset(fecha, fecha_hora.hour(), fecha_hora.minute(), fecha_hora.second(), offset_minutos))
+    }
+}
+
+impl PartialEq for FechaHora {
+    fn eq(&self, other: &Self) -> bool {
+        self.timestamp() == other.timestamp()
+    }
+}
+
+impl Eq for FechaHora {}
+
+impl Hash for FechaHora {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.timestamp().hash(state);
+    }
+}
+
+impl Ord for FechaHora {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.timestamp().cmp(&other.timestamp())
+    }
+}
+
+impl PartialOrd for FechaHora {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add<Duracion> for FechaHora {
+    type Output = FechaHora;
+
+    fn add(mut self, duracion: Duracion) -> FechaHora {
+        self.sumar_segundos(duracion.en_segundos());
+        self
+    }
+}
+
+impl Sub<Duracion> for FechaHora {
+    type Output = FechaHora;
+
+    fn sub(mut self, duracion: Duracion) -> FechaHora {
+        self.sumar_segundos(-duracion.en_segundos());
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fecha_hora_sumar() {
+        let mut fecha_hora = FechaHora::new(Fecha::new(31, 12, 2023), 23, 59, 30);
+        fecha_hora.sumar_segundos(45);
+        assert_eq!(fecha_hora.fecha(), &Fecha::new(1, 1, 2024));
+        assert_eq!((fecha_hora.hora(), fecha_hora.minuto(), fecha_hora.segundo()), (0, 0, 15));
+
+        fecha_hora.sumar_horas(-1);
+        assert_eq!(fecha_hora.fecha(), &Fecha::new(31, 12, 2023));
+        assert_eq!((fecha_hora.hora(), fecha_hora.minuto(), fecha_hora.segundo()), (23, 0, 15));
+
+        let fecha_hora = fecha_hora + Duracion::dias(60);
+        assert_eq!(fecha_hora.fecha(), &Fecha::new(29, 2, 2024));
+    }
+
+    #[test]
+    fn test_fecha_hora_orden_mismo_dia() {
+        let primera = FechaHora::new(Fecha::new(5, 3, 2024), 10, 0, 0);
+        let segunda = FechaHora::new(Fecha::new(5, 3, 2024), 10, 0, 1);
+        assert!(primera < segunda);
+        assert_eq!(primera.segundos_entre(&segunda), 1);
+        assert_eq!(Fecha::from(primera), Fecha::from(segunda));
+    }
+
+    #[test]
+    fn test_fecha_hora_offset() {
+        let argentina = FechaHora::new_con_offset(Fecha::new(5, 3, 2024), 21, 30, 0, -180);
+        let utc = argentina.a_utc();
+        assert_eq!(utc.fecha(), &Fecha::new(6, 3, 2024));
+        assert_eq!(utc.hora(), 0);
+        assert_eq!(utc.offset_minutos(), 0);
+        assert_eq!(argentina, utc);
+        assert_eq!(argentina.to_string(), "5/3/2024 21:30:00-03:00");
+        assert_eq!(utc.to_string(), "6/3/2024 00:30:00+00:00");
+        assert_eq!(utc.con_offset(-180).hora(), 21);
+    }
+
+    #[test]
+    fn test_fecha_hora_valida() {
+        assert!(FechaHora::new(Fecha::new(5, 3, 2024), 23, 59, 59).es_fecha_hora_valida());
+        assert!(!FechaHora::new(Fecha::new(5, 3, 2024), 24, 0, 0).es_fecha_hora_valida());
+        assert!(!FechaHora::new(Fecha::new(30, 2, 2024), 10, 0, 0).es_fecha_hora_valida());
+    }
+
+    #[test]
+    fn test_fecha_hora_serde() {
+        let fecha_hora = FechaHora::new_con_offset(Fecha::new(5, 3, 2024), 10, 20, 30, -180);
+        let json = serde_json::to_string(&fecha_hora).unwrap();
+        let leida: FechaHora = serde_json::from_str(&json).unwrap();
+        assert_eq!(leida, fecha_hora);
+        assert_eq!(leida.offset_minutos(), -180);
+
+        // Una Fecha serializada se lee como el inicio del dia en UTC
+        let json = serde_json::to_string(&Fecha::new(5, 3, 2024)).unwrap();
+        let leida: FechaHora = serde_json::from_str(&json).unwrap();
+        assert_eq!(leida, FechaHora::new(Fecha::new(5, 3, 2024), 0, 0, 0));
+
+        // Formato anterior de la fecha, con los campos en ingles
+        let json = r#"{"day":5,"month":3,"year":2024,"hora":10,"minuto":20,"segundo":30,"offset_minutos":0}"#;
+        let leida: FechaHora = serde_json::from_str(json).unwrap();
+        assert_eq!(leida, FechaHora::new(Fecha::new(5, 3, 2024), 10, 20, 30));
+    }
+
+    #[test]
+    fn test_fecha_hora_from_str() {
+        let fecha_hora: FechaHora = "2024-03-05T10:00:00-03:00".parse().unwrap();
+        assert_eq!(fecha_hora.offset_minutos(), -180);
+        assert_eq!(fecha_hora, "2024-03-05T13:00:00Z".parse().unwrap());
+        assert_eq!(fecha_hora, FechaHora::new(Fecha::new(5, 3, 2024), 13, 0, 0));
+
+        assert!("2024-03-05".parse::<FechaHora>().is_err());
+        assert!("2024-03-05T25:00:00Z".parse::<FechaHora>().is_err());
+    }
+
+    #[test]
+    fn test_fecha_hora_now() {
+        let fecha_hora = FechaHora::now();
+        let now = Local::now();
+        assert!(fecha_hora.es_fecha_hora_valida());
+        assert!((fecha_hora.timestamp() - now.timestamp()).abs() <= 1);
+    }
+}
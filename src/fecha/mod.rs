@@ -0,0 +1,996 @@
+#![allow(dead_code)]
+use std::{cmp::Ordering, fmt::{Display, Formatter}, ops::{Add, Sub}, str::FromStr};
+use chrono::{Datelike, Local};
+use serde::{Deserialize, Serialize};
+
+mod calendario;
+mod fecha_hora;
+mod reloj;
+
+pub use calendario::Calendario;
+pub use fecha_hora::FechaHora;
+pub use reloj::{Reloj, RelojManual, RelojSistema};
+
+/// Fecha del calendario gregoriano.
+/// Se serializa como `{"version": 1, "dia": .., "mes": .., "anio": ..}` y al deserializar
+/// tambien acepta el formato anterior `{"day": .., "month": .., "year": ..}`
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "FechaSerializada", into = "FechaV1")]
+pub struct Fecha {
+    day: u32,
+    month: u32,
+    year: i32,
+}
+
+/// Version actual de la representacion serializada de Fecha
+pub const VERSION_SERIALIZACION: u32 = 1;
+
+#[derive(Deserialize, Serialize)]
+struct FechaV1 {
+    version: u32,
+    dia: u32,
+    mes: u32,
+    anio: i32,
+}
+
+#[derive(Deserialize)]
+struct FechaLegacy {
+    day: u32,
+    month: u32,
+    year: i32,
+}
+
+/// Formatos aceptados al leer una Fecha, del mas nuevo al mas viejo
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FechaSerializada {
+    V1(FechaV1),
+    Legacy(FechaLegacy),
+}
+
+/// Cantidad de dias entre dos fechas, puede ser negativa
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct Duracion {
+    dias: i64,
+}
+
+/// Iterador sobre todas las fechas entre dos fechas, ambas incluidas
+pub struct RangoFechas {
+    actual: Fecha,
+    hasta: Fecha,
+}
+
+/// Error al convertir un texto en una Fecha, indica que componente no se pudo leer
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ErrorParseoFecha {
+    Dia(String),
+    Mes(String),
+    Anio(String),
+    Formato(String),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum DiaSemana {
+    Lunes,
+    Martes,
+    Miercoles,
+    Jueves,
+    Viernes,
+    Sabado,
+    Domingo,
+}
+
+const MESES: [&str; 12] = [
+    "enero", "febrero", "marzo", "abril", "mayo", "junio",
+    "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre"
+];
+
+impl Default for Fecha {
+    fn default() -> Self {
+        Fecha::now()
+    }
+}
+
+impl Fecha {
+    pub fn now() -> Self {
+        let now = Local::now();
+        Fecha {
+            day: now.day(),
+            month: now.month(),
+            year: now.year(),
+        }
+    }
+
+    pub fn new(day: u32, month: u32, year: i32) -> Self {
+        Fecha {
+            day,
+            month,
+            year
+        }
+    }
+
+    pub fn dia(&self) -> u32 {
+        self.day
+    }
+
+    pub fn mes(&self) -> u32 {
+        self.month
+    }
+
+    pub fn anio(&self) -> i32 {
+        self.year
+    }
+
+    /// Crea una fecha validando cada componente
+    pub fn try_new(day: u32, month: u32, year: i32) -> Result<Self, ErrorParseoFecha> {
+        if !(1..=12).contains(&month) {
+            return Err(ErrorParseoFecha::Mes(month.to_string()));
+        }
+
+        let fecha = Fecha::new(day, month, year);
+        if fecha.es_fecha_valida() {
+            Ok(fecha)
+        } else {
+            Err(ErrorParseoFecha::Dia(day.to_string()))
+        }
+    }
+
+    /// Lee una fecha segun un formato al estilo strftime. Los especificadores soportados son:
+    /// `%d` dia, `%m` mes, `%Y` anio, `%B` nombre del mes en español y `%%` para un `%` literal.
+    /// `%-d` y `%-m` se aceptan como sinonimos de `%d` y `%m`
+    pub fn parse_con_formato(texto: &str, formato: &str) -> Result<Self, ErrorParseoFecha> {
+        let mut day = None;
+        let mut month = None;
+        let mut year = None;
+        let mut resto = texto;
+        let mut especificadores = formato.chars();
+
+        while let Some(c) = especificadores.next() {
+            if c != '%' {
+                resto = consumir_literal(resto, c)?;
+                continue;
+            }
+
+            let mut especificador = especificadores.next();
+            if especificador == Some('-') {
+                especificador = especificadores.next();
+            }
+
+            match especificador {
+                Some('d') => {
+                    let (valor, r) = leer_numero(resto, 2).ok_or_else(|| ErrorParseoFecha::Dia(resto.to_string()))?;
+                    day = Some(valor);
+                    resto = r;
+                },
+                Some('m') => {
+                    let (valor, r) = leer_numero(resto, 2).ok_or_else(|| ErrorParseoFecha::Mes(resto.to_string()))?;
+                    month = Some(valor);
+                    resto = r;
+                },
+                Some('B') => {
+                    let (valor, r) = leer_nombre_mes(resto)?;
+                    month = Some(valor);
+                    resto = r;
+                },
+                Some('Y') => {
+                    let (valor, r) = leer_anio(resto)?;
+                    year = Some(valor);
+                    resto = r;
+                },
+                Some('%') => resto = consumir_literal(resto, '%')?,
+                Some(otro) => return Err(ErrorParseoFecha::Formato(format!("Especificador desconocido: %{}", otro))),
+                None => return Err(ErrorParseoFecha::Formato(String::from("El formato termina en %"))),
+            }
+        }
+
+        if !resto.is_empty() {
+            return Err(ErrorParseoFecha::Formato(format!("Texto sobrante: {}", resto)));
+        }
+
+        match (day, month, year) {
+            (Some(day), Some(month), Some(year)) => Fecha::try_new(day, month, year),
+            (None, _, _) => Err(ErrorParseoFecha::Dia(String::from("El formato no incluye el dia"))),
+            (_, None, _) => Err(ErrorParseoFecha::Mes(String::from("El formato no incluye el mes"))),
+            (_, _, None) => Err(ErrorParseoFecha::Anio(String::from("El formato no incluye el anio"))),
+        }
+    }
+
+    /// Escribe la fecha segun un formato al estilo strftime, con los mismos especificadores que
+    /// `parse_con_formato`. `%d` y `%m` se completan con ceros, `%-d` y `%-m` no
+    pub fn formatear(&self, formato: &str) -> String {
+        let mut result = String::new();
+        let mut especificadores = formato.chars();
+
+        while let Some(c) = especificadores.next() {
+            if c != '%' {
+                result.push(c);
+                continue;
+            }
+
+            match especificadores.next() {
+                Some('d') => result.push_str(&format!("{:02}", self.day)),
+                Some('m') => result.push_str(&format!("{:02}", self.month)),
+                Some('B') => result.push_str(MESES.get((self.month as usize).wrapping_sub(1)).unwrap_or(&"")),
+                Some('Y') => result.push_str(&format!("{:04}", self.year)),
+                Some('-') => match especificadores.next() {
+                    Some('d') => result.push_str(&self.day.to_string()),
+                    Some('m') => result.push_str(&self.month.to_string()),
+                    Some(otro) => result.push_str(&format!("%-{}", otro)),
+                    None => result.push_str("%-"),
+                },
+                Some(otro) => {
+                    if otro != '%' {
+                        result.push('%');
+                    }
+                    result.push(otro);
+                },
+                None => result.push('%'),
+            }
+        }
+
+        result
+    }
+
+    pub fn equals(&self, other: &Fecha) -> bool {
+        self.to_string() == other.to_string()
+    }
+
+    pub fn es_fecha_valida(&self) -> bool {
+        self.day <= self.obtener_dias_para_mes() && self.day > 0
+    }
+
+    pub fn es_bisiesto(&self) -> bool {
+        (self.year % 4 == 0 && self.year % 100 != 0) || self.year % 400 == 0
+    }
+
+    /// Devuelve la cantidad de dias que tiene el mes actual
+    fn obtener_dias_para_mes(&self) -> u32 {
+        if self.month > 12 || self.month < 1 {
+            return 0;
+        }
+
+        const DIAS_POR_MES: [u32; 12] = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+        let dias = DIAS_POR_MES[(self.month - 1) as usize];
+        // bool as u32 = if true { 1 } else { 0 }
+        dias + (self.month == 2 && self.es_bisiesto()) as u32
+    }
+
+    pub fn sumar_dias(&mut self, dias: u32) {
+        let mut dias_restantes = dias;
+        while dias_restantes > 0 {
+            let dias_en_mes = self.obtener_dias_para_mes();
+            // Se suma 1 ya que tengo que contar el dia actual
+            let dias_hasta_fin_de_mes = dias_en_mes - self.day + 1;
+
+            if dias_hasta_fin_de_mes > dias_restantes {
+                self.day += dias_restantes;
+                dias_restantes = 0;
+            } else {
+                dias_restantes -= dias_hasta_fin_de_mes;
+                self.month += 1;
+                if self.month > 12 {
+                    self.month = 1;
+                    self.year += 1;
+                }
+                self.day = 1;
+            }
+        }
+    }
+
+    pub fn restar_dias(&mut self, dias: u32) {
+        let mut dias_restantes = dias;
+        while dias_restantes > 0 {
+            if self.day > dias_restantes {
+                self.day -= dias_restantes;
+                dias_restantes = 0;
+            } else {
+                dias_restantes -= self.day;
+                self.month -= 1;
+                if self.month == 0 {
+                    self.month = 12;
+                    self.year -= 1;
+                }
+                self.day = self.obtener_dias_para_mes();
+            }
+        }
+    }
+
+    /// Suma la cantidad de meses indicada. Si el dia no existe en el mes resultante
+    /// se ajusta al ultimo dia valido del mes (ej: 31/1/2024 + 1 mes = 29/2/2024)
+    pub fn sumar_meses(&mut self, meses: u32) {
+        let meses_totales = (self.month - 1) + meses;
+        self.year += (meses_totales / 12) as i32;
+        self.month = meses_totales % 12 + 1;
+        self.day = self.day.min(self.obtener_dias_para_mes());
+    }
+
+    /// Suma la cantidad de anios indicada, ajustando el 29/2 al 28/2 si el anio resultante no es bisiesto
+    pub fn sumar_anios(&mut self, anios: u32) {
+        self.sumar_meses(anios * 12);
+    }
+
+    /// Devuelve la cantidad de dias que hay desde self hasta otra fecha.
+    /// El resultado es negativo si la otra fecha es anterior a self
+    pub fn dias_entre(&self, otra: &Fecha) -> i64 {
+        otra.dias_desde_epoca() - self.dias_desde_epoca()
+    }
+
+    pub fn dia_de_la_semana(&self) -> DiaSemana {
+        // El 1/1/1970 fue jueves
+        match (self.dias_desde_epoca() + 3).rem_euclid(7) {
+            0 => DiaSemana::Lunes,
+            1 => DiaSemana::Martes,
+            2 => DiaSemana::Miercoles,
+            3 => DiaSemana::Jueves,
+            4 => DiaSemana::Viernes,
+            5 => DiaSemana::Sabado,
+            _ => DiaSemana::Domingo,
+        }
+    }
+
+    pub fn es_fin_de_semana(&self) -> bool {
+        matches!(self.dia_de_la_semana(), DiaSemana::Sabado | DiaSemana::Domingo)
+    }
+
+    /// Devuelve el numero de dia dentro del anio, empezando en 1 para el 1 de enero
+    pub fn dia_del_anio(&self) -> u32 {
+        (Fecha::new(1, 1, self.year).dias_entre(self) + 1) as u32
+    }
+
+    /// Devuelve el anio y el numero de semana segun ISO-8601. Las semanas empiezan el lunes
+    /// y la semana 1 es la que contiene el primer jueves del anio, por lo que los primeros
+    /// o ultimos dias del anio pueden pertenecer a una semana del anio anterior o siguiente
+    pub fn semana_iso(&self) -> (i32, u32) {
+        let dia_semana = self.dia_de_la_semana().numero() as i64;
+        let semana = (self.dia_del_anio() as i64 - dia_semana + 10) / 7;
+
+        if semana < 1 {
+            (self.year - 1, Fecha::semanas_iso_en_anio(self.year - 1))
+        } else if semana as u32 > Fecha::semanas_iso_en_anio(self.year) {
+            (self.year + 1, 1)
+        } else {
+            (self.year, semana as u32)
+        }
+    }
+
+    fn semanas_iso_en_anio(year: i32) -> u32 {
+        let primero = Fecha::new(1, 1, year);
+        match primero.dia_de_la_semana() {
+            DiaSemana::Jueves => 53,
+            DiaSemana::Miercoles if primero.es_bisiesto() => 53,
+            _ => 52,
+        }
+    }
+
+    /// Devuelve el domingo de Pascua del anio (calendario gregoriano)
+    pub fn domingo_de_pascua(year: i32) -> Fecha {
+        let a = year % 19;
+        let b = year / 100;
+        let c = year % 100;
+        let d = b / 4;
+        let e = b % 4;
+        let f = (b + 8) / 25;
+        let g = (b - f + 1) / 3;
+        let h = (19 * a + b - d - g + 15) % 30;
+        let i = c / 4;
+        let k = c % 4;
+        let l = (32 + 2 * e + 2 * i - h - k) % 7;
+        let m = (a + 11 * h + 22 * l) / 451;
+        let month = (h + l - 7 * m + 114) / 31;
+        let day = (h + l - 7 * m + 114) % 31 + 1;
+        Fecha::new(day as u32, month as u32, year)
+    }
+
+    /// Devuelve un iterador que recorre dia por dia desde `desde` hasta `hasta`, ambas incluidas.
+    /// Si `desde` es mayor que `hasta` el iterador no devuelve ninguna fecha
+    pub fn rango(desde: &Fecha, hasta: &Fecha) -> RangoFechas {
+        RangoFechas {
+            actual: desde.clone(),
+            hasta: hasta.clone(),
+        }
+    }
+
+    /// Devuelve la fecha que esta a la cantidad de dias indicada del 1/1/1970
+    fn desde_dias_epoca(dias: i64) -> Fecha {
+        let dias = dias + 719468;
+        let era = dias.div_euclid(146097);
+        let dia_de_era = dias.rem_euclid(146097);
+        let anio_de_era = (dia_de_era - dia_de_era / 1460 + dia_de_era / 36524 - dia_de_era / 146096) / 365;
+        let dia_del_anio = dia_de_era - (365 * anio_de_era + anio_de_era / 4 - anio_de_era / 100);
+        let mes_desde_marzo = (5 * dia_del_anio + 2) / 153;
+        let day = (dia_del_anio - (153 * mes_desde_marzo + 2) / 5 + 1) as u32;
+        let month = if mes_desde_marzo < 10 { mes_desde_marzo + 3 } else { mes_desde_marzo - 9 } as u32;
+        let year = (anio_de_era + era * 400 + (month <= 2) as i64) as i32;
+        Fecha::new(day, month, year)
+    }
+
+    /// Devuelve la cantidad de dias transcurridos desde el 1/1/1970
+    fn dias_desde_epoca(&self) -> i64 {
+        // Se cuentan los anios desde marzo para que el 29 de febrero quede al final
+        let year = self.year as i64 - (self.month <= 2) as i64;
+        let era = year.div_euclid(400);
+        let anio_de_era = year.rem_euclid(400);
+        let mes_desde_marzo = (self.month as i64 + 9) % 12;
+        let dia_del_anio = (153 * mes_desde_marzo + 2) / 5 + self.day as i64 - 1;
+        let dia_de_era = anio_de_era * 365 + anio_de_era / 4 - anio_de_era / 100 + dia_del_anio;
+        era * 146097 + dia_de_era - 719468
+    }
+
+    pub fn es_mayor(&self, una_fecha: &Fecha) -> bool {
+        self > una_fecha
+    }
+}
+
+impl DiaSemana {
+    /// Devuelve el numero de dia segun ISO-8601, del 1 (lunes) al 7 (domingo)
+    pub fn numero(&self) -> u32 {
+        match self {
+            DiaSemana::Lunes => 1,
+            DiaSemana::Martes => 2,
+            DiaSemana::Miercoles => 3,
+            DiaSemana::Jueves => 4,
+            DiaSemana::Viernes => 5,
+            DiaSemana::Sabado => 6,
+            DiaSemana::Domingo => 7,
+        }
+    }
+}
+
+impl Display for Fecha {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}/{}", self.day, self.month, self.year)
+    }
+}
+
+/// Acepta fechas como `dd/mm/yyyy`, `yyyy-mm-dd` (ISO-8601) o `d de mes de yyyy`
+impl FromStr for Fecha {
+    type Err = ErrorParseoFecha;
+
+    fn from_str(texto: &str) -> Result<Self, Self::Err> {
+        let texto = texto.trim();
+        if texto.contains('/') {
+            Fecha::parse_con_formato(texto, "%d/%m/%Y")
+        } else if texto.contains(" de ") {
+            Fecha::parse_con_formato(&texto.to_lowercase(), "%d de %B de %Y")
+        } else if texto.contains('-') {
+            Fecha::parse_con_formato(texto, "%Y-%m-%d")
+        } else {
+            Err(ErrorParseoFecha::Formato(texto.to_string()))
+        }
+    }
+}
+
+impl Display for ErrorParseoFecha {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorParseoFecha::Dia(valor) => write!(f, "Dia invalido: {}", valor),
+            ErrorParseoFecha::Mes(valor) => write!(f, "Mes invalido: {}", valor),
+            ErrorParseoFecha::Anio(valor) => write!(f, "Anio invalido: {}", valor),
+            ErrorParseoFecha::Formato(valor) => write!(f, "Formato invalido: {}", valor),
+        }
+    }
+}
+
+impl std::error::Error for ErrorParseoFecha {}
+
+/// Consume el caracter esperado del inicio del texto
+fn consumir_literal(texto: &str, esperado: char) -> Result<&str, ErrorParseoFecha> {
+    texto.strip_prefix(esperado)
+        .ok_or_else(|| ErrorParseoFecha::Formato(format!("Se esperaba '{}' en: {}", esperado, texto)))
+}
+
+/// Lee un numero de hasta `max_digitos` digitos del inicio del texto
+fn leer_numero(texto: &str, max_digitos: usize) -> Option<(u32, &str)> {
+    let digitos = texto.chars().take(max_digitos).take_while(|c| c.is_ascii_digit()).count();
+    if digitos == 0 {
+        return None;
+    }
+    texto[..digitos].parse().ok().map(|valor| (valor, &texto[digitos..]))
+}
+
+fn leer_anio(texto: &str) -> Result<(i32, &str), ErrorParseoFecha> {
+    let signo = texto.starts_with('-') as usize;
+    let digitos = texto[signo..].chars().take_while(|c| c.is_ascii_digit()).count();
+    texto[..signo + digitos].parse()
+        .map(|valor| (valor, &texto[signo + digitos..]))
+        .map_err(|_| ErrorParseoFecha::Anio(texto.to_string()))
+}
+
+fn leer_nombre_mes(texto: &str) -> Result<(u32, &str), ErrorParseoFecha> {
+    let largo: usize = texto.chars().take_while(|c| c.is_alphabetic()).map(|c| c.len_utf8()).sum();
+    let nombre = texto[..largo].to_lowercase();
+    let nombre = if nombre == "setiembre" { "septiembre" } else { nombre.as_str() };
+
+    MESES.iter().position(|mes| *mes == nombre)
+        .map(|pos| (pos as u32 + 1, &texto[largo..]))
+        .ok_or_else(|| ErrorParseoFecha::Mes(texto[..largo].to_string()))
+}
+
+impl From<Fecha> for FechaV1 {
+    fn from(fecha: Fecha) -> Self {
+        FechaV1 {
+            version: VERSION_SERIALIZACION,
+            dia: fecha.day,
+            mes: fecha.month,
+            anio: fecha.year,
+        }
+    }
+}
+
+impl TryFrom<FechaSerializada> for Fecha {
+    type Error = String;
+
+    fn try_from(serializada: FechaSerializada) -> Result<Self, Self::Error> {
+        match serializada {
+            FechaSerializada::V1(v1) if v1.version == VERSION_SERIALIZACION => {
+                Ok(Fecha::new(v1.dia, v1.mes, v1.anio))
+            }
+            FechaSerializada::V1(v1) => {
+                Err(format!("version de Fecha no soportada: {}", v1.version))
+            }
+            FechaSerializada::Legacy(legacy) => Ok(Fecha::new(legacy.day, legacy.month, legacy.year)),
+        }
+    }
+}
+
+impl Ord for Fecha {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.year, self.month, self.day).cmp(&(other.year, other.month, other.day))
+    }
+}
+
+impl PartialOrd for Fecha {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add<Duracion> for Fecha {
+    type Output = Fecha;
+
+    fn add(self, duracion: Duracion) -> Fecha {
+        Fecha::desde_dias_epoca(self.dias_desde_epoca() + duracion.dias)
+    }
+}
+
+impl Sub<Duracion> for Fecha {
+    type Output = Fecha;
+
+    fn sub(self, duracion: Duracion) -> Fecha {
+        Fecha::desde_dias_epoca(self.dias_desde_epoca() - duracion.dias)
+    }
+}
+
+impl Sub<Fecha> for Fecha {
+    type Output = Duracion;
+
+    fn sub(self, other: Fecha) -> Duracion {
+        Duracion::dias(other.dias_entre(&self))
+    }
+}
+
+impl Duracion {
+    pub fn dias(dias: i64) -> Self {
+        Duracion { dias }
+    }
+
+    pub fn semanas(semanas: i64) -> Self {
+        Duracion { dias: semanas * 7 }
+    }
+
+    pub fn en_dias(&self) -> i64 {
+        self.dias
+    }
+
+    pub fn en_segundos(&self) -> i64 {
+        self.dias * 86400
+    }
+}
+
+impl Iterator for RangoFechas {
+    type Item = Fecha;
+
+    fn next(&mut self) -> Option<Fecha> {
+        if self.actual > self.hasta {
+            return None;
+        }
+
+        let fecha = self.actual.clone();
+        self.actual.sumar_dias(1);
+        Some(fecha)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, HashSet};
+    use chrono::NaiveDate;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_es_fecha_valida() {
+        // Fecha válida
+        let fecha_valida = Fecha::new(15, 6, 2024);
+        assert!(fecha_valida.es_fecha_valida());
+
+        // Fecha inválida (día fuera de rango)
+        let fecha_invalida_dia = Fecha::new(32, 6, 2024);
+        assert!(!fecha_invalida_dia.es_fecha_valida());
+
+        // Fecha inválida (mes fuera de rango)
+        let fecha_invalida_mes = Fecha::new(15, 13, 2024);
+        assert!(!fecha_invalida_mes.es_fecha_valida());
+
+        // Fecha inválida (febrero en anio no bisiesto)
+        let fecha_invalida_febrero_no_bisiesto = Fecha::new(29, 2, 2023);
+        assert!(!fecha_invalida_febrero_no_bisiesto.es_fecha_valida());
+
+        // Fecha válida (febrero en anio bisiesto)
+        let fecha_valida_febrero_bisiesto = Fecha::new(29, 2, 2024);
+        assert!(fecha_valida_febrero_bisiesto.es_fecha_valida());
+    }
+
+    #[test]
+    fn test_es_bisiesto() {
+        // Anio bisiesto
+        let fecha_bisiesto = Fecha::new(1, 1, 2024);
+        assert!(fecha_bisiesto.es_bisiesto());
+
+        // Anio no bisiesto
+        let fecha_no_bisiesto = Fecha::new(1, 1, 2023);
+        assert!(!fecha_no_bisiesto.es_bisiesto());
+    }
+
+    #[test]
+    fn test_sumar_dias() {
+        let mut fecha = Fecha::new(1, 1, 2024);
+        fecha.sumar_dias(365);
+        assert!(fecha.equals(&Fecha::new(31, 12, 2024)));
+        fecha.sumar_dias(1);
+        assert!(fecha.equals(&Fecha::new(1, 1, 2025)));
+        fecha.sumar_dias(5);
+        assert!(fecha.equals(&Fecha::new(6, 1, 2025)));
+    }
+
+    #[test]
+    fn test_restar_dias() {
+        let mut fecha = Fecha::new(31, 12, 2024);
+        fecha.restar_dias(365);
+        assert!(fecha.equals(&Fecha::new(1, 1, 2024)));
+        fecha.restar_dias(1);
+        assert!(fecha.equals(&Fecha::new(31, 12, 2023)));
+        fecha.restar_dias(5);
+        assert!(fecha.equals(&Fecha::new(26, 12, 2023)));
+    }
+
+    #[test]
+    fn test_es_bisiesto_siglos() {
+        assert!(!Fecha::new(1, 1, 1900).es_bisiesto());
+        assert!(Fecha::new(1, 1, 2000).es_bisiesto());
+        assert!(!Fecha::new(1, 1, 2100).es_bisiesto());
+    }
+
+    #[test]
+    fn test_sumar_meses() {
+        let mut fecha = Fecha::new(15, 3, 2024);
+        fecha.sumar_meses(2);
+        assert!(fecha.equals(&Fecha::new(15, 5, 2024)));
+        fecha.sumar_meses(10);
+        assert!(fecha.equals(&Fecha::new(15, 3, 2025)));
+        fecha.sumar_meses(0);
+        assert!(fecha.equals(&Fecha::new(15, 3, 2025)));
+    }
+
+    #[test]
+    fn test_sumar_meses_ajusta_fin_de_mes() {
+        let mut fecha = Fecha::new(31, 1, 2024);
+        fecha.sumar_meses(1);
+        assert!(fecha.equals(&Fecha::new(29, 2, 2024)));
+
+        let mut fecha = Fecha::new(31, 1, 2023);
+        fecha.sumar_meses(1);
+        assert!(fecha.equals(&Fecha::new(28, 2, 2023)));
+
+        let mut fecha = Fecha::new(31, 8, 2024);
+        fecha.sumar_meses(1);
+        assert!(fecha.equals(&Fecha::new(30, 9, 2024)));
+    }
+
+    #[test]
+    fn test_sumar_anios() {
+        let mut fecha = Fecha::new(29, 2, 2024);
+        fecha.sumar_anios(1);
+        assert!(fecha.equals(&Fecha::new(28, 2, 2025)));
+
+        let mut fecha = Fecha::new(29, 2, 2024);
+        fecha.sumar_anios(4);
+        assert!(fecha.equals(&Fecha::new(29, 2, 2028)));
+    }
+
+    #[test]
+    fn test_dias_entre() {
+        let fecha1 = Fecha::new(1, 1, 2024);
+        let fecha2 = Fecha::new(31, 12, 2024);
+        assert_eq!(fecha1.dias_entre(&fecha2), 365);
+        assert_eq!(fecha2.dias_entre(&fecha1), -365);
+        assert_eq!(fecha1.dias_entre(&fecha1), 0);
+
+        let fecha3 = Fecha::new(1, 3, 2023);
+        let fecha4 = Fecha::new(28, 2, 2023);
+        assert_eq!(fecha3.dias_entre(&fecha4), -1);
+        assert_eq!(Fecha::new(1, 1, 1970).dias_entre(&Fecha::new(1, 1, 2000)), 10957);
+    }
+
+    #[test]
+    fn test_es_mayor() {
+        let fecha1 = Fecha::new(5, 3, 2024);
+        let fecha2 = Fecha::new(5, 3, 2023);
+        assert!(fecha1.es_mayor(&fecha2));
+
+        let fecha3 = Fecha::new(5, 3, 2023);
+        let fecha4 = Fecha::new(5, 3, 2024);
+        assert!(!fecha3.es_mayor(&fecha4));
+
+        let fecha5 = Fecha::new(5, 4, 2024);
+        let fecha6 = Fecha::new(5, 3, 2024);
+        assert!(fecha5.es_mayor(&fecha6));
+
+        let fecha7 = Fecha::new(5, 3, 2024);
+        let fecha8 = Fecha::new(5, 4, 2024);
+        assert!(!fecha7.es_mayor(&fecha8));
+
+        let fecha9 = Fecha::new(6, 3, 2024);
+        let fecha10 = Fecha::new(5, 3, 2024);
+        assert!(fecha9.es_mayor(&fecha10));
+
+        let fecha11 = Fecha::new(5, 3, 2024);
+        let fecha12 = Fecha::new(6, 3, 2024);
+        assert!(!fecha11.es_mayor(&fecha12));
+    }
+
+    #[test]
+    fn test_now() {
+        let fecha = Fecha::now();
+        let now = Local::now();
+        assert_eq!(fecha.day, now.day());
+        assert_eq!(fecha.month, now.month());
+        assert_eq!(fecha.year, now.year());
+    }
+
+    #[test]
+    fn test_ordenar_fechas() {
+        let mut fechas = vec![Fecha::new(5, 3, 2024), Fecha::new(1, 1, 2025), Fecha::new(31, 12, 2023), Fecha::new(6, 3, 2024)];
+        fechas.sort();
+        assert_eq!(fechas, vec![Fecha::new(31, 12, 2023), Fecha::new(5, 3, 2024), Fecha::new(6, 3, 2024), Fecha::new(1, 1, 2025)]);
+        assert_eq!(fechas.iter().max(), Some(&Fecha::new(1, 1, 2025)));
+    }
+
+    #[test]
+    fn test_fecha_como_clave() {
+        let mut por_fecha = BTreeMap::new();
+        por_fecha.insert(Fecha::new(2, 1, 2024), "segundo");
+        por_fecha.insert(Fecha::new(1, 1, 2024), "primero");
+        assert_eq!(por_fecha.values().collect::<Vec<_>>(), vec![&"primero", &"segundo"]);
+
+        let mut fechas = HashSet::new();
+        fechas.insert(Fecha::new(1, 1, 2024));
+        fechas.insert(Fecha::new(1, 1, 2024));
+        assert_eq!(fechas.len(), 1);
+    }
+
+    #[test]
+    fn test_operadores() {
+        assert_eq!(Fecha::new(28, 2, 2024) + Duracion::dias(2), Fecha::new(1, 3, 2024));
+        assert_eq!(Fecha::new(1, 3, 2024) - Duracion::dias(1), Fecha::new(29, 2, 2024));
+        assert_eq!(Fecha::new(1, 1, 2024) + Duracion::semanas(2), Fecha::new(15, 1, 2024));
+        assert_eq!(Fecha::new(1, 1, 2024) + Duracion::dias(-1), Fecha::new(31, 12, 2023));
+        assert_eq!(Fecha::new(1, 3, 2024) - Fecha::new(1, 2, 2024), Duracion::dias(29));
+        assert_eq!((Fecha::new(1, 2, 2024) - Fecha::new(1, 3, 2024)).en_dias(), -29);
+    }
+
+    #[test]
+    fn test_rango() {
+        let rango: Vec<Fecha> = Fecha::rango(&Fecha::new(30, 12, 2023), &Fecha::new(2, 1, 2024)).collect();
+        assert_eq!(rango, vec![Fecha::new(30, 12, 2023), Fecha::new(31, 12, 2023), Fecha::new(1, 1, 2024), Fecha::new(2, 1, 2024)]);
+
+        assert_eq!(Fecha::rango(&Fecha::new(1, 1, 2024), &Fecha::new(1, 1, 2024)).count(), 1);
+        assert_eq!(Fecha::rango(&Fecha::new(2, 1, 2024), &Fecha::new(1, 1, 2024)).count(), 0);
+    }
+
+    fn a_naive(fecha: &Fecha) -> NaiveDate {
+        NaiveDate::from_ymd_opt(fecha.year, fecha.month, fecha.day).unwrap()
+    }
+
+    fn fecha_valida() -> impl Strategy<Value = Fecha> {
+        // Entre el anio 1600 y el 2400 para cubrir varios anios seculares
+        (-135140i64..157054).prop_map(Fecha::desde_dias_epoca)
+    }
+
+    #[test]
+    fn test_serde_version_actual() {
+        let fecha = Fecha::new(5, 3, 2024);
+        let json = serde_json::to_string(&fecha).unwrap();
+        assert_eq!(json, r#"{"version":1,"dia":5,"mes":3,"anio":2024}"#);
+        let leida: Fecha = serde_json::from_str(&json).unwrap();
+        assert_eq!(leida, fecha);
+    }
+
+    #[test]
+    fn test_serde_formato_anterior() {
+        let leida: Fecha = serde_json::from_str(r#"{"day":5,"month":3,"year":2024}"#).unwrap();
+        assert_eq!(leida, Fecha::new(5, 3, 2024));
+
+        let leidas: Vec<Fecha> = serde_json::from_str(
+            r#"[{"day":1,"month":1,"year":2023},{"version":1,"dia":2,"mes":1,"anio":2023}]"#
+        ).unwrap();
+        assert_eq!(leidas, vec![Fecha::new(1, 1, 2023), Fecha::new(2, 1, 2023)]);
+    }
+
+    #[test]
+    fn test_serde_version_desconocida() {
+        let error = serde_json::from_str::<Fecha>(r#"{"version":2,"dia":5,"mes":3,"anio":2024}"#);
+        assert!(error.is_err());
+        assert!(serde_json::from_str::<Fecha>(r#"{"dia":5,"mes":3}"#).is_err());
+    }
+
+    #[test]
+    fn test_getters() {
+        let fecha = Fecha::new(5, 3, 2024);
+        assert_eq!((fecha.dia(), fecha.mes(), fecha.anio()), (5, 3, 2024));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_desde_dias_epoca_coincide_con_chrono(dias in -135140i64..157054) {
+            let esperada = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap() + chrono::Duration::days(dias);
+            let fecha = Fecha::desde_dias_epoca(dias);
+            prop_assert!(fecha.es_fecha_valida());
+            prop_assert_eq!(a_naive(&fecha), esperada);
+            prop_assert_eq!(fecha.dias_desde_epoca(), dias);
+        }
+
+        #[test]
+        fn prop_orden_coincide_con_chrono(a in fecha_valida(), b in fecha_valida()) {
+            prop_assert_eq!(a.cmp(&b), a_naive(&a).cmp(&a_naive(&b)));
+            prop_assert_eq!(a.es_mayor(&b), a_naive(&a) > a_naive(&b));
+        }
+
+        #[test]
+        fn prop_dias_entre_coincide_con_chrono(a in fecha_valida(), b in fecha_valida()) {
+            let esperado = a_naive(&b).signed_duration_since(a_naive(&a)).num_days();
+            prop_assert_eq!(a.dias_entre(&b), esperado);
+            prop_assert_eq!((b - a).en_dias(), esperado);
+        }
+
+        #[test]
+        fn prop_sumar_dias_coincide_con_chrono(a in fecha_valida(), dias in 0u32..2000) {
+            let esperada = a_naive(&a) + chrono::Duration::days(dias as i64);
+            prop_assert_eq!(a_naive(&(a.clone() + Duracion::dias(dias as i64))), esperada);
+            let mut fecha = a.clone();
+            fecha.sumar_dias(dias);
+            prop_assert_eq!(a_naive(&fecha), esperada);
+        }
+
+        #[test]
+        fn prop_restar_dias_coincide_con_chrono(a in fecha_valida(), dias in 0u32..2000) {
+            let esperada = a_naive(&a) - chrono::Duration::days(dias as i64);
+            prop_assert_eq!(a_naive(&(a.clone() - Duracion::dias(dias as i64))), esperada);
+            let mut fecha = a.clone();
+            fecha.restar_dias(dias);
+            prop_assert_eq!(a_naive(&fecha), esperada);
+        }
+
+        #[test]
+        fn prop_sumar_meses_coincide_con_chrono(a in fecha_valida(), meses in 0u32..240) {
+            let esperada = a_naive(&a).checked_add_months(chrono::Months::new(meses)).unwrap();
+            let mut fecha = a.clone();
+            fecha.sumar_meses(meses);
+            prop_assert_eq!(a_naive(&fecha), esperada);
+        }
+
+        #[test]
+        fn prop_es_bisiesto_coincide_con_chrono(year in 1600i32..2400) {
+            prop_assert_eq!(Fecha::new(1, 1, year).es_bisiesto(), NaiveDate::from_ymd_opt(year, 2, 29).is_some());
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Fecha::new(5, 3, 2024).to_string(), "5/3/2024");
+        assert_eq!(format!("{}", Fecha::new(15, 12, 2023)), "15/12/2023");
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("05/03/2024".parse::<Fecha>(), Ok(Fecha::new(5, 3, 2024)));
+        assert_eq!("5/3/2024".parse::<Fecha>(), Ok(Fecha::new(5, 3, 2024)));
+        assert_eq!("2024-03-05".parse::<Fecha>(), Ok(Fecha::new(5, 3, 2024)));
+        assert_eq!("5 de marzo de 2024".parse::<Fecha>(), Ok(Fecha::new(5, 3, 2024)));
+        assert_eq!("1 de Septiembre de 2023".parse::<Fecha>(), Ok(Fecha::new(1, 9, 2023)));
+        assert_eq!("20 de setiembre de 2023".parse::<Fecha>(), Ok(Fecha::new(20, 9, 2023)));
+    }
+
+    #[test]
+    fn test_from_str_errores() {
+        assert!(matches!("30/02/2024".parse::<Fecha>(), Err(ErrorParseoFecha::Dia(_))));
+        assert!(matches!("2024-13-01".parse::<Fecha>(), Err(ErrorParseoFecha::Mes(_))));
+        assert!(matches!("5 de marzzo de 2024".parse::<Fecha>(), Err(ErrorParseoFecha::Mes(_))));
+        assert!(matches!("05/03/abcd".parse::<Fecha>(), Err(ErrorParseoFecha::Anio(_))));
+        assert!(matches!("xx/03/2024".parse::<Fecha>(), Err(ErrorParseoFecha::Dia(_))));
+        assert!(matches!("05/03/2024 hola".parse::<Fecha>(), Err(ErrorParseoFecha::Formato(_))));
+        assert!(matches!("20240305".parse::<Fecha>(), Err(ErrorParseoFecha::Formato(_))));
+    }
+
+    #[test]
+    fn test_parse_con_formato() {
+        assert_eq!(Fecha::parse_con_formato("03.05.2024", "%m.%d.%Y"), Ok(Fecha::new(5, 3, 2024)));
+        assert_eq!(Fecha::parse_con_formato("2024/marzo/5", "%Y/%B/%-d"), Ok(Fecha::new(5, 3, 2024)));
+        assert_eq!(Fecha::parse_con_formato("5% 3 2024", "%d%% %m %Y"), Ok(Fecha::new(5, 3, 2024)));
+        assert!(matches!(Fecha::parse_con_formato("05/03", "%d/%m"), Err(ErrorParseoFecha::Anio(_))));
+        assert!(matches!(Fecha::parse_con_formato("05/03/2024", "%d/%m/%Q"), Err(ErrorParseoFecha::Formato(_))));
+    }
+
+    #[test]
+    fn test_formatear() {
+        let fecha = Fecha::new(5, 3, 2024);
+        assert_eq!(fecha.formatear("%d/%m/%Y"), "05/03/2024");
+        assert_eq!(fecha.formatear("%Y-%m-%d"), "2024-03-05");
+        assert_eq!(fecha.formatear("%-d de %B de %Y"), "5 de marzo de 2024");
+        assert_eq!(fecha.formatear("100%% %-m"), "100% 3");
+    }
+
+    proptest! {
+        #[test]
+        fn prop_formatear_y_parsear(a in fecha_valida()) {
+            for formato in ["%d/%m/%Y", "%Y-%m-%d", "%-d de %B de %Y"] {
+                prop_assert_eq!(Fecha::parse_con_formato(&a.formatear(formato), formato), Ok(a.clone()));
+                prop_assert_eq!(a.formatear(formato).parse::<Fecha>(), Ok(a.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_dia_de_la_semana() {
+        assert_eq!(Fecha::new(1, 1, 1970).dia_de_la_semana(), DiaSemana::Jueves);
+        assert_eq!(Fecha::new(9, 7, 1816).dia_de_la_semana(), DiaSemana::Martes);
+        assert_eq!(Fecha::new(19, 5, 2024).dia_de_la_semana(), DiaSemana::Domingo);
+        assert_eq!(Fecha::new(20, 5, 2024).dia_de_la_semana(), DiaSemana::Lunes);
+        assert!(Fecha::new(18, 5, 2024).es_fin_de_semana());
+        assert!(!Fecha::new(17, 5, 2024).es_fin_de_semana());
+    }
+
+    #[test]
+    fn test_dia_del_anio() {
+        assert_eq!(Fecha::new(1, 1, 2024).dia_del_anio(), 1);
+        assert_eq!(Fecha::new(1, 3, 2024).dia_del_anio(), 61);
+        assert_eq!(Fecha::new(1, 3, 2023).dia_del_anio(), 60);
+        assert_eq!(Fecha::new(31, 12, 2024).dia_del_anio(), 366);
+    }
+
+    #[test]
+    fn test_semana_iso() {
+        assert_eq!(Fecha::new(1, 1, 2024).semana_iso(), (2024, 1));
+        assert_eq!(Fecha::new(31, 12, 2024).semana_iso(), (2025, 1));
+        assert_eq!(Fecha::new(1, 1, 2021).semana_iso(), (2020, 53));
+        assert_eq!(Fecha::new(1, 1, 2023).semana_iso(), (2022, 52));
+        assert_eq!(Fecha::new(20, 5, 2024).semana_iso(), (2024, 21));
+    }
+
+    #[test]
+    fn test_domingo_de_pascua() {
+        assert_eq!(Fecha::domingo_de_pascua(2024), Fecha::new(31, 3, 2024));
+        assert_eq!(Fecha::domingo_de_pascua(2025), Fecha::new(20, 4, 2025));
+        assert_eq!(Fecha::domingo_de_pascua(2019), Fecha::new(21, 4, 2019));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_calendario_coincide_con_chrono(a in fecha_valida()) {
+            let naive = a_naive(&a);
+            prop_assert_eq!(a.dia_de_la_semana().numero(), naive.weekday().number_from_monday());
+            prop_assert_eq!(a.dia_del_anio(), naive.ordinal());
+            let iso = naive.iso_week();
+            prop_assert_eq!(a.semana_iso(), (iso.year(), iso.week()));
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::cell::RefCell;
+
+use super::{Fecha, FechaHora};
+
+/// Fuente de la fecha y hora actual. Permite reemplazar el reloj del sistema en los tests
+pub trait Reloj {
+    fn ahora(&self) -> FechaHora;
+
+    fn hoy(&self) -> Fecha {
+        self.ahora().into()
+    }
+}
+
+/// Reloj que devuelve la hora local del sistema
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RelojSistema;
+
+/// Reloj que se queda fijo en una fecha y hora hasta que se lo avanza manualmente
+#[derive(Debug)]
+pub struct RelojManual {
+    ahora: RefCell<FechaHora>,
+}
+
+impl Reloj for RelojSistema {
+    fn ahora(&self) -> FechaHora {
+        FechaHora::now()
+    }
+}
+
+impl RelojManual {
+    pub fn new(ahora: FechaHora) -> Self {
+        RelojManual {
+            ahora: RefCell::new(ahora)
+        }
+    }
+
+    pub fn set(&self, ahora: FechaHora) {
+        *self.ahora.borrow_mut() = ahora;
+    }
+
+    pub fn avanzar_segundos(&self, segundos: i64) {
+        self.ahora.borrow_mut().sumar_segundos(segundos);
+    }
+
+    pub fn avanzar_dias(&self, dias: u32) {
+        self.ahora.borrow_mut().fecha.sumar_dias(dias);
+    }
+
+    pub fn avanzar_meses(&self, meses: u32) {
+        self.ahora.borrow_mut().fecha.sumar_meses(meses);
+    }
+}
+
+impl Reloj for RelojManual {
+    fn ahora(&self) -> FechaHora {
+        self.ahora.borrow().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reloj_manual() {
+        let reloj = RelojManual::new(FechaHora::new(Fecha::new(31, 1, 2024), 23, 0, 0));
+        assert_eq!(reloj.hoy(), Fecha::new(31, 1, 2024));
+
+        reloj.avanzar_meses(1);
+        assert_eq!(reloj.hoy(), Fecha::new(29, 2, 2024));
+        reloj.avanzar_dias(1);
+        assert_eq!(reloj.hoy(), Fecha::new(1, 3, 2024));
+        reloj.avanzar_segundos(3600);
+        assert_eq!(reloj.ahora(), FechaHora::new(Fecha::new(2, 3, 2024), 0, 0, 0));
+
+        reloj.set(FechaHora::new(Fecha::new(1, 1, 2020), 0, 0, 0));
+        assert_eq!(reloj.hoy(), Fecha::new(1, 1, 2020));
+    }
+
+    #[test]
+    fn test_reloj_sistema() {
+        assert_eq!(RelojSistema.hoy(), Fecha::now());
+    }
+}
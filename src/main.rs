@@ -3,6 +3,9 @@ mod practica3;
 mod practica4;
 mod practica5;
 mod entregas;
+pub mod almacenamiento;
+pub mod dinero;
+pub mod fecha;
 
 fn main() {
     
@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use super::ej3::Fecha;
+use crate::fecha::Fecha;
 
 struct Biblioteca {
     nombre: String,
@@ -52,7 +52,7 @@ impl Prestamo {
         let mut result = String::new();
         result.push_str(&format!("Libro: {}\n", self.libro.isbn));
         result.push_str(&format!("Cliente: {}\n", self.cliente.to_string()));
-        result.push_str(&format!("Fecha de vencimiento: {}\n", self.fecha_vencimiento.to_string()));
+        result.push_str(&format!("Fecha de vencimiento: {}\n", self.fecha_vencimiento));
         result.push_str(&format!("Fecha de devolución: {}\n", 
             match &self.fecha_devolucion {
                 Some(fecha) => fecha.to_string(),
@@ -1,5 +1,5 @@
 use std::collections::VecDeque;
-use super::ej3::Fecha;
+use crate::fecha::Fecha;
 
 #[derive(Debug, Clone)]
 enum Animal {
@@ -1,7 +1,6 @@
 #![allow(dead_code)]
 pub mod ej1;
 pub mod ej2;
-pub mod ej3;
 pub mod ej4;
 pub mod ej5;
 pub mod ej6;
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
-use crate::practica3::ej3::Fecha;
+use crate::dinero::{Dinero, ErrorDinero, Moneda};
+use crate::fecha::Fecha;
 
 struct StreamingRust {
     usuarios: Vec<Usuario>,
@@ -218,21 +219,28 @@ impl Suscripcion {
         self.estado = EstadoSuscripcion::Inactiva;
     }
 
-    fn costo(&self) -> f64 {
-        self.tipo_suscripcion.costo() * self.duracion_meses as f64
+    fn costo(&self) -> Result<Dinero, ErrorDinero> {
+        self.tipo_suscripcion.costo().multiplicar(self.duracion_meses as i64)
     }
 
     fn esta_activa(&self) -> bool {
         self.estado == EstadoSuscripcion::Activa
     }
+    /// Devuelve la fecha en la que termina la suscripcion segun su duracion en meses
+    fn fecha_fin(&self) -> Fecha {
+        let mut fecha = self.fecha_inicio.clone();
+        fecha.sumar_meses(self.duracion_meses);
+        fecha
+    }
 }
 
 impl TipoSuscripcion {
-    fn costo(&self) -> f64 {
+    /// Costo mensual de la suscripcion
+    fn costo(&self) -> Dinero {
         match self {
-            TipoSuscripcion::Basic => 10.0,
-            TipoSuscripcion::Clasic => 20.0,
-            TipoSuscripcion::Super => 30.0,
+            TipoSuscripcion::Basic => Dinero::new(1000, Moneda::ars()),
+            TipoSuscripcion::Clasic => Dinero::new(2000, Moneda::ars()),
+            TipoSuscripcion::Super => Dinero::new(3000, Moneda::ars()),
         }
     }
 }
@@ -244,7 +252,14 @@ mod tests {
     #[test]
     fn test_subscripcion_cost() {
         let subscripcion = Suscripcion::new(TipoSuscripcion::Basic, 3, 1);
-        assert_eq!(subscripcion.costo(), 30.0);
+        assert_eq!(subscripcion.costo(), Ok(Dinero::new(3000, Moneda::ars())));
+    }
+
+    #[test]
+    fn test_subscripcion_fecha_fin() {
+        let mut subscripcion = Suscripcion::new(TipoSuscripcion::Basic, 3, 1);
+        subscripcion.fecha_inicio = Fecha::new(30, 11, 2024);
+        assert!(subscripcion.fecha_fin().equals(&Fecha::new(28, 2, 2025)));
     }
 
     #[test]
@@ -1,13 +1,17 @@
 //TODO: Terminar
-use std::ops::Deref;
-use crate::practica3::ej3::Fecha;
+use std::{ops::Deref, rc::Rc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use crate::dinero::{Dinero, ErrorDinero, Moneda, Porcentaje, Redondeo};
+use crate::almacenamiento::sqlite::{abrir_sqlite, error_sqlite, fecha_a_sql, fecha_desde_sql, valor_invalido};
+use crate::fecha::{FechaHora, Reloj, RelojSistema};
 
-const DESCUENTO_NEWSLETTER: f32 = 0.1; // 10%
+const DESCUENTO_NEWSLETTER: Porcentaje = Porcentaje::new(10);
 
 struct SistemaVentas {
     ventas: Vec<Venta>,
     vendedores: Vec<Vendedor>,
     clientes: Vec<Cliente>,
+    reloj: Rc<dyn Reloj>,
 }
 
 struct Venta {
@@ -15,7 +19,7 @@ struct Venta {
     dni_cliente: String,
     legajo_vendedor: u32,
     metodo_pago: MetodoPago,
-    fecha: Fecha
+    fecha: FechaHora
 }
 
 #[derive(Debug, PartialEq)]
@@ -26,10 +30,21 @@ enum MetodoPago {
     Debito  { numero_tarjeta: String, expiracion: String },
 }
 
+impl MetodoPago {
+    fn get_nombre(&self) -> &'static str {
+        match self {
+            MetodoPago::Efectivo => "efectivo",
+            MetodoPago::Credito { .. } => "credito",
+            MetodoPago::Transferencia { .. } => "transferencia",
+            MetodoPago::Debito { .. } => "debito",
+        }
+    }
+}
+
 #[derive(Clone)]
 struct Producto {
     nombre: String,
-    precio: f32,
+    precio: Dinero,
     categoria: CategoriaProducto,
 }
 
@@ -74,7 +89,7 @@ trait GestorClientes {
 
 trait GestorVentas {
     fn crear_venta(&mut self, productos: Vec<Producto>, datos_persona_cliente: DatosPersona, dni_cliente: String, datos_persona_vendedor: DatosPersona, legajo_vendedor: u32, metodo_pago: MetodoPago) -> &Venta;
-    fn get_precio_final_venta(&self, venta: &Venta) -> f32;
+    fn get_precio_final_venta(&self, venta: &Venta) -> Result<Dinero, ErrorDinero>;
 }
 
 trait Reporte {
@@ -117,15 +132,15 @@ impl GestorVentas for SistemaVentas {
             self.crear_vendedor(datos_persona_vendedor.nombre, datos_persona_vendedor.apellido, datos_persona_vendedor.direccion, datos_persona_vendedor.dni, legajo_vendedor);
         }
         
-        self.ventas.push(Venta::new(productos, dni_cliente, legajo_vendedor, metodo_pago));
+        self.ventas.push(Venta::new(productos, dni_cliente, legajo_vendedor, metodo_pago, self.reloj.ahora()));
         self.ventas.last().unwrap()
     }
 
-    fn get_precio_final_venta(&self, venta: &Venta) -> f32 {
+    fn get_precio_final_venta(&self, venta: &Venta) -> Result<Dinero, ErrorDinero> {
         if (self.get_cliente(&venta.dni_cliente)).unwrap().esta_suscrito() {
             venta.get_precio_final(DESCUENTO_NEWSLETTER)
         } else {
-            venta.get_precio_final(0.0)
+            venta.get_precio_final(Porcentaje::new(0))
         }
     }
 }
@@ -142,33 +157,41 @@ impl Reporte for SistemaVentas {
 
 impl SistemaVentas {
     fn new() -> Self {
+        SistemaVentas::new_con_reloj(Rc::new(RelojSistema))
+    }
+
+    fn new_con_reloj(reloj: Rc<dyn Reloj>) -> Self {
         SistemaVentas {
             ventas: Vec::new(),
             vendedores: Vec::new(),
             clientes: Vec::new(),
+            reloj,
         }
     }
 }
 
 impl Venta {
-    fn new(productos: Vec<Producto>, dni_cliente: String, legajo_vendedor: u32, metodo_pago: MetodoPago) -> Self {
+    fn new(productos: Vec<Producto>, dni_cliente: String, legajo_vendedor: u32, metodo_pago: MetodoPago, fecha: FechaHora) -> Self {
         Venta {
             productos,
             dni_cliente,
             legajo_vendedor,
             metodo_pago,
-            fecha: Fecha::now()
+            fecha
         }
     }
 
-    fn get_precio_final(&self, descuento: f32) -> f32 {
-        let total: f32 = self.productos.iter().map(|p| p.get_precio_final()).sum();
-        total * (1.0 - descuento)
+    /// Suma los precios finales de los productos y le aplica el descuento al total.
+    /// Los descuentos se redondean al centavo mas cercano
+    fn get_precio_final(&self, descuento: Porcentaje) -> Result<Dinero, ErrorDinero> {
+        let total = self.productos.iter()
+            .try_fold(Dinero::cero(Moneda::ars()), |total, p| total.sumar(&p.get_precio_final()?))?;
+        total.descontar(descuento, Redondeo::MitadArriba)
     }
 }
 
 impl Producto {
-    fn new(nombre: String, precio: f32, categoria: CategoriaProducto) -> Self {
+    fn new(nombre: String, precio: Dinero, categoria: CategoriaProducto) -> Self {
         Producto {
             nombre,
             precio,
@@ -176,8 +199,8 @@ impl Producto {
         }
     }
 
-    fn get_precio_final(&self) -> f32 {
-        self.precio * (1.0 - self.categoria.get_descuento())
+    fn get_precio_final(&self) -> Result<Dinero, ErrorDinero> {
+        self.precio.descontar(self.categoria.get_descuento(), Redondeo::MitadArriba)
     }
 
     fn get_nombre(&self) -> &String {
@@ -188,18 +211,37 @@ impl Producto {
         &self.categoria
     }
 
-    fn get_precio(&self) -> f32 {
-        self.precio
+    fn get_precio(&self) -> &Dinero {
+        &self.precio
     }
 }
 
 impl CategoriaProducto {
-    fn get_descuento(&self) -> f32 {
+    fn get_nombre(&self) -> &'static str {
         match self {
-            CategoriaProducto::Alimentos => 0.1,    // 10%
-            CategoriaProducto::Bebidas => 0.05,     // 5%
-            CategoriaProducto::Limpieza => 0.15,    // 15%
-            CategoriaProducto::Otros => 0.0,        // 0%
+            CategoriaProducto::Alimentos => "alimentos",
+            CategoriaProducto::Bebidas => "bebidas",
+            CategoriaProducto::Limpieza => "limpieza",
+            CategoriaProducto::Otros => "otros",
+        }
+    }
+
+    fn desde_nombre(nombre: &str) -> Option<CategoriaProducto> {
+        match nombre {
+            "alimentos" => Some(CategoriaProducto::Alimentos),
+            "bebidas" => Some(CategoriaProducto::Bebidas),
+            "limpieza" => Some(CategoriaProducto::Limpieza),
+            "otros" => Some(CategoriaProducto::Otros),
+            _ => None,
+        }
+    }
+
+    fn get_descuento(&self) -> Porcentaje {
+        match self {
+            CategoriaProducto::Alimentos => Porcentaje::new(10),
+            CategoriaProducto::Bebidas => Porcentaje::new(5),
+            CategoriaProducto::Limpieza => Porcentaje::new(15),
+            CategoriaProducto::Otros => Porcentaje::new(0),
         }
     }
 }
@@ -279,9 +321,241 @@ impl Cliente {
     }
 }
 
+const ESQUEMA_VENTAS: &str = "
+    CREATE TABLE IF NOT EXISTS vendedores (
+        legajo INTEGER PRIMARY KEY,
+        nombre TEXT NOT NULL,
+        apellido TEXT NOT NULL,
+        direccion TEXT NOT NULL,
+        dni TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS clientes (
+        dni TEXT PRIMARY KEY,
+        nombre TEXT NOT NULL,
+        apellido TEXT NOT NULL,
+        direccion TEXT NOT NULL,
+        correo TEXT
+    );
+    CREATE TABLE IF NOT EXISTS ventas (
+        id INTEGER PRIMARY KEY,
+        dni_cliente TEXT NOT NULL REFERENCES clientes (dni),
+        legajo_vendedor INTEGER NOT NULL REFERENCES vendedores (legajo),
+        metodo_pago TEXT NOT NULL,
+        numero_tarjeta TEXT,
+        expiracion TEXT,
+        cuenta TEXT,
+        fecha TEXT NOT NULL,
+        hora INTEGER NOT NULL,
+        minuto INTEGER NOT NULL,
+        segundo INTEGER NOT NULL,
+        offset_minutos INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS productos (
+        id INTEGER PRIMARY KEY,
+        venta_id INTEGER NOT NULL REFERENCES ventas (id),
+        nombre TEXT NOT NULL,
+        precio INTEGER NOT NULL,
+        moneda TEXT NOT NULL,
+        decimales INTEGER NOT NULL,
+        categoria TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_vendedores_dni ON vendedores (dni);
+    CREATE INDEX IF NOT EXISTS idx_ventas_cliente ON ventas (dni_cliente);
+    CREATE INDEX IF NOT EXISTS idx_ventas_vendedor ON ventas (legajo_vendedor);
+    CREATE INDEX IF NOT EXISTS idx_productos_venta ON productos (venta_id);
+    CREATE INDEX IF NOT EXISTS idx_productos_categoria ON productos (categoria);
+";
+
+const SELECT_VENTAS: &str = "
+    SELECT id, dni_cliente, legajo_vendedor, metodo_pago, numero_tarjeta, expiracion, cuenta,
+           fecha, hora, minuto, segundo, offset_minutos
+    FROM ventas
+";
+
+/// Sistema de ventas que guarda vendedores, clientes y ventas en una base SQLite, en un archivo
+/// o en memoria. Las busquedas por DNI y legajo usan los indices de la base y los reportes se
+/// resuelven con SQL. Las ventas se identifican por el id que devuelve `crear_venta`
+struct SistemaVentasSqlite {
+    conexion: Connection,
+    reloj: Rc<dyn Reloj>,
+}
+
+impl SistemaVentasSqlite {
+    /// Abre la base de datos en la ruta (o `EN_MEMORIA`) y crea las tablas que falten
+    fn abrir(ruta: &str) -> std::io::Result<Self> {
+        SistemaVentasSqlite::abrir_con_reloj(ruta, Rc::new(RelojSistema))
+    }
+
+    fn abrir_con_reloj(ruta: &str, reloj: Rc<dyn Reloj>) -> std::io::Result<Self> {
+        let conexion = abrir_sqlite(ruta)?;
+        conexion.execute_batch(ESQUEMA_VENTAS).map_err(error_sqlite)?;
+        Ok(SistemaVentasSqlite { conexion, reloj })
+    }
+
+    fn crear_vendedor(&mut self, nombre: String, apellido: String, direccion: String, dni: String, legajo: u32) -> std::io::Result<Vendedor> {
+        self.conexion.execute(
+            "INSERT INTO vendedores (legajo, nombre, apellido, direccion, dni) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![legajo, nombre, apellido, direccion, dni],
+        ).map_err(error_sqlite)?;
+
+        Ok(Vendedor::new(nombre, apellido, direccion, dni, legajo))
+    }
+
+    fn get_vendedor(&self, legajo: u32) -> std::io::Result<Option<Vendedor>> {
+        self.conexion.query_row(
+            "SELECT nombre, apellido, direccion, dni, legajo FROM vendedores WHERE legajo = ?1",
+            [legajo],
+            |fila| Ok(Vendedor::new(fila.get(0)?, fila.get(1)?, fila.get(2)?, fila.get(3)?, fila.get(4)?)),
+        ).optional().map_err(error_sqlite)
+    }
+
+    fn crear_cliente(&mut self, nombre: String, apellido: String, direccion: String, dni: String) -> std::io::Result<Cliente> {
+        self.conexion.execute(
+            "INSERT INTO clientes (dni, nombre, apellido, direccion) VALUES (?1, ?2, ?3, ?4)",
+            params![dni, nombre, apellido, direccion],
+        ).map_err(error_sqlite)?;
+
+        Ok(Cliente::new(nombre, apellido, direccion, dni))
+    }
+
+    fn get_cliente(&self, dni: &str) -> std::io::Result<Option<Cliente>> {
+        self.conexion.query_row(
+            "SELECT nombre, apellido, direccion, dni, correo FROM clientes WHERE dni = ?1",
+            [dni],
+            |fila| {
+                let mut cliente = Cliente::new(fila.get(0)?, fila.get(1)?, fila.get(2)?, fila.get(3)?);
+                cliente.correo = fila.get(4)?;
+                Ok(cliente)
+            },
+        ).optional().map_err(error_sqlite)
+    }
+
+    /// Guarda el correo del cliente para el newsletter, o lo borra si es None
+    fn set_newsletter(&mut self, dni: &str, correo: Option<String>) -> std::io::Result<()> {
+        let modificados = self.conexion.execute("UPDATE clientes SET correo = ?1 WHERE dni = ?2", params![correo, dni]).map_err(error_sqlite)?;
+
+        if modificados == 0 {
+            Err(std::io::Error::new(std::io::ErrorKind::NotFound, "No se encontró el cliente"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Registra la venta y sus productos, y crea el cliente y el vendedor si no existian, todo en
+    /// una sola transaccion. Devuelve el id de la venta
+    fn crear_venta(&mut self, productos: Vec<Producto>, datos_persona_cliente: DatosPersona, dni_cliente: String, datos_persona_vendedor: DatosPersona, legajo_vendedor: u32, metodo_pago: MetodoPago) -> std::io::Result<i64> {
+        let fecha = self.reloj.ahora();
+        let (numero_tarjeta, expiracion, cuenta) = match &metodo_pago {
+            MetodoPago::Efectivo => (None, None, None),
+            MetodoPago::Credito { numero_tarjeta, expiracion } | MetodoPago::Debito { numero_tarjeta, expiracion } => (Some(numero_tarjeta), Some(expiracion), None),
+            MetodoPago::Transferencia { cuenta } => (None, None, Some(cuenta)),
+        };
+
+        let transaccion = self.conexion.transaction().map_err(error_sqlite)?;
+        transaccion.execute(
+            "INSERT OR IGNORE INTO clientes (dni, nombre, apellido, direccion) VALUES (?1, ?2, ?3, ?4)",
+            params![dni_cliente, datos_persona_cliente.nombre, datos_persona_cliente.apellido, datos_persona_cliente.direccion],
+        ).map_err(error_sqlite)?;
+        transaccion.execute(
+            "INSERT OR IGNORE INTO vendedores (legajo, nombre, apellido, direccion, dni) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![legajo_vendedor, datos_persona_vendedor.nombre, datos_persona_vendedor.apellido, datos_persona_vendedor.direccion, datos_persona_vendedor.dni],
+        ).map_err(error_sqlite)?;
+        transaccion.execute(
+            "INSERT INTO ventas (dni_cliente, legajo_vendedor, metodo_pago, numero_tarjeta, expiracion, cuenta, fecha, hora, minuto, segundo, offset_minutos)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![dni_cliente, legajo_vendedor, metodo_pago.get_nombre(), numero_tarjeta, expiracion, cuenta,
+                fecha_a_sql(fecha.fecha()), fecha.hora(), fecha.minuto(), fecha.segundo(), fecha.offset_minutos()],
+        ).map_err(error_sqlite)?;
+        let id = transaccion.last_insert_rowid();
+
+        {
+            let mut insertar = transaccion.prepare("INSERT INTO productos (venta_id, nombre, precio, moneda, decimales, categoria) VALUES (?1, ?2, ?3, ?4, ?5, ?6)").map_err(error_sqlite)?;
+            for producto in &productos {
+                let precio = &producto.precio;
+                insertar.execute(params![id, producto.nombre, precio.unidades(), precio.moneda().codigo(), precio.moneda().decimales(), producto.categoria.get_nombre()]).map_err(error_sqlite)?;
+            }
+        }
+
+        transaccion.commit().map_err(error_sqlite)?;
+        Ok(id)
+    }
+
+    fn get_venta(&self, id: i64) -> std::io::Result<Option<Venta>> {
+        let ventas = self.consultar_ventas(&format!("{} WHERE id = ?1", SELECT_VENTAS), params![id])?;
+        Ok(ventas.into_iter().next())
+    }
+
+    /// Devuelve las ventas hechas al cliente, en el orden en que se registraron
+    fn get_ventas_cliente(&self, dni: &str) -> std::io::Result<Vec<Venta>> {
+        self.consultar_ventas(&format!("{} WHERE dni_cliente = ?1 ORDER BY id", SELECT_VENTAS), params![dni])
+    }
+
+    fn get_precio_final_venta(&self, id: i64) -> std::io::Result<Dinero> {
+        let venta = self.get_venta(id)?.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "No se encontró la venta"))?;
+        let suscrito = self.get_cliente(&venta.dni_cliente)?.is_some_and(|c| c.esta_suscrito());
+
+        venta.get_precio_final(if suscrito { DESCUENTO_NEWSLETTER } else { Porcentaje::new(0) }).map_err(std::io::Error::other)
+    }
+
+    fn ventas_totales_vendedor(&self, legajo: u32) -> std::io::Result<u32> {
+        self.conexion.query_row("SELECT COUNT(*) FROM ventas WHERE legajo_vendedor = ?1", [legajo], |fila| fila.get(0)).map_err(error_sqlite)
+    }
+
+    fn ventas_totales_categoria(&self, categoria: CategoriaProducto) -> std::io::Result<u32> {
+        self.conexion.query_row("SELECT COUNT(*) FROM productos WHERE categoria = ?1", [categoria.get_nombre()], |fila| fila.get(0)).map_err(error_sqlite)
+    }
+
+    fn consultar_ventas(&self, consulta: &str, parametros: impl rusqlite::Params) -> std::io::Result<Vec<Venta>> {
+        let mut sentencia = self.conexion.prepare(consulta).map_err(error_sqlite)?;
+        let filas = sentencia.query_map(parametros, |fila| Ok((fila.get::<_, i64>(0)?, venta_desde_fila(fila)?))).map_err(error_sqlite)?;
+        let mut productos = self.conexion.prepare("SELECT nombre, precio, moneda, decimales, categoria FROM productos WHERE venta_id = ?1 ORDER BY id").map_err(error_sqlite)?;
+
+        let mut ventas = Vec::new();
+        for fila in filas {
+            let (id, mut venta) = fila.map_err(error_sqlite)?;
+            venta.productos = productos.query_map([id], producto_desde_fila).map_err(error_sqlite)?
+                .collect::<rusqlite::Result<Vec<Producto>>>().map_err(error_sqlite)?;
+            ventas.push(venta);
+        }
+
+        Ok(ventas)
+    }
+}
+
+/// Arma la venta sin sus productos, que se guardan en otra tabla
+fn venta_desde_fila(fila: &Row) -> rusqlite::Result<Venta> {
+    let metodo: String = fila.get(3)?;
+    let metodo_pago = match metodo.as_str() {
+        "efectivo" => MetodoPago::Efectivo,
+        "credito" => MetodoPago::Credito { numero_tarjeta: fila.get(4)?, expiracion: fila.get(5)? },
+        "debito" => MetodoPago::Debito { numero_tarjeta: fila.get(4)?, expiracion: fila.get(5)? },
+        "transferencia" => MetodoPago::Transferencia { cuenta: fila.get(6)? },
+        _ => return Err(valor_invalido(3, &metodo)),
+    };
+    let fecha = FechaHora::new_con_offset(fecha_desde_sql(&fila.get::<_, String>(7)?)?, fila.get(8)?, fila.get(9)?, fila.get(10)?, fila.get(11)?);
+
+    Ok(Venta::new(vec![], fila.get(1)?, fila.get(2)?, metodo_pago, fecha))
+}
+
+fn producto_desde_fila(fila: &Row) -> rusqlite::Result<Producto> {
+    let moneda: String = fila.get(2)?;
+    let categoria: String = fila.get(4)?;
+    Ok(Producto::new(
+        fila.get(0)?,
+        Dinero::new(fila.get(1)?, Moneda::new(&moneda, fila.get(3)?)),
+        CategoriaProducto::desde_nombre(&categoria).ok_or_else(|| valor_invalido(4, &categoria))?,
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::almacenamiento::sqlite::EN_MEMORIA;
+    use crate::fecha::{Fecha, RelojManual};
+
+    fn pesos(pesos: i64) -> Dinero {
+        Dinero::new(pesos * 100, Moneda::ars())
+    }
 
     #[test]
     fn test_crear_vendedor() {
@@ -337,8 +611,8 @@ mod tests {
     fn test_crear_venta() {
         let mut sistema = SistemaVentas::new();
         let productos = vec![
-            Producto::new("Pan".to_string(), 100.0, CategoriaProducto::Alimentos),
-            Producto::new("Coca".to_string(), 150.0, CategoriaProducto::Bebidas),
+            Producto::new("Pan".to_string(), pesos(100), CategoriaProducto::Alimentos),
+            Producto::new("Coca".to_string(), pesos(150), CategoriaProducto::Bebidas),
         ];
         let datos_persona_cliente = DatosPersona::new("Juan".to_string(), "Perez".to_string(), "Calle falsa 123".to_string(), "12345678".to_string());
         let datos_persona_vendedor = DatosPersona::new("Pedro".to_string(), "Gomez".to_string(), "Calle falsa 456".to_string(), "87654321".to_string());
@@ -352,12 +626,27 @@ mod tests {
         assert_eq!(venta.metodo_pago, MetodoPago::Efectivo);
     }
     
+    #[test]
+    fn test_crear_venta_con_reloj() {
+        let reloj = Rc::new(RelojManual::new(FechaHora::new(Fecha::new(1, 3, 2024), 10, 0, 0)));
+        let mut sistema = SistemaVentas::new_con_reloj(reloj.clone());
+        let datos_persona_cliente = DatosPersona::new("Juan".to_string(), "Perez".to_string(), "Calle falsa 123".to_string(), "12345678".to_string());
+        let datos_persona_vendedor = DatosPersona::new("Pedro".to_string(), "Gomez".to_string(), "Calle falsa 456".to_string(), "87654321".to_string());
+
+        sistema.crear_venta(vec![], datos_persona_cliente.clone(), "12345678".to_string(), datos_persona_vendedor.clone(), 1, MetodoPago::Efectivo);
+        reloj.avanzar_segundos(1);
+        sistema.crear_venta(vec![], datos_persona_cliente, "12345678".to_string(), datos_persona_vendedor, 1, MetodoPago::Efectivo);
+
+        assert_eq!(sistema.ventas[0].fecha, FechaHora::new(Fecha::new(1, 3, 2024), 10, 0, 0));
+        assert!(sistema.ventas[0].fecha < sistema.ventas[1].fecha);
+    }
+
     #[test]
     fn test_ventas_totales_vendedor() {
         let mut sistema = SistemaVentas::new();
         let productos = vec![
-            Producto::new("Pan".to_string(), 100.0, CategoriaProducto::Alimentos),
-            Producto::new("Coca".to_string(), 150.0, CategoriaProducto::Bebidas),
+            Producto::new("Pan".to_string(), pesos(100), CategoriaProducto::Alimentos),
+            Producto::new("Coca".to_string(), pesos(150), CategoriaProducto::Bebidas),
         ];
         let datos_persona_cliente = DatosPersona::new("Juan".to_string(), "Perez".to_string(), "Calle falsa 123".to_string(), "12345678".to_string());
         let datos_persona_vendedor = DatosPersona::new("Pedro".to_string(), "Gomez".to_string(), "Calle falsa 456".to_string(), "87654321".to_string());
@@ -374,8 +663,8 @@ mod tests {
     fn test_ventas_totales_categoria() {
         let mut sistema = SistemaVentas::new();
         let productos = vec![
-            Producto::new("Pan".to_string(), 100.0, CategoriaProducto::Alimentos),
-            Producto::new("Coca".to_string(), 150.0, CategoriaProducto::Bebidas),
+            Producto::new("Pan".to_string(), pesos(100), CategoriaProducto::Alimentos),
+            Producto::new("Coca".to_string(), pesos(150), CategoriaProducto::Bebidas),
         ];
         let datos_persona_cliente = DatosPersona::new("Juan".to_string(), "Perez".to_string(), "Calle falsa 123".to_string(), "12345678".to_string());
         let datos_persona_vendedor = DatosPersona::new("Pedro".to_string(), "Gomez".to_string(), "Calle falsa 456".to_string(), "87654321".to_string());
@@ -393,42 +682,48 @@ mod tests {
     fn test_get_precio_final_venta() {
         let mut sistema = SistemaVentas::new();
         let productos = vec![
-            Producto::new("Pan".to_string(), 100.0, CategoriaProducto::Alimentos),
-            Producto::new("Coca".to_string(), 150.0, CategoriaProducto::Bebidas),
+            Producto::new("Pan".to_string(), pesos(100), CategoriaProducto::Alimentos),
+            Producto::new("Coca".to_string(), pesos(150), CategoriaProducto::Bebidas),
         ];
         let datos_persona_cliente = DatosPersona::new("Juan".to_string(), "Perez".to_string(), "Calle falsa 123".to_string(), "12345678".to_string());
         let datos_persona_vendedor = DatosPersona::new("Pedro".to_string(), "Gomez".to_string(), "Calle falsa 456".to_string(), "87654321".to_string());
         sistema.crear_venta(productos.clone(), datos_persona_cliente.clone(), "12345678".to_string(), datos_persona_vendedor.clone(), 1, MetodoPago::Efectivo);
-        assert_eq!(sistema.get_precio_final_venta(&sistema.ventas[0]), 232.5);
+        assert_eq!(sistema.get_precio_final_venta(&sistema.ventas[0]), Ok(Dinero::new(23250, Moneda::ars())));
 
         sistema.get_cliente_mut("12345678").unwrap().correo = Some("test@example.com".to_string());
 
-        assert_eq!(sistema.get_precio_final_venta(&sistema.ventas[0]), 209.25);
+        assert_eq!(sistema.get_precio_final_venta(&sistema.ventas[0]), Ok(Dinero::new(20925, Moneda::ars())));
     }
 
     #[test]
     fn test_get_precio_final() {
         let productos = vec![
-            Producto::new("Pan".to_string(), 100.0, CategoriaProducto::Alimentos),
-            Producto::new("Coca".to_string(), 150.0, CategoriaProducto::Bebidas),
+            Producto::new("Pan".to_string(), pesos(100), CategoriaProducto::Alimentos),
+            Producto::new("Coca".to_string(), pesos(150), CategoriaProducto::Bebidas),
         ];
-        let venta = Venta::new(productos, "12345678".to_string(), 1, MetodoPago::Efectivo);
-        assert_eq!(venta.get_precio_final(0.0), 232.5);
-        assert_eq!(venta.get_precio_final(0.1), 209.25);
+        let venta = Venta::new(productos, "12345678".to_string(), 1, MetodoPago::Efectivo, FechaHora::now());
+        assert_eq!(venta.get_precio_final(Porcentaje::new(0)), Ok(Dinero::new(23250, Moneda::ars())));
+        assert_eq!(venta.get_precio_final(Porcentaje::new(10)), Ok(Dinero::new(20925, Moneda::ars())));
+        // El descuento del 5% sobre 0,15 es 0,0075 y se redondea a 0,01
+        let venta = Venta::new(vec![Producto::new("Chicle".to_string(), Dinero::new(15, Moneda::ars()), CategoriaProducto::Otros)], "12345678".to_string(), 1, MetodoPago::Efectivo, FechaHora::now());
+        assert_eq!(venta.get_precio_final(Porcentaje::new(5)), Ok(Dinero::new(14, Moneda::ars())));
+        // No se pueden mezclar monedas en una misma venta
+        let venta = Venta::new(vec![Producto::new("Importado".to_string(), Dinero::new(100, Moneda::usd()), CategoriaProducto::Otros)], "12345678".to_string(), 1, MetodoPago::Efectivo, FechaHora::now());
+        assert!(venta.get_precio_final(Porcentaje::new(0)).is_err());
     }
 
     #[test]
     fn test_get_precio_final_producto() {
-        let producto = Producto::new("Pan".to_string(), 100.0, CategoriaProducto::Alimentos);
-        assert_eq!(producto.get_precio_final(), 90.0);
+        let producto = Producto::new("Pan".to_string(), pesos(100), CategoriaProducto::Alimentos);
+        assert_eq!(producto.get_precio_final(), Ok(pesos(90)));
     }
 
     #[test]
     fn test_get_descuento_categoria() {
-        assert_eq!(CategoriaProducto::Alimentos.get_descuento(), 0.1);
-        assert_eq!(CategoriaProducto::Bebidas.get_descuento(), 0.05);
-        assert_eq!(CategoriaProducto::Limpieza.get_descuento(), 0.15);
-        assert_eq!(CategoriaProducto::Otros.get_descuento(), 0.0);
+        assert_eq!(CategoriaProducto::Alimentos.get_descuento(), Porcentaje::new(10));
+        assert_eq!(CategoriaProducto::Bebidas.get_descuento(), Porcentaje::new(5));
+        assert_eq!(CategoriaProducto::Limpieza.get_descuento(), Porcentaje::new(15));
+        assert_eq!(CategoriaProducto::Otros.get_descuento(), Porcentaje::new(0));
     }
 
     #[test]
@@ -442,4 +737,87 @@ mod tests {
         let vendedor = Vendedor::new("Juan".to_string(), "Perez".to_string(), "Calle falsa 123".to_string(), "12345678".to_string(), 1);
         assert_eq!(*vendedor, vendedor.datos);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_sqlite_vendedores_y_clientes() {
+        let mut sistema = SistemaVentasSqlite::abrir(EN_MEMORIA).unwrap();
+        let vendedor = sistema.crear_vendedor("Juan".to_string(), "Perez".to_string(), "Calle falsa 123".to_string(), "12345678".to_string(), 1).unwrap();
+        assert_eq!(sistema.get_vendedor(1).unwrap(), Some(vendedor));
+        assert_eq!(sistema.get_vendedor(2).unwrap(), None);
+        // El legajo es unico
+        assert!(sistema.crear_vendedor("Otro".to_string(), "Perez".to_string(), "Calle falsa 123".to_string(), "1".to_string(), 1).is_err());
+
+        let cliente = sistema.crear_cliente("Ana".to_string(), "Lopez".to_string(), "Calle falsa 456".to_string(), "87654321".to_string()).unwrap();
+        assert_eq!(sistema.get_cliente("87654321").unwrap(), Some(cliente));
+        assert!(sistema.set_newsletter("87654321", Some("ana@example.com".to_string())).is_ok());
+        assert!(sistema.get_cliente("87654321").unwrap().unwrap().esta_suscrito());
+        assert!(sistema.set_newsletter("11111111", None).is_err());
+    }
+
+    #[test]
+    fn test_sqlite_crear_venta() {
+        let reloj = Rc::new(RelojManual::new(FechaHora::new_con_offset(Fecha::new(1, 3, 2024), 10, 0, 0, -180)));
+        let mut sistema = SistemaVentasSqlite::abrir_con_reloj(EN_MEMORIA, reloj).unwrap();
+        let productos = vec![
+            Producto::new("Pan".to_string(), pesos(100), CategoriaProducto::Alimentos),
+            Producto::new("Coca".to_string(), pesos(150), CategoriaProducto::Bebidas),
+        ];
+        let datos_persona_cliente = DatosPersona::new("Juan".to_string(), "Perez".to_string(), "Calle falsa 123".to_string(), "12345678".to_string());
+        let datos_persona_vendedor = DatosPersona::new("Pedro".to_string(), "Gomez".to_string(), "Calle falsa 456".to_string(), "87654321".to_string());
+        let metodo_pago = MetodoPago::Credito { numero_tarjeta: "4111".to_string(), expiracion: "12/30".to_string() };
+        let id = sistema.crear_venta(productos, datos_persona_cliente, "12345678".to_string(), datos_persona_vendedor, 1, metodo_pago).unwrap();
+
+        let venta = sistema.get_venta(id).unwrap().unwrap();
+        assert_eq!(venta.productos.len(), 2);
+        assert_eq!(venta.productos[1].get_nombre(), "Coca");
+        assert_eq!(venta.dni_cliente, "12345678");
+        assert_eq!(venta.legajo_vendedor, 1);
+        assert_eq!(venta.metodo_pago, MetodoPago::Credito { numero_tarjeta: "4111".to_string(), expiracion: "12/30".to_string() });
+        assert_eq!(venta.fecha, FechaHora::new(Fecha::new(1, 3, 2024), 13, 0, 0));
+        assert_eq!(venta.fecha.offset_minutos(), -180);
+        assert!(sistema.get_cliente("12345678").unwrap().is_some());
+        assert_eq!(sistema.get_vendedor(1).unwrap().unwrap().get_dni(), "87654321");
+        assert_eq!(sistema.get_ventas_cliente("12345678").unwrap().len(), 1);
+        assert!(sistema.get_venta(id + 1).unwrap().is_none());
+
+        assert_eq!(sistema.get_precio_final_venta(id).unwrap(), Dinero::new(23250, Moneda::ars()));
+        assert!(sistema.set_newsletter("12345678", Some("test@example.com".to_string())).is_ok());
+        assert_eq!(sistema.get_precio_final_venta(id).unwrap(), Dinero::new(20925, Moneda::ars()));
+    }
+
+    #[test]
+    fn test_sqlite_crear_venta_fallida_no_deja_cambios() {
+        let mut sistema = SistemaVentasSqlite::abrir(EN_MEMORIA).unwrap();
+        let datos_persona_cliente = DatosPersona::new("Juan".to_string(), "Perez".to_string(), "Calle falsa 123".to_string(), "12345678".to_string());
+        let datos_persona_vendedor = DatosPersona::new("Pedro".to_string(), "Gomez".to_string(), "Calle falsa 456".to_string(), "87654321".to_string());
+
+        // Se rompe la tabla de productos para que falle el ultimo paso de la transaccion
+        sistema.conexion.execute_batch("DROP TABLE productos").unwrap();
+        let productos = vec![Producto::new("Pan".to_string(), pesos(100), CategoriaProducto::Alimentos)];
+        assert!(sistema.crear_venta(productos, datos_persona_cliente, "12345678".to_string(), datos_persona_vendedor, 1, MetodoPago::Efectivo).is_err());
+        assert!(sistema.get_cliente("12345678").unwrap().is_none());
+        assert!(sistema.get_vendedor(1).unwrap().is_none());
+        assert_eq!(sistema.ventas_totales_vendedor(1).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_sqlite_reportes() {
+        let mut sistema = SistemaVentasSqlite::abrir(EN_MEMORIA).unwrap();
+        let productos = vec![
+            Producto::new("Pan".to_string(), pesos(100), CategoriaProducto::Alimentos),
+            Producto::new("Coca".to_string(), pesos(150), CategoriaProducto::Bebidas),
+        ];
+        let datos_persona_cliente = DatosPersona::new("Juan".to_string(), "Perez".to_string(), "Calle falsa 123".to_string(), "12345678".to_string());
+        let datos_persona_vendedor = DatosPersona::new("Pedro".to_string(), "Gomez".to_string(), "Calle falsa 456".to_string(), "87654321".to_string());
+        sistema.crear_venta(productos.clone(), datos_persona_cliente.clone(), "12345678".to_string(), datos_persona_vendedor.clone(), 1, MetodoPago::Efectivo).unwrap();
+        sistema.crear_venta(productos.clone(), datos_persona_cliente.clone(), "12345678".to_string(), datos_persona_vendedor.clone(), 1, MetodoPago::Transferencia { cuenta: "123".to_string() }).unwrap();
+        sistema.crear_venta(productos.clone(), datos_persona_cliente.clone(), "12345678".to_string(), datos_persona_vendedor.clone(), 2, MetodoPago::Efectivo).unwrap();
+
+        assert_eq!(sistema.ventas_totales_vendedor(1).unwrap(), 2);
+        assert_eq!(sistema.ventas_totales_vendedor(2).unwrap(), 1);
+        assert_eq!(sistema.ventas_totales_vendedor(3).unwrap(), 0);
+        assert_eq!(sistema.ventas_totales_categoria(CategoriaProducto::Alimentos).unwrap(), 3);
+        assert_eq!(sistema.ventas_totales_categoria(CategoriaProducto::Limpieza).unwrap(), 0);
+        assert_eq!(sistema.get_ventas_cliente("12345678").unwrap()[1].metodo_pago, MetodoPago::Transferencia { cuenta: "123".to_string() });
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use rand::{thread_rng, Rng};
 
-use crate::practica3::ej3::Fecha;
+use crate::fecha::FechaHora;
 
 struct XYZ {
     usuarios: Vec<Usuario>,
@@ -33,7 +33,7 @@ struct BlockChain {
 }
 
 struct Transaccion {
-    fecha: Fecha,
+    fecha: FechaHora,
     tipo: TipoTransaccion,
     dni_usuario: String
 }
@@ -139,7 +139,7 @@ impl GestorMonedas for XYZ {
 
 impl GestorTransacciones for XYZ {
     fn crear_transaccion(&mut self, tipo: TipoTransaccion, dni_usuario: &str) {
-        self.transacciones.push(Transaccion { fecha: Fecha::now(), tipo, dni_usuario: dni_usuario.to_string() });
+        self.transacciones.push(Transaccion { fecha: FechaHora::now(), tipo, dni_usuario: dni_usuario.to_string() });
     }
 }
 
@@ -1,6 +1,8 @@
-use std::{fmt::{Display, Formatter}, fs::File, io::{self, Read, Write}};
+use std::{fmt::{Display, Formatter}, io, rc::Rc};
 use serde::{Deserialize, Serialize};
 
+use crate::almacenamiento::{cargar_json, escribir_json, Almacenamiento, ErrorCarga, PoliticaCarga, Versionado};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 enum Color {
     Rojo,
@@ -24,7 +26,8 @@ struct ConcesionarioAuto {
     nombre: String,
     direccion: String,
     capacidad: usize,
-    autos: Vec<Auto>
+    autos: Vec<Auto>,
+    almacenamiento: Rc<dyn Almacenamiento>
 }
 
 #[derive(Debug)]
@@ -101,36 +104,32 @@ impl Auto {
     }
 }
 
+// Formatos guardados, todavia en su version 1
+impl Versionado for Vec<Auto> {}
+
 impl ConcesionarioAuto {
-    fn new(nombre: String, direccion: String, capacidad: usize) -> ConcesionarioAuto {
-        let autos = match std::fs::File::open("test/".to_string() + nombre.as_str() + ".json") {
-            Ok(mut file) => {
-                let mut buf = String::new();
-                //TODO: Preguntar si se puede hacer unwrap en lugar de match
-                file.read_to_string(&mut buf).unwrap();
-                let autos: Vec<Auto> = serde_json::from_str(&buf).unwrap();
-
-                if autos.len() > capacidad {
-                    Vec::with_capacity(capacidad)
-                } else {
-                    autos
-                }
-            },
-            Err(_) => Vec::with_capacity(capacidad)
-        };
-        ConcesionarioAuto {
+    /// Crea el concesionario con los autos guardados usando `PoliticaCarga::compatible`.
+    /// Entra en panico solo si lo guardado esta corrupto y sin respaldo valido, para manejar el error usar `cargar`
+    fn new(nombre: String, direccion: String, capacidad: usize, almacenamiento: Rc<dyn Almacenamiento>) -> ConcesionarioAuto {
+        ConcesionarioAuto::cargar(nombre, direccion, capacidad, almacenamiento, &PoliticaCarga::compatible()).unwrap()
+    }
+
+    fn cargar(nombre: String, direccion: String, capacidad: usize, almacenamiento: Rc<dyn Almacenamiento>, politica: &PoliticaCarga) -> Result<ConcesionarioAuto, ErrorCarga> {
+        let autos: Vec<Auto> = cargar_json(almacenamiento.as_ref(), &nombre, politica)?;
+        let mut autos = politica.ajustar_capacidad(autos, capacidad)?;
+        autos.reserve(capacidad - autos.len());
+
+        Ok(ConcesionarioAuto {
             nombre,
             direccion,
             capacidad,
             autos,
-        }
+            almacenamiento,
+        })
     }
 
     fn escribir_archivo(&self) -> Result<(), io::Error> {
-        let mut file = File::create("test/".to_string() + self.nombre.as_str() + ".json")?;
-        let serialized = serde_json::to_string(&self.autos)?;
-        file.write_all(&serialized.as_bytes())?;
-        Ok(())
+        escribir_json(self.almacenamiento.as_ref(), &self.nombre, &self.autos)
     }
 
     fn agregar_auto(&mut self, auto: Auto) -> Result<(), ErrorConcesionaria> {
@@ -176,6 +175,7 @@ impl Display for ErrorConcesionaria {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::almacenamiento::{fixtures::cargar_fixtures, AccionCapacidad, AlmacenamientoMemoria};
 
     #[test]
     fn test_color_es_primario() {
@@ -221,7 +221,7 @@ mod tests {
 
     #[test]
     fn test_new_concesionario_auto() {
-        let concesionario = ConcesionarioAuto::new("test_new_concesionario_auto".to_string(), "Calle A".to_string(), 10);
+        let concesionario = ConcesionarioAuto::new("test_new_concesionario_auto".to_string(), "Calle A".to_string(), 10, Rc::new(AlmacenamientoMemoria::new()));
 
         assert_eq!(concesionario.nombre, "test_new_concesionario_auto");
         assert_eq!(concesionario.direccion, "Calle A");
@@ -231,12 +231,13 @@ mod tests {
 
     #[test]
     fn test_new_concesionario_auto_file() {
-        let mut concesionario1 = ConcesionarioAuto::new("test_new_concesionario_auto_file".to_string(), "Calle A".to_string(), 10);
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+        let mut concesionario1 = ConcesionarioAuto::new("test_new_concesionario_auto_file".to_string(), "Calle A".to_string(), 10, almacenamiento.clone());
 
         let auto1 = Auto::new("Toyota".to_string(), "Corolla".to_string(), 2022, 25000.0, Color::Azul);
         concesionario1.agregar_auto(auto1).unwrap();
 
-        let mut concesionario2 = ConcesionarioAuto::new("test_new_concesionario_auto_file".to_string(), "Calle A".to_string(), 10);
+        let mut concesionario2 = ConcesionarioAuto::new("test_new_concesionario_auto_file".to_string(), "Calle A".to_string(), 10, almacenamiento);
         let auto2 = Auto::new("BMW".to_string(), "X5".to_string(), 2000, 50000.0, Color::Rojo);
         concesionario2.agregar_auto(auto2).unwrap();
 
@@ -246,9 +247,53 @@ mod tests {
         assert_eq!(concesionario2.autos.len(), 2);
     }
 
+    #[test]
+    fn test_cargar_concesionario_sobre_capacidad() {
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+        let mut concesionario = ConcesionarioAuto::new("test_cargar_sobre_capacidad".to_string(), "Calle A".to_string(), 3, almacenamiento.clone());
+        concesionario.agregar_auto(Auto::new("Toyota".to_string(), "Corolla".to_string(), 2022, 25000.0, Color::Azul)).unwrap();
+        concesionario.agregar_auto(Auto::new("BMW".to_string(), "X5".to_string(), 2020, 50000.0, Color::Rojo)).unwrap();
+        concesionario.agregar_auto(Auto::new("Audi".to_string(), "A3".to_string(), 2020, 50000.0, Color::Verde)).unwrap();
+
+        let politica = PoliticaCarga::default();
+        let error = ConcesionarioAuto::cargar("test_cargar_sobre_capacidad".to_string(), "Calle A".to_string(), 2, almacenamiento.clone(), &politica);
+        assert_eq!(error.err(), Some(ErrorCarga::SobreCapacidad { guardados: 3, capacidad: 2 }));
+
+        let politica = PoliticaCarga { sobre_capacidad: AccionCapacidad::Truncar, ..PoliticaCarga::default() };
+        let concesionario = ConcesionarioAuto::cargar("test_cargar_sobre_capacidad".to_string(), "Calle A".to_string(), 2, almacenamiento.clone(), &politica).unwrap();
+        assert_eq!(concesionario.autos.len(), 2);
+        assert_eq!(concesionario.autos[1].marca, "BMW");
+
+        let politica = PoliticaCarga { sobre_capacidad: AccionCapacidad::Descartar, ..PoliticaCarga::default() };
+        let concesionario = ConcesionarioAuto::cargar("test_cargar_sobre_capacidad".to_string(), "Calle A".to_string(), 2, almacenamiento.clone(), &politica).unwrap();
+        assert_eq!(concesionario.autos.len(), 0);
+
+        // `new` no entra en panico si hay mas autos guardados que la capacidad, se queda con los primeros
+        let concesionario = ConcesionarioAuto::new("test_cargar_sobre_capacidad".to_string(), "Calle A".to_string(), 2, almacenamiento.clone());
+        assert_eq!(concesionario.autos.len(), 2);
+        assert_eq!(concesionario.autos[1].marca, "BMW");
+
+        // Lo guardado no se toca al cargar, al reabrir con mas capacidad siguen todos los autos
+        let concesionario = ConcesionarioAuto::new("test_cargar_sobre_capacidad".to_string(), "Calle A".to_string(), 3, almacenamiento);
+        assert_eq!(concesionario.autos.len(), 3);
+        assert_eq!(concesionario.autos[2].marca, "Audi");
+    }
+
+    #[test]
+    fn test_cargar_concesionario_corrupto() {
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+        almacenamiento.escribir("test_cargar_corrupto", "[{\"marca\":").unwrap();
+
+        let resultado = ConcesionarioAuto::cargar("test_cargar_corrupto".to_string(), "Calle A".to_string(), 2, almacenamiento.clone(), &PoliticaCarga::default());
+        assert!(matches!(resultado, Err(ErrorCarga::Corrupto(_))));
+
+        let resultado = ConcesionarioAuto::cargar("test_no_existe".to_string(), "Calle A".to_string(), 2, almacenamiento, &PoliticaCarga::estricta());
+        assert_eq!(resultado.err(), Some(ErrorCarga::NoEncontrado("test_no_existe".to_string())));
+    }
+
     #[test]
     fn test_agregar_auto() {
-        let mut concesionario = ConcesionarioAuto::new("test_agregar_auto".to_string(), "Calle A".to_string(), 2);
+        let mut concesionario = ConcesionarioAuto::new("test_agregar_auto".to_string(), "Calle A".to_string(), 2, Rc::new(AlmacenamientoMemoria::new()));
         let auto1 = Auto::new("Toyota".to_string(), "Corolla".to_string(), 2022, 25000.0, Color::Azul);
         let auto2 = Auto::new("BMW".to_string(), "X5".to_string(), 2020, 50000.0, Color::Rojo);
 
@@ -259,7 +304,7 @@ mod tests {
 
     #[test]
     fn test_eliminar_auto() {
-        let mut concesionario = ConcesionarioAuto::new("test_eliminar_auto".to_string(), "Calle A".to_string(), 2);
+        let mut concesionario = ConcesionarioAuto::new("test_eliminar_auto".to_string(), "Calle A".to_string(), 2, Rc::new(AlmacenamientoMemoria::new()));
         let auto1 = Auto::new("Toyota".to_string(), "Corolla".to_string(), 2022, 25000.0, Color::Amarillo);
         let auto2 = Auto::new("BMW".to_string(), "X5".to_string(), 2020, 50000.0, Color::Rojo);
         let auto3 = Auto::new("Audi".to_string(), "A3".to_string(), 2020, 50000.0, Color::Verde);
@@ -275,7 +320,7 @@ mod tests {
 
     #[test]
     fn test_buscar_auto() {
-        let mut concesionario = ConcesionarioAuto::new("test_buscar_auto".to_string(), "Calle A".to_string(), 2);
+        let mut concesionario = ConcesionarioAuto::new("test_buscar_auto".to_string(), "Calle A".to_string(), 2, Rc::new(AlmacenamientoMemoria::new()));
         let auto1 = Auto::new("Toyota".to_string(), "Corolla".to_string(), 2022, 25000.0, Color::Azul);
         let auto2 = Auto::new("BMW".to_string(), "X5".to_string(), 2020, 50000.0, Color::Rojo);
         let auto3 = Auto::new("BMW".to_string(), "M3 Classic".to_string(), 1999, 20000.0, Color::Negro);
@@ -287,4 +332,14 @@ mod tests {
         assert!(concesionario.buscar_auto(&auto2).unwrap().equals(&auto2));
         assert!(concesionario.buscar_auto(&auto3).is_none())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_cargar_fixtures() {
+        for (version, almacenamiento) in cargar_fixtures("practica5/ej1") {
+            let concesionario = ConcesionarioAuto::cargar("fixture".to_string(), "Calle A".to_string(), 10, almacenamiento, &PoliticaCarga::estricta())
+                .unwrap_or_else(|e| panic!("{}: {}", version, e));
+            assert_eq!(concesionario.autos.len(), 2, "{}", version);
+            assert_eq!(concesionario.autos[1].calcular_precio(), 70000.0, "{}", version);
+        }
+    }
+}
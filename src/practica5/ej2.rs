@@ -1,6 +1,8 @@
-use std::io::{Read, Write};
+use std::rc::Rc;
 use serde::{Deserialize, Serialize};
 
+use crate::almacenamiento::{cargar_json, escribir_json, Almacenamiento, ErrorCarga, PoliticaCarga, Versionado};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 enum Genero {
     Rock,
@@ -56,32 +58,33 @@ impl Cancion {
 
 struct Playlist {
     nombre: String,
-    canciones: Vec<Cancion>
+    canciones: Vec<Cancion>,
+    almacenamiento: Rc<dyn Almacenamiento>
 }
 
+// Formatos guardados, todavia en su version 1
+impl Versionado for Vec<Cancion> {}
+
 impl Playlist {
-    fn new(nombre: String, mut canciones: Vec<Cancion>) -> Playlist {
-        let canciones = match std::fs::File::open("test/".to_owned() + nombre.as_str() + ".json") {
-            Ok(mut file) => {
-                let mut buf = String::new();
-                file.read_to_string(&mut buf).unwrap();
-                let mut canciones_nuevas: Vec<Cancion> = serde_json::from_str(&buf).unwrap();
-                canciones_nuevas.append(&mut canciones);
-                canciones_nuevas
-            },
-            Err(_) => canciones
-        };
-        Playlist {
+    /// Crea la playlist agregando las canciones al final de las guardadas usando `PoliticaCarga::compatible`.
+    /// Entra en panico solo si lo guardado esta corrupto y sin respaldo valido, para manejar el error usar `cargar`
+    fn new(nombre: String, canciones: Vec<Cancion>, almacenamiento: Rc<dyn Almacenamiento>) -> Playlist {
+        Playlist::cargar(nombre, canciones, almacenamiento, &PoliticaCarga::compatible()).unwrap()
+    }
+
+    fn cargar(nombre: String, mut canciones: Vec<Cancion>, almacenamiento: Rc<dyn Almacenamiento>, politica: &PoliticaCarga) -> Result<Playlist, ErrorCarga> {
+        let mut canciones_guardadas: Vec<Cancion> = cargar_json(almacenamiento.as_ref(), &nombre, politica)?;
+        canciones_guardadas.append(&mut canciones);
+
+        Ok(Playlist {
             nombre,
-            canciones
-        }
+            canciones: canciones_guardadas,
+            almacenamiento
+        })
     }
 
     fn escribir_archivo(&self) -> Result<(), std::io::Error> {
-        let mut file = std::fs::File::create("test/".to_owned() + self.nombre.as_str() + ".json")?;
-        let serialized = serde_json::to_string(&self.canciones)?;
-        file.write_all(&serialized.as_bytes())?;
-        Ok(())
+        escribir_json(self.almacenamiento.as_ref(), &self.nombre, &self.canciones)
     }
 
     fn agregar_cancion(&mut self, cancion: Cancion) -> Result<(), std::io::Error> {
@@ -179,6 +182,7 @@ impl Playlist {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::almacenamiento::{fixtures::cargar_fixtures, AlmacenamientoMemoria};
 
     #[test]
     fn test_new_cancion() {
@@ -194,7 +198,7 @@ mod tests {
         let playlist = Playlist::new("test_new_playlist".to_string(), vec![
             Cancion::new("Bohemian Rhapsody".to_string(), "Queen".to_string(), Genero::Rock),
             Cancion::new("Stairway to Heaven".to_string(), "Led Zeppelin".to_string(), Genero::Rock)
-        ]);
+        ], Rc::new(AlmacenamientoMemoria::new()));
 
         assert_eq!(playlist.nombre, "test_new_playlist");
         assert_eq!(playlist.canciones.len(), 2);
@@ -202,16 +206,17 @@ mod tests {
 
     #[test]
     fn test_new_playlist_with_songs() {
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
         let playlist = Playlist::new("test_new_playlist_with_songs_1".to_string(), vec![
             Cancion::new("Bohemian Rhapsody".to_string(), "Queen".to_string(), Genero::Rock),
             Cancion::new("Stairway to Heaven".to_string(), "Led Zeppelin".to_string(), Genero::Rock)
-        ]);
+        ], almacenamiento.clone());
 
         playlist.escribir_archivo().unwrap();
 
         let playlist = Playlist::new("test_new_playlist_with_songs_1".to_string(), vec![
             Cancion::new("Thriller".to_string(), "Micheal Jackson".to_string(), Genero::Pop),
-        ]);
+        ], almacenamiento);
 
         assert_eq!(playlist.nombre, "test_new_playlist_with_songs_1");
         assert_eq!(playlist.canciones.len(), 3);
@@ -221,7 +226,7 @@ mod tests {
     fn test_agregar_cancion() {
         let mut playlist = Playlist::new("test_agregar_cancion".to_string(), vec![
             Cancion::new("Bohemian Rhapsody".to_string(), "Queen".to_string(), Genero::Rock),
-        ]);
+        ], Rc::new(AlmacenamientoMemoria::new()));
 
         let cancion = Cancion::new("Stairway to Heaven".to_string(), "Led Zeppelin".to_string(), Genero::Rock);
         playlist.agregar_cancion(cancion.clone()).unwrap();
@@ -235,7 +240,7 @@ mod tests {
         let mut playlist = Playlist::new("test_eliminar_cancion".to_string(), vec![
             Cancion::new("Bohemian Rhapsody".to_string(), "Queen".to_string(), Genero::Rock),
             Cancion::new("Stairway to Heaven".to_string(), "Led Zeppelin".to_string(), Genero::Rock)
-        ]);
+        ], Rc::new(AlmacenamientoMemoria::new()));
 
         let cancion = Cancion::new("Bohemian Rhapsody".to_string(), "Queen".to_string(), Genero::Rock);
         playlist.eliminar_cancion(&cancion).unwrap();
@@ -249,7 +254,7 @@ mod tests {
             Cancion::new("Bohemian Rhapsody".to_string(), "Queen".to_string(), Genero::Rock),
             Cancion::new("Stairway to Heaven".to_string(), "Led Zeppelin".to_string(), Genero::Rock),
             Cancion::new("Hotel California".to_string(), "Eagles".to_string(), Genero::Rock)
-        ]);
+        ], Rc::new(AlmacenamientoMemoria::new()));
 
         let cancion = Cancion::new("Stairway to Heaven".to_string(), "Led Zeppelin".to_string(), Genero::Rock);
         playlist.mover_cancion(&cancion, 0).unwrap();
@@ -263,7 +268,7 @@ mod tests {
             Cancion::new("Bohemian Rhapsody".to_string(), "Queen".to_string(), Genero::Rock),
             Cancion::new("Stairway to Heaven".to_string(), "Led Zeppelin".to_string(), Genero::Rock),
             Cancion::new("Hotel California".to_string(), "Eagles".to_string(), Genero::Rock)
-        ]);
+        ], Rc::new(AlmacenamientoMemoria::new()));
 
         let cancion = playlist.buscar_cancion_por_nombre("Stairway to Heaven".to_string());
 
@@ -278,7 +283,7 @@ mod tests {
             Cancion::new("Thriller".to_string(), "Michael Jackson".to_string(), Genero::Pop),
             Cancion::new("Stairway to Heaven".to_string(), "Led Zeppelin".to_string(), Genero::Rock),
             Cancion::new("Billie Jean".to_string(), "Michael Jackson".to_string(), Genero::Pop),
-        ]);
+        ], Rc::new(AlmacenamientoMemoria::new()));
 
         let canciones_rock = playlist.obtener_canciones_genero(&Genero::Rock);
         let canciones_pop = playlist.obtener_canciones_genero(&Genero::Pop);
@@ -294,7 +299,7 @@ mod tests {
             Cancion::new("Thriller".to_string(), "Michael Jackson".to_string(), Genero::Pop),
             Cancion::new("Stairway to Heaven".to_string(), "Led Zeppelin".to_string(), Genero::Rock),
             Cancion::new("Billie Jean".to_string(), "Michael Jackson".to_string(), Genero::Pop),
-        ]);
+        ], Rc::new(AlmacenamientoMemoria::new()));
 
         let canciones_queen = playlist.obtener_canciones_artista("Queen".to_string());
         let canciones_mj = playlist.obtener_canciones_artista("Michael Jackson".to_string());
@@ -308,7 +313,7 @@ mod tests {
         let mut playlist = Playlist::new("test_modificar_titulo".to_string(), vec![
             Cancion::new("Bohemian Rhapsody".to_string(), "Queen".to_string(), Genero::Rock),
             Cancion::new("Stairway to Heaven".to_string(), "Led Zeppelin".to_string(), Genero::Rock)
-        ]);
+        ], Rc::new(AlmacenamientoMemoria::new()));
 
         playlist.modificar_titulo("Best of Rock".to_string());
 
@@ -320,10 +325,20 @@ mod tests {
         let mut playlist = Playlist::new("test_vaciar".to_string(), vec![
             Cancion::new("Bohemian Rhapsody".to_string(), "Queen".to_string(), Genero::Rock),
             Cancion::new("Stairway to Heaven".to_string(), "Led Zeppelin".to_string(), Genero::Rock)
-        ]);
+        ], Rc::new(AlmacenamientoMemoria::new()));
 
         playlist.vaciar().unwrap();
 
         assert_eq!(playlist.canciones.len(), 0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_cargar_fixtures() {
+        for (version, almacenamiento) in cargar_fixtures("practica5/ej2") {
+            let playlist = Playlist::cargar("fixture".to_string(), vec![], almacenamiento, &PoliticaCarga::estricta())
+                .unwrap_or_else(|e| panic!("{}: {}", version, e));
+            assert_eq!(playlist.canciones.len(), 2, "{}", version);
+            assert_eq!(playlist.obtener_canciones_genero(&Genero::Pop).len(), 1, "{}", version);
+        }
+    }
+}
@@ -1,7 +1,8 @@
-use std::{collections::VecDeque, io::{Read, Write}};
+use std::{collections::VecDeque, rc::Rc};
 use serde::{Deserialize, Serialize};
 
-use crate::practica3::ej3::Fecha;
+use crate::almacenamiento::{cargar_json, escribir_json, Almacenamiento, ErrorCarga, PoliticaCarga, Versionado};
+use crate::fecha::Fecha;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 enum Animal {
@@ -126,28 +127,31 @@ struct Veterinaria {
     direccion: String,
     id: u32,
     atenciones: Vec<AtencionRealizada>,
-    cola: VecDeque<Mascota>
+    cola: VecDeque<Mascota>,
+    almacenamiento: Rc<dyn Almacenamiento>
 }
 
+// Formatos guardados, todavia en su version 1
+impl Versionado for Vec<AtencionRealizada> {}
+
 impl Veterinaria {
-    fn new(nombre: String, direccion: String, id: u32) -> Veterinaria {
-        let atenciones = match std::fs::File::open("test/".to_owned() + nombre.as_str() + ".json") {
-            Ok(mut file) => {
-                let mut buf = String::new();
-                file.read_to_string(&mut buf).unwrap();
-                let atenciones: Vec<AtencionRealizada> = serde_json::from_str(&buf).unwrap();
-                atenciones
-            },
-            Err(_) => Vec::new()
-        };
-
-        Veterinaria {
+    /// Crea la veterinaria con las atenciones guardadas usando `PoliticaCarga::compatible`.
+    /// Entra en panico solo si lo guardado esta corrupto y sin respaldo valido, para manejar el error usar `cargar`
+    fn new(nombre: String, direccion: String, id: u32, almacenamiento: Rc<dyn Almacenamiento>) -> Veterinaria {
+        Veterinaria::cargar(nombre, direccion, id, almacenamiento, &PoliticaCarga::compatible()).unwrap()
+    }
+
+    fn cargar(nombre: String, direccion: String, id: u32, almacenamiento: Rc<dyn Almacenamiento>, politica: &PoliticaCarga) -> Result<Veterinaria, ErrorCarga> {
+        let atenciones = cargar_json(almacenamiento.as_ref(), &nombre, politica)?;
+
+        Ok(Veterinaria {
             nombre,
             direccion,
             id,
             atenciones,
-            cola: VecDeque::new()
-        }
+            cola: VecDeque::new(),
+            almacenamiento
+        })
     }
 
     fn agregar_mascota(&mut self, mascota: Mascota) {
@@ -181,10 +185,7 @@ impl Veterinaria {
     }
 
     fn escribir_archivo(&self) -> Result<(), std::io::Error> {
-        let mut file = std::fs::File::create("test/".to_owned() + self.nombre.as_str() + ".json")?;
-        let serialized = serde_json::to_string(&self.atenciones)?;
-        file.write_all(&serialized.as_bytes())?;
-        Ok(())
+        escribir_json(self.almacenamiento.as_ref(), &self.nombre, &self.atenciones)
     }
 
     fn registrar_atencion(&mut self, atencion: AtencionRealizada) -> Result<(), std::io::Error> {
@@ -280,6 +281,7 @@ impl Veterinaria {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::almacenamiento::{fixtures::cargar_fixtures, AlmacenamientoMemoria};
 
     #[test]
     fn test_to_string_animal() {
@@ -307,7 +309,8 @@ mod tests {
 
     #[test]
     fn test_new_veterinaria_con_atenciones() {
-        let mut vet1 = Veterinaria::new("test_new_veterinaria_con_atenciones".to_string(), "Dirección".to_string(), 1);
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+        let mut vet1 = Veterinaria::new("test_new_veterinaria_con_atenciones".to_string(), "Dirección".to_string(), 1, almacenamiento.clone());
         
         assert_eq!(vet1.atenciones.len(), 0);
         assert!(vet1.registrar_atencion(AtencionRealizada::new(
@@ -318,7 +321,7 @@ mod tests {
         )).is_ok());
         assert_eq!(vet1.atenciones.len(), 1);
 
-        let vet2 = Veterinaria::new("test_new_veterinaria_con_atenciones".to_string(), "Dirección".to_string(), 1);
+        let vet2 = Veterinaria::new("test_new_veterinaria_con_atenciones".to_string(), "Dirección".to_string(), 1, almacenamiento);
 
         assert_eq!(vet2.nombre, "test_new_veterinaria_con_atenciones");
         assert_eq!(vet2.atenciones.len(), 1);
@@ -326,7 +329,7 @@ mod tests {
     
     #[test]
     fn test_agregar_mascota() {
-        let mut vet = Veterinaria::new("test_agregar_mascota".to_string(), "Dirección".to_string(), 1);
+        let mut vet = Veterinaria::new("test_agregar_mascota".to_string(), "Dirección".to_string(), 1, Rc::new(AlmacenamientoMemoria::new()));
         let mascota = Mascota::new("Luna".to_string(), 5, Animal::Perro, Duenio::new("Juan".to_string(), "Calle A".to_string(), "123456789".to_string()));
         
         vet.agregar_mascota(mascota.clone());
@@ -339,7 +342,7 @@ mod tests {
     
     #[test]
     fn test_agregar_mascota_prioridad() {
-        let mut vet = Veterinaria::new("test_agregar_mascota_prioridad".to_string(), "Dirección".to_string(), 1);
+        let mut vet = Veterinaria::new("test_agregar_mascota_prioridad".to_string(), "Dirección".to_string(), 1, Rc::new(AlmacenamientoMemoria::new()));
         let mascota1 = Mascota::new("Luna".to_string(), 5, Animal::Perro, Duenio::new("Juan".to_string(), "Calle A".to_string(), "123456789".to_string()));
         let mascota2 = Mascota::new("Sol".to_string(), 3, Animal::Gato, Duenio::new("Maria".to_string(), "Calle B".to_string(), "987654321".to_string()));
         
@@ -354,7 +357,7 @@ mod tests {
     
     #[test]
     fn test_atender_mascota() {
-        let mut vet = Veterinaria::new("test_atender_mascota".to_string(), "Dirección".to_string(), 1);
+        let mut vet = Veterinaria::new("test_atender_mascota".to_string(), "Dirección".to_string(), 1, Rc::new(AlmacenamientoMemoria::new()));
         let mascota = Mascota::new("Luna".to_string(), 5, Animal::Perro, Duenio::new("Juan".to_string(), "Calle A".to_string(), "123456789".to_string()));
         
         vet.agregar_mascota(mascota.clone());
@@ -368,7 +371,7 @@ mod tests {
     
     #[test]
     fn test_eliminar_mascota() {
-        let mut vet = Veterinaria::new("test_eliminar_mascota".to_string(), "Dirección".to_string(), 1);
+        let mut vet = Veterinaria::new("test_eliminar_mascota".to_string(), "Dirección".to_string(), 1, Rc::new(AlmacenamientoMemoria::new()));
         let mascota = Mascota::new("Luna".to_string(), 5, Animal::Perro, Duenio::new("Juan".to_string(), "Calle A".to_string(), "123456789".to_string()));
         
         vet.agregar_mascota(mascota.clone());
@@ -380,7 +383,7 @@ mod tests {
     
     #[test]
     fn test_registrar_atencion() {
-        let mut vet = Veterinaria::new("test_registrar_atencion".to_string(), "Dirección".to_string(), 1);
+        let mut vet = Veterinaria::new("test_registrar_atencion".to_string(), "Dirección".to_string(), 1, Rc::new(AlmacenamientoMemoria::new()));
         let atencion = AtencionRealizada::new(Mascota::new("Luna".to_string(), 5, Animal::Perro, Duenio::new("Juan".to_string(), "Calle A".to_string(), "123456789".to_string())), "Diagnóstico".to_string(), "Tratamiento".to_string(), None);
         
         vet.registrar_atencion(atencion.clone()).unwrap();
@@ -393,7 +396,7 @@ mod tests {
     
     #[test]
     fn test_buscar_atencion_mascota() {
-        let mut vet = Veterinaria::new("test_buscar_atencion_mascota".to_string(), "Dirección".to_string(), 1);
+        let mut vet = Veterinaria::new("test_buscar_atencion_mascota".to_string(), "Dirección".to_string(), 1, Rc::new(AlmacenamientoMemoria::new()));
         let mascota = Mascota::new("Luna".to_string(), 5, Animal::Perro, Duenio::new("Juan".to_string(), "Calle A".to_string(), "123456789".to_string()));
         let atencion = AtencionRealizada::new(mascota.clone(), "Diagnóstico".to_string(), "Tratamiento".to_string(), None);
         
@@ -406,7 +409,7 @@ mod tests {
     
     #[test]
     fn test_buscar_atencion_duenio() {
-        let mut vet = Veterinaria::new("test_buscar_atencion_duenio".to_string(), "Dirección".to_string(), 1);
+        let mut vet = Veterinaria::new("test_buscar_atencion_duenio".to_string(), "Dirección".to_string(), 1, Rc::new(AlmacenamientoMemoria::new()));
         let duenio = Duenio::new("Juan".to_string(), "Calle A".to_string(), "123456789".to_string());
         let atencion = AtencionRealizada::new(Mascota::new("Luna".to_string(), 5, Animal::Perro, duenio.clone()), "Diagnóstico".to_string(), "Tratamiento".to_string(), None);
         
@@ -419,7 +422,7 @@ mod tests {
     
     #[test]
     fn test_buscar_atencion_telefono() {
-        let mut vet = Veterinaria::new("test_buscar_atencion_telefono".to_string(), "Dirección".to_string(), 1);
+        let mut vet = Veterinaria::new("test_buscar_atencion_telefono".to_string(), "Dirección".to_string(), 1, Rc::new(AlmacenamientoMemoria::new()));
         let duenio = Duenio::new("Juan".to_string(), "Calle A".to_string(), "123456789".to_string());
         let atencion = AtencionRealizada::new(Mascota::new("Luna".to_string(), 5, Animal::Perro, duenio.clone()), "Diagnóstico".to_string(), "Tratamiento".to_string(), None);
         
@@ -432,7 +435,7 @@ mod tests {
     
     #[test]
     fn test_modificar_diagnostico() {
-        let mut vet = Veterinaria::new("test_modificar_diagnostico".to_string(), "Dirección".to_string(), 1);
+        let mut vet = Veterinaria::new("test_modificar_diagnostico".to_string(), "Dirección".to_string(), 1, Rc::new(AlmacenamientoMemoria::new()));
         let atencion = AtencionRealizada::new(Mascota::new("Luna".to_string(), 5, Animal::Perro, Duenio::new("Juan".to_string(), "Calle A".to_string(), "123456789".to_string())), "Diagnóstico".to_string(), "Tratamiento".to_string(), None);
         
         vet.registrar_atencion(atencion.clone()).unwrap();
@@ -446,7 +449,7 @@ mod tests {
     
     #[test]
     fn test_modificar_fecha() {
-        let mut vet = Veterinaria::new("test_modificar_fecha".to_string(), "Dirección".to_string(), 1);
+        let mut vet = Veterinaria::new("test_modificar_fecha".to_string(), "Dirección".to_string(), 1, Rc::new(AlmacenamientoMemoria::new()));
         let atencion = AtencionRealizada::new(Mascota::new("Luna".to_string(), 5, Animal::Perro, Duenio::new("Juan".to_string(), "Calle A".to_string(), "123456789".to_string())), "Diagnóstico".to_string(), "Tratamiento".to_string(), None);
         
         vet.registrar_atencion(atencion.clone()).unwrap();
@@ -463,7 +466,7 @@ mod tests {
 
     #[test]
     fn test_eliminar_atencion() {
-        let mut vet = Veterinaria::new("test_eliminar_atencion".to_string(), "Dirección".to_string(), 1);
+        let mut vet = Veterinaria::new("test_eliminar_atencion".to_string(), "Dirección".to_string(), 1, Rc::new(AlmacenamientoMemoria::new()));
         let atencion = AtencionRealizada::new(Mascota::new("Luna".to_string(), 5, Animal::Perro, Duenio::new("Juan".to_string(), "Calle A".to_string(), "123456789".to_string())), "Diagnóstico".to_string(), "Tratamiento".to_string(), None);
         
         vet.registrar_atencion(atencion.clone()).unwrap();
@@ -472,4 +475,15 @@ mod tests {
         
         assert_eq!(vet.atenciones.len(), 0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_cargar_fixtures() {
+        for (version, almacenamiento) in cargar_fixtures("practica5/ej3") {
+            let vet = Veterinaria::cargar("fixture".to_string(), "Dirección".to_string(), 1, almacenamiento, &PoliticaCarga::estricta())
+                .unwrap_or_else(|e| panic!("{}: {}", version, e));
+            assert_eq!(vet.atenciones.len(), 2, "{}", version);
+            assert_eq!(vet.atenciones[0].proxima_visita, Some(Fecha::new(15, 6, 2024)), "{}", version);
+            assert!(vet.buscar_atencion_mascota("Michi".to_string()).is_some(), "{}", version);
+        }
+    }
+}
@@ -1,8 +1,11 @@
-use std::{collections::HashMap, io::{Read, Write}};
+use std::{collections::HashMap, rc::Rc};
 
+use rusqlite::{params, Connection, OptionalExtension, Row};
 use serde::{Deserialize, Serialize};
 
-use crate::practica3::ej3::Fecha;
+use crate::almacenamiento::sqlite::{abrir_sqlite, error_sqlite, fecha_a_sql, fecha_desde_sql, valor_invalido};
+use crate::almacenamiento::{cargar_json, escribir_json, Almacenamiento, ErrorCarga, PoliticaCarga, Versionado};
+use crate::fecha::{Calendario, Fecha, Reloj, RelojSistema};
 
 struct Biblioteca {
     nombre: String,
@@ -10,6 +13,10 @@ struct Biblioteca {
     // key: libro.isbn, value: cant de ese libro
     libros: HashMap<u32, u32>,
     prestamos: Vec<Prestamo>,
+    // Se usa para que los prestamos nunca venzan en un dia no habil
+    calendario: Calendario,
+    reloj: Rc<dyn Reloj>,
+    almacenamiento: Rc<dyn Almacenamiento>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +36,14 @@ impl EstadoPrestamo {
     fn equals(&self, other: &EstadoPrestamo) -> bool {
         self.to_string() == other.to_string()
     }
+
+    fn desde_texto(texto: &str) -> Option<EstadoPrestamo> {
+        match texto {
+            "devuelto" => Some(EstadoPrestamo::Devuelto),
+            "en_prestamo" => Some(EstadoPrestamo::EnPrestamo),
+            _ => None
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,7 +70,7 @@ impl Prestamo {
         let mut result = String::new();
         result.push_str(&format!("Libro: {}\n", self.libro.isbn));
         result.push_str(&format!("Cliente: {}\n", self.cliente.to_string()));
-        result.push_str(&format!("Fecha de vencimiento: {}\n", self.fecha_vencimiento.to_string()));
+        result.push_str(&format!("Fecha de vencimiento: {}\n", self.fecha_vencimiento));
         result.push_str(&format!("Fecha de devolución: {}\n", 
             match &self.fecha_devolucion {
                 Some(fecha) => fecha.to_string(),
@@ -123,6 +138,16 @@ impl Genero {
     fn equals(&self, other: &Genero) -> bool {
         self.to_string() == other.to_string()
     }
+
+    fn desde_texto(texto: &str) -> Option<Genero> {
+        match texto {
+            "novela" => Some(Genero::Novela),
+            "infantil" => Some(Genero::Infantil),
+            "tecnico" => Some(Genero::Tecnico),
+            "otros" => Some(Genero::Otros),
+            _ => None
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -150,48 +175,46 @@ impl Libro {
     }
 }
 
+// Formatos guardados, todavia en su version 1
+impl Versionado for HashMap<u32, u32> {}
+impl Versionado for Vec<Prestamo> {}
+
 impl Biblioteca {
-    fn new(nombre: String, direccion: String) -> Biblioteca {
-        let libros = match std::fs::File::open("test/".to_owned() + nombre.as_str() + "_libros.json") {
-            Ok(mut file) => {
-                let mut buf = String::new();
-                file.read_to_string(&mut buf).unwrap();
-                let atenciones: HashMap<u32, u32> = serde_json::from_str(&buf).unwrap();
-                atenciones
-            },
-            Err(_) => HashMap::new()
-        };
+    fn new(nombre: String, direccion: String, almacenamiento: Rc<dyn Almacenamiento>) -> Biblioteca {
+        Biblioteca::new_con_reloj(nombre, direccion, almacenamiento, Rc::new(RelojSistema))
+    }
 
-        let prestamos = match std::fs::File::open("test/".to_owned() + nombre.as_str() + "_prestamos.json") {
-            Ok(mut file) => {
-                let mut buf = String::new();
-                file.read_to_string(&mut buf).unwrap();
-                let atenciones: Vec<Prestamo> = serde_json::from_str(&buf).unwrap();
-                atenciones
-            },
-            Err(_) => Vec::new()
-        };
+    /// Crea la biblioteca con los libros y prestamos guardados usando `PoliticaCarga::compatible`.
+    /// Entra en panico solo si lo guardado esta corrupto y sin respaldo valido, para manejar el error usar `cargar`
+    fn new_con_reloj(nombre: String, direccion: String, almacenamiento: Rc<dyn Almacenamiento>, reloj: Rc<dyn Reloj>) -> Biblioteca {
+        Biblioteca::cargar_con_reloj(nombre, direccion, almacenamiento, reloj, &PoliticaCarga::compatible()).unwrap()
+    }
+
+    fn cargar(nombre: String, direccion: String, almacenamiento: Rc<dyn Almacenamiento>, politica: &PoliticaCarga) -> Result<Biblioteca, ErrorCarga> {
+        Biblioteca::cargar_con_reloj(nombre, direccion, almacenamiento, Rc::new(RelojSistema), politica)
+    }
+
+    fn cargar_con_reloj(nombre: String, direccion: String, almacenamiento: Rc<dyn Almacenamiento>, reloj: Rc<dyn Reloj>, politica: &PoliticaCarga) -> Result<Biblioteca, ErrorCarga> {
+        let libros = cargar_json(almacenamiento.as_ref(), &format!("{}_libros", nombre), politica)?;
+        let prestamos = cargar_json(almacenamiento.as_ref(), &format!("{}_prestamos", nombre), politica)?;
 
-        Biblioteca {
+        Ok(Biblioteca {
             nombre,
             direccion,
             libros,
-            prestamos
-        }
+            prestamos,
+            calendario: Calendario::argentina(),
+            reloj,
+            almacenamiento
+        })
     }
 
     fn escribir_archivo_libros(&self) -> Result<(), std::io::Error> {
-        let mut file = std::fs::File::create("test/".to_owned() + self.nombre.as_str() + "_libros.json")?;
-        let serialized = serde_json::to_string(&self.libros)?;
-        file.write_all(&serialized.as_bytes())?;
-        Ok(())
+        escribir_json(self.almacenamiento.as_ref(), &format!("{}_libros", self.nombre), &self.libros)
     }
 
     fn escribir_archivo_prestamos(&self) -> Result<(), std::io::Error> {
-        let mut file = std::fs::File::create("test/".to_owned() + self.nombre.as_str() + "_prestamos.json")?;
-        let serialized = serde_json::to_string(&self.prestamos)?;
-        file.write_all(&serialized.as_bytes())?;
-        Ok(())
+        escribir_json(self.almacenamiento.as_ref(), &format!("{}_prestamos", self.nombre), &self.prestamos)
     }
 
     fn obtener_cantidad_copias(&self, libro: &Libro) -> u32 {
@@ -250,7 +273,7 @@ impl Biblioteca {
             let prestamo = Prestamo::new(
                 libro,
                 cliente,
-                fecha_devolucion,
+                self.calendario.siguiente_dia_habil(&fecha_devolucion),
                 None,
                 EstadoPrestamo::EnPrestamo
             );
@@ -284,6 +307,11 @@ impl Biblioteca {
         vec
     }
 
+    /// Devuelve los prestamos vencidos a la fecha actual del reloj
+    fn obtener_prestamos_vencidos_hoy(&self) -> Vec<&Prestamo> {
+        self.obtener_prestamos_vencidos(&self.reloj.hoy())
+    }
+
     fn buscar_prestamo(&mut self, libro: &Libro, cliente: &Cliente) -> Option<&mut Prestamo> {
         let mut ret = None;
 
@@ -312,9 +340,206 @@ impl Biblioteca {
     }
 }
 
+const ESQUEMA_BIBLIOTECA: &str = "
+    CREATE TABLE IF NOT EXISTS libros (
+        isbn INTEGER PRIMARY KEY,
+        titulo TEXT NOT NULL,
+        autor TEXT NOT NULL,
+        numero_paginas INTEGER NOT NULL,
+        genero TEXT NOT NULL,
+        copias INTEGER NOT NULL CHECK (copias >= 0)
+    );
+    CREATE TABLE IF NOT EXISTS clientes (
+        id INTEGER PRIMARY KEY,
+        nombre TEXT NOT NULL,
+        telefono TEXT NOT NULL,
+        email TEXT NOT NULL,
+        UNIQUE (nombre, telefono, email)
+    );
+    CREATE TABLE IF NOT EXISTS prestamos (
+        id INTEGER PRIMARY KEY,
+        isbn INTEGER NOT NULL REFERENCES libros (isbn),
+        cliente_id INTEGER NOT NULL REFERENCES clientes (id),
+        fecha_vencimiento TEXT NOT NULL,
+        fecha_devolucion TEXT,
+        estado TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_prestamos_isbn ON prestamos (isbn);
+    CREATE INDEX IF NOT EXISTS idx_prestamos_cliente ON prestamos (cliente_id, estado);
+    CREATE INDEX IF NOT EXISTS idx_prestamos_vencimiento ON prestamos (estado, fecha_vencimiento);
+";
+
+const SELECT_PRESTAMOS: &str = "
+    SELECT l.titulo, l.autor, l.isbn, l.numero_paginas, l.genero,
+           c.nombre, c.telefono, c.email,
+           p.fecha_vencimiento, p.fecha_devolucion, p.estado
+    FROM prestamos p
+    JOIN libros l ON l.isbn = p.isbn
+    JOIN clientes c ON c.id = p.cliente_id
+";
+
+/// Biblioteca que guarda sus libros y prestamos en una base SQLite, en un archivo o en memoria.
+/// Las consultas se resuelven con SQL en lugar de recorrer los prestamos
+struct BibliotecaSqlite {
+    nombre: String,
+    direccion: String,
+    conexion: Connection,
+    calendario: Calendario,
+    reloj: Rc<dyn Reloj>,
+}
+
+impl BibliotecaSqlite {
+    /// Abre la base de datos en la ruta (o `EN_MEMORIA`) y crea las tablas que falten
+    fn abrir(nombre: String, direccion: String, ruta: &str) -> std::io::Result<BibliotecaSqlite> {
+        BibliotecaSqlite::abrir_con_reloj(nombre, direccion, ruta, Rc::new(RelojSistema))
+    }
+
+    fn abrir_con_reloj(nombre: String, direccion: String, ruta: &str, reloj: Rc<dyn Reloj>) -> std::io::Result<BibliotecaSqlite> {
+        let conexion = abrir_sqlite(ruta)?;
+        conexion.execute_batch(ESQUEMA_BIBLIOTECA).map_err(error_sqlite)?;
+
+        Ok(BibliotecaSqlite {
+            nombre,
+            direccion,
+            conexion,
+            calendario: Calendario::argentina(),
+            reloj,
+        })
+    }
+
+    fn obtener_cantidad_copias(&self, libro: &Libro) -> std::io::Result<u32> {
+        let copias = self.conexion
+            .query_row("SELECT copias FROM libros WHERE isbn = ?1", [libro.isbn], |fila| fila.get(0))
+            .optional()
+            .map_err(error_sqlite)?;
+
+        Ok(copias.unwrap_or(0))
+    }
+
+    fn incrementar_cantidad_copias(&mut self, libro: Libro) -> std::io::Result<()> {
+        self.conexion.execute(
+            "INSERT INTO libros (isbn, titulo, autor, numero_paginas, genero, copias) VALUES (?1, ?2, ?3, ?4, ?5, 1)
+             ON CONFLICT (isbn) DO UPDATE SET copias = copias + 1",
+            params![libro.isbn, libro.titulo, libro.autor, libro.numero_paginas, libro.genero.to_string()],
+        ).map_err(error_sqlite)?;
+
+        Ok(())
+    }
+
+    fn contar_prestamos_cliente(&self, cliente: &Cliente) -> std::io::Result<u32> {
+        self.conexion.query_row(
+            "SELECT COUNT(*) FROM prestamos p JOIN clientes c ON c.id = p.cliente_id
+             WHERE c.nombre = ?1 AND c.telefono = ?2 AND c.email = ?3 AND p.estado = ?4",
+            params![cliente.nombre, cliente.telefono, cliente.email, EstadoPrestamo::EnPrestamo.to_string()],
+            |fila| fila.get(0),
+        ).map_err(error_sqlite)
+    }
+
+    /// Descuenta la copia, registra al cliente y agrega el prestamo en una sola transaccion,
+    /// si algo falla no queda ningun cambio guardado
+    fn realizar_prestamo(&mut self, libro: Libro, cliente: Cliente, fecha_devolucion: Fecha) -> std::io::Result<()> {
+        if self.contar_prestamos_cliente(&cliente)? > 5 {
+            return Err(std::io::Error::other("El cliente ya tiene 5 libros prestados"));
+        }
+        if self.obtener_cantidad_copias(&libro)? < 1 {
+            return Err(std::io::Error::other("No hay copias disponibles del libro"));
+        }
+
+        let fecha_vencimiento = self.calendario.siguiente_dia_habil(&fecha_devolucion);
+        let transaccion = self.conexion.transaction().map_err(error_sqlite)?;
+        transaccion.execute("UPDATE libros SET copias = copias - 1 WHERE isbn = ?1", [libro.isbn]).map_err(error_sqlite)?;
+        transaccion.execute(
+            "INSERT OR IGNORE INTO clientes (nombre, telefono, email) VALUES (?1, ?2, ?3)",
+            params![cliente.nombre, cliente.telefono, cliente.email],
+        ).map_err(error_sqlite)?;
+        transaccion.execute(
+            "INSERT INTO prestamos (isbn, cliente_id, fecha_vencimiento, fecha_devolucion, estado)
+             SELECT ?1, id, ?2, NULL, ?3 FROM clientes WHERE nombre = ?4 AND telefono = ?5 AND email = ?6",
+            params![libro.isbn, fecha_a_sql(&fecha_vencimiento), EstadoPrestamo::EnPrestamo.to_string(), cliente.nombre, cliente.telefono, cliente.email],
+        ).map_err(error_sqlite)?;
+        transaccion.commit().map_err(error_sqlite)
+    }
+
+    fn obtener_prestamos_a_vencer(&self, mut fecha_actual: Fecha, dias: u32) -> std::io::Result<Vec<Prestamo>> {
+        fecha_actual.sumar_dias(dias);
+        self.obtener_prestamos_vencidos(&fecha_actual)
+    }
+
+    fn obtener_prestamos_vencidos(&self, fecha_actual: &Fecha) -> std::io::Result<Vec<Prestamo>> {
+        self.consultar_prestamos(
+            &format!("{} WHERE p.estado = ?1 AND p.fecha_vencimiento < ?2 ORDER BY p.id", SELECT_PRESTAMOS),
+            params![EstadoPrestamo::EnPrestamo.to_string(), fecha_a_sql(fecha_actual)],
+        )
+    }
+
+    /// Devuelve los prestamos vencidos a la fecha actual del reloj
+    fn obtener_prestamos_vencidos_hoy(&self) -> std::io::Result<Vec<Prestamo>> {
+        self.obtener_prestamos_vencidos(&self.reloj.hoy())
+    }
+
+    /// Devuelve los prestamos de un libro, buscandolo por su ISBN
+    fn obtener_prestamos_libro(&self, isbn: u32) -> std::io::Result<Vec<Prestamo>> {
+        self.consultar_prestamos(&format!("{} WHERE p.isbn = ?1 ORDER BY p.id", SELECT_PRESTAMOS), params![isbn])
+    }
+
+    fn buscar_prestamo(&self, libro: &Libro, cliente: &Cliente) -> std::io::Result<Option<Prestamo>> {
+        let prestamos = self.consultar_prestamos(
+            &format!("{} WHERE p.isbn = ?1 AND c.nombre = ?2 AND c.telefono = ?3 AND c.email = ?4 ORDER BY p.id LIMIT 1", SELECT_PRESTAMOS),
+            params![libro.isbn, cliente.nombre, cliente.telefono, cliente.email],
+        )?;
+
+        Ok(prestamos.into_iter().next())
+    }
+
+    /// Marca el prestamo como devuelto y repone la copia en una sola transaccion
+    fn devolver_libro(&mut self, libro: Libro, cliente: &Cliente, fecha_actual: Fecha) -> std::io::Result<()> {
+        let transaccion = self.conexion.transaction().map_err(error_sqlite)?;
+        let id: Option<i64> = transaccion.query_row(
+            "SELECT p.id FROM prestamos p JOIN clientes c ON c.id = p.cliente_id
+             WHERE p.isbn = ?1 AND c.nombre = ?2 AND c.telefono = ?3 AND c.email = ?4 AND p.estado = ?5
+             ORDER BY p.id LIMIT 1",
+            params![libro.isbn, cliente.nombre, cliente.telefono, cliente.email, EstadoPrestamo::EnPrestamo.to_string()],
+            |fila| fila.get(0),
+        ).optional().map_err(error_sqlite)?;
+
+        let Some(id) = id else {
+            return Err(std::io::Error::other("No se encontró el préstamo"));
+        };
+
+        transaccion.execute(
+            "UPDATE prestamos SET estado = ?1, fecha_devolucion = ?2 WHERE id = ?3",
+            params![EstadoPrestamo::Devuelto.to_string(), fecha_a_sql(&fecha_actual), id],
+        ).map_err(error_sqlite)?;
+        transaccion.execute("UPDATE libros SET copias = copias + 1 WHERE isbn = ?1", [libro.isbn]).map_err(error_sqlite)?;
+        transaccion.commit().map_err(error_sqlite)
+    }
+
+    fn consultar_prestamos(&self, consulta: &str, parametros: impl rusqlite::Params) -> std::io::Result<Vec<Prestamo>> {
+        let mut sentencia = self.conexion.prepare(consulta).map_err(error_sqlite)?;
+        let filas = sentencia.query_map(parametros, prestamo_desde_fila).map_err(error_sqlite)?;
+        filas.collect::<rusqlite::Result<Vec<Prestamo>>>().map_err(error_sqlite)
+    }
+}
+
+fn prestamo_desde_fila(fila: &Row) -> rusqlite::Result<Prestamo> {
+    let genero: String = fila.get(4)?;
+    let estado: String = fila.get(10)?;
+    let fecha_devolucion: Option<String> = fila.get(9)?;
+
+    Ok(Prestamo::new(
+        Libro::new(fila.get(0)?, fila.get(1)?, fila.get(2)?, fila.get(3)?, Genero::desde_texto(&genero).ok_or_else(|| valor_invalido(4, &genero))?),
+        Cliente::new(fila.get(5)?, fila.get(6)?, fila.get(7)?),
+        fecha_desde_sql(&fila.get::<_, String>(8)?)?,
+        fecha_devolucion.map(|f| fecha_desde_sql(&f)).transpose()?,
+        EstadoPrestamo::desde_texto(&estado).ok_or_else(|| valor_invalido(10, &estado))?,
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::almacenamiento::{fixtures::cargar_fixtures, sqlite::EN_MEMORIA, AlmacenamientoMemoria};
+    use crate::fecha::{FechaHora, RelojManual};
 
     #[test]
     fn test_genero_to_string() {
@@ -361,7 +586,8 @@ mod tests {
 
     #[test]
     fn test_new_biblioteca_con_datos() {
-        let mut biblio1 = Biblioteca::new("test_new_biblioteca_con_datos".to_string(), "Calle Principal".to_string());
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+        let mut biblio1 = Biblioteca::new("test_new_biblioteca_con_datos".to_string(), "Calle Principal".to_string(), almacenamiento.clone());
         let libro = Libro::new("Harry Potter".to_string(), "J.K. Rowling".to_string(), 1, 300, Genero::Novela);
         let cliente = Cliente::new("John Doe".to_string(), "123456789".to_string(), "john@example.com".to_string());
         assert_eq!(biblio1.libros.len(), 0);
@@ -370,16 +596,27 @@ mod tests {
         assert!(biblio1.incrementar_cantidad_copias(libro.clone()).is_ok());
         assert!(biblio1.realizar_prestamo(libro.clone(), cliente.clone(), Fecha::now()).is_ok());
 
-        let mut biblio2 = Biblioteca::new("test_new_biblioteca_con_datos".to_string(), "Calle Principal".to_string());
+        let mut biblio2 = Biblioteca::new("test_new_biblioteca_con_datos".to_string(), "Calle Principal".to_string(), almacenamiento);
         assert_eq!(biblio2.libros.len(), 1);
         assert_eq!(biblio2.obtener_cantidad_copias(&libro), 0);
         assert_eq!(biblio2.prestamos.len(), 1);
         assert!(biblio2.buscar_prestamo(&libro, &cliente).is_some());
     }
 
+    #[test]
+    fn test_cargar_biblioteca_no_encontrada() {
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+        let resultado = Biblioteca::cargar("test_cargar_biblioteca".to_string(), "Calle Principal".to_string(), almacenamiento.clone(), &PoliticaCarga::estricta());
+        assert_eq!(resultado.err(), Some(ErrorCarga::NoEncontrado("test_cargar_biblioteca_libros".to_string())));
+
+        let biblioteca = Biblioteca::cargar("test_cargar_biblioteca".to_string(), "Calle Principal".to_string(), almacenamiento, &PoliticaCarga::default()).unwrap();
+        assert_eq!(biblioteca.libros.len(), 0);
+        assert_eq!(biblioteca.prestamos.len(), 0);
+    }
+
     #[test]
     fn test_realizar_prestamo_exitoso() {
-        let mut biblioteca = Biblioteca::new("test_realizar_prestamo_exitoso".to_string(), "Calle Principal".to_string());
+        let mut biblioteca = Biblioteca::new("test_realizar_prestamo_exitoso".to_string(), "Calle Principal".to_string(), Rc::new(AlmacenamientoMemoria::new()));
         let cliente = Cliente::new("John Doe".to_string(), "123456789".to_string(), "john@example.com".to_string());
         let libro = Libro::new("Harry Potter".to_string(), "J.K. Rowling".to_string(), 1, 300, Genero::Novela);
         let fecha_devolucion = Fecha::new(20, 5, 2024);
@@ -390,9 +627,21 @@ mod tests {
         assert_eq!(biblioteca.prestamos.len(), 1); // Se agregó un préstamo
     }
 
+    #[test]
+    fn test_realizar_prestamo_vence_dia_habil() {
+        let mut biblioteca = Biblioteca::new("test_realizar_prestamo_vence_dia_habil".to_string(), "Calle Principal".to_string(), Rc::new(AlmacenamientoMemoria::new()));
+        let cliente = Cliente::new("John Doe".to_string(), "123456789".to_string(), "john@example.com".to_string());
+        let libro = Libro::new("Harry Potter".to_string(), "J.K. Rowling".to_string(), 1, 300, Genero::Novela);
+
+        // El 24/3/2024 es domingo y el 25/3 es lunes
+        assert!(biblioteca.incrementar_cantidad_copias(libro.clone()).is_ok());
+        assert!(biblioteca.realizar_prestamo(libro.clone(), cliente.clone(), Fecha::new(24, 3, 2024)).is_ok());
+        assert_eq!(biblioteca.prestamos[0].fecha_vencimiento, Fecha::new(25, 3, 2024));
+    }
+
     #[test]
     fn test_contar_prestamos_cliente() {
-        let mut biblioteca = Biblioteca::new("test_contar_prestamos_cliente".to_string(), "Calle Principal".to_string());
+        let mut biblioteca = Biblioteca::new("test_contar_prestamos_cliente".to_string(), "Calle Principal".to_string(), Rc::new(AlmacenamientoMemoria::new()));
         let cliente = Cliente::new("John Doe".to_string(), "123456789".to_string(), "john@example.com".to_string());
         let libro1 = Libro::new("Libro1".to_string(), "Autor1".to_string(), 1, 100, Genero::Novela);
         let libro2 = Libro::new("Libro2".to_string(), "Autor2".to_string(), 2, 200, Genero::Infantil);
@@ -416,7 +665,7 @@ mod tests {
 
     #[test]
     fn test_obtener_prestamos_vencidos() {
-        let mut biblioteca = Biblioteca::new("test_obtener_prestamos_vencidos".to_string(), "Calle Principal".to_string());
+        let mut biblioteca = Biblioteca::new("test_obtener_prestamos_vencidos".to_string(), "Calle Principal".to_string(), Rc::new(AlmacenamientoMemoria::new()));
         let cliente = Cliente::new("John Doe".to_string(), "123456789".to_string(), "john@example.com".to_string());
         let libro = Libro::new("Harry Potter".to_string(), "J.K. Rowling".to_string(), 1, 300, Genero::Novela);
         let fecha_vencimiento = Fecha::new(20, 5, 2023);
@@ -430,9 +679,26 @@ mod tests {
         assert!(prestamos_vencidos[0].equals(&prestamo_vencido));
     }
 
+    #[test]
+    fn test_obtener_prestamos_vencidos_hoy() {
+        let reloj = Rc::new(RelojManual::new(FechaHora::new(Fecha::new(1, 4, 2024), 10, 0, 0)));
+        let mut biblioteca = Biblioteca::new_con_reloj("test_obtener_prestamos_vencidos_hoy".to_string(), "Calle Principal".to_string(), Rc::new(AlmacenamientoMemoria::new()), reloj.clone());
+        let cliente = Cliente::new("John Doe".to_string(), "123456789".to_string(), "john@example.com".to_string());
+        let libro = Libro::new("Harry Potter".to_string(), "J.K. Rowling".to_string(), 1, 300, Genero::Novela);
+        let mut fecha_vencimiento = reloj.hoy();
+        fecha_vencimiento.sumar_dias(14);
+
+        assert!(biblioteca.incrementar_cantidad_copias(libro.clone()).is_ok());
+        assert!(biblioteca.realizar_prestamo(libro.clone(), cliente.clone(), fecha_vencimiento).is_ok());
+        assert!(biblioteca.obtener_prestamos_vencidos_hoy().is_empty());
+
+        reloj.avanzar_meses(1);
+        assert_eq!(biblioteca.obtener_prestamos_vencidos_hoy().len(), 1);
+    }
+
     #[test]
     fn test_obtener_prestamos_a_vencer() {
-        let mut biblioteca = Biblioteca::new("test_obtener_prestamos_a_vencer".to_string(), "Calle Principal".to_string());
+        let mut biblioteca = Biblioteca::new("test_obtener_prestamos_a_vencer".to_string(), "Calle Principal".to_string(), Rc::new(AlmacenamientoMemoria::new()));
         let fecha_actual = Fecha::new(19, 5, 2023);
         let cliente = Cliente::new("John Doe".to_string(), "123456789".to_string(), "john@example.com".to_string());
         let libro = Libro::new("Harry Potter".to_string(), "J.K. Rowling".to_string(), 1, 300, Genero::Novela);
@@ -448,7 +714,7 @@ mod tests {
 
     #[test]
     fn test_buscar_prestamo() {
-        let mut biblioteca = Biblioteca::new("test_buscar_prestamo".to_string(), "Calle Principal".to_string());
+        let mut biblioteca = Biblioteca::new("test_buscar_prestamo".to_string(), "Calle Principal".to_string(), Rc::new(AlmacenamientoMemoria::new()));
         let cliente = Cliente::new("John Doe".to_string(), "123456789".to_string(), "john@example.com".to_string());
         let libro = Libro::new("Harry Potter".to_string(), "J.K. Rowling".to_string(), 1, 300, Genero::Novela);
         let fecha_devolucion = Fecha::new(20, 5, 2024);
@@ -460,7 +726,7 @@ mod tests {
 
     #[test]
     fn test_devolver_libro() {
-        let mut biblioteca = Biblioteca::new("test_devolver_libro".to_string(), "Calle Principal".to_string());
+        let mut biblioteca = Biblioteca::new("test_devolver_libro".to_string(), "Calle Principal".to_string(), Rc::new(AlmacenamientoMemoria::new()));
         let cliente = Cliente::new("John Doe".to_string(), "123456789".to_string(), "john@example.com".to_string());
         let libro = Libro::new("Harry Potter".to_string(), "J.K. Rowling".to_string(), 1, 300, Genero::Novela);
         let fecha_devolucion = Fecha::new(20, 5, 2024);
@@ -474,4 +740,111 @@ mod tests {
         // Se marca el préstamo como devuelto
         assert!(biblioteca.prestamos[0].estado.equals(&EstadoPrestamo::Devuelto));
     }
+
+    #[test]
+    fn test_cargar_fixtures() {
+        for (version, almacenamiento) in cargar_fixtures("practica5/ej4") {
+            let biblioteca = Biblioteca::cargar("fixture".to_string(), "Calle Principal".to_string(), almacenamiento, &PoliticaCarga::estricta())
+                .unwrap_or_else(|e| panic!("{}: {}", version, e));
+            assert_eq!(biblioteca.libros.len(), 2, "{}", version);
+            assert_eq!(biblioteca.prestamos.len(), 2, "{}", version);
+            assert_eq!(biblioteca.prestamos[1].fecha_vencimiento, Fecha::new(1, 4, 2024), "{}", version);
+        }
+    }
+
+    fn biblioteca_sqlite(nombre: &str) -> BibliotecaSqlite {
+        BibliotecaSqlite::abrir(nombre.to_string(), "Calle Principal".to_string(), EN_MEMORIA).unwrap()
+    }
+
+    #[test]
+    fn test_sqlite_realizar_y_devolver_prestamo() {
+        let mut biblioteca = biblioteca_sqlite("test_sqlite_realizar_y_devolver_prestamo");
+        let cliente = Cliente::new("John Doe".to_string(), "123456789".to_string(), "john@example.com".to_string());
+        let libro = Libro::new("Harry Potter".to_string(), "J.K. Rowling".to_string(), 1, 300, Genero::Novela);
+
+        assert!(biblioteca.realizar_prestamo(libro.clone(), cliente.clone(), Fecha::new(20, 5, 2024)).is_err());
+        assert!(biblioteca.incrementar_cantidad_copias(libro.clone()).is_ok());
+        assert!(biblioteca.incrementar_cantidad_copias(libro.clone()).is_ok());
+        assert_eq!(biblioteca.obtener_cantidad_copias(&libro).unwrap(), 2);
+
+        // El 24/3/2024 es domingo y el 25/3 es lunes
+        assert!(biblioteca.realizar_prestamo(libro.clone(), cliente.clone(), Fecha::new(24, 3, 2024)).is_ok());
+        assert_eq!(biblioteca.obtener_cantidad_copias(&libro).unwrap(), 1);
+        assert_eq!(biblioteca.contar_prestamos_cliente(&cliente).unwrap(), 1);
+        let prestamo = biblioteca.buscar_prestamo(&libro, &cliente).unwrap().unwrap();
+        assert_eq!(prestamo.fecha_vencimiento, Fecha::new(25, 3, 2024));
+        assert!(prestamo.estado.equals(&EstadoPrestamo::EnPrestamo));
+
+        assert!(biblioteca.devolver_libro(libro.clone(), &cliente, Fecha::new(26, 3, 2024)).is_ok());
+        assert_eq!(biblioteca.obtener_cantidad_copias(&libro).unwrap(), 2);
+        assert_eq!(biblioteca.contar_prestamos_cliente(&cliente).unwrap(), 0);
+        let prestamo = biblioteca.buscar_prestamo(&libro, &cliente).unwrap().unwrap();
+        assert!(prestamo.estado.equals(&EstadoPrestamo::Devuelto));
+        assert_eq!(prestamo.fecha_devolucion, Some(Fecha::new(26, 3, 2024)));
+
+        // No queda ningun prestamo pendiente para devolver
+        assert!(biblioteca.devolver_libro(libro, &cliente, Fecha::new(27, 3, 2024)).is_err());
+    }
+
+    #[test]
+    fn test_sqlite_prestamo_fallido_no_deja_cambios() {
+        let mut biblioteca = biblioteca_sqlite("test_sqlite_prestamo_fallido_no_deja_cambios");
+        let cliente = Cliente::new("John Doe".to_string(), "123456789".to_string(), "john@example.com".to_string());
+        let libro = Libro::new("Harry Potter".to_string(), "J.K. Rowling".to_string(), 1, 300, Genero::Novela);
+        assert!(biblioteca.incrementar_cantidad_copias(libro.clone()).is_ok());
+
+        // Se rompe la tabla de prestamos para que falle el ultimo paso de la transaccion
+        biblioteca.conexion.execute_batch("DROP TABLE prestamos").unwrap();
+        assert!(biblioteca.realizar_prestamo(libro.clone(), cliente, Fecha::new(20, 5, 2024)).is_err());
+        assert_eq!(biblioteca.obtener_cantidad_copias(&libro).unwrap(), 1);
+        let clientes: u32 = biblioteca.conexion.query_row("SELECT COUNT(*) FROM clientes", [], |fila| fila.get(0)).unwrap();
+        assert_eq!(clientes, 0);
+    }
+
+    #[test]
+    fn test_sqlite_obtener_prestamos_vencidos() {
+        let reloj = Rc::new(RelojManual::new(FechaHora::new(Fecha::new(1, 4, 2024), 10, 0, 0)));
+        let mut biblioteca = BibliotecaSqlite::abrir_con_reloj("test_sqlite_obtener_prestamos_vencidos".to_string(), "Calle Principal".to_string(), EN_MEMORIA, reloj.clone()).unwrap();
+        let cliente = Cliente::new("John Doe".to_string(), "123456789".to_string(), "john@example.com".to_string());
+        let libro1 = Libro::new("Libro1".to_string(), "Autor1".to_string(), 1, 100, Genero::Novela);
+        let libro2 = Libro::new("Libro2".to_string(), "Autor2".to_string(), 2, 200, Genero::Infantil);
+        assert!(biblioteca.incrementar_cantidad_copias(libro1.clone()).is_ok());
+        assert!(biblioteca.incrementar_cantidad_copias(libro2.clone()).is_ok());
+
+        assert!(biblioteca.realizar_prestamo(libro1.clone(), cliente.clone(), Fecha::new(10, 4, 2024)).is_ok());
+        assert!(biblioteca.realizar_prestamo(libro2.clone(), cliente.clone(), Fecha::new(20, 12, 2024)).is_ok());
+        assert!(biblioteca.obtener_prestamos_vencidos_hoy().unwrap().is_empty());
+        assert_eq!(biblioteca.obtener_prestamos_a_vencer(reloj.hoy(), 10).unwrap().len(), 1);
+
+        reloj.avanzar_meses(1);
+        let vencidos = biblioteca.obtener_prestamos_vencidos_hoy().unwrap();
+        assert_eq!(vencidos.len(), 1);
+        assert_eq!(vencidos[0].libro.isbn, 1);
+
+        // Un prestamo devuelto ya no vence
+        assert!(biblioteca.devolver_libro(libro1, &cliente, reloj.hoy()).is_ok());
+        assert!(biblioteca.obtener_prestamos_vencidos_hoy().unwrap().is_empty());
+        assert_eq!(biblioteca.obtener_prestamos_vencidos(&Fecha::new(1, 1, 2025)).unwrap().len(), 1);
+        assert_eq!(biblioteca.obtener_prestamos_libro(1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_sqlite_archivo_persiste() {
+        let ruta = std::env::temp_dir().join(format!("test_sqlite_archivo_persiste-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&ruta);
+        let ruta = ruta.to_str().unwrap();
+        let cliente = Cliente::new("John Doe".to_string(), "123456789".to_string(), "john@example.com".to_string());
+        let libro = Libro::new("Harry Potter".to_string(), "J.K. Rowling".to_string(), 1, 300, Genero::Novela);
+
+        let mut biblio1 = BibliotecaSqlite::abrir("test_sqlite_archivo_persiste".to_string(), "Calle Principal".to_string(), ruta).unwrap();
+        assert!(biblio1.incrementar_cantidad_copias(libro.clone()).is_ok());
+        assert!(biblio1.realizar_prestamo(libro.clone(), cliente.clone(), Fecha::new(20, 5, 2024)).is_ok());
+        drop(biblio1);
+
+        let biblio2 = BibliotecaSqlite::abrir("test_sqlite_archivo_persiste".to_string(), "Calle Principal".to_string(), ruta).unwrap();
+        assert_eq!(biblio2.obtener_cantidad_copias(&libro).unwrap(), 0);
+        assert!(biblio2.buscar_prestamo(&libro, &cliente).unwrap().is_some());
+        drop(biblio2);
+        std::fs::remove_file(ruta).unwrap();
+    }
 }
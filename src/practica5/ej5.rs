@@ -1,12 +1,16 @@
-use std::{collections::HashMap, io::{Read, Write}};
+use std::{collections::HashMap, rc::Rc};
 use serde::{Deserialize, Serialize};
 
-use crate::practica3::ej3::Fecha;
+use crate::almacenamiento::{cargar_json, escribir_json, Almacenamiento, ErrorCarga, PoliticaCarga, Versionado};
+use crate::dinero::{Dinero, ErrorDinero, Moneda};
+use crate::fecha::{Fecha, Reloj, RelojSistema};
 
 struct StreamingRust {
     file_name: String,
     usuarios: Vec<Usuario>,
-    suscripciones: Vec<Suscripcion>
+    suscripciones: Vec<Suscripcion>,
+    reloj: Rc<dyn Reloj>,
+    almacenamiento: Rc<dyn Almacenamiento>
 }
 
 struct Usuario {
@@ -46,23 +50,43 @@ enum MetodoPago {
     Cripto { billetera: String },
 }
 
+// Formatos guardados, todavia en su version 1
+impl Versionado for Vec<Suscripcion> {}
+
 impl StreamingRust {
-    fn new(file_name: &str) -> Self {
-        let suscripciones = match std::fs::File::open("test/".to_owned() + file_name + ".json") {
-            Ok(mut file) => {
-                let mut buf = String::new();
-                file.read_to_string(&mut buf).unwrap();
-                let suscripciones: Vec<Suscripcion> = serde_json::from_str(&buf).unwrap();
-                suscripciones
-            },
-            Err(_) => Vec::new()
-        };
+    fn new(file_name: &str, almacenamiento: Rc<dyn Almacenamiento>) -> Self {
+        StreamingRust::new_con_reloj(file_name, almacenamiento, Rc::new(RelojSistema))
+    }
+
+    /// Crea la plataforma con las suscripciones guardadas usando `PoliticaCarga::compatible`.
+    /// Entra en panico solo si lo guardado esta corrupto y sin respaldo valido, para manejar el error usar `cargar`
+    fn new_con_reloj(file_name: &str, almacenamiento: Rc<dyn Almacenamiento>, reloj: Rc<dyn Reloj>) -> Self {
+        StreamingRust::cargar_con_reloj(file_name, almacenamiento, reloj, &PoliticaCarga::compatible()).unwrap()
+    }
+
+    fn cargar(file_name: &str, almacenamiento: Rc<dyn Almacenamiento>, politica: &PoliticaCarga) -> Result<Self, ErrorCarga> {
+        StreamingRust::cargar_con_reloj(file_name, almacenamiento, Rc::new(RelojSistema), politica)
+    }
 
-        StreamingRust {
+    fn cargar_con_reloj(file_name: &str, almacenamiento: Rc<dyn Almacenamiento>, reloj: Rc<dyn Reloj>, politica: &PoliticaCarga) -> Result<Self, ErrorCarga> {
+        let suscripciones = cargar_json(almacenamiento.as_ref(), file_name, politica)?;
+
+        Ok(StreamingRust {
             file_name: file_name.to_string(),
             usuarios: Vec::new(),
-            suscripciones
-        }
+            suscripciones,
+            reloj,
+            almacenamiento
+        })
+    }
+
+    /// Marca como inactivas las suscripciones cuya duracion ya termino segun el reloj
+    fn actualizar_vencimientos(&mut self) -> Result<(), std::io::Error> {
+        let hoy = self.reloj.hoy();
+        self.suscripciones.iter_mut()
+            .filter(|subscripcion| subscripcion.esta_activa() && subscripcion.vencio(&hoy))
+            .for_each(|subscripcion| subscripcion.cancel());
+        self.escribir_archivo()
     }
 }
 
@@ -159,7 +183,7 @@ impl GestorSuscripciones for StreamingRust {
         match self.get_subscripcion(id_usuario) {
             None => {
                 let usuario = self.get_usuario(id_usuario).unwrap();
-                let subscripcion = Suscripcion::new(tipo_suscripcion, duracion_meses, usuario.id);
+                let subscripcion = Suscripcion::new(tipo_suscripcion, duracion_meses, usuario.id, self.reloj.hoy());
                 self.suscripciones.push(subscripcion);
                 self.escribir_archivo()
             },
@@ -205,10 +229,7 @@ impl GestorSuscripciones for StreamingRust {
     }
 
     fn escribir_archivo(&self) -> Result<(), std::io::Error>{
-        let mut file = std::fs::File::create("test/".to_owned() + &self.file_name + ".json")?;
-        let serialized = serde_json::to_string(&self.suscripciones)?;
-        file.write_all(&serialized.as_bytes())?;
-        Ok(())
+        escribir_json(self.almacenamiento.as_ref(), &self.file_name, &self.suscripciones)
     }
 }
 
@@ -224,10 +245,10 @@ impl Usuario {
 }
 
 impl Suscripcion {
-    fn new(tipo_suscripcion: TipoSuscripcion, duracion_meses: u32, id_usuario: u32) -> Self {
+    fn new(tipo_suscripcion: TipoSuscripcion, duracion_meses: u32, id_usuario: u32, fecha_inicio: Fecha) -> Self {
         Suscripcion {
             estado: EstadoSuscripcion::Activa,
-            fecha_inicio: Fecha::now(),
+            fecha_inicio,
             tipo_suscripcion,
             duracion_meses,
             id_usuario
@@ -257,21 +278,33 @@ impl Suscripcion {
         self.estado = EstadoSuscripcion::Inactiva;
     }
 
-    fn costo(&self) -> f64 {
-        self.tipo_suscripcion.costo() * self.duracion_meses as f64
+    fn costo(&self) -> Result<Dinero, ErrorDinero> {
+        self.tipo_suscripcion.costo().multiplicar(self.duracion_meses as i64)
     }
 
     fn esta_activa(&self) -> bool {
         self.estado == EstadoSuscripcion::Activa
     }
+    /// Devuelve la fecha en la que termina la suscripcion segun su duracion en meses
+    fn fecha_fin(&self) -> Fecha {
+        let mut fecha = self.fecha_inicio.clone();
+        fecha.sumar_meses(self.duracion_meses);
+        fecha
+    }
+
+    /// Devuelve true si la fecha indicada es igual o posterior a la fecha de fin
+    fn vencio(&self, hoy: &Fecha) -> bool {
+        *hoy >= self.fecha_fin()
+    }
 }
 
 impl TipoSuscripcion {
-    fn costo(&self) -> f64 {
+    /// Costo mensual de la suscripcion
+    fn costo(&self) -> Dinero {
         match self {
-            TipoSuscripcion::Basic => 10.0,
-            TipoSuscripcion::Clasic => 20.0,
-            TipoSuscripcion::Super => 30.0,
+            TipoSuscripcion::Basic => Dinero::new(1000, Moneda::ars()),
+            TipoSuscripcion::Clasic => Dinero::new(2000, Moneda::ars()),
+            TipoSuscripcion::Super => Dinero::new(3000, Moneda::ars()),
         }
     }
 }
@@ -279,27 +312,57 @@ impl TipoSuscripcion {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::almacenamiento::{fixtures::cargar_fixtures, AlmacenamientoMemoria};
+    use crate::fecha::{FechaHora, RelojManual};
 
     #[test]
     fn test_crear_streaming_con_datos() {
-        let mut streaming = StreamingRust::new("test_crear_streaming_con_datos");
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+        let mut streaming = StreamingRust::new("test_crear_streaming_con_datos", almacenamiento.clone());
         assert_eq!(streaming.suscripciones.len(), 0);
         assert!(streaming.crear_subscripcion(1, "Juan".to_string(), TipoSuscripcion::Basic, 3, MetodoPago::Efectivo).is_ok());
         assert_eq!(streaming.suscripciones.len(), 1);
 
-        let streaming = StreamingRust::new("test_crear_streaming_con_datos");
+        let streaming = StreamingRust::new("test_crear_streaming_con_datos", almacenamiento);
         assert_eq!(streaming.suscripciones.len(), 1);
     }
 
     #[test]
     fn test_subscripcion_cost() {
-        let subscripcion = Suscripcion::new(TipoSuscripcion::Basic, 3, 1);
-        assert_eq!(subscripcion.costo(), 30.0);
+        let subscripcion = Suscripcion::new(TipoSuscripcion::Basic, 3, 1, Fecha::now());
+        assert_eq!(subscripcion.costo(), Ok(Dinero::new(3000, Moneda::ars())));
+    }
+
+    #[test]
+    fn test_subscripcion_fecha_fin() {
+        let subscripcion = Suscripcion::new(TipoSuscripcion::Basic, 3, 1, Fecha::new(30, 11, 2024));
+        assert!(subscripcion.fecha_fin().equals(&Fecha::new(28, 2, 2025)));
+        assert!(!subscripcion.vencio(&Fecha::new(27, 2, 2025)));
+        assert!(subscripcion.vencio(&Fecha::new(28, 2, 2025)));
+    }
+
+    #[test]
+    fn test_actualizar_vencimientos() {
+        let reloj = Rc::new(RelojManual::new(FechaHora::new(Fecha::new(1, 1, 2024), 12, 0, 0)));
+        let mut streaming = StreamingRust::new_con_reloj("test_actualizar_vencimientos", Rc::new(AlmacenamientoMemoria::new()), reloj.clone());
+        assert!(streaming.crear_subscripcion(1, "Juan".to_string(), TipoSuscripcion::Basic, 1, MetodoPago::Efectivo).is_ok());
+        assert!(streaming.crear_subscripcion(2, "Pedro".to_string(), TipoSuscripcion::Super, 3, MetodoPago::Efectivo).is_ok());
+        assert!(streaming.suscripciones[0].fecha_inicio.equals(&Fecha::new(1, 1, 2024)));
+
+        reloj.avanzar_meses(2);
+        assert!(streaming.actualizar_vencimientos().is_ok());
+        assert!(!streaming.suscripciones[0].esta_activa());
+        assert!(streaming.suscripciones[1].esta_activa());
+        assert_eq!(streaming.tipo_suscripcion_activa_mas_usada(), Some(TipoSuscripcion::Super));
+
+        reloj.avanzar_meses(1);
+        assert!(streaming.actualizar_vencimientos().is_ok());
+        assert_eq!(streaming.tipo_suscripcion_activa_mas_usada(), None);
     }
 
     #[test]
     fn test_crear_subscripcion() {
-        let mut streaming = StreamingRust::new("test_crear_subscripcion");
+        let mut streaming = StreamingRust::new("test_crear_subscripcion", Rc::new(AlmacenamientoMemoria::new()));
         assert!(streaming.crear_subscripcion(1, "Juan".to_string(), TipoSuscripcion::Basic, 3, MetodoPago::Efectivo).is_ok());
         let usuario = streaming.get_usuario(1).unwrap();
         assert_eq!(usuario.nombre, "Juan");
@@ -312,7 +375,7 @@ mod tests {
 
     #[test]
     fn test_upgrade_subscripcion() {
-        let mut streaming = StreamingRust::new("test_upgrade_subscripcion");
+        let mut streaming = StreamingRust::new("test_upgrade_subscripcion", Rc::new(AlmacenamientoMemoria::new()));
         assert!(streaming.crear_subscripcion(1, "Juan".to_string(), TipoSuscripcion::Basic, 3, MetodoPago::Efectivo).is_ok());
         assert!(streaming.upgrade_subscripcion(1).is_ok());
         let subscripcion = streaming.suscripciones.first().unwrap();
@@ -321,7 +384,7 @@ mod tests {
 
     #[test]
     fn test_upgrade_subscripcion_super() {
-        let mut streaming = StreamingRust::new("test_upgrade_subscripcion_super");
+        let mut streaming = StreamingRust::new("test_upgrade_subscripcion_super", Rc::new(AlmacenamientoMemoria::new()));
         assert!(streaming.crear_subscripcion(1, "Juan".to_string(), TipoSuscripcion::Super, 3, MetodoPago::Efectivo).is_ok());
         assert!(streaming.upgrade_subscripcion(1).is_ok());
         let subscripcion = streaming.suscripciones.first().unwrap();
@@ -330,7 +393,7 @@ mod tests {
 
     #[test]
     fn test_downgrade_subscripcion() {
-        let mut streaming = StreamingRust::new("test_downgrade_subscripcion");
+        let mut streaming = StreamingRust::new("test_downgrade_subscripcion", Rc::new(AlmacenamientoMemoria::new()));
         assert!(streaming.crear_subscripcion(1, "Juan".to_string(), TipoSuscripcion::Super, 3, MetodoPago::Efectivo).is_ok());
         assert!(streaming.downgrade_subscripcion(1).is_ok());
         let subscripcion = streaming.suscripciones.first().unwrap();
@@ -339,7 +402,7 @@ mod tests {
 
     #[test]
     fn test_downgrade_subscripcion_cancel() {
-        let mut streaming = StreamingRust::new("test_downgrade_subscripcion_cancel");
+        let mut streaming = StreamingRust::new("test_downgrade_subscripcion_cancel", Rc::new(AlmacenamientoMemoria::new()));
         assert!(streaming.crear_subscripcion(1, "Juan".to_string(), TipoSuscripcion::Basic, 3, MetodoPago::Efectivo).is_ok());
         assert!(streaming.downgrade_subscripcion(1).is_ok());
         let subscripcion = streaming.suscripciones.first().unwrap();
@@ -348,7 +411,7 @@ mod tests {
 
     #[test]
     fn test_cancel_subscripcion() {
-        let mut streaming = StreamingRust::new("test_cancel_subscripcion");
+        let mut streaming = StreamingRust::new("test_cancel_subscripcion", Rc::new(AlmacenamientoMemoria::new()));
         assert!(streaming.crear_subscripcion(1, "Juan".to_string(), TipoSuscripcion::Super, 3, MetodoPago::Efectivo).is_ok());
         assert!(streaming.cancel_subscripcion(1).is_ok());
         let subscripcion = streaming.suscripciones.first().unwrap();
@@ -357,7 +420,7 @@ mod tests {
 
     #[test]
     fn test_most_used_active_metodo_pago() {
-        let mut streaming = StreamingRust::new("test_most_used_active_metodo_pago");
+        let mut streaming = StreamingRust::new("test_most_used_active_metodo_pago", Rc::new(AlmacenamientoMemoria::new()));
         assert!(streaming.crear_subscripcion(1, "Juan".to_string(), TipoSuscripcion::Super, 3, MetodoPago::Efectivo).is_ok());
         assert!(streaming.crear_subscripcion(2, "Pedro".to_string(), TipoSuscripcion::Super, 3, MetodoPago::Efectivo).is_ok());
         assert!(streaming.crear_subscripcion(3, "Pablo".to_string(), TipoSuscripcion::Super, 3, MetodoPago::MercadoPago { id_cuenta: "123".to_string() }).is_ok());
@@ -371,7 +434,7 @@ mod tests {
 
     #[test]
     fn test_most_popular_active_subscripcion() {
-        let mut streaming = StreamingRust::new("test_most_popular_active_subscripcion");
+        let mut streaming = StreamingRust::new("test_most_popular_active_subscripcion", Rc::new(AlmacenamientoMemoria::new()));
         assert!(streaming.crear_subscripcion(1, "Juan".to_string(), TipoSuscripcion::Super, 3, MetodoPago::Efectivo).is_ok());
         assert!(streaming.crear_subscripcion(2, "Pedro".to_string(), TipoSuscripcion::Super, 3, MetodoPago::Efectivo).is_ok());
         assert!(streaming.crear_subscripcion(3, "Pablo".to_string(), TipoSuscripcion::Basic, 3, MetodoPago::Efectivo).is_ok());
@@ -385,7 +448,7 @@ mod tests {
 
     #[test]
     fn test_most_used_metodo_pago() {
-        let mut streaming = StreamingRust::new("test_most_used_metodo_pago");
+        let mut streaming = StreamingRust::new("test_most_used_metodo_pago", Rc::new(AlmacenamientoMemoria::new()));
         assert!(streaming.crear_subscripcion(1, "Juan".to_string(), TipoSuscripcion::Super, 3, MetodoPago::Efectivo).is_ok());
         assert!(streaming.crear_subscripcion(2, "Pedro".to_string(), TipoSuscripcion::Super, 3, MetodoPago::Efectivo).is_ok());
         assert!(streaming.crear_subscripcion(3, "Pablo".to_string(), TipoSuscripcion::Super, 3, MetodoPago::Efectivo).is_ok());
@@ -399,7 +462,7 @@ mod tests {
 
     #[test]
     fn test_most_popular_subscripcion() {
-        let mut streaming = StreamingRust::new("test_most_popular_subscripcion");
+        let mut streaming = StreamingRust::new("test_most_popular_subscripcion", Rc::new(AlmacenamientoMemoria::new()));
         assert!(streaming.crear_subscripcion(1, "Juan".to_string(), TipoSuscripcion::Super, 3, MetodoPago::Efectivo).is_ok());
         assert!(streaming.crear_subscripcion(2, "Pedro".to_string(), TipoSuscripcion::Super, 3, MetodoPago::Efectivo).is_ok());
         assert!(streaming.crear_subscripcion(3, "Pablo".to_string(), TipoSuscripcion::Basic, 3, MetodoPago::Efectivo).is_ok());
@@ -410,4 +473,15 @@ mod tests {
         let tipo_suscripcion = streaming.tipo_suscripcion_mas_usado().unwrap();
         assert_eq!(tipo_suscripcion, TipoSuscripcion::Basic);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_cargar_fixtures() {
+        for (version, almacenamiento) in cargar_fixtures("practica5/ej5") {
+            let streaming = StreamingRust::cargar("fixture", almacenamiento, &PoliticaCarga::estricta())
+                .unwrap_or_else(|e| panic!("{}: {}", version, e));
+            assert_eq!(streaming.suscripciones.len(), 2, "{}", version);
+            assert_eq!(streaming.suscripciones[0].fecha_inicio, Fecha::new(1, 1, 2024), "{}", version);
+            assert!(streaming.suscripciones[0].esta_activa(), "{}", version);
+        }
+    }
+}
@@ -1,26 +1,124 @@
-use std::{collections::HashMap, io::{Read, Write}};
-use rand::{thread_rng, Rng};
+use std::{collections::{BTreeMap, HashMap, VecDeque}, fmt::{Display, Formatter}, path::Path, rc::Rc};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
 
-use crate::practica3::ej3::Fecha;
+use crate::almacenamiento::{
+    agregar_json_linea, cargar_json, cargar_json_lineas, cargar_json_opcional, escribir_atomico, escribir_json, AccionCarga,
+    Almacenamiento, ErrorCarga, Migraciones, PoliticaCarga, Versionado,
+};
+use crate::dinero::{Dinero, ErrorDinero, Moneda, Porcentaje, Redondeo};
+use crate::fecha::{Duracion, Fecha, FechaHora, Reloj, RelojSistema};
 
 struct XYZ {
     file_name: String,
     usuarios: Vec<Usuario>,
-    cotizaciones: HashMap<String, f64>,
+    cotizaciones: HistorialCotizaciones,
+    fuente_cotizaciones: Option<Box<dyn FuenteCotizaciones>>,
+    monedas_fiat: Vec<Moneda>,
     criptomonedas: Vec<CriptoMoneda>,
     transacciones: Vec<Transaccion>,
-    // HashMap<dni, hashmap<criptomonedas, count>>
-    balances: HashMap<String, HashMap<String, f64>>
+    // HashMap<dni, hashmap<codigo de la moneda, balance>>
+    balances: HashMap<String, HashMap<String, Dinero>>,
+    // Ordenes pendientes de cada par (criptomoneda, fiat)
+    libros: BTreeMap<(String, String), LibroOrdenes>,
+    comisiones: Comisiones,
+    // Cadena simulada de cada blockchain por nombre, se crea con el primer retiro
+    cadenas: HashMap<String, CadenaSimulada>,
+    confirmaciones_retiro: u64,
+    // Limites de cada nivel de identidad, los niveles que no estan no tienen limites
+    limites: HashMap<NivelIdentidad, LimitesNivel>,
+    validez_kyc_dias: i64,
+    reloj: Rc<dyn Reloj>,
+    almacenamiento: Rc<dyn Almacenamiento>,
+    // Numero de la ultima linea agregada al log de operaciones
+    ultima_operacion: u64,
+    operaciones_sin_compactar: usize,
+    limite_compactacion: usize
 }
 
+/// Cantidad de lineas del log de operaciones despues de la cual se guarda una foto del estado
+const LIMITE_COMPACTACION: usize = 100;
+
+/// Clave que usaban los balances en pesos cuando era la unica moneda fiat (version 2 y anteriores)
+const FIAT_ANTERIOR: &str = "fiat";
+
+/// Hash anterior del bloque inicial de las cadenas simuladas
+const HASH_INICIAL: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Bloques que tienen que minarse desde el que incluye un retiro (contandolo) para confirmarlo
+const CONFIRMACIONES_RETIRO: u64 = 6;
+
+/// Dias que dura la verificacion de identidad aprobada
+const VALIDEZ_KYC_DIAS: i64 = 365;
+
+/// Cuenta en la que se acreditan las comisiones cobradas. Tiene balances como un usuario pero no opera
+const CUENTA_CASA: &str = "casa";
+
 struct Usuario {
     nombre: String,
     apellido: String,
     email: String,
     dni: String,
     // balance: HashMap<String, f64>,
-    identidad: bool
+    kyc: EstadoKyc,
+    historial_kyc: Vec<CambioKyc>
+}
+
+/// Hasta donde se verifico la identidad del usuario, de esto dependen sus limites
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum NivelIdentidad {
+    SinVerificar,
+    Basico,
+    Completo,
+}
+
+/// Estado de la verificacion de identidad (KYC). Empieza pendiente, con los documentos enviados para un
+/// nivel se aprueba o se rechaza, y la aprobacion vence. Mientras no este aprobada y vigente el usuario
+/// tiene los limites de `NivelIdentidad::SinVerificar`
+#[derive(Clone, Debug, PartialEq)]
+enum EstadoKyc {
+    Pendiente,
+    DocumentosEnviados { nivel: NivelIdentidad },
+    Aprobado { nivel: NivelIdentidad, vence: FechaHora },
+    Rechazado,
+    Vencido { nivel: NivelIdentidad },
+}
+
+/// Registro de auditoria de un cambio de estado de la verificacion
+#[derive(Clone, Debug, PartialEq)]
+struct CambioKyc {
+    fecha: FechaHora,
+    desde: EstadoKyc,
+    hacia: EstadoKyc,
+    motivo: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ErrorKyc {
+    UsuarioNoEncontrado(String),
+    /// La accion no se puede hacer desde el estado actual
+    TransicionInvalida { desde: EstadoKyc, accion: &'static str },
+}
+
+/// Montos maximos de un nivel de identidad, cada uno en cualquier moneda. None es sin limite y un limite
+/// en cero no permite la operacion
+#[derive(Clone, Debug)]
+struct LimitesNivel {
+    // Ingresos de dinero y recepciones de criptomonedas en el dia
+    ingreso_diario: Option<Dinero>,
+    // Valor de cada compra o venta
+    operacion: Option<Dinero>,
+    // Retiros de dinero y de criptomonedas en el dia
+    retiro_diario: Option<Dinero>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TipoLimite {
+    IngresoDiario,
+    Operacion,
+    RetiroDiario,
 }
 
 struct CriptoMoneda {
@@ -34,36 +132,412 @@ struct BlockChain {
     prefijo: String
 }
 
-#[derive(Serialize, Deserialize)]
+/// Cadena de bloques local que simula una blockchain. Cada bloque guarda el hash SHA-256 del anterior,
+/// asi cambiar un bloque invalida todos los siguientes. Los retiros quedan pendientes hasta que se mina
+/// un bloque, que incluye todos los pendientes
+#[derive(Clone, Serialize, Deserialize)]
+struct CadenaSimulada {
+    blockchain: String,
+    bloques: Vec<Bloque>,
+    pendientes: Vec<RetiroEnCadena>,
+    // Cantidad de retiros registrados, se usa para que dos retiros iguales tengan hashes distintos
+    retiros: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Bloque {
+    altura: u64,
+    hash_anterior: String,
+    fecha: FechaHora,
+    retiros: Vec<RetiroEnCadena>,
+    hash: String,
+}
+
+/// Retiro enviado a la cadena, el hash se calcula con el resto de los datos
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct RetiroEnCadena {
+    numero: u64,
+    dni_usuario: String,
+    monto: Dinero,
+    fecha: FechaHora,
+    hash: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum EstadoRetiro {
+    /// Todavia no se mino un bloque que lo incluya
+    Pendiente,
+    /// Incluido en el bloque de la altura indicada pero con menos confirmaciones que las necesarias
+    EnBloque { altura: u64, confirmaciones: u64 },
+    Confirmado { altura: u64, confirmaciones: u64 },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 struct Transaccion {
-    fecha: Fecha,
+    fecha: FechaHora,
     tipo: TipoTransaccion,
     dni_usuario: String
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
+// En la compra el monto es lo pagado en la moneda fiat elegida y la cantidad es la criptomoneda acreditada,
+// en el resto de las operaciones con criptomonedas el monto es la cantidad de la criptomoneda. En la compra
+// y la venta la cotizacion es el precio de una unidad en la moneda fiat usada (con el spread aplicado), en los
+// retiros y recepciones es el precio en pesos. La comision de la compra es parte del monto pagado, la de la
+// venta se descuenta de lo obtenido y la del retiro se cobra aparte
 enum TipoTransaccion {
-    IngresoDinero { monto: f64 },
-    CompraCripto { monto: f64, criptomoneda: String, cotizacion: f64 },
-    VentaCripto { monto: f64, criptomoneda: String, cotizacion: f64 },
-    RetiroCripto { monto: f64, criptomoneda: String, cotizacion: f64, blockchain: String, hash: String },
-    RecepcionCripto { monto: f64, criptomoneda: String, blockchain: String, cotizacion: f64 },
-    RetiroFiat {monto: f64, medio: MedioRetiro},
+    IngresoDinero { monto: Dinero },
+    CompraCripto { monto: Dinero, criptomoneda: String, cotizacion: Dinero, cantidad: Dinero, comision: Dinero },
+    VentaCripto { monto: Dinero, criptomoneda: String, cotizacion: Dinero, comision: Dinero },
+    RetiroCripto { monto: Dinero, criptomoneda: String, cotizacion: Dinero, blockchain: String, hash: String, comision: Dinero },
+    RecepcionCripto { monto: Dinero, criptomoneda: String, blockchain: String, cotizacion: Dinero },
+    RetiroFiat {monto: Dinero, medio: MedioRetiro},
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 enum MedioRetiro {
     MercadoPago,
     TransferenciaBancaria
 }
 
+/// Cambio guardado en el log de operaciones, al iniciar se vuelven a aplicar en orden
+#[derive(Clone, Serialize, Deserialize)]
+enum Operacion {
+    Balance { dni_usuario: String, moneda: String, monto: Dinero },
+    Transaccion(Transaccion),
+    OrdenNueva(Orden),
+    // Parte ejecutada de una orden del libro y cuanto de lo reservado se uso para pagarla
+    OrdenEjecutada { id: u64, cantidad: Dinero, reservado: Dinero },
+    OrdenCancelada { id: u64 },
+    // Retiro enviado a la cadena simulada de la blockchain, queda pendiente hasta que se mine un bloque
+    RetiroEnviado { blockchain: String, retiro: RetiroEnCadena },
+    BloqueMinado { blockchain: String, fecha: FechaHora },
+}
+
+/// Linea del log de operaciones (JSON Lines), sus operaciones se aplican todas juntas
+#[derive(Serialize, Deserialize)]
+struct RegistroOperaciones {
+    numero: u64,
+    operaciones: Vec<Operacion>,
+}
+
+/// Cambios de balances y transacciones que se preparan juntos y se guardan con `XYZ::confirmar`
+/// como una sola linea del log, asi se aplican todos o ninguno. Para descartarlos alcanza con no confirmarla
+struct UnidadTrabajo {
+    fecha: FechaHora,
+    operaciones: Vec<Operacion>,
+}
+
+/// Copia de lo que pueden cambiar las operaciones, para volver atras si alguna no se puede aplicar.
+/// Las transacciones solo se agregan al final, alcanza con saber cuantas habia
+struct EstadoAnterior {
+    balances: HashMap<String, HashMap<String, Dinero>>,
+    transacciones: usize,
+    libros: BTreeMap<(String, String), LibroOrdenes>,
+    cadenas: HashMap<String, CadenaSimulada>,
+}
+
+/// Foto del estado que se guarda al compactar el log de operaciones.
+/// Las lineas del log con numero menor o igual a `ultima_operacion` ya estan incluidas
+#[derive(Default, Serialize, Deserialize)]
+struct EstadoXYZ {
+    ultima_operacion: u64,
+    balances: HashMap<String, HashMap<String, Dinero>>,
+    transacciones: Vec<Transaccion>,
+    // Ordenes de todos los libros, cada libro en su orden de prioridad
+    ordenes: Vec<Orden>,
+    cadenas: Vec<CadenaSimulada>,
+}
+
+/// Precio de una unidad de `base` expresado en la moneda del precio, desde la fecha indicada
+#[derive(Clone, Debug, PartialEq)]
+struct Cotizacion {
+    base: String,
+    precio: Dinero,
+    fecha: FechaHora,
+}
+
+/// Serie de precios de cada par, la clave es (base, cotizada). Por ejemplo (BTC, USD) o (USD, ARS)
+#[derive(Default)]
+struct HistorialCotizaciones {
+    series: BTreeMap<(String, String), BTreeMap<FechaHora, Dinero>>,
+}
+
+/// Origen de las cotizaciones que se cargan en el historial
+trait FuenteCotizaciones {
+    /// Cotizaciones nuevas con fecha hasta la indicada inclusive, en orden cronologico.
+    /// Cada cotizacion se entrega una sola vez
+    fn cotizaciones_hasta(&mut self, fecha: &FechaHora) -> Result<Vec<Cotizacion>, ErrorCotizacion>;
+}
+
+/// Genera cotizaciones con una caminata aleatoria: cada `intervalo_segundos` el precio de cada par
+/// sube o baja un porcentaje al azar de hasta `volatilidad`. Con la misma semilla genera la misma serie
+struct SimuladorCotizaciones {
+    rng: StdRng,
+    precios: Vec<(String, Dinero)>,
+    volatilidad: Porcentaje,
+    intervalo_segundos: i64,
+    siguiente: FechaHora,
+}
+
+/// Reproduce cotizaciones leidas de un CSV con las columnas `fecha,base,cotizada,precio`, por ejemplo
+/// `2024-03-05T10:00:00-03:00,BTC,USD,50000.00`. La primera linea es el encabezado
+struct ReproduccionCsv {
+    pendientes: VecDeque<Cotizacion>,
+}
+
+#[derive(Deserialize)]
+struct FilaCotizacion {
+    fecha: String,
+    base: String,
+    cotizada: String,
+    precio: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ErrorCotizacion {
+    /// No hay ningun camino de pares con precio entre las dos monedas
+    SinCotizacion { base: String, cotizada: String },
+    /// No se pudo leer la fuente de cotizaciones
+    Fuente(String),
+    Dinero(ErrorDinero),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+enum Lado {
+    Compra,
+    Venta
+}
+
+/// Orden limite que espera en el libro. El id es el numero de la linea del log en la que se coloco,
+/// asi ordenar por id es ordenar por antiguedad. La cantidad es lo que falta ejecutar de la
+/// criptomoneda y el precio es por unidad en la moneda fiat del par.
+/// Lo reservado se desconto del balance del usuario al colocarla: la moneda fiat en las compras y
+/// la criptomoneda en las ventas. Lo que sobra al terminar de ejecutarla o al cancelarla se devuelve
+#[derive(Clone, Serialize, Deserialize)]
+struct Orden {
+    id: u64,
+    dni_usuario: String,
+    lado: Lado,
+    precio: Dinero,
+    cantidad: Dinero,
+    reservado: Dinero,
+    fecha: FechaHora,
+}
+
+/// Ordenes pendientes de un par criptomoneda/fiat. Las compras estan ordenadas de mayor a menor
+/// precio y las ventas de menor a mayor, a igual precio primero la mas antigua
+#[derive(Clone, Default)]
+struct LibroOrdenes {
+    compras: Vec<Orden>,
+    ventas: Vec<Orden>,
+}
+
+/// Comisiones que cobra XYZ, lo cobrado se acredita en la `CUENTA_CASA`. Por defecto no se cobra nada
+#[derive(Clone)]
+struct Comisiones {
+    // Porcentaje de lo pagado en las compras y de lo obtenido en las ventas
+    operacion: Porcentaje,
+    // Se compra a la cotizacion mas este porcentaje y se vende a la cotizacion menos este porcentaje
+    spread: Porcentaje,
+    // Monto fijo por retiro en cada blockchain, en la moneda en la que se cobra
+    red: HashMap<String, Dinero>,
+}
+
+/// Balance guardado de una cuenta que no coincide con el que resulta de volver a aplicar las transacciones
+#[derive(Clone, Debug, PartialEq)]
+struct Discrepancia {
+    dni_usuario: String,
+    moneda: String,
+    esperado: Dinero,
+    guardado: Dinero,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Violacion {
+    /// Un balance guardado es negativo
+    SaldoNegativo { dni_usuario: String, moneda: String, balance: Dinero },
+    /// Al volver a aplicar la transaccion en la posicion `transaccion` del log el balance queda negativo
+    SaldoNegativoEnLog { transaccion: usize, dni_usuario: String, moneda: String, balance: Dinero },
+    /// El total de la criptomoneda en las cuentas, sin lo reservado en ordenes, no es el que resulta de las transacciones
+    CriptoNoConservada { criptomoneda: String, esperado: Dinero, total: Dinero },
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct ReporteConciliacion {
+    discrepancias: Vec<Discrepancia>,
+    violaciones: Vec<Violacion>,
+}
+
+/// Fechas entre las que se calculan las estadisticas, ambas incluidas. Un extremo en None no tiene limite
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Periodo {
+    desde: Option<Fecha>,
+    hasta: Option<Fecha>,
+}
+
+/// Tamaño de los intervalos en los que se agrupan las estadisticas. Las semanas empiezan el lunes
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Intervalo {
+    Dia,
+    Semana,
+    Mes,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Metrica {
+    Compras,
+    Ventas,
+    VolumenVentas,
+}
+
+/// Criptomonedas que mas se operaron en un intervalo, None si no hubo operaciones de ese tipo
+#[derive(Clone, Debug, PartialEq)]
+struct EstadisticasIntervalo {
+    inicio: Fecha,
+    cripto_mas_compras: Option<String>,
+    cripto_mas_ventas: Option<String>,
+    cripto_mas_volumen_compras: Option<String>,
+    cripto_mas_volumen_venta: Option<String>,
+}
+
+/// Vela OHLC de una criptomoneda con las cotizaciones a las que se ejecutaron compras y ventas
+#[derive(Clone, Debug, PartialEq)]
+struct Vela {
+    inicio: Fecha,
+    apertura: Dinero,
+    maximo: Dinero,
+    minimo: Dinero,
+    cierre: Dinero,
+    // Cantidad de criptomoneda operada
+    volumen: Dinero,
+    operaciones: usize,
+}
+
+/// Tenencias de criptomonedas de un usuario calculadas a partir de sus transacciones, valuadas en
+/// `moneda` a las cotizaciones de `fecha`
+#[derive(Clone, Debug, PartialEq)]
+struct ReportePortafolio {
+    dni_usuario: String,
+    moneda: Moneda,
+    fecha: FechaHora,
+    posiciones: Vec<PosicionCripto>,
+    valor_total: Dinero,
+    total_fifo: ResultadoCosteo,
+    total_promedio: ResultadoCosteo,
+}
+
+/// Tenencia de una criptomoneda, con el costo y las ganancias calculados por los dos metodos
+#[derive(Clone, Debug, PartialEq)]
+struct PosicionCripto {
+    criptomoneda: String,
+    cantidad: Dinero,
+    valor_actual: Dinero,
+    fifo: ResultadoCosteo,
+    promedio: ResultadoCosteo,
+}
+
+/// Las ganancias negativas son perdidas
+#[derive(Clone, Debug, PartialEq)]
+struct ResultadoCosteo {
+    // Lo que costo la cantidad que todavia se tiene
+    costo: Dinero,
+    // Lo obtenido en las ventas menos lo que costo lo vendido
+    realizado: Dinero,
+    // Valor actual menos costo
+    no_realizado: Dinero,
+}
+
+/// Operaciones de un usuario en un año con su equivalente en la moneda del reporte, a las cotizaciones
+/// del momento de cada operacion. Las ganancias se calculan por FIFO con todo lo operado desde el inicio
+#[derive(Clone, Debug, PartialEq)]
+struct ReporteImpositivo {
+    dni_usuario: String,
+    anio: i32,
+    moneda: Moneda,
+    movimientos: Vec<MovimientoImpositivo>,
+    totales: TotalesImpositivos,
+    tenencias: Vec<TenenciaFinDeAnio>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TipoMovimiento {
+    Compra,
+    Venta,
+    Deposito,
+    Retiro,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct MovimientoImpositivo {
+    fecha: FechaHora,
+    tipo: TipoMovimiento,
+    // Monto en la moneda o criptomoneda operada
+    cantidad: Dinero,
+    // En las compras lo pagado con la comision incluida, en las ventas lo cobrado antes de la comision
+    equivalente: Dinero,
+    comision: Dinero,
+    // Solo en las ventas
+    ganancia: Option<Dinero>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct TotalesImpositivos {
+    compras: Dinero,
+    ventas: Dinero,
+    depositos: Dinero,
+    retiros: Dinero,
+    comisiones: Dinero,
+    ganancia_realizada: Dinero,
+}
+
+/// Criptomoneda que se tenia al terminar el año, valuada con la ultima cotizacion del año
+#[derive(Clone, Debug, PartialEq)]
+struct TenenciaFinDeAnio {
+    cantidad: Dinero,
+    costo: Dinero,
+    valor: Dinero,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FormatoExportacion {
+    Csv,
+    Json,
+}
+
+/// Fila del reporte impositivo en CSV. Los totales y las tenencias van al final con la fecha de cierre
+#[derive(Serialize)]
+struct FilaImpositiva {
+    fecha: String,
+    tipo: String,
+    activo: String,
+    cantidad: String,
+    moneda: String,
+    equivalente: String,
+    comision: String,
+    costo: String,
+    ganancia: String,
+}
+
+/// Lotes de una criptomoneda que se van sumando con las compras y recepciones y consumiendo con las
+/// ventas y retiros. Con FIFO cada salida consume los lotes mas antiguos; con costo promedio se lleva
+/// solo la cantidad y el costo totales. Los costos estan en la moneda del reporte
+struct Costeo {
+    // (cantidad, costo) de cada lote que queda, del mas antiguo al mas nuevo
+    lotes: VecDeque<(Dinero, Dinero)>,
+    cantidad: Dinero,
+    costo_promedio: Dinero,
+    realizado_fifo: Dinero,
+    realizado_promedio: Dinero,
+}
+
 trait GestorMonedas {
-    fn ingresar_dinero(&mut self, dni_usuario: &str, monto: f64) -> std::io::Result<()>;
-    fn comprar_cripto(&mut self, dni_usuario: &str, monto: f64, criptomoneda: &str) -> std::io::Result<()>;
-    fn vender_cripto(&mut self, dni_usuario: &str, monto: f64, criptomoneda: &str) -> std::io::Result<()>;
-    fn retirar_cripto(&mut self, dni_usuario: &str, monto: f64, criptomoneda: &str, blockchain: &str) -> std::io::Result<()>;
-    fn recibir_cripto(&mut self, dni_usuario: &str, monto: f64, criptomoneda: &str, blockchain: &str) -> std::io::Result<()>;
-    fn retirar_dinero(&mut self, dni_usuario: &str, monto: f64, medio: MedioRetiro) -> std::io::Result<()>;   
+    fn ingresar_dinero(&mut self, dni_usuario: &str, monto: Dinero) -> std::io::Result<()>;
+    fn comprar_cripto(&mut self, dni_usuario: &str, monto: Dinero, criptomoneda: &str) -> std::io::Result<()>;
+    fn vender_cripto(&mut self, dni_usuario: &str, monto: Dinero, criptomoneda: &str) -> std::io::Result<()>;
+    fn retirar_cripto(&mut self, dni_usuario: &str, monto: Dinero, criptomoneda: &str, blockchain: &str) -> std::io::Result<()>;
+    fn recibir_cripto(&mut self, dni_usuario: &str, monto: Dinero, criptomoneda: &str, blockchain: &str) -> std::io::Result<()>;
+    fn retirar_dinero(&mut self, dni_usuario: &str, monto: Dinero, medio: MedioRetiro) -> std::io::Result<()>;   
 }
 
 trait GestorUsuarios {
@@ -72,369 +546,2732 @@ trait GestorUsuarios {
     fn get_usuario(&self, dni_usuario: &str) -> Option<&Usuario>;
 }
 
+// Cada cambio de estado queda en el historial del usuario con la fecha y el motivo
+trait GestorKyc {
+    fn enviar_documentos(&mut self, dni_usuario: &str, nivel: NivelIdentidad, motivo: &str) -> Result<(), ErrorKyc>;
+    fn aprobar_kyc(&mut self, dni_usuario: &str, motivo: &str) -> Result<(), ErrorKyc>;
+    fn rechazar_kyc(&mut self, dni_usuario: &str, motivo: &str) -> Result<(), ErrorKyc>;
+    fn estado_kyc(&self, dni_usuario: &str) -> Option<EstadoKyc>;
+    fn historial_kyc(&self, dni_usuario: &str) -> &[CambioKyc];
+    fn actualizar_vencimientos(&mut self) -> usize;
+}
+
 trait GestorTransacciones {
     fn crear_transaccion(&mut self, tipo: TipoTransaccion, dni_usuario: &str) -> std::io::Result<()>;
-    fn escribir_archivo_transacciones(&self) -> std::io::Result<()>;
 }
 
 trait GestorBalances {
-    fn get_balance(&self, dni_usuario: &str, moneda: &str) -> f64;
-    fn add_balance(&mut self, dni_usuario: &str, moneda: &str, monto: f64) -> std::io::Result<()>;
-    fn remove_balance(&mut self, dni_usuario: &str, moneda: &str, monto: f64) -> std::io::Result<()>;
-    fn escribir_archivo_balance(&self) -> std::io::Result<()>;
+    fn get_balance(&self, dni_usuario: &str, moneda: &str) -> Dinero;
+    fn add_balance(&mut self, dni_usuario: &str, moneda: &str, monto: Dinero) -> std::io::Result<()>;
+    fn remove_balance(&mut self, dni_usuario: &str, moneda: &str, monto: Dinero) -> std::io::Result<()>;
+}
+
+// Las ejecuciones se registran como compras y ventas, asi tambien cuentan para las estadisticas.
+// En el libro no se cobran comisiones
+trait GestorOrdenes {
+    fn colocar_orden_limite(&mut self, dni_usuario: &str, lado: Lado, cantidad: Dinero, precio: Dinero) -> std::io::Result<u64>;
+    fn colocar_orden_mercado(&mut self, dni_usuario: &str, lado: Lado, cantidad: Dinero, fiat: &Moneda) -> std::io::Result<Dinero>;
+    fn cancelar_orden(&mut self, dni_usuario: &str, id: u64) -> std::io::Result<()>;
 }
 
+// Las transacciones se ubican en el dia segun el offset del reloj. Los intervalos sin operaciones no aparecen
 trait Estadisticas {
-    fn cripto_mas_ventas(&self) -> String;
-    fn cripto_mas_compras(&self) -> String;
-    fn cripto_mas_volumen_venta(&self) -> String;
-    fn cripto_mas_volumen_compras(&self) -> String;
+    fn cripto_mas_ventas(&self, periodo: &Periodo) -> Option<String>;
+    fn cripto_mas_compras(&self, periodo: &Periodo) -> Option<String>;
+    fn cripto_mas_volumen_venta(&self, periodo: &Periodo) -> Option<String>;
+    fn cripto_mas_volumen_compras(&self, periodo: &Periodo) -> Result<Option<String>, ErrorCotizacion>;
+    fn estadisticas_por_intervalo(&self, periodo: &Periodo, intervalo: Intervalo) -> Result<Vec<EstadisticasIntervalo>, ErrorCotizacion>;
+    fn velas(&self, criptomoneda: &str, moneda: &Moneda, periodo: &Periodo, intervalo: Intervalo) -> Result<Vec<Vela>, ErrorDinero>;
+}
+
+trait Portafolios {
+    fn reporte_portafolio(&self, dni_usuario: &str, moneda: &Moneda) -> Result<ReportePortafolio, ErrorCotizacion>;
+    fn reporte_impositivo(&self, dni_usuario: &str, anio: i32, moneda: &Moneda) -> Result<ReporteImpositivo, ErrorCotizacion>;
 }
 
 impl GestorMonedas for XYZ {
-    fn ingresar_dinero(&mut self, dni_usuario: &str, monto: f64) -> std::io::Result<()>{
-        if self.verificar_identidad(dni_usuario) {
-            self.add_balance(dni_usuario, "fiat", monto)?;
-            self.crear_transaccion(TipoTransaccion::IngresoDinero { monto }, dni_usuario)
+    fn ingresar_dinero(&mut self, dni_usuario: &str, monto: Dinero) -> std::io::Result<()>{
+        self.validar_fiat(monto.moneda())?;
+        self.validar_limite(dni_usuario, TipoLimite::IngresoDiario, &monto)?;
+        let mut unidad = self.iniciar_unidad();
+        unidad.add_balance(dni_usuario, monto.moneda().codigo(), monto.clone());
+        unidad.crear_transaccion(TipoTransaccion::IngresoDinero { monto }, dni_usuario);
+        self.confirmar(unidad)
+    }
+
+    // Se paga con el balance de la moneda fiat del monto
+    fn comprar_cripto(&mut self, dni_usuario: &str, monto: Dinero, criptomoneda: &str) -> std::io::Result<()>{
+        self.validar_fiat(monto.moneda())?;
+        let fiat = monto.moneda().codigo().to_string();
+        self.validar_limite(dni_usuario, TipoLimite::Operacion, &monto)?;
+        if self.get_balance(dni_usuario, &fiat) >= monto {
+            let cotizacion = self.comisiones.precio_compra(&self.get_cotizacion(criptomoneda, monto.moneda())?).map_err(std::io::Error::other)?;
+            let comision = self.comisiones.comision_operacion(&monto).map_err(std::io::Error::other)?;
+            // Lo que no llega a la unidad menor de la criptomoneda queda para la casa
+            let comprado = monto.restar(&comision)
+                .and_then(|neto| neto.convertir(&cotizacion, &self.moneda(criptomoneda), Redondeo::Truncar))
+                .map_err(std::io::Error::other)?;
+            let mut unidad = self.iniciar_unidad();
+            unidad.add_balance(dni_usuario, criptomoneda, comprado.clone());
+            unidad.remove_balance(dni_usuario, &fiat, monto.clone());
+            unidad.acreditar_comision(&comision);
+            unidad.crear_transaccion(TipoTransaccion::CompraCripto { monto, criptomoneda: criptomoneda.to_string(), cotizacion, cantidad: comprado, comision }, dni_usuario);
+            self.confirmar(unidad)
         } else {
-            Err(std::io::Error::new(std::io::ErrorKind::Other, "Ha ocurrido un error en la transaccion"))
+            Err(std::io::Error::other("Ha ocurrido un error en la transaccion"))
         }
     }
 
-    fn comprar_cripto(&mut self, dni_usuario: &str, monto: f64, criptomoneda: &str) -> std::io::Result<()>{
-        if self.verificar_identidad(dni_usuario) && self.get_balance(dni_usuario, "fiat") >= monto {
-            let cotizacion = self.get_cotizacion(&criptomoneda);
-            self.add_balance(dni_usuario, &criptomoneda, monto / cotizacion)?;
-            self.remove_balance(dni_usuario, "fiat", monto)?;
-            self.crear_transaccion(TipoTransaccion::CompraCripto { monto, criptomoneda: criptomoneda.to_string(), cotizacion }, dni_usuario)
+    // Lo obtenido se acredita en pesos, para otra moneda usar `vender_cripto_en`
+    fn vender_cripto(&mut self, dni_usuario: &str, monto: Dinero, criptomoneda: &str) -> std::io::Result<()>{
+        self.vender_cripto_en(dni_usuario, monto, criptomoneda, &Moneda::ars())
+    }
+
+    fn retirar_cripto(&mut self, dni_usuario: &str, monto: Dinero, criptomoneda: &str, blockchain: &str) -> std::io::Result<()>{
+        if self.get_usuario(dni_usuario).is_some() && self.get_balance(dni_usuario, criptomoneda) >= monto {
+            let cripto = self.get_criptomoneda(criptomoneda)
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Criptomoneda no encontrada"))?;
+            if !cripto.blockchains.iter().any(|b| b.nombre == blockchain) {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{} no se puede retirar por {}", criptomoneda, blockchain)));
+            }
+            let cotizacion = self.get_cotizacion(criptomoneda, &Moneda::ars())?;
+            self.validar_limite(dni_usuario, TipoLimite::RetiroDiario, &monto.valuar(&cotizacion, Redondeo::MitadPar).map_err(std::io::Error::other)?)?;
+            let comision = self.comisiones.comision_red(blockchain, monto.moneda());
+
+            let mut unidad = self.iniciar_unidad();
+            let numero = self.cadenas.get(blockchain).map_or(0, |cadena| cadena.retiros);
+            let retiro = RetiroEnCadena::new(blockchain, numero, dni_usuario, monto.clone(), unidad.fecha.clone());
+            unidad.remove_balance(dni_usuario, criptomoneda, monto.clone());
+            unidad.remove_balance(dni_usuario, comision.moneda().codigo(), comision.clone());
+            unidad.acreditar_comision(&comision);
+            unidad.crear_transaccion(TipoTransaccion::RetiroCripto { monto, criptomoneda: criptomoneda.to_string(), cotizacion, blockchain: blockchain.to_string(), hash: retiro.hash.clone(), comision }, dni_usuario);
+            // Se envia a la cadena en la misma unidad, asi solo llega si se guardo el retiro
+            unidad.enviar_retiro(blockchain, retiro);
+            self.confirmar(unidad)
         } else {
-            Err(std::io::Error::new(std::io::ErrorKind::Other, "Ha ocurrido un error en la transaccion"))
+            Err(std::io::Error::other("Ha ocurrido un error en la transaccion"))
         }
     }
 
-    fn vender_cripto(&mut self, dni_usuario: &str, monto: f64, criptomoneda: &str) -> std::io::Result<()>{
-        if self.verificar_identidad(dni_usuario) && self.get_balance(dni_usuario, criptomoneda) >= monto {
-            let cotizacion: f64 = self.get_cotizacion(&criptomoneda);
-            self.add_balance(dni_usuario, "fiat", monto * cotizacion)?;
-            self.remove_balance(dni_usuario, &criptomoneda, monto)?;
-            self.crear_transaccion(TipoTransaccion::VentaCripto { monto, criptomoneda: criptomoneda.to_string(), cotizacion }, dni_usuario)
+    fn recibir_cripto(&mut self, dni_usuario: &str, monto: Dinero, criptomoneda: &str, blockchain: &str) -> std::io::Result<()>{
+        let cotizacion = self.get_cotizacion(criptomoneda, &Moneda::ars())?;
+        self.validar_limite(dni_usuario, TipoLimite::IngresoDiario, &monto.valuar(&cotizacion, Redondeo::MitadPar).map_err(std::io::Error::other)?)?;
+        let mut unidad = self.iniciar_unidad();
+        unidad.add_balance(dni_usuario, criptomoneda, monto.clone());
+        unidad.crear_transaccion(TipoTransaccion::RecepcionCripto { monto, criptomoneda: criptomoneda.to_string(), blockchain: blockchain.to_string(), cotizacion }, dni_usuario);
+        self.confirmar(unidad)
+    }
+
+    fn retirar_dinero(&mut self, dni_usuario: &str, monto: Dinero, medio: MedioRetiro) -> std::io::Result<()>{
+        self.validar_fiat(monto.moneda())?;
+        let fiat = monto.moneda().codigo().to_string();
+        self.validar_limite(dni_usuario, TipoLimite::RetiroDiario, &monto)?;
+        if self.get_balance(dni_usuario, &fiat) >= monto {
+            let mut unidad = self.iniciar_unidad();
+            unidad.remove_balance(dni_usuario, &fiat, monto.clone());
+            unidad.crear_transaccion(TipoTransaccion::RetiroFiat { monto, medio }, dni_usuario);
+            self.confirmar(unidad)
         } else {
-            Err(std::io::Error::new(std::io::ErrorKind::Other, "Ha ocurrido un error en la transaccion"))
+            Err(std::io::Error::other("Ha ocurrido un error en la transaccion"))
+        
+        }
+    }
+}
+
+impl GestorTransacciones for XYZ {
+    fn crear_transaccion(&mut self, tipo: TipoTransaccion, dni_usuario: &str) -> std::io::Result<()>{
+        let mut unidad = self.iniciar_unidad();
+        unidad.crear_transaccion(tipo, dni_usuario);
+        self.confirmar(unidad)
+    }
+}
+
+impl GestorUsuarios for XYZ {
+    /// Con `identidad` el usuario empieza con la verificacion aprobada en el nivel basico
+    fn crear_usuario(&mut self, nombre: &str, apellido: &str, email: &str, dni: &str, identidad: bool) -> &Usuario {
+        let mut usuario = Usuario::new(nombre.to_string(), apellido.to_string(), email.to_string(), dni.to_string());
+        if identidad {
+            let ahora = self.reloj.ahora();
+            let aprobado = EstadoKyc::Aprobado { nivel: NivelIdentidad::Basico, vence: self.vencimiento_kyc(&ahora) };
+            usuario.registrar_kyc(aprobado, ahora, "Identidad verificada al crear el usuario");
+        }
+        self.usuarios.push(usuario);
+        // Si el usuario ya tenia balances guardados se conservan
+        self.balances.entry(dni.to_string()).or_default();
+        self.usuarios.last().unwrap()
+    }
+
+    /// Si tiene la verificacion aprobada y vigente
+    fn verificar_identidad(&self, dni_usuario: &str) -> bool {
+        let usuario = self.get_usuario(dni_usuario);
+        match usuario {
+            Some(usuario) => usuario.nivel(&self.reloj.ahora()) != NivelIdentidad::SinVerificar,
+            None => false
+        }
+    }
+
+    fn get_usuario(&self, dni_usuario: &str) -> Option<&Usuario> {
+        self.usuarios.iter().find(|u| u.get_dni() == dni_usuario)
+    }
+}
+
+impl GestorKyc for XYZ {
+    fn enviar_documentos(&mut self, dni_usuario: &str, nivel: NivelIdentidad, motivo: &str) -> Result<(), ErrorKyc> {
+        let ahora = self.reloj.ahora();
+        let usuario = self.get_usuario_mut(dni_usuario)?;
+        let valida = match usuario.estado_kyc(&ahora) {
+            EstadoKyc::Pendiente | EstadoKyc::Rechazado | EstadoKyc::Vencido { .. } => nivel != NivelIdentidad::SinVerificar,
+            // Para subir de nivel, mientras se revisan los documentos opera como sin verificar
+            EstadoKyc::Aprobado { nivel: actual, .. } => nivel > actual,
+            EstadoKyc::DocumentosEnviados { .. } => false,
+        };
+        if !valida {
+            return Err(ErrorKyc::TransicionInvalida { desde: usuario.estado_kyc(&ahora), accion: "enviar documentos" });
+        }
+        usuario.registrar_kyc(EstadoKyc::DocumentosEnviados { nivel }, ahora, motivo);
+        Ok(())
+    }
+
+    fn aprobar_kyc(&mut self, dni_usuario: &str, motivo: &str) -> Result<(), ErrorKyc> {
+        let ahora = self.reloj.ahora();
+        let vence = self.vencimiento_kyc(&ahora);
+        let usuario = self.get_usuario_mut(dni_usuario)?;
+        match usuario.estado_kyc(&ahora) {
+            EstadoKyc::DocumentosEnviados { nivel } => {
+                usuario.registrar_kyc(EstadoKyc::Aprobado { nivel, vence }, ahora, motivo);
+                Ok(())
+            },
+            desde => Err(ErrorKyc::TransicionInvalida { desde, accion: "aprobar" }),
+        }
+    }
+
+    fn rechazar_kyc(&mut self, dni_usuario: &str, motivo: &str) -> Result<(), ErrorKyc> {
+        let ahora = self.reloj.ahora();
+        let usuario = self.get_usuario_mut(dni_usuario)?;
+        match usuario.estado_kyc(&ahora) {
+            EstadoKyc::DocumentosEnviados { .. } => {
+                usuario.registrar_kyc(EstadoKyc::Rechazado, ahora, motivo);
+                Ok(())
+            },
+            desde => Err(ErrorKyc::TransicionInvalida { desde, accion: "rechazar" }),
+        }
+    }
+
+    /// Estado a la fecha actual del reloj, una aprobacion que ya paso su vencimiento se ve como vencida
+    fn estado_kyc(&self, dni_usuario: &str) -> Option<EstadoKyc> {
+        self.get_usuario(dni_usuario).map(|usuario| usuario.estado_kyc(&self.reloj.ahora()))
+    }
+
+    fn historial_kyc(&self, dni_usuario: &str) -> &[CambioKyc] {
+        self.get_usuario(dni_usuario).map_or(&[], |usuario| &usuario.historial_kyc)
+    }
+
+    /// Registra el vencimiento de las aprobaciones que ya vencieron. Devuelve cuantas se vencieron
+    fn actualizar_vencimientos(&mut self) -> usize {
+        let ahora = self.reloj.ahora();
+        let mut vencidos = 0;
+        for usuario in self.usuarios.iter_mut() {
+            let estado = usuario.estado_kyc(&ahora);
+            if matches!(estado, EstadoKyc::Vencido { .. }) && estado != usuario.kyc {
+                usuario.registrar_kyc(estado, ahora.clone(), "Vencio la verificacion de identidad");
+                vencidos += 1;
+            }
+        }
+        vencidos
+    }
+}
+
+impl GestorBalances for XYZ {
+    fn get_balance(&self, dni_usuario: &str, moneda: &str) -> Dinero {
+        let balance = match self.existe_cuenta(dni_usuario) {
+            true => self.balances.get(dni_usuario).and_then(|balances| balances.get(moneda)).cloned(),
+            false => None
+        };
+        balance.unwrap_or_else(|| Dinero::cero(self.moneda(moneda)))
+    }
+
+    fn add_balance(&mut self, dni_usuario: &str, moneda: &str, monto: Dinero) -> std::io::Result<()> {
+        let mut unidad = self.iniciar_unidad();
+        unidad.add_balance(dni_usuario, moneda, monto);
+        self.confirmar(unidad)
+    }
+
+    fn remove_balance(&mut self, dni_usuario: &str, moneda: &str, monto: Dinero) -> std::io::Result<()>{
+        let mut unidad = self.iniciar_unidad();
+        unidad.remove_balance(dni_usuario, moneda, monto);
+        self.confirmar(unidad)
+    }
+}
+
+impl GestorOrdenes for XYZ {
+    /// Ejecuta lo que pueda contra el libro y deja el resto como orden pendiente. Devuelve el id de la orden
+    fn colocar_orden_limite(&mut self, dni_usuario: &str, lado: Lado, cantidad: Dinero, precio: Dinero) -> std::io::Result<u64> {
+        if precio.es_negativo() || precio.es_cero() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "El precio debe ser positivo"));
+        }
+        let fiat = precio.moneda().clone();
+        self.colocar_orden(dni_usuario, lado, cantidad, Some(precio), &fiat).map(|(id, _)| id)
+    }
+
+    /// Ejecuta contra las mejores ordenes del libro y descarta lo que no se pudo ejecutar.
+    /// Devuelve la cantidad ejecutada
+    fn colocar_orden_mercado(&mut self, dni_usuario: &str, lado: Lado, cantidad: Dinero, fiat: &Moneda) -> std::io::Result<Dinero> {
+        self.colocar_orden(dni_usuario, lado, cantidad, None, fiat).map(|(_, ejecutado)| ejecutado)
+    }
+
+    fn cancelar_orden(&mut self, dni_usuario: &str, id: u64) -> std::io::Result<()> {
+        let orden = self.libros.values()
+            .find_map(|libro| libro.get_orden(id))
+            .filter(|orden| orden.dni_usuario == dni_usuario)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Orden no encontrada"))?;
+
+        let mut unidad = self.iniciar_unidad();
+        unidad.cancelar_orden(id);
+        unidad.add_balance(dni_usuario, orden.reservado.moneda().codigo(), orden.reservado.clone());
+        self.confirmar(unidad)
+    }
+}
+
+impl Estadisticas for XYZ {
+    fn cripto_mas_compras(&self, periodo: &Periodo) -> Option<String> {
+        self.cripto_mas(self.transacciones_en(periodo), Metrica::Compras)
+    }
+
+    fn cripto_mas_ventas(&self, periodo: &Periodo) -> Option<String> {
+        self.cripto_mas(self.transacciones_en(periodo), Metrica::Ventas)
+    }
+
+    fn cripto_mas_volumen_venta(&self, periodo: &Periodo) -> Option<String> {
+        self.cripto_mas(self.transacciones_en(periodo), Metrica::VolumenVentas)
+    }
+
+    /// Falla si alguna compra no se puede convertir a pesos con las cotizaciones de su fecha
+    fn cripto_mas_volumen_compras(&self, periodo: &Periodo) -> Result<Option<String>, ErrorCotizacion> {
+        self.cripto_mas_volumen_compras_de(self.transacciones_en(periodo))
+    }
+
+    fn estadisticas_por_intervalo(&self, periodo: &Periodo, intervalo: Intervalo) -> Result<Vec<EstadisticasIntervalo>, ErrorCotizacion> {
+        self.agrupar(periodo, intervalo).into_iter().map(|(inicio, transacciones)| Ok(EstadisticasIntervalo {
+            inicio,
+            cripto_mas_compras: self.cripto_mas(transacciones.iter().copied(), Metrica::Compras),
+            cripto_mas_ventas: self.cripto_mas(transacciones.iter().copied(), Metrica::Ventas),
+            cripto_mas_volumen_compras: self.cripto_mas_volumen_compras_de(transacciones.iter().copied())?,
+            cripto_mas_volumen_venta: self.cripto_mas(transacciones.iter().copied(), Metrica::VolumenVentas),
+        })).collect()
+    }
+
+    /// Solo se usan las operaciones cotizadas en `moneda`, en el orden en que se ejecutaron. Cada
+    /// ejecucion del libro de ordenes cuenta una sola vez aunque genere una compra y una venta
+    fn velas(&self, criptomoneda: &str, moneda: &Moneda, periodo: &Periodo, intervalo: Intervalo) -> Result<Vec<Vela>, ErrorDinero> {
+        let mut velas: Vec<Vela> = Vec::new();
+        for (inicio, transacciones) in self.agrupar(periodo, intervalo) {
+            let mut vela: Option<Vela> = None;
+            let mut anterior: Option<&Transaccion> = None;
+            for transaccion in transacciones {
+                let contraparte = anterior.is_some_and(|compra| transaccion.es_contraparte_de(compra));
+                anterior = Some(transaccion);
+                if contraparte {
+                    continue;
+                }
+                let (precio, cantidad) = match &transaccion.tipo {
+                    TipoTransaccion::CompraCripto { criptomoneda: c, cotizacion, cantidad, .. } if c == criptomoneda => (cotizacion, cantidad),
+                    TipoTransaccion::VentaCripto { criptomoneda: c, cotizacion, monto, .. } if c == criptomoneda => (cotizacion, monto),
+                    _ => continue,
+                };
+                if precio.moneda() != moneda {
+                    continue;
+                }
+                match vela.as_mut() {
+                    None => vela = Some(Vela {
+                        inicio: inicio.clone(),
+                        apertura: precio.clone(),
+                        maximo: precio.clone(),
+                        minimo: precio.clone(),
+                        cierre: precio.clone(),
+                        volumen: cantidad.clone(),
+                        operaciones: 1,
+                    }),
+                    Some(vela) => {
+                        if *precio > vela.maximo {
+                            vela.maximo = precio.clone();
+                        }
+                        if *precio < vela.minimo {
+                            vela.minimo = precio.clone();
+                        }
+                        vela.cierre = precio.clone();
+                        vela.volumen = vela.volumen.sumar(cantidad)?;
+                        vela.operaciones += 1;
+                    }
+                }
+            }
+            velas.extend(vela);
         }
+        Ok(velas)
+    }
+}
+
+// Las compras se costean a lo pagado y las recepciones al valor en pesos del momento en que llegaron, los
+// dos convertidos a la moneda del reporte con las cotizaciones de la fecha de la transaccion. Los retiros
+// y sus comisiones en criptomonedas sacan la cantidad al costo sin realizar ganancias. Lo que se acredito sin una transaccion (por ejemplo con
+// `add_balance`) no tiene costo conocido y no forma parte del reporte; si se vende, se toma con costo cero
+impl Portafolios for XYZ {
+    fn reporte_portafolio(&self, dni_usuario: &str, moneda: &Moneda) -> Result<ReportePortafolio, ErrorCotizacion> {
+        let mut costeos: BTreeMap<&str, Costeo> = BTreeMap::new();
+
+        for transaccion in self.transacciones.iter().filter(|t| t.dni_usuario == dni_usuario) {
+            let fecha = Some(&transaccion.fecha);
+            match &transaccion.tipo {
+                TipoTransaccion::CompraCripto { monto, criptomoneda, cantidad, .. } => {
+                    let costo = self.convertir_en(monto, moneda, fecha, Redondeo::MitadPar)?;
+                    costeos.entry(criptomoneda).or_insert_with(|| Costeo::new(cantidad.moneda(), moneda)).entrada(cantidad, &costo)?;
+                },
+                TipoTransaccion::RecepcionCripto { monto, criptomoneda, cotizacion, .. } => {
+                    let pesos = monto.valuar(cotizacion, Redondeo::MitadPar)?;
+                    let costo = self.convertir_en(&pesos, moneda, fecha, Redondeo::MitadPar)?;
+                    costeos.entry(criptomoneda).or_insert_with(|| Costeo::new(monto.moneda(), moneda)).entrada(monto, &costo)?;
+                },
+                TipoTransaccion::VentaCripto { monto, criptomoneda, cotizacion, comision } => {
+                    // Lo mismo que se acredito al vender
+                    let obtenido = monto.valuar(cotizacion, Redondeo::Truncar)?.restar(comision)?;
+                    let obtenido = self.convertir_en(&obtenido, moneda, fecha, Redondeo::MitadPar)?;
+                    costeos.entry(criptomoneda).or_insert_with(|| Costeo::new(monto.moneda(), moneda)).salida(monto, Some(&obtenido))?;
+                },
+                TipoTransaccion::RetiroCripto { monto, criptomoneda, comision, .. } => {
+                    costeos.entry(criptomoneda).or_insert_with(|| Costeo::new(monto.moneda(), moneda)).salida(monto, None)?;
+                    if !comision.es_cero() && !self.es_fiat(comision.moneda().codigo()) {
+                        let codigo = comision.moneda().codigo();
+                        costeos.entry(codigo).or_insert_with(|| Costeo::new(comision.moneda(), moneda)).salida(comision, None)?;
+                    }
+                },
+                TipoTransaccion::IngresoDinero { .. } | TipoTransaccion::RetiroFiat { .. } => {},
+            }
+        }
+
+        let cero = Dinero::cero(moneda.clone());
+        let mut reporte = ReportePortafolio {
+            dni_usuario: dni_usuario.to_string(),
+            moneda: moneda.clone(),
+            fecha: self.reloj.ahora(),
+            posiciones: Vec::new(),
+            valor_total: cero.clone(),
+            total_fifo: ResultadoCosteo { costo: cero.clone(), realizado: cero.clone(), no_realizado: cero.clone() },
+            total_promedio: ResultadoCosteo { costo: cero.clone(), realizado: cero.clone(), no_realizado: cero },
+        };
+        for (criptomoneda, costeo) in costeos {
+            // Lo que ya se vendio todo no necesita cotizacion actual
+            let valor_actual = if costeo.cantidad.es_cero() {
+                Dinero::cero(moneda.clone())
+            } else {
+                self.convertir(&costeo.cantidad, moneda, Redondeo::MitadPar)?
+            };
+            let posicion = PosicionCripto {
+                criptomoneda: criptomoneda.to_string(),
+                fifo: costeo.resultado_fifo(&valor_actual)?,
+                promedio: costeo.resultado_promedio(&valor_actual)?,
+                cantidad: costeo.cantidad,
+                valor_actual,
+            };
+            reporte.valor_total = reporte.valor_total.sumar(&posicion.valor_actual)?;
+            reporte.total_fifo = reporte.total_fifo.sumar(&posicion.fifo)?;
+            reporte.total_promedio = reporte.total_promedio.sumar(&posicion.promedio)?;
+            reporte.posiciones.push(posicion);
+        }
+        Ok(reporte)
+    }
+
+    fn reporte_impositivo(&self, dni_usuario: &str, anio: i32, moneda: &Moneda) -> Result<ReporteImpositivo, ErrorCotizacion> {
+        let cero = Dinero::cero(moneda.clone());
+        let mut costeos: BTreeMap<&str, Costeo> = BTreeMap::new();
+        let mut movimientos = Vec::new();
+        let mut totales = TotalesImpositivos {
+            compras: cero.clone(),
+            ventas: cero.clone(),
+            depositos: cero.clone(),
+            retiros: cero.clone(),
+            comisiones: cero.clone(),
+            ganancia_realizada: cero.clone(),
+        };
+
+        for transaccion in self.transacciones.iter().filter(|t| t.dni_usuario == dni_usuario) {
+            let anio_transaccion = self.dia_de(&transaccion.fecha).anio();
+            if anio_transaccion > anio {
+                continue;
+            }
+            let fecha = Some(&transaccion.fecha);
+            let convertir = |monto: &Dinero| self.convertir_en(monto, moneda, fecha, Redondeo::MitadPar);
+
+            // Las operaciones de los años anteriores solo se usan para el costo, asi que solo se
+            // convierten las entradas y las salidas se sacan al costo
+            if anio_transaccion < anio {
+                match &transaccion.tipo {
+                    TipoTransaccion::CompraCripto { monto, criptomoneda, cantidad, .. } => {
+                        let costo = convertir(monto)?;
+                        costeos.entry(criptomoneda).or_insert_with(|| Costeo::new(cantidad.moneda(), moneda)).entrada(cantidad, &costo)?;
+                    },
+                    TipoTransaccion::RecepcionCripto { monto, criptomoneda, cotizacion, .. } => {
+                        let valor = convertir(&monto.valuar(cotizacion, Redondeo::MitadPar)?)?;
+                        costeos.entry(criptomoneda).or_insert_with(|| Costeo::new(monto.moneda(), moneda)).entrada(monto, &valor)?;
+                    },
+                    TipoTransaccion::VentaCripto { monto, criptomoneda, .. } => {
+                        costeos.entry(criptomoneda).or_insert_with(|| Costeo::new(monto.moneda(), moneda)).salida(monto, None)?;
+                    },
+                    TipoTransaccion::RetiroCripto { monto, criptomoneda, comision, .. } => self.salida_retiro(&mut costeos, criptomoneda, monto, comision, moneda)?,
+                    TipoTransaccion::IngresoDinero { .. } | TipoTransaccion::RetiroFiat { .. } => {},
+                }
+                continue;
+            }
+
+            let movimiento = match &transaccion.tipo {
+                TipoTransaccion::IngresoDinero { monto } => (TipoMovimiento::Deposito, monto.clone(), convertir(monto)?, cero.clone(), None),
+                TipoTransaccion::RetiroFiat { monto, .. } => (TipoMovimiento::Retiro, monto.clone(), convertir(monto)?, cero.clone(), None),
+                TipoTransaccion::CompraCripto { monto, criptomoneda, cantidad, comision, .. } => {
+                    let costo = convertir(monto)?;
+                    costeos.entry(criptomoneda).or_insert_with(|| Costeo::new(cantidad.moneda(), moneda)).entrada(cantidad, &costo)?;
+                    (TipoMovimiento::Compra, cantidad.clone(), costo, convertir(comision)?, None)
+                },
+                TipoTransaccion::VentaCripto { monto, criptomoneda, cotizacion, comision } => {
+                    let bruto = monto.valuar(cotizacion, Redondeo::Truncar)?;
+                    let obtenido = convertir(&bruto.restar(comision)?)?;
+                    let costeo = costeos.entry(criptomoneda).or_insert_with(|| Costeo::new(monto.moneda(), moneda));
+                    let realizado = costeo.realizado_fifo.clone();
+                    costeo.salida(monto, Some(&obtenido))?;
+                    let ganancia = costeo.realizado_fifo.restar(&realizado)?;
+                    (TipoMovimiento::Venta, monto.clone(), convertir(&bruto)?, convertir(comision)?, Some(ganancia))
+                },
+                TipoTransaccion::RecepcionCripto { monto, criptomoneda, cotizacion, .. } => {
+                    let valor = convertir(&monto.valuar(cotizacion, Redondeo::MitadPar)?)?;
+                    costeos.entry(criptomoneda).or_insert_with(|| Costeo::new(monto.moneda(), moneda)).entrada(monto, &valor)?;
+                    (TipoMovimiento::Deposito, monto.clone(), valor, cero.clone(), None)
+                },
+                TipoTransaccion::RetiroCripto { monto, criptomoneda, cotizacion, comision, .. } => {
+                    self.salida_retiro(&mut costeos, criptomoneda, monto, comision, moneda)?;
+                    // La comision de red suele cobrarse en la misma criptomoneda, a la cotizacion del retiro
+                    let comision = match comision.moneda() == monto.moneda() {
+                        true => convertir(&comision.valuar(cotizacion, Redondeo::MitadPar)?)?,
+                        false => convertir(comision)?,
+                    };
+                    (TipoMovimiento::Retiro, monto.clone(), convertir(&monto.valuar(cotizacion, Redondeo::MitadPar)?)?, comision, None)
+                },
+            };
+
+            let (tipo, cantidad, equivalente, comision, ganancia) = movimiento;
+            let total = match tipo {
+                TipoMovimiento::Compra => &mut totales.compras,
+                TipoMovimiento::Venta => &mut totales.ventas,
+                TipoMovimiento::Deposito => &mut totales.depositos,
+                TipoMovimiento::Retiro => &mut totales.retiros,
+            };
+            *total = total.sumar(&equivalente)?;
+            totales.comisiones = totales.comisiones.sumar(&comision)?;
+            if let Some(ganancia) = &ganancia {
+                totales.ganancia_realizada = totales.ganancia_realizada.sumar(ganancia)?;
+            }
+            movimientos.push(MovimientoImpositivo { fecha: transaccion.fecha.clone(), tipo, cantidad, equivalente, comision, ganancia });
+        }
+
+        let cierre = FechaHora::new_con_offset(Fecha::new(31, 12, anio), 23, 59, 59, self.reloj.ahora().offset_minutos());
+        let mut tenencias = Vec::new();
+        for costeo in costeos.into_values().filter(|costeo| !costeo.cantidad.es_cero()) {
+            let valor = self.convertir_en(&costeo.cantidad, moneda, Some(&cierre), Redondeo::MitadPar)?;
+            let costo = costeo.resultado_fifo(&valor)?.costo;
+            tenencias.push(TenenciaFinDeAnio { cantidad: costeo.cantidad, costo, valor });
+        }
+
+        Ok(ReporteImpositivo { dni_usuario: dni_usuario.to_string(), anio, moneda: moneda.clone(), movimientos, totales, tenencias })
+    }
+}
+
+// Los balances y transacciones por separado son los archivos que se usaban antes del log de
+// operaciones, se siguen leyendo si no hay una foto del estado.
+// En la version 2 los montos pasaron de numeros de punto flotante a `Dinero` y en la 3 los
+// balances en pesos pasaron de la clave "fiat" al codigo de la moneda
+impl Versionado for HashMap<String, HashMap<String, Dinero>> {
+    fn migraciones() -> Migraciones {
+        Migraciones::new().agregar(|mut balances| {
+            migrar_balances(&mut balances)?;
+            Ok(balances)
+        }).agregar(|mut balances| {
+            renombrar_fiat(&mut balances)?;
+            Ok(balances)
+        })
+    }
+}
+
+// En la version 3 de las transacciones las compras guardan la cantidad de criptomoneda acreditada y en
+// la 4 las compras, ventas y retiros de criptomonedas guardan la comision cobrada
+impl Versionado for Vec<Transaccion> {
+    fn migraciones() -> Migraciones {
+        Migraciones::new().agregar(|mut transacciones| {
+            migrar_transacciones(&mut transacciones)?;
+            Ok(transacciones)
+        }).agregar(|mut transacciones| {
+            migrar_tipos(&mut transacciones, agregar_cantidad_compra)?;
+            Ok(transacciones)
+        }).agregar(|mut transacciones| {
+            migrar_tipos(&mut transacciones, agregar_comision)?;
+            Ok(transacciones)
+        })
+    }
+}
+
+impl Versionado for EstadoXYZ {
+    fn migraciones() -> Migraciones {
+        Migraciones::new().agregar(|mut estado| {
+            migrar_balances(&mut estado["balances"])?;
+            migrar_transacciones(&mut estado["transacciones"])?;
+            Ok(estado)
+        }).agregar(|mut estado| {
+            renombrar_fiat(&mut estado["balances"])?;
+            Ok(estado)
+        }).agregar(|mut estado| {
+            estado["ordenes"] = Value::Array(Vec::new());
+            Ok(estado)
+        }).agregar(|mut estado| {
+            migrar_tipos(&mut estado["transacciones"], agregar_cantidad_compra)?;
+            Ok(estado)
+        }).agregar(|mut estado| {
+            migrar_tipos(&mut estado["transacciones"], agregar_comision)?;
+            Ok(estado)
+        }).agregar(|mut estado| {
+            estado["cadenas"] = Value::Array(Vec::new());
+            Ok(estado)
+        })
+    }
+}
+
+impl Versionado for RegistroOperaciones {
+    fn migraciones() -> Migraciones {
+        Migraciones::new().agregar(|mut registro| {
+            for operacion in registro["operaciones"].as_array_mut().ok_or("se esperaba una lista de operaciones")? {
+                if let Some(balance) = operacion.get_mut("Balance") {
+                    let moneda = moneda_de(balance["moneda"].as_str().ok_or("falta la moneda")?);
+                    migrar_monto(&mut balance["monto"], moneda)?;
+                } else if let Some(transaccion) = operacion.get_mut("Transaccion") {
+                    migrar_tipo_transaccion(&mut transaccion["tipo"])?;
+                }
+            }
+            Ok(registro)
+        }).agregar(|mut registro| {
+            for operacion in registro["operaciones"].as_array_mut().ok_or("se esperaba una lista de operaciones")? {
+                if let Some(balance) = operacion.get_mut("Balance").filter(|b| b["moneda"] == FIAT_ANTERIOR) {
+                    balance["moneda"] = Value::from(Moneda::ars().codigo());
+                }
+            }
+            Ok(registro)
+        }).agregar(|mut registro| {
+            for operacion in registro["operaciones"].as_array_mut().ok_or("se esperaba una lista de operaciones")? {
+                if let Some(transaccion) = operacion.get_mut("Transaccion") {
+                    agregar_cantidad_compra(&mut transaccion["tipo"])?;
+                }
+            }
+            Ok(registro)
+        }).agregar(|mut registro| {
+            for operacion in registro["operaciones"].as_array_mut().ok_or("se esperaba una lista de operaciones")? {
+                if let Some(transaccion) = operacion.get_mut("Transaccion") {
+                    agregar_comision(&mut transaccion["tipo"])?;
+                }
+            }
+            Ok(registro)
+        })
+    }
+}
+
+/// Moneda de los montos guardados con la clave de balance de la version 1
+fn moneda_de(clave: &str) -> Moneda {
+    if clave == FIAT_ANTERIOR {
+        Moneda::ars()
+    } else {
+        Moneda::cripto(clave)
+    }
+}
+
+/// Reemplaza un monto guardado como numero por el `Dinero` equivalente
+fn migrar_monto(monto: &mut Value, moneda: Moneda) -> Result<(), String> {
+    let valor = monto.as_f64().ok_or_else(|| format!("monto invalido: {}", monto))?;
+    let dinero = Dinero::desde_f64(valor, moneda, Redondeo::MitadArriba).map_err(|e| e.to_string())?;
+    *monto = serde_json::to_value(dinero).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn migrar_balances(balances: &mut Value) -> Result<(), String> {
+    for monedas in balances.as_object_mut().ok_or("se esperaba un mapa de balances")?.values_mut() {
+        for (moneda, monto) in monedas.as_object_mut().ok_or("se esperaba un mapa de monedas")? {
+            migrar_monto(monto, moneda_de(moneda))?;
+        }
+    }
+    Ok(())
+}
+
+fn renombrar_fiat(balances: &mut Value) -> Result<(), String> {
+    for monedas in balances.as_object_mut().ok_or("se esperaba un mapa de balances")?.values_mut() {
+        let monedas = monedas.as_object_mut().ok_or("se esperaba un mapa de monedas")?;
+        if let Some(pesos) = monedas.remove(FIAT_ANTERIOR) {
+            monedas.insert(Moneda::ars().codigo().to_string(), pesos);
+        }
+    }
+    Ok(())
+}
+
+fn migrar_transacciones(transacciones: &mut Value) -> Result<(), String> {
+    for transaccion in transacciones.as_array_mut().ok_or("se esperaba una lista de transacciones")? {
+        migrar_tipo_transaccion(&mut transaccion["tipo"])?;
+    }
+    Ok(())
+}
+
+fn migrar_tipo_transaccion(tipo: &mut Value) -> Result<(), String> {
+    let (variante, campos) = tipo.as_object_mut().and_then(|t| t.iter_mut().next()).ok_or("tipo de transaccion invalido")?;
+    let moneda_monto = match variante.as_str() {
+        "IngresoDinero" | "CompraCripto" | "RetiroFiat" => Moneda::ars(),
+        _ => moneda_de(campos["criptomoneda"].as_str().ok_or("falta la criptomoneda")?),
+    };
+
+    migrar_monto(&mut campos["monto"], moneda_monto)?;
+    if campos.get("cotizacion").is_some() {
+        migrar_monto(&mut campos["cotizacion"], Moneda::ars())?;
+    }
+    Ok(())
+}
+
+/// Aplica la migracion al tipo de cada transaccion de la lista
+fn migrar_tipos(transacciones: &mut Value, migrar: fn(&mut Value) -> Result<(), String>) -> Result<(), String> {
+    for transaccion in transacciones.as_array_mut().ok_or("se esperaba una lista de transacciones")? {
+        migrar(&mut transaccion["tipo"])?;
+    }
+    Ok(())
+}
+
+/// Las compras anteriores no guardaban lo acreditado, se recalcula como lo hacia `comprar_cripto`
+fn agregar_cantidad_compra(tipo: &mut Value) -> Result<(), String> {
+    if let Some(campos) = tipo.get_mut("CompraCripto") {
+        let monto: Dinero = serde_json::from_value(campos["monto"].clone()).map_err(|e| e.to_string())?;
+        let cotizacion: Dinero = serde_json::from_value(campos["cotizacion"].clone()).map_err(|e| e.to_string())?;
+        let criptomoneda = Moneda::cripto(campos["criptomoneda"].as_str().ok_or("falta la criptomoneda")?);
+        let cantidad = monto.convertir(&cotizacion, &criptomoneda, Redondeo::Truncar).map_err(|e| e.to_string())?;
+        campos["cantidad"] = serde_json::to_value(cantidad).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Antes no se cobraban comisiones, se agrega una en cero en la moneda en la que se cobra ahora
+fn agregar_comision(tipo: &mut Value) -> Result<(), String> {
+    let (variante, campos) = tipo.as_object_mut().and_then(|t| t.iter_mut().next()).ok_or("tipo de transaccion invalido")?;
+    let referencia = match variante.as_str() {
+        "CompraCripto" | "RetiroCripto" => "monto",
+        "VentaCripto" => "cotizacion",
+        _ => return Ok(()),
+    };
+    let mut comision = campos[referencia].clone();
+    comision["unidades"] = Value::from(0);
+    campos["comision"] = comision;
+    Ok(())
+}
+
+impl XYZ {
+    /// Dia de la fecha segun el offset del reloj
+    fn dia_de(&self, fecha: &FechaHora) -> Fecha {
+        fecha.con_offset(self.reloj.ahora().offset_minutos()).fecha().clone()
     }
 
-    fn retirar_cripto(&mut self, dni_usuario: &str, monto: f64, criptomoneda: &str, blockchain: &str) -> std::io::Result<()>{
-        if self.verificar_identidad(dni_usuario) && self.get_balance(dni_usuario, criptomoneda) >= monto {
-            let cotizacion: f64 = self.get_cotizacion(&criptomoneda);
-            self.remove_balance(dni_usuario, &criptomoneda, monto)?;
+    fn transacciones_en<'a>(&'a self, periodo: &'a Periodo) -> impl Iterator<Item = &'a Transaccion> + 'a {
+        self.transacciones.iter().filter(move |t| periodo.contiene(&self.dia_de(&t.fecha)))
+    }
+
+    /// Transacciones del periodo agrupadas por el inicio de su intervalo, en orden
+    fn agrupar<'a>(&'a self, periodo: &'a Periodo, intervalo: Intervalo) -> BTreeMap<Fecha, Vec<&'a Transaccion>> {
+        let mut grupos: BTreeMap<Fecha, Vec<&Transaccion>> = BTreeMap::new();
+        for transaccion in self.transacciones_en(periodo) {
+            grupos.entry(intervalo.inicio(&self.dia_de(&transaccion.fecha))).or_default().push(transaccion);
+        }
+        grupos
+    }
+
+    /// Saca al costo lo retirado y la comision de red si se cobro en una criptomoneda
+    fn salida_retiro<'a>(&self, costeos: &mut BTreeMap<&'a str, Costeo>, criptomoneda: &'a str, monto: &Dinero, comision: &'a Dinero, moneda: &Moneda) -> Result<(), ErrorDinero> {
+        costeos.entry(criptomoneda).or_insert_with(|| Costeo::new(monto.moneda(), moneda)).salida(monto, None)?;
+        if !comision.es_cero() && !self.es_fiat(comision.moneda().codigo()) {
+            let codigo = comision.moneda().codigo();
+            costeos.entry(codigo).or_insert_with(|| Costeo::new(comision.moneda(), moneda)).salida(comision, None)?;
+        }
+        Ok(())
+    }
+
+    /// Criptomoneda con mas operaciones o volumen de la metrica, en un empate la de menor prefijo
+    fn cripto_mas<'a>(&self, transacciones: impl Iterator<Item = &'a Transaccion>, metrica: Metrica) -> Option<String> {
+        let mut totales: HashMap<&str, i128> = HashMap::new();
+        for transaccion in transacciones {
+            let (criptomoneda, valor) = match (metrica, &transaccion.tipo) {
+                (Metrica::Compras, TipoTransaccion::CompraCripto { criptomoneda, .. })
+                | (Metrica::Ventas, TipoTransaccion::VentaCripto { criptomoneda, .. }) => (criptomoneda, 1),
+                // Se suman las unidades menores para no tener que manejar desbordes
+                (Metrica::VolumenVentas, TipoTransaccion::VentaCripto { criptomoneda, monto, .. }) => (criptomoneda, monto.unidades() as i128),
+                _ => continue,
+            };
+            *totales.entry(criptomoneda.as_str()).or_insert(0) += valor;
+        }
+        mayor_total(totales)
+    }
+
+    /// Las compras pueden estar en distintas monedas, se comparan en pesos a las cotizaciones de la
+    /// fecha de cada compra. Si alguna no se puede convertir devuelve el error en lugar de no contarla
+    fn cripto_mas_volumen_compras_de<'a>(&self, transacciones: impl Iterator<Item = &'a Transaccion>) -> Result<Option<String>, ErrorCotizacion> {
+        let mut totales: HashMap<&str, i128> = HashMap::new();
+        for transaccion in transacciones {
+            if let TipoTransaccion::CompraCripto { criptomoneda, monto, .. } = &transaccion.tipo {
+                let pesos = self.convertir_en(monto, &Moneda::ars(), Some(&transaccion.fecha), Redondeo::Truncar)?;
+                *totales.entry(criptomoneda.as_str()).or_insert(0) += pesos.unidades() as i128;
+            }
+        }
+        Ok(mayor_total(totales))
+    }
+
+    fn new(file_name: &str, almacenamiento: Rc<dyn Almacenamiento>) -> XYZ {
+        XYZ::new_con_reloj(file_name, almacenamiento, Rc::new(RelojSistema))
+    }
+
+    /// Crea el sistema con los balances y transacciones guardados usando `PoliticaCarga::compatible`.
+    /// Entra en panico solo si lo guardado esta corrupto y sin respaldo valido, para manejar el error usar `cargar`
+    fn new_con_reloj(file_name: &str, almacenamiento: Rc<dyn Almacenamiento>, reloj: Rc<dyn Reloj>) -> XYZ {
+        XYZ::cargar_con_reloj(file_name, almacenamiento, reloj, &PoliticaCarga::compatible()).unwrap()
+    }
+
+    fn cargar(file_name: &str, almacenamiento: Rc<dyn Almacenamiento>, politica: &PoliticaCarga) -> Result<XYZ, ErrorCarga> {
+        XYZ::cargar_con_reloj(file_name, almacenamiento, Rc::new(RelojSistema), politica)
+    }
+
+    fn cargar_con_reloj(file_name: &str, almacenamiento: Rc<dyn Almacenamiento>, reloj: Rc<dyn Reloj>, politica: &PoliticaCarga) -> Result<XYZ, ErrorCarga> {
+        let registros: Vec<RegistroOperaciones> = cargar_json_lineas(almacenamiento.as_ref(), &format!("{}_operaciones", file_name), politica)?;
+        let estado = match cargar_json_opcional(almacenamiento.as_ref(), &format!("{}_estado", file_name), politica)? {
+            Some(estado) => estado,
+            None => {
+                // Si hay operaciones en el log no hace falta que existan los archivos anteriores
+                let politica = if registros.is_empty() {
+                    *politica
+                } else {
+                    PoliticaCarga { no_encontrado: AccionCarga::EmpezarVacio, ..*politica }
+                };
+                EstadoXYZ {
+                    ultima_operacion: 0,
+                    ordenes: Vec::new(),
+                    cadenas: Vec::new(),
+                    balances: cargar_json(almacenamiento.as_ref(), &format!("{}_balances", file_name), &politica)?,
+                    transacciones: cargar_json(almacenamiento.as_ref(), &format!("{}_transacciones", file_name), &politica)?,
+                }
+            }
+        };
+
+        let mut xyz = XYZ {
+            file_name: file_name.to_string(),
+            usuarios: Vec::new(),
+            cotizaciones: HistorialCotizaciones::default(),
+            fuente_cotizaciones: None,
+            monedas_fiat: vec![Moneda::ars(), Moneda::usd()],
+            criptomonedas: Vec::new(),
+            transacciones: estado.transacciones,
+            balances: estado.balances,
+            libros: BTreeMap::new(),
+            comisiones: Comisiones::new(),
+            cadenas: estado.cadenas.into_iter().map(|cadena| (cadena.blockchain.clone(), cadena)).collect(),
+            confirmaciones_retiro: CONFIRMACIONES_RETIRO,
+            limites: HashMap::from([(NivelIdentidad::SinVerificar, LimitesNivel::bloqueado())]),
+            validez_kyc_dias: VALIDEZ_KYC_DIAS,
+            reloj,
+            almacenamiento,
+            ultima_operacion: estado.ultima_operacion,
+            operaciones_sin_compactar: 0,
+            limite_compactacion: LIMITE_COMPACTACION
+        };
+
+        for orden in estado.ordenes {
+            xyz.libros.entry(orden.par()).or_default().agregar(orden);
+        }
+
+        for registro in registros.into_iter().filter(|r| r.numero > estado.ultima_operacion) {
+            xyz.ultima_operacion = registro.numero;
+            xyz.operaciones_sin_compactar += 1;
+            for operacion in registro.operaciones {
+                xyz.aplicar(operacion).map_err(|e| ErrorCarga::Corrupto(format!("{}_operaciones: {}", file_name, e)))?;
+            }
+        }
+        Ok(xyz)
+    }
+
+    /// Carga los archivos guardados y los concilia sin modificarlos
+    fn conciliar_archivos(file_name: &str, almacenamiento: Rc<dyn Almacenamiento>, politica: &PoliticaCarga) -> Result<ReporteConciliacion, ErrorCarga> {
+        XYZ::cargar(file_name, almacenamiento, politica)?
+            .conciliar()
+            .map_err(|e| ErrorCarga::Corrupto(format!("{}_transacciones: {}", file_name, e)))
+    }
+
+    /// Vuelve a aplicar las transacciones desde cero y compara los balances que resultan con los guardados.
+    /// Lo reservado en ordenes abiertas no tiene transaccion, se descuenta de lo esperado. Ademas revisa
+    /// que no haya balances negativos y que la criptomoneda se conserve: dentro del sistema solo cambia de
+    /// cuenta, asi que el total guardado tiene que ser el que resulta del log
+    fn conciliar(&self) -> Result<ReporteConciliacion, ErrorDinero> {
+        let mut reporte = ReporteConciliacion::default();
+        let salida = |monto: &Dinero| Dinero::cero(monto.moneda().clone()).restar(monto);
+        let mut esperados: BTreeMap<(String, String), Dinero> = BTreeMap::new();
+
+        for (numero, transaccion) in self.transacciones.iter().enumerate() {
+            let dni_usuario = transaccion.dni_usuario.as_str();
+            let movimientos = match &transaccion.tipo {
+                TipoTransaccion::IngresoDinero { monto } | TipoTransaccion::RecepcionCripto { monto, .. } => vec![(dni_usuario, monto.clone())],
+                TipoTransaccion::RetiroFiat { monto, .. } => vec![(dni_usuario, salida(monto)?)],
+                TipoTransaccion::CompraCripto { monto, cantidad, comision, .. } => {
+                    vec![(dni_usuario, salida(monto)?), (dni_usuario, cantidad.clone()), (CUENTA_CASA, comision.clone())]
+                },
+                TipoTransaccion::VentaCripto { monto, cotizacion, comision, .. } => {
+                    let obtenido = monto.valuar(cotizacion, Redondeo::Truncar)?.restar(comision)?;
+                    vec![(dni_usuario, salida(monto)?), (dni_usuario, obtenido), (CUENTA_CASA, comision.clone())]
+                },
+                TipoTransaccion::RetiroCripto { monto, comision, .. } => {
+                    vec![(dni_usuario, salida(monto)?), (dni_usuario, salida(comision)?), (CUENTA_CASA, comision.clone())]
+                },
+            };
+            for (cuenta, monto) in movimientos.into_iter().filter(|(_, monto)| !monto.es_cero()) {
+                let clave = (cuenta.to_string(), monto.moneda().codigo().to_string());
+                let balance = esperados.entry(clave.clone()).or_insert_with(|| Dinero::cero(monto.moneda().clone()));
+                *balance = balance.sumar(&monto)?;
+                if balance.es_negativo() {
+                    reporte.violaciones.push(Violacion::SaldoNegativoEnLog { transaccion: numero, dni_usuario: clave.0, moneda: clave.1, balance: balance.clone() });
+                }
+            }
+        }
+
+        for orden in self.libros.values().flat_map(|libro| libro.compras.iter().chain(libro.ventas.iter())) {
+            let clave = (orden.dni_usuario.clone(), orden.reservado.moneda().codigo().to_string());
+            let balance = esperados.entry(clave).or_insert_with(|| Dinero::cero(orden.reservado.moneda().clone()));
+            *balance = balance.restar(&orden.reservado)?;
+        }
+
+        let mut claves: Vec<(String, String)> = esperados.keys().cloned().collect();
+        for (dni_usuario, balances) in &self.balances {
+            claves.extend(balances.keys().map(|moneda| (dni_usuario.clone(), moneda.clone())));
+        }
+        claves.sort();
+        claves.dedup();
+
+        let mut totales: BTreeMap<String, (Dinero, Dinero)> = BTreeMap::new();
+        for (dni_usuario, moneda) in claves {
+            let guardado = self.balances.get(&dni_usuario).and_then(|balances| balances.get(&moneda)).cloned();
+            let esperado = esperados.get(&(dni_usuario.clone(), moneda.clone())).cloned();
+            let cero = Dinero::cero(guardado.as_ref().or(esperado.as_ref()).map_or_else(|| self.moneda(&moneda), |monto| monto.moneda().clone()));
+            let guardado = guardado.unwrap_or_else(|| cero.clone());
+            let esperado = esperado.unwrap_or_else(|| cero.clone());
+
+            if guardado.es_negativo() {
+                reporte.violaciones.push(Violacion::SaldoNegativo { dni_usuario: dni_usuario.clone(), moneda: moneda.clone(), balance: guardado.clone() });
+            }
+            if !self.es_fiat(&moneda) {
+                let (total_esperado, total) = totales.entry(moneda.clone()).or_insert_with(|| (cero.clone(), cero.clone()));
+                *total_esperado = total_esperado.sumar(&esperado)?;
+                *total = total.sumar(&guardado)?;
+            }
+            if guardado != esperado {
+                reporte.discrepancias.push(Discrepancia { dni_usuario, moneda, esperado, guardado });
+            }
+        }
+
+        for (criptomoneda, (esperado, total)) in totales {
+            if esperado != total {
+                reporte.violaciones.push(Violacion::CriptoNoConservada { criptomoneda, esperado, total });
+            }
+        }
+        Ok(reporte)
+    }
+
+    fn set_limite_compactacion(&mut self, limite: usize) {
+        self.limite_compactacion = limite;
+    }
+
+    /// Empieza una unidad de trabajo vacia, sus transacciones llevan la fecha actual del reloj
+    fn iniciar_unidad(&self) -> UnidadTrabajo {
+        UnidadTrabajo { fecha: self.reloj.ahora(), operaciones: Vec::new() }
+    }
+
+    /// Guarda todos los cambios de la unidad juntos. Si algun usuario no existe o algun balance
+    /// quedaria negativo no se guarda ni se aplica nada, lo mismo si falla la escritura del log
+    fn confirmar(&mut self, unidad: UnidadTrabajo) -> std::io::Result<()> {
+        let mut balances: HashMap<(&str, &str), Dinero> = HashMap::new();
+
+        for operacion in &unidad.operaciones {
+            if let Operacion::Balance { dni_usuario, moneda, monto } = operacion {
+                if !self.existe_cuenta(dni_usuario) {
+                    return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Usuario no encontrado"));
+                }
+                if monto.moneda().codigo() != moneda {
+                    return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("El monto en {} no corresponde al balance en {}", monto.moneda(), moneda)));
+                }
+                let balance = balances.entry((dni_usuario, moneda)).or_insert_with(|| self.get_balance(dni_usuario, moneda));
+                *balance = balance.sumar(monto).map_err(std::io::Error::other)?;
+            }
+        }
+
+        if balances.values().any(|balance| balance.es_negativo()) {
+            return Err(std::io::Error::other("Saldo insuficiente"));
+        }
+        self.registrar(unidad.operaciones)
+    }
+
+    /// Aplica las operaciones en memoria y despues las agrega como una sola linea al log. Si alguna no
+    /// se puede aplicar o falla la escritura se vuelve al estado anterior, asi no queda nada a medias
+    fn registrar(&mut self, operaciones: Vec<Operacion>) -> std::io::Result<()> {
+        let anterior = self.estado_anterior();
+        let registro = RegistroOperaciones { numero: self.ultima_operacion + 1, operaciones };
+        let resultado = registro.operaciones.iter().cloned()
+            .try_for_each(|operacion| self.aplicar(operacion))
+            .map_err(std::io::Error::other)
+            .and_then(|_| agregar_json_linea(self.almacenamiento.as_ref(), &format!("{}_operaciones", self.file_name), &registro));
+        if let Err(e) = resultado {
+            self.restaurar(anterior);
+            return Err(e);
+        }
+        self.ultima_operacion = registro.numero;
+
+        // La operacion ya quedo registrada en el log, si no se puede guardar la foto se reintenta
+        // en la proxima porque el contador no vuelve a cero
+        self.operaciones_sin_compactar += 1;
+        if self.operaciones_sin_compactar >= self.limite_compactacion {
+            let _ = self.compactar();
+        }
+        Ok(())
+    }
+
+    fn estado_anterior(&self) -> EstadoAnterior {
+        EstadoAnterior {
+            balances: self.balances.clone(),
+            transacciones: self.transacciones.len(),
+            libros: self.libros.clone(),
+            cadenas: self.cadenas.clone(),
+        }
+    }
+
+    fn restaurar(&mut self, anterior: EstadoAnterior) {
+        self.balances = anterior.balances;
+        self.transacciones.truncate(anterior.transacciones);
+        self.libros = anterior.libros;
+        self.cadenas = anterior.cadenas;
+    }
+
+    fn aplicar(&mut self, operacion: Operacion) -> Result<(), String> {
+        match operacion {
+            Operacion::Balance { dni_usuario, moneda, monto } => {
+                let balance = self.balances.entry(dni_usuario).or_default().entry(moneda).or_insert_with(|| Dinero::cero(monto.moneda().clone()));
+                *balance = balance.sumar(&monto).map_err(|e| e.to_string())?;
+            },
+            Operacion::Transaccion(transaccion) => self.transacciones.push(transaccion),
+            Operacion::OrdenNueva(orden) => self.libros.entry(orden.par()).or_default().agregar(orden),
+            Operacion::OrdenEjecutada { id, cantidad, reservado } => {
+                let libro = self.libros.values_mut().find(|libro| libro.get_orden(id).is_some())
+                    .ok_or_else(|| format!("la orden {} no existe", id))?;
+                libro.ejecutar(id, &cantidad, &reservado).map_err(|e| e.to_string())?;
+            },
+            Operacion::OrdenCancelada { id } => {
+                self.libros.values_mut().find_map(|libro| libro.quitar(id)).ok_or_else(|| format!("la orden {} no existe", id))?;
+            },
+            Operacion::RetiroEnviado { blockchain, retiro } => {
+                let fecha = retiro.fecha.clone();
+                self.cadena(&blockchain, fecha).agregar_retiro(retiro);
+            },
+            Operacion::BloqueMinado { blockchain, fecha } => {
+                self.cadena(&blockchain, fecha.clone()).minar(fecha);
+            },
+        }
+        Ok(())
+    }
+
+    /// Guarda una foto del estado actual y vacia el log de operaciones
+    fn compactar(&mut self) -> std::io::Result<()> {
+        let estado = EstadoXYZ {
+            ultima_operacion: self.ultima_operacion,
+            ordenes: self.libros.values().flat_map(|libro| libro.compras.iter().chain(libro.ventas.iter())).cloned().collect(),
+            balances: self.balances.clone(),
+            transacciones: self.transacciones.clone(),
+            cadenas: self.cadenas.values().cloned().collect(),
+        };
+        escribir_json(self.almacenamiento.as_ref(), &format!("{}_estado", self.file_name), &estado)?;
+        // Si se corta antes de vaciar el log, al cargar se saltean las lineas que ya estan en la foto
+        self.almacenamiento.vaciar_lineas(&format!("{}_operaciones", self.file_name))?;
+        self.operaciones_sin_compactar = 0;
+        Ok(())
+    }
+
+    /// Registra el precio de una unidad de `base` en la moneda de la cotizacion desde ahora, por
+    /// ejemplo BTC a 50000 USD o USD a 1000 ARS
+    fn add_cotizacion(&mut self, base: String, cotizacion: Dinero) {
+        self.cotizaciones.agregar(Cotizacion { base, precio: cotizacion, fecha: self.reloj.ahora() });
+    }
+
+    fn set_fuente_cotizaciones(&mut self, fuente: Box<dyn FuenteCotizaciones>) {
+        self.fuente_cotizaciones = Some(fuente);
+    }
+
+    /// Agrega al historial las cotizaciones de la fuente hasta la fecha actual del reloj.
+    /// Devuelve cuantas se agregaron
+    fn actualizar_cotizaciones(&mut self) -> Result<usize, ErrorCotizacion> {
+        let ahora = self.reloj.ahora();
+        let fuente = self.fuente_cotizaciones.as_mut().ok_or_else(|| ErrorCotizacion::Fuente("No hay una fuente de cotizaciones".to_string()))?;
+        let cotizaciones = fuente.cotizaciones_hasta(&ahora)?;
+        let cantidad = cotizaciones.len();
+        cotizaciones.into_iter().for_each(|cotizacion| self.cotizaciones.agregar(cotizacion));
+        Ok(cantidad)
+    }
+
+    fn add_moneda_fiat(&mut self, moneda: Moneda) {
+        if !self.es_fiat(moneda.codigo()) {
+            self.monedas_fiat.push(moneda);
+        }
+    }
+
+    fn es_fiat(&self, codigo: &str) -> bool {
+        self.monedas_fiat.iter().any(|m| m.codigo() == codigo)
+    }
+
+    fn validar_fiat(&self, moneda: &Moneda) -> std::io::Result<()> {
+        if self.monedas_fiat.contains(moneda) {
+            Ok(())
+        } else {
+            Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{} no es una moneda fiat soportada", moneda)))
+        }
+    }
+
+    /// Moneda con el codigo indicado, los codigos que no son fiat se toman como criptomonedas
+    fn moneda(&self, codigo: &str) -> Moneda {
+        self.monedas_fiat.iter().find(|m| m.codigo() == codigo).cloned().unwrap_or_else(|| Moneda::cripto(codigo))
+    }
+
+    fn add_criptomoneda(&mut self, criptomoneda: CriptoMoneda) {
+        self.criptomonedas.push(criptomoneda);
+    }
+
+    fn get_criptomoneda(&self, prefijo: &str) -> Option<&CriptoMoneda> {
+        self.criptomonedas.iter().find(|c| c.prefijo == prefijo)
+    }
+
+    fn set_confirmaciones_retiro(&mut self, confirmaciones: u64) {
+        self.confirmaciones_retiro = confirmaciones;
+    }
+
+    /// Cadena simulada de la blockchain, si todavia no existe se crea con el bloque inicial en la fecha
+    fn cadena(&mut self, blockchain: &str, fecha: FechaHora) -> &mut CadenaSimulada {
+        self.cadenas.entry(blockchain.to_string()).or_insert_with(|| CadenaSimulada::new(blockchain, fecha))
+    }
+
+    /// Mina un bloque en la cadena de la blockchain con los retiros pendientes. Devuelve su hash
+    fn minar_bloque(&mut self, blockchain: &str) -> std::io::Result<String> {
+        if !self.criptomonedas.iter().flat_map(|c| c.blockchains.iter()).any(|b| b.nombre == blockchain) {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Blockchain no encontrada"));
+        }
+        let ahora = self.reloj.ahora();
+        self.registrar(vec![Operacion::BloqueMinado { blockchain: blockchain.to_string(), fecha: ahora }])?;
+        Ok(self.cadenas[blockchain].bloques.last().unwrap().hash.clone())
+    }
+
+    /// Busca el retiro con el hash en las cadenas de todas las blockchains
+    fn verificar_retiro(&self, hash: &str) -> Option<EstadoRetiro> {
+        self.cadenas.values().find_map(|cadena| cadena.verificar(hash, self.confirmaciones_retiro))
+    }
+
+    fn set_comisiones(&mut self, comisiones: Comisiones) {
+        self.comisiones = comisiones;
+    }
+
+    /// Total de comisiones cobradas en la moneda
+    fn get_comisiones_cobradas(&self, moneda: &str) -> Dinero {
+        self.get_balance(CUENTA_CASA, moneda)
+    }
+
+    fn set_limites(&mut self, nivel: NivelIdentidad, limites: LimitesNivel) {
+        self.limites.insert(nivel, limites);
+    }
+
+    fn set_limite_retiro_diario(&mut self, nivel: NivelIdentidad, limite: Dinero) {
+        self.limites.entry(nivel).or_insert_with(LimitesNivel::sin_limites).retiro_diario = Some(limite);
+    }
+
+    fn set_validez_kyc(&mut self, dias: i64) {
+        self.validez_kyc_dias = dias;
+    }
+
+    fn vencimiento_kyc(&self, desde: &FechaHora) -> FechaHora {
+        let mut vence = desde.clone();
+        vence.sumar_segundos(self.validez_kyc_dias * 24 * 3600);
+        vence
+    }
+
+    fn get_usuario_mut(&mut self, dni_usuario: &str) -> Result<&mut Usuario, ErrorKyc> {
+        self.usuarios.iter_mut().find(|u| u.get_dni() == dni_usuario)
+            .ok_or_else(|| ErrorKyc::UsuarioNoEncontrado(dni_usuario.to_string()))
+    }
+
+    fn existe_cuenta(&self, dni_usuario: &str) -> bool {
+        dni_usuario == CUENTA_CASA || self.get_usuario(dni_usuario).is_some()
+    }
+
+    /// Falla si el usuario no existe o si con una operacion por `valor` supera el limite de su nivel de
+    /// identidad. Los limites diarios suman lo que ya opero en el dia
+    fn validar_limite(&self, dni_usuario: &str, tipo: TipoLimite, valor: &Dinero) -> std::io::Result<()> {
+        let usuario = self.get_usuario(dni_usuario)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Usuario no encontrado"))?;
+        let nivel = usuario.nivel(&self.reloj.ahora());
+        let Some(limite) = self.limites.get(&nivel).and_then(|limites| limites.limite(tipo)) else {
+            return Ok(());
+        };
+        if limite.es_cero() {
+            return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "El nivel de identidad del usuario no permite esta operacion"));
+        }
+
+        let mut total = self.convertir(valor, limite.moneda(), Redondeo::MitadPar)?;
+        if tipo != TipoLimite::Operacion {
+            total = total.sumar(&self.sumar_en_el_dia(dni_usuario, tipo, limite.moneda())?).map_err(std::io::Error::other)?;
+        }
+        if total > *limite {
+            return Err(std::io::Error::other(format!("Se supera el limite {} de {}", tipo, limite)));
+        }
+        Ok(())
+    }
+
+    /// Suma de los ingresos o retiros del usuario en el dia actual del reloj, convertidos a la moneda con
+    /// las cotizaciones del momento de cada transaccion
+    fn sumar_en_el_dia(&self, dni_usuario: &str, tipo: TipoLimite, moneda: &Moneda) -> Result<Dinero, ErrorCotizacion> {
+        let ahora = self.reloj.ahora();
+        let mut total = Dinero::cero(moneda.clone());
+        for transaccion in self.transacciones.iter().filter(|t| t.dni_usuario == dni_usuario) {
+            if transaccion.fecha.con_offset(ahora.offset_minutos()).fecha() != ahora.fecha() {
+                continue;
+            }
+            let valor = match (tipo, &transaccion.tipo) {
+                (TipoLimite::IngresoDiario, TipoTransaccion::IngresoDinero { monto })
+                | (TipoLimite::RetiroDiario, TipoTransaccion::RetiroFiat { monto, .. }) => monto.clone(),
+                (TipoLimite::IngresoDiario, TipoTransaccion::RecepcionCripto { monto, cotizacion, .. })
+                | (TipoLimite::RetiroDiario, TipoTransaccion::RetiroCripto { monto, cotizacion, .. }) => monto.valuar(cotizacion, Redondeo::MitadPar)?,
+                _ => continue,
+            };
+            total = total.sumar(&self.convertir_en(&valor, moneda, Some(&transaccion.fecha), Redondeo::MitadPar)?)?;
+        }
+        Ok(total)
+    }
+
+    /// Precio actual de una unidad de `base` en la moneda `cotizada`, pasando por los pares que haga falta
+    fn get_cotizacion(&self, base: &str, cotizada: &Moneda) -> Result<Dinero, ErrorCotizacion> {
+        self.cotizar(base, cotizada, None)
+    }
+
+    /// Precio de una unidad de `base` en la moneda `cotizada` vigente en la fecha, usando el ultimo
+    /// precio de cada par registrado hasta esa fecha
+    fn cotizacion_en(&self, base: &str, cotizada: &Moneda, fecha: &FechaHora) -> Result<Dinero, ErrorCotizacion> {
+        self.cotizar(base, cotizada, Some(fecha))
+    }
+
+    fn cotizar(&self, base: &str, cotizada: &Moneda, fecha: Option<&FechaHora>) -> Result<Dinero, ErrorCotizacion> {
+        let uno = Dinero::desde_decimal("1", self.moneda(base))?;
+        self.convertir_en(&uno, cotizada, fecha, Redondeo::MitadArriba)
+    }
+
+    /// Convierte el monto a la moneda `destino` con las cotizaciones actuales
+    fn convertir(&self, monto: &Dinero, destino: &Moneda, redondeo: Redondeo) -> Result<Dinero, ErrorCotizacion> {
+        self.convertir_en(monto, destino, None, redondeo)
+    }
+
+    /// Convierte el monto a la moneda `destino` encadenando los pares cotizados en la fecha (o los
+    /// ultimos precios si no hay fecha), que se pueden usar en los dos sentidos. Se usa el camino con
+    /// menos pares y se redondea en cada paso
+    fn convertir_en(&self, monto: &Dinero, destino: &Moneda, fecha: Option<&FechaHora>, redondeo: Redondeo) -> Result<Dinero, ErrorCotizacion> {
+        let origen = monto.moneda().codigo();
+        let camino = self.camino_cotizaciones(origen, destino.codigo(), fecha).ok_or_else(|| {
+            ErrorCotizacion::SinCotizacion { base: origen.to_string(), cotizada: destino.codigo().to_string() }
+        })?;
+
+        let convertido = camino.into_iter().try_fold(monto.clone(), |monto, (desde, hasta)| {
+            match self.cotizaciones.precio(desde, hasta, fecha) {
+                Some(precio) => monto.valuar(precio, redondeo),
+                // Solo existe el par inverso, el precio esta expresado en la moneda que se tiene
+                None => monto.convertir(self.cotizaciones.precio(hasta, desde, fecha).unwrap(), &self.moneda(hasta), redondeo),
+            }
+        })?;
+        Ok(convertido)
+    }
+
+    /// Pares (desde, hasta) a recorrer para ir de una moneda a otra, con una busqueda a lo ancho.
+    /// Solo se usan los pares que tienen precio en la fecha
+    fn camino_cotizaciones<'a>(&'a self, origen: &'a str, destino: &str, fecha: Option<&FechaHora>) -> Option<Vec<(&'a str, &'a str)>> {
+        let mut anteriores: HashMap<&str, &str> = HashMap::new();
+        let mut pendientes = VecDeque::from([origen]);
+
+        while let Some(actual) = pendientes.pop_front() {
+            if actual == destino {
+                let mut camino = Vec::new();
+                let mut hasta = actual;
+                while let Some(&desde) = anteriores.get(hasta) {
+                    camino.push((desde, hasta));
+                    hasta = desde;
+                }
+                camino.reverse();
+                return Some(camino);
+            }
+
+            for (base, cotizada) in self.cotizaciones.pares(fecha) {
+                let vecino = if base == actual {
+                    cotizada.as_str()
+                } else if cotizada == actual {
+                    base.as_str()
+                } else {
+                    continue;
+                };
+                if vecino != origen && !anteriores.contains_key(vecino) {
+                    anteriores.insert(vecino, actual);
+                    pendientes.push_back(vecino);
+                }
+            }
+        }
+        None
+    }
+
+    fn get_libro(&self, criptomoneda: &str, fiat: &Moneda) -> Option<&LibroOrdenes> {
+        self.libros.get(&(criptomoneda.to_string(), fiat.codigo().to_string()))
+    }
+
+    /// Cruza la orden con las del lado contrario del libro, por precio y despues por antiguedad,
+    /// salteando las del mismo usuario.
+    /// Cada ejecucion se hace al precio de la orden que ya estaba en el libro. Si hay limite, lo que no
+    /// se ejecuto queda en el libro; si no, se descarta. Todo se guarda en una sola unidad de trabajo, si
+    /// algun balance no alcanza no se ejecuta nada. Devuelve el id de la orden y la cantidad ejecutada
+    fn colocar_orden(&mut self, dni_usuario: &str, lado: Lado, cantidad: Dinero, limite: Option<Dinero>, fiat: &Moneda) -> std::io::Result<(u64, Dinero)> {
+        self.validar_fiat(fiat)?;
+        if self.es_fiat(cantidad.moneda().codigo()) || cantidad.es_negativo() || cantidad.es_cero() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "La cantidad debe ser positiva y en una criptomoneda"));
+        }
+        if !self.verificar_identidad(dni_usuario) {
+            return Err(std::io::Error::other("Ha ocurrido un error en la transaccion"));
+        }
+
+        let criptomoneda = cantidad.moneda().codigo().to_string();
+        let id = self.ultima_operacion + 1;
+        let mut pendiente = cantidad;
+        let mut ejecutado = Dinero::cero(pendiente.moneda().clone());
+        let mut unidad = self.iniciar_unidad();
+        let contrapartes = self.get_libro(&criptomoneda, fiat).map_or(&[][..], |libro| libro.contrapartes(lado));
+
+        for orden in contrapartes {
+            let cruza = match (&limite, lado) {
+                (None, _) => true,
+                (Some(limite), Lado::Compra) => orden.precio <= *limite,
+                (Some(limite), Lado::Venta) => orden.precio >= *limite,
+            };
+            if pendiente.es_cero() || !cruza {
+                break;
+            }
+            // Un usuario no puede operar contra sus propias ordenes
+            if orden.dni_usuario == dni_usuario {
+                continue;
+            }
+
+            let cripto = if pendiente < orden.cantidad { pendiente.clone() } else { orden.cantidad.clone() };
+            let pago = cripto.valuar(&orden.precio, Redondeo::Truncar).map_err(std::io::Error::other)?;
+            let (comprador, vendedor) = match lado {
+                Lado::Compra => (dni_usuario, orden.dni_usuario.as_str()),
+                Lado::Venta => (orden.dni_usuario.as_str(), dni_usuario),
+            };
+
+            // La parte de la orden del libro ya se desconto al colocarla, solo se acredita la contraparte
+            let reservado = match orden.lado {
+                Lado::Compra => pago.clone(),
+                Lado::Venta => cripto.clone(),
+            };
+            unidad.ejecutar_orden(orden.id, cripto.clone(), reservado.clone());
+            if cripto == orden.cantidad {
+                // Lo que sobra por redondeo al terminar la orden vuelve al usuario
+                let sobrante = orden.reservado.restar(&reservado).map_err(std::io::Error::other)?;
+                if !sobrante.es_cero() {
+                    unidad.add_balance(&orden.dni_usuario, orden.reservado.moneda().codigo(), sobrante);
+                }
+            }
+            match lado {
+                Lado::Compra => unidad.remove_balance(dni_usuario, fiat.codigo(), pago.clone()),
+                Lado::Venta => unidad.remove_balance(dni_usuario, &criptomoneda, cripto.clone()),
+            }
+            unidad.add_balance(comprador, &criptomoneda, cripto.clone());
+            unidad.add_balance(vendedor, fiat.codigo(), pago.clone());
+            unidad.crear_transaccion(TipoTransaccion::CompraCripto { monto: pago, criptomoneda: criptomoneda.clone(), cotizacion: orden.precio.clone(), cantidad: cripto.clone(), comision: Dinero::cero(fiat.clone()) }, comprador);
+            unidad.crear_transaccion(TipoTransaccion::VentaCripto { monto: cripto.clone(), criptomoneda: criptomoneda.clone(), cotizacion: orden.precio.clone(), comision: Dinero::cero(fiat.clone()) }, vendedor);
+
+            pendiente = pendiente.restar(&cripto).map_err(std::io::Error::other)?;
+            ejecutado = ejecutado.sumar(&cripto).map_err(std::io::Error::other)?;
+        }
+
+        match limite {
+            Some(precio) if !pendiente.es_cero() => {
+                let reservado = match lado {
+                    Lado::Compra => pendiente.valuar(&precio, Redondeo::Techo).map_err(std::io::Error::other)?,
+                    Lado::Venta => pendiente.clone(),
+                };
+                unidad.remove_balance(dni_usuario, reservado.moneda().codigo(), reservado.clone());
+                unidad.agregar_orden(Orden { id, dni_usuario: dni_usuario.to_string(), lado, precio, cantidad: pendiente, reservado, fecha: unidad.fecha.clone() });
+            },
+            None if ejecutado.es_cero() => {
+                return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "No hay ordenes para ejecutar"));
+            },
+            _ => {}
+        }
+
+        self.confirmar(unidad)?;
+        Ok((id, ejecutado))
+    }
+
+    /// Vende la criptomoneda y acredita lo obtenido en la moneda fiat indicada
+    fn vender_cripto_en(&mut self, dni_usuario: &str, monto: Dinero, criptomoneda: &str, fiat: &Moneda) -> std::io::Result<()> {
+        self.validar_fiat(fiat)?;
+        if self.get_usuario(dni_usuario).is_some() && self.get_balance(dni_usuario, criptomoneda) >= monto {
+            let cotizacion = self.comisiones.precio_venta(&self.get_cotizacion(criptomoneda, fiat)?).map_err(std::io::Error::other)?;
+            let obtenido = monto.valuar(&cotizacion, Redondeo::Truncar).map_err(std::io::Error::other)?;
+            self.validar_limite(dni_usuario, TipoLimite::Operacion, &obtenido)?;
+            let comision = self.comisiones.comision_operacion(&obtenido).map_err(std::io::Error::other)?;
+            let mut unidad = self.iniciar_unidad();
+            unidad.add_balance(dni_usuario, fiat.codigo(), obtenido.restar(&comision).map_err(std::io::Error::other)?);
+            unidad.remove_balance(dni_usuario, criptomoneda, monto.clone());
+            unidad.acreditar_comision(&comision);
+            unidad.crear_transaccion(TipoTransaccion::VentaCripto { monto, criptomoneda: criptomoneda.to_string(), cotizacion, comision }, dni_usuario);
+            self.confirmar(unidad)
+        } else {
+            Err(std::io::Error::other("Ha ocurrido un error en la transaccion"))
+        }
+    }
+}
+
+impl UnidadTrabajo {
+    fn add_balance(&mut self, dni_usuario: &str, moneda: &str, monto: Dinero) {
+        self.operaciones.push(Operacion::Balance { dni_usuario: dni_usuario.to_string(), moneda: moneda.to_string(), monto });
+    }
+
+    fn remove_balance(&mut self, dni_usuario: &str, moneda: &str, monto: Dinero) {
+        let monto = Dinero::new(-monto.unidades(), monto.moneda().clone());
+        self.add_balance(dni_usuario, moneda, monto);
+    }
+
+    fn crear_transaccion(&mut self, tipo: TipoTransaccion, dni_usuario: &str) {
+        let transaccion = Transaccion { fecha: self.fecha.clone(), tipo, dni_usuario: dni_usuario.to_string() };
+        self.operaciones.push(Operacion::Transaccion(transaccion));
+    }
+
+    fn agregar_orden(&mut self, orden: Orden) {
+        self.operaciones.push(Operacion::OrdenNueva(orden));
+    }
+
+    fn ejecutar_orden(&mut self, id: u64, cantidad: Dinero, reservado: Dinero) {
+        self.operaciones.push(Operacion::OrdenEjecutada { id, cantidad, reservado });
+    }
+
+    /// Acredita en la cuenta de la casa la comision cobrada
+    fn acreditar_comision(&mut self, comision: &Dinero) {
+        if !comision.es_cero() {
+            self.add_balance(CUENTA_CASA, comision.moneda().codigo(), comision.clone());
+        }
+    }
+
+    fn cancelar_orden(&mut self, id: u64) {
+        self.operaciones.push(Operacion::OrdenCancelada { id });
+    }
+
+    fn enviar_retiro(&mut self, blockchain: &str, retiro: RetiroEnCadena) {
+        self.operaciones.push(Operacion::RetiroEnviado { blockchain: blockchain.to_string(), retiro });
+    }
+}
+
+impl Transaccion {
+    /// Si es la venta que se creo junto con la compra al ejecutar una orden del libro: van seguidas,
+    /// con la misma fecha, precio y cantidad, sin comision y para usuarios distintos
+    fn es_contraparte_de(&self, compra: &Transaccion) -> bool {
+        match (&compra.tipo, &self.tipo) {
+            (TipoTransaccion::CompraCripto { criptomoneda, cotizacion, cantidad, comision, .. },
+             TipoTransaccion::VentaCripto { criptomoneda: vendida, cotizacion: precio, monto, comision: comision_venta }) => {
+                criptomoneda == vendida && cotizacion == precio && cantidad == monto
+                    && comision.es_cero() && comision_venta.es_cero()
+                    && compra.fecha.timestamp() == self.fecha.timestamp() && compra.dni_usuario != self.dni_usuario
+            },
+            _ => false,
+        }
+    }
+}
+
+impl Orden {
+    /// Par (criptomoneda, fiat) del libro al que pertenece
+    fn par(&self) -> (String, String) {
+        (self.cantidad.moneda().codigo().to_string(), self.precio.moneda().codigo().to_string())
+    }
+}
+
+impl LibroOrdenes {
+    /// Agrega la orden despues de las que tienen un precio igual o mejor
+    fn agregar(&mut self, orden: Orden) {
+        match orden.lado {
+            Lado::Compra => {
+                let posicion = self.compras.partition_point(|o| o.precio >= orden.precio);
+                self.compras.insert(posicion, orden);
+            },
+            Lado::Venta => {
+                let posicion = self.ventas.partition_point(|o| o.precio <= orden.precio);
+                self.ventas.insert(posicion, orden);
+            },
+        }
+    }
+
+    fn quitar(&mut self, id: u64) -> Option<Orden> {
+        for ordenes in [&mut self.compras, &mut self.ventas] {
+            if let Some(posicion) = ordenes.iter().position(|o| o.id == id) {
+                return Some(ordenes.remove(posicion));
+            }
+        }
+        None
+    }
+
+    fn get_orden(&self, id: u64) -> Option<&Orden> {
+        self.compras.iter().chain(self.ventas.iter()).find(|o| o.id == id)
+    }
+
+    /// Ordenes contra las que se ejecuta una orden del lado indicado, en orden de prioridad
+    fn contrapartes(&self, lado: Lado) -> &[Orden] {
+        match lado {
+            Lado::Compra => &self.ventas,
+            Lado::Venta => &self.compras,
+        }
+    }
+
+    /// Descuenta lo ejecutado de la orden y la quita del libro cuando se completa
+    fn ejecutar(&mut self, id: u64, cantidad: &Dinero, reservado: &Dinero) -> Result<(), ErrorDinero> {
+        let orden = self.compras.iter_mut().chain(self.ventas.iter_mut()).find(|o| o.id == id);
+        if let Some(orden) = orden {
+            orden.cantidad = orden.cantidad.restar(cantidad)?;
+            orden.reservado = orden.reservado.restar(reservado)?;
+            if orden.cantidad.es_cero() {
+                self.quitar(id);
+            }
+        }
+        Ok(())
+    }
+
+    fn mejor_compra(&self) -> Option<&Orden> {
+        self.compras.first()
+    }
+
+    fn mejor_venta(&self) -> Option<&Orden> {
+        self.ventas.first()
+    }
+}
+
+impl Costeo {
+    fn new(criptomoneda: &Moneda, moneda: &Moneda) -> Self {
+        Costeo {
+            lotes: VecDeque::new(),
+            cantidad: Dinero::cero(criptomoneda.clone()),
+            costo_promedio: Dinero::cero(moneda.clone()),
+            realizado_fifo: Dinero::cero(moneda.clone()),
+            realizado_promedio: Dinero::cero(moneda.clone()),
+        }
+    }
+
+    fn entrada(&mut self, cantidad: &Dinero, costo: &Dinero) -> Result<(), ErrorDinero> {
+        self.lotes.push_back((cantidad.clone(), costo.clone()));
+        self.cantidad = self.cantidad.sumar(cantidad)?;
+        self.costo_promedio = self.costo_promedio.sumar(costo)?;
+        Ok(())
+    }
+
+    /// Saca la cantidad de la tenencia. Si es una venta, `obtenido` es lo que se cobro y se realiza la
+    /// ganancia. Lo que se saca de mas de lo que hay en los lotes se toma con costo cero
+    fn salida(&mut self, cantidad: &Dinero, obtenido: Option<&Dinero>) -> Result<(), ErrorDinero> {
+        let mut pendiente = cantidad.clone();
+        let mut costo_fifo = Dinero::cero(self.costo_promedio.moneda().clone());
+        while !pendiente.es_cero() {
+            let Some((lote, costo)) = self.lotes.front_mut() else { break };
+            if *lote <= pendiente {
+                pendiente = pendiente.restar(lote)?;
+                costo_fifo = costo_fifo.sumar(costo)?;
+                self.lotes.pop_front();
+            } else {
+                let parte = costo.prorratear(&pendiente, lote, Redondeo::MitadPar)?;
+                *lote = lote.restar(&pendiente)?;
+                *costo = costo.restar(&parte)?;
+                costo_fifo = costo_fifo.sumar(&parte)?;
+                pendiente = Dinero::cero(pendiente.moneda().clone());
+            }
+        }
+
+        let costo_promedio = if *cantidad >= self.cantidad {
+            self.costo_promedio.clone()
+        } else {
+            self.costo_promedio.prorratear(cantidad, &self.cantidad, Redondeo::MitadPar)?
+        };
+        self.costo_promedio = self.costo_promedio.restar(&costo_promedio)?;
+        self.cantidad = if *cantidad >= self.cantidad { Dinero::cero(self.cantidad.moneda().clone()) } else { self.cantidad.restar(cantidad)? };
+
+        if let Some(obtenido) = obtenido {
+            self.realizado_fifo = self.realizado_fifo.sumar(&obtenido.restar(&costo_fifo)?)?;
+            self.realizado_promedio = self.realizado_promedio.sumar(&obtenido.restar(&costo_promedio)?)?;
+        }
+        Ok(())
+    }
+
+    fn resultado_fifo(&self, valor_actual: &Dinero) -> Result<ResultadoCosteo, ErrorDinero> {
+        let costo = self.lotes.iter().try_fold(Dinero::cero(valor_actual.moneda().clone()), |total, (_, costo)| total.sumar(costo))?;
+        Ok(ResultadoCosteo { no_realizado: valor_actual.restar(&costo)?, costo, realizado: self.realizado_fifo.clone() })
+    }
+
+    fn resultado_promedio(&self, valor_actual: &Dinero) -> Result<ResultadoCosteo, ErrorDinero> {
+        Ok(ResultadoCosteo {
+            costo: self.costo_promedio.clone(),
+            realizado: self.realizado_promedio.clone(),
+            no_realizado: valor_actual.restar(&self.costo_promedio)?,
+        })
+    }
+}
+
+impl ResultadoCosteo {
+    fn sumar(&self, otro: &ResultadoCosteo) -> Result<ResultadoCosteo, ErrorDinero> {
+        Ok(ResultadoCosteo {
+            costo: self.costo.sumar(&otro.costo)?,
+            realizado: self.realizado.sumar(&otro.realizado)?,
+            no_realizado: self.no_realizado.sumar(&otro.no_realizado)?,
+        })
+    }
+}
+
+impl Comisiones {
+    fn new() -> Self {
+        Comisiones { operacion: Porcentaje::new(0), spread: Porcentaje::new(0), red: HashMap::new() }
+    }
+
+    fn con_operacion(mut self, operacion: Porcentaje) -> Self {
+        self.operacion = operacion;
+        self
+    }
+
+    fn con_spread(mut self, spread: Porcentaje) -> Self {
+        self.spread = spread;
+        self
+    }
+
+    fn con_comision_red(mut self, blockchain: &str, comision: Dinero) -> Self {
+        self.red.insert(blockchain.to_string(), comision);
+        self
+    }
+
+    // Los redondeos de las comisiones y del spread favorecen a la casa
+    fn comision_operacion(&self, monto: &Dinero) -> Result<Dinero, ErrorDinero> {
+        monto.multiplicar_porcentaje(self.operacion, Redondeo::Techo)
+    }
+
+    fn precio_compra(&self, cotizacion: &Dinero) -> Result<Dinero, ErrorDinero> {
+        cotizacion.sumar(&cotizacion.multiplicar_porcentaje(self.spread, Redondeo::Techo)?)
+    }
+
+    fn precio_venta(&self, cotizacion: &Dinero) -> Result<Dinero, ErrorDinero> {
+        cotizacion.descontar(self.spread, Redondeo::Techo)
+    }
+
+    /// Comision por retirar en la blockchain, si no tiene una configurada es cero en la moneda retirada
+    fn comision_red(&self, blockchain: &str, moneda: &Moneda) -> Dinero {
+        self.red.get(blockchain).cloned().unwrap_or_else(|| Dinero::cero(moneda.clone()))
+    }
+}
+
+impl ReporteImpositivo {
+    /// Una fila por movimiento, despues los totales del año y las tenencias al cierre
+    fn a_csv(&self) -> std::io::Result<String> {
+        let cierre = Fecha::new(31, 12, self.anio).to_string();
+        let moneda = self.moneda.codigo().to_string();
+        let opcional = |monto: &Option<Dinero>| monto.as_ref().map_or_else(String::new, Dinero::a_decimal);
+        let mut escritor = csv::Writer::from_writer(Vec::new());
+
+        for movimiento in &self.movimientos {
+            escritor.serialize(FilaImpositiva {
+                fecha: movimiento.fecha.to_string(),
+                tipo: movimiento.tipo.to_string(),
+                activo: movimiento.cantidad.moneda().codigo().to_string(),
+                cantidad: movimiento.cantidad.a_decimal(),
+                moneda: moneda.clone(),
+                equivalente: movimiento.equivalente.a_decimal(),
+                comision: movimiento.comision.a_decimal(),
+                costo: String::new(),
+                ganancia: opcional(&movimiento.ganancia),
+            })?;
+        }
+        let totales = [
+            ("total_compras", &self.totales.compras),
+            ("total_ventas", &self.totales.ventas),
+            ("total_depositos", &self.totales.depositos),
+            ("total_retiros", &self.totales.retiros),
+            ("total_comisiones", &self.totales.comisiones),
+            ("ganancia_realizada", &self.totales.ganancia_realizada),
+        ];
+        for (tipo, total) in totales {
+            escritor.serialize(FilaImpositiva {
+                fecha: cierre.clone(),
+                tipo: tipo.to_string(),
+                activo: moneda.clone(),
+                cantidad: String::new(),
+                moneda: moneda.clone(),
+                equivalente: total.a_decimal(),
+                comision: String::new(),
+                costo: String::new(),
+                ganancia: String::new(),
+            })?;
+        }
+        for tenencia in &self.tenencias {
+            escritor.serialize(FilaImpositiva {
+                fecha: cierre.clone(),
+                tipo: "tenencia".to_string(),
+                activo: tenencia.cantidad.moneda().codigo().to_string(),
+                cantidad: tenencia.cantidad.a_decimal(),
+                moneda: moneda.clone(),
+                equivalente: tenencia.valor.a_decimal(),
+                comision: String::new(),
+                costo: tenencia.costo.a_decimal(),
+                ganancia: String::new(),
+            })?;
+        }
+
+        let bytes = escritor.into_inner().map_err(|e| std::io::Error::other(e.to_string()))?;
+        String::from_utf8(bytes).map_err(std::io::Error::other)
+    }
+
+    /// Los montos van como texto con todos los decimales para no perder precision
+    fn a_json(&self) -> std::io::Result<String> {
+        let opcional = |monto: &Option<Dinero>| monto.as_ref().map(Dinero::a_decimal);
+        let valor = serde_json::json!({
+            "dni_usuario": self.dni_usuario,
+            "anio": self.anio,
+            "moneda": self.moneda.codigo(),
+            "movimientos": self.movimientos.iter().map(|movimiento| serde_json::json!({
+                "fecha": movimiento.fecha.to_string(),
+                "tipo": movimiento.tipo.to_string(),
+                "activo": movimiento.cantidad.moneda().codigo(),
+                "cantidad": movimiento.cantidad.a_decimal(),
+                "equivalente": movimiento.equivalente.a_decimal(),
+                "comision": movimiento.comision.a_decimal(),
+                "ganancia": opcional(&movimiento.ganancia),
+            })).collect::<Vec<Value>>(),
+            "totales": {
+                "compras": self.totales.compras.a_decimal(),
+                "ventas": self.totales.ventas.a_decimal(),
+                "depositos": self.totales.depositos.a_decimal(),
+                "retiros": self.totales.retiros.a_decimal(),
+                "comisiones": self.totales.comisiones.a_decimal(),
+                "ganancia_realizada": self.totales.ganancia_realizada.a_decimal(),
+            },
+            "tenencias": self.tenencias.iter().map(|tenencia| serde_json::json!({
+                "activo": tenencia.cantidad.moneda().codigo(),
+                "cantidad": tenencia.cantidad.a_decimal(),
+                "costo": tenencia.costo.a_decimal(),
+                "valor": tenencia.valor.a_decimal(),
+            })).collect::<Vec<Value>>(),
+        });
+        Ok(serde_json::to_string_pretty(&valor)?)
+    }
+
+    fn exportar(&self, ruta: &Path, formato: FormatoExportacion) -> std::io::Result<()> {
+        let texto = match formato {
+            FormatoExportacion::Csv => self.a_csv()?,
+            FormatoExportacion::Json => self.a_json()?,
+        };
+        escribir_atomico(ruta, &texto)
+    }
+}
+
+impl ReporteConciliacion {
+    fn es_consistente(&self) -> bool {
+        self.discrepancias.is_empty() && self.violaciones.is_empty()
+    }
+}
+
+impl Periodo {
+    fn todo() -> Self {
+        Periodo::default()
+    }
+
+    fn entre(desde: Fecha, hasta: Fecha) -> Self {
+        Periodo { desde: Some(desde), hasta: Some(hasta) }
+    }
+
+    fn contiene(&self, fecha: &Fecha) -> bool {
+        self.desde.as_ref().is_none_or(|desde| desde <= fecha) && self.hasta.as_ref().is_none_or(|hasta| fecha <= hasta)
+    }
+}
+
+impl Intervalo {
+    /// Primer dia del intervalo que contiene a la fecha
+    fn inicio(&self, fecha: &Fecha) -> Fecha {
+        match self {
+            Intervalo::Dia => fecha.clone(),
+            Intervalo::Semana => fecha.clone() - Duracion::dias(fecha.dia_de_la_semana().numero() as i64 - 1),
+            Intervalo::Mes => Fecha::new(1, fecha.mes(), fecha.anio()),
+        }
+    }
+}
+
+impl LimitesNivel {
+    fn sin_limites() -> Self {
+        LimitesNivel { ingreso_diario: None, operacion: None, retiro_diario: None }
+    }
+
+    /// No permite ninguna operacion
+    fn bloqueado() -> Self {
+        let cero = Some(Dinero::cero(Moneda::ars()));
+        LimitesNivel { ingreso_diario: cero.clone(), operacion: cero.clone(), retiro_diario: cero }
+    }
+
+    fn limite(&self, tipo: TipoLimite) -> Option<&Dinero> {
+        match tipo {
+            TipoLimite::IngresoDiario => self.ingreso_diario.as_ref(),
+            TipoLimite::Operacion => self.operacion.as_ref(),
+            TipoLimite::RetiroDiario => self.retiro_diario.as_ref(),
+        }
+    }
+}
+
+impl Usuario {
+    fn new(nombre: String, apellido: String, email: String, dni: String) -> Usuario {
+        Usuario {
+            nombre,
+            apellido,
+            email,
+            dni,
+            kyc: EstadoKyc::Pendiente,
+            historial_kyc: Vec::new()
+        }
+    }
+
+    fn get_dni(&self) -> &str {
+        &self.dni
+    }
+
+    /// Estado de la verificacion en la fecha, una aprobacion vencida se ve como `Vencido`
+    fn estado_kyc(&self, fecha: &FechaHora) -> EstadoKyc {
+        match &self.kyc {
+            EstadoKyc::Aprobado { nivel, vence } if vence.timestamp() <= fecha.timestamp() => EstadoKyc::Vencido { nivel: *nivel },
+            estado => estado.clone(),
+        }
+    }
+
+    /// Nivel con el que opera en la fecha
+    fn nivel(&self, fecha: &FechaHora) -> NivelIdentidad {
+        match self.estado_kyc(fecha) {
+            EstadoKyc::Aprobado { nivel, .. } => nivel,
+            _ => NivelIdentidad::SinVerificar,
+        }
+    }
+
+    /// Cambia el estado sin validar la transicion y lo agrega al historial
+    fn registrar_kyc(&mut self, hacia: EstadoKyc, fecha: FechaHora, motivo: &str) {
+        let desde = self.estado_kyc(&fecha);
+        self.historial_kyc.push(CambioKyc { fecha, desde, hacia: hacia.clone(), motivo: motivo.to_string() });
+        self.kyc = hacia;
+    }
+}
+
+impl CadenaSimulada {
+    fn new(blockchain: &str, fecha: FechaHora) -> Self {
+        let hash = Bloque::calcular_hash(blockchain, 0, HASH_INICIAL, &fecha, &[]);
+        let inicial = Bloque { altura: 0, hash_anterior: HASH_INICIAL.to_string(), fecha, retiros: Vec::new(), hash };
+        CadenaSimulada { blockchain: blockchain.to_string(), bloques: vec![inicial], pendientes: Vec::new(), retiros: 0 }
+    }
+
+    fn agregar_retiro(&mut self, retiro: RetiroEnCadena) {
+        self.retiros += 1;
+        self.pendientes.push(retiro);
+    }
+
+    /// Agrega un bloque con todos los retiros pendientes
+    fn minar(&mut self, fecha: FechaHora) -> &Bloque {
+        let anterior = self.bloques.last().unwrap();
+        let altura = anterior.altura + 1;
+        let retiros: Vec<RetiroEnCadena> = self.pendientes.drain(..).collect();
+        let hash = Bloque::calcular_hash(&self.blockchain, altura, &anterior.hash, &fecha, &retiros);
+        let bloque = Bloque { altura, hash_anterior: anterior.hash.clone(), fecha, retiros, hash };
+        self.bloques.push(bloque);
+        self.bloques.last().unwrap()
+    }
+
+    fn altura(&self) -> u64 {
+        self.bloques.last().unwrap().altura
+    }
+
+    /// Estado del retiro con el hash, o None si no se envio a esta cadena
+    fn verificar(&self, hash: &str, confirmaciones_requeridas: u64) -> Option<EstadoRetiro> {
+        if self.pendientes.iter().any(|r| r.hash == hash) {
+            return Some(EstadoRetiro::Pendiente);
+        }
+        let bloque = self.bloques.iter().find(|b| b.retiros.iter().any(|r| r.hash == hash))?;
+        let confirmaciones = self.altura() - bloque.altura + 1;
+        if confirmaciones >= confirmaciones_requeridas {
+            Some(EstadoRetiro::Confirmado { altura: bloque.altura, confirmaciones })
+        } else {
+            Some(EstadoRetiro::EnBloque { altura: bloque.altura, confirmaciones })
+        }
+    }
+
+    /// Vuelve a calcular los hashes de los retiros y los bloques y revisa que cada bloque apunte al anterior
+    fn es_valida(&self) -> bool {
+        let mut hash_anterior = HASH_INICIAL;
+        self.bloques.iter().enumerate().all(|(altura, bloque)| {
+            let valido = bloque.altura == altura as u64
+                && bloque.hash_anterior == hash_anterior
+                && bloque.retiros.iter().all(|r| r.hash == r.calcular_hash(&self.blockchain))
+                && bloque.hash == Bloque::calcular_hash(&self.blockchain, bloque.altura, &bloque.hash_anterior, &bloque.fecha, &bloque.retiros);
+            hash_anterior = &bloque.hash;
+            valido
+        })
+    }
+}
+
+impl Bloque {
+    fn calcular_hash(blockchain: &str, altura: u64, hash_anterior: &str, fecha: &FechaHora, retiros: &[RetiroEnCadena]) -> String {
+        let retiros: Vec<&str> = retiros.iter().map(|r| r.hash.as_str()).collect();
+        sha256(&format!("{}|{}|{}|{}|{}", blockchain, altura, hash_anterior, fecha.timestamp(), retiros.join(",")))
+    }
+}
+
+impl RetiroEnCadena {
+    fn new(blockchain: &str, numero: u64, dni_usuario: &str, monto: Dinero, fecha: FechaHora) -> Self {
+        let mut retiro = RetiroEnCadena { numero, dni_usuario: dni_usuario.to_string(), monto, fecha, hash: String::new() };
+        retiro.hash = retiro.calcular_hash(blockchain);
+        retiro
+    }
+
+    fn calcular_hash(&self, blockchain: &str) -> String {
+        sha256(&format!("{}|{}|{}|{}|{}|{}", blockchain, self.numero, self.dni_usuario, self.monto.unidades(), self.monto.moneda(), self.fecha.timestamp()))
+    }
+}
+
+/// Criptomoneda con el mayor total, en un empate la de menor prefijo
+fn mayor_total(totales: HashMap<&str, i128>) -> Option<String> {
+    totales.into_iter()
+        .max_by(|(a, total_a), (b, total_b)| total_a.cmp(total_b).then(b.cmp(a)))
+        .map(|(criptomoneda, _)| criptomoneda.to_string())
+}
+
+/// Hash SHA-256 del texto en hexadecimal
+fn sha256(texto: &str) -> String {
+    Sha256::digest(texto.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+impl HistorialCotizaciones {
+    /// Agrega el precio a la serie del par, si ya habia uno en la misma fecha lo reemplaza
+    fn agregar(&mut self, cotizacion: Cotizacion) {
+        let par = (cotizacion.base, cotizacion.precio.moneda().codigo().to_string());
+        self.series.entry(par).or_default().insert(cotizacion.fecha, cotizacion.precio);
+    }
+
+    /// Precio vigente del par en la fecha, el ultimo registrado hasta esa fecha inclusive.
+    /// Sin fecha es el ultimo registrado
+    fn precio(&self, base: &str, cotizada: &str, fecha: Option<&FechaHora>) -> Option<&Dinero> {
+        let serie = self.series.get(&(base.to_string(), cotizada.to_string()))?;
+        match fecha {
+            Some(fecha) => serie.range(..=fecha).next_back().map(|(_, precio)| precio),
+            None => serie.values().next_back(),
+        }
+    }
+
+    /// Pares (base, cotizada) que tienen precio en la fecha
+    fn pares(&self, fecha: Option<&FechaHora>) -> Vec<&(String, String)> {
+        self.series.keys().filter(|(base, cotizada)| self.precio(base, cotizada, fecha).is_some()).collect()
+    }
+
+    /// Precios registrados del par en orden cronologico
+    fn serie(&self, base: &str, cotizada: &str) -> Vec<(&FechaHora, &Dinero)> {
+        self.series.get(&(base.to_string(), cotizada.to_string())).map_or(Vec::new(), |serie| serie.iter().collect())
+    }
+}
+
+impl SimuladorCotizaciones {
+    /// La primera cotizacion de cada par es su precio inicial, en la fecha de inicio. Una volatilidad
+    /// negativa se toma como la positiva
+    fn new(semilla: u64, inicio: FechaHora, intervalo_segundos: i64, volatilidad: Porcentaje) -> Self {
+        SimuladorCotizaciones {
+            rng: StdRng::seed_from_u64(semilla),
+            precios: Vec::new(),
+            volatilidad: Porcentaje::desde_centesimas(volatilidad.centesimas().abs()),
+            intervalo_segundos: intervalo_segundos.max(1),
+            siguiente: inicio,
+        }
+    }
+
+    fn agregar_par(mut self, base: &str, precio_inicial: Dinero) -> Self {
+        self.precios.push((base.to_string(), precio_inicial));
+        self
+    }
+}
+
+impl FuenteCotizaciones for SimuladorCotizaciones {
+    fn cotizaciones_hasta(&mut self, fecha: &FechaHora) -> Result<Vec<Cotizacion>, ErrorCotizacion> {
+        let mut cotizaciones = Vec::new();
+        while self.siguiente <= *fecha {
+            for (base, precio) in self.precios.iter_mut() {
+                cotizaciones.push(Cotizacion { base: base.clone(), precio: precio.clone(), fecha: self.siguiente.clone() });
+
+                let maximo = self.volatilidad.centesimas();
+                let variacion = Porcentaje::desde_centesimas(self.rng.gen_range(-maximo..=maximo));
+                let nuevo = precio.sumar(&precio.multiplicar_porcentaje(variacion, Redondeo::MitadPar)?)?;
+                // El precio nunca llega a cero, como minimo vale una unidad menor
+                *precio = if nuevo.es_negativo() || nuevo.es_cero() { Dinero::new(1, nuevo.moneda().clone()) } else { nuevo };
+            }
+            self.siguiente.sumar_segundos(self.intervalo_segundos);
+        }
+        Ok(cotizaciones)
+    }
+}
+
+impl ReproduccionCsv {
+    /// Lee las cotizaciones del texto. Los precios se leen en la moneda de `monedas` con el codigo de
+    /// la columna `cotizada`, los codigos que no estan se toman como criptomonedas
+    fn desde_texto(texto: &str, monedas: &[Moneda]) -> Result<Self, ErrorCotizacion> {
+        let mut lector = csv::Reader::from_reader(texto.as_bytes());
+        let mut pendientes = Vec::new();
+        for fila in lector.deserialize::<FilaCotizacion>() {
+            let fila = fila.map_err(|e| ErrorCotizacion::Fuente(e.to_string()))?;
+            let error = |detalle: String| ErrorCotizacion::Fuente(format!("{},{},{},{}: {}", fila.fecha, fila.base, fila.cotizada, fila.precio, detalle));
+
+            let fecha: FechaHora = fila.fecha.parse().map_err(|e| error(format!("{:?}", e)))?;
+            let moneda = monedas.iter().find(|m| m.codigo() == fila.cotizada).cloned().unwrap_or_else(|| Moneda::cripto(&fila.cotizada));
+            let precio = Dinero::desde_decimal(fila.precio.trim(), moneda).map_err(|e| error(e.to_string()))?;
+            pendientes.push(Cotizacion { base: fila.base.clone(), precio, fecha });
+        }
+        // Se respeta el orden del archivo entre las cotizaciones de la misma fecha
+        pendientes.sort_by(|a, b| a.fecha.cmp(&b.fecha));
+        Ok(ReproduccionCsv { pendientes: pendientes.into() })
+    }
+
+    fn abrir(ruta: &Path, monedas: &[Moneda]) -> Result<Self, ErrorCotizacion> {
+        let texto = std::fs::read_to_string(ruta).map_err(|e| ErrorCotizacion::Fuente(format!("{}: {}", ruta.display(), e)))?;
+        ReproduccionCsv::desde_texto(&texto, monedas)
+    }
+}
+
+impl FuenteCotizaciones for ReproduccionCsv {
+    fn cotizaciones_hasta(&mut self, fecha: &FechaHora) -> Result<Vec<Cotizacion>, ErrorCotizacion> {
+        let cantidad = self.pendientes.iter().take_while(|c| c.fecha <= *fecha).count();
+        Ok(self.pendientes.drain(..cantidad).collect())
+    }
+}
+
+impl Display for ErrorCotizacion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorCotizacion::SinCotizacion { base, cotizada } => write!(f, "No hay cotizacion de {} a {}", base, cotizada),
+            ErrorCotizacion::Fuente(detalle) => write!(f, "Error en la fuente de cotizaciones: {}", detalle),
+            ErrorCotizacion::Dinero(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ErrorCotizacion {}
+
+impl Display for TipoMovimiento {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TipoMovimiento::Compra => write!(f, "compra"),
+            TipoMovimiento::Venta => write!(f, "venta"),
+            TipoMovimiento::Deposito => write!(f, "deposito"),
+            TipoMovimiento::Retiro => write!(f, "retiro"),
+        }
+    }
+}
+
+impl Display for ReporteConciliacion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.es_consistente() {
+            return write!(f, "Los balances coinciden con las transacciones");
+        }
+        for d in &self.discrepancias {
+            writeln!(f, "{} {}: guardado {}, esperado {}", d.dni_usuario, d.moneda, d.guardado, d.esperado)?;
+        }
+        for violacion in &self.violaciones {
+            match violacion {
+                Violacion::SaldoNegativo { dni_usuario, moneda, balance } => writeln!(f, "{} {}: saldo negativo {}", dni_usuario, moneda, balance)?,
+                Violacion::SaldoNegativoEnLog { transaccion, dni_usuario, moneda, balance } => {
+                    writeln!(f, "{} {}: saldo negativo {} en la transaccion {}", dni_usuario, moneda, balance, transaccion)?
+                },
+                Violacion::CriptoNoConservada { criptomoneda, esperado, total } => writeln!(f, "{}: hay {} y deberia haber {}", criptomoneda, total, esperado)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for EstadoKyc {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EstadoKyc::Pendiente => write!(f, "pendiente"),
+            EstadoKyc::DocumentosEnviados { nivel } => write!(f, "con documentos enviados para el nivel {:?}", nivel),
+            EstadoKyc::Aprobado { nivel, vence } => write!(f, "aprobada en el nivel {:?} hasta {}", nivel, vence),
+            EstadoKyc::Rechazado => write!(f, "rechazada"),
+            EstadoKyc::Vencido { nivel } => write!(f, "vencida en el nivel {:?}", nivel),
+        }
+    }
+}
+
+impl Display for ErrorKyc {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorKyc::UsuarioNoEncontrado(dni) => write!(f, "No existe el usuario con dni {}", dni),
+            ErrorKyc::TransicionInvalida { desde, accion } => write!(f, "No se puede {} con la verificacion {}", accion, desde),
+        }
+    }
+}
+
+impl std::error::Error for ErrorKyc {}
+
+impl Display for TipoLimite {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TipoLimite::IngresoDiario => write!(f, "diario de ingresos"),
+            TipoLimite::Operacion => write!(f, "por operacion"),
+            TipoLimite::RetiroDiario => write!(f, "diario de retiros"),
+        }
+    }
+}
+
+impl From<ErrorDinero> for ErrorCotizacion {
+    fn from(error: ErrorDinero) -> Self {
+        ErrorCotizacion::Dinero(error)
+    }
+}
+
+impl From<ErrorCotizacion> for std::io::Error {
+    fn from(error: ErrorCotizacion) -> Self {
+        let tipo = match error {
+            ErrorCotizacion::SinCotizacion { .. } => std::io::ErrorKind::NotFound,
+            _ => std::io::ErrorKind::Other,
+        };
+        std::io::Error::new(tipo, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::almacenamiento::{fixtures::cargar_fixtures, AccionCarga, AlmacenamientoArchivos, AlmacenamientoMemoria};
+    use crate::fecha::RelojManual;
+
+    fn ars(monto: &str) -> Dinero {
+        Dinero::desde_decimal(monto, Moneda::ars()).unwrap()
+    }
+
+    fn cripto(monto: &str, prefijo: &str) -> Dinero {
+        Dinero::desde_decimal(monto, Moneda::cripto(prefijo)).unwrap()
+    }
+
+    fn usd(monto: &str) -> Dinero {
+        Dinero::desde_decimal(monto, Moneda::usd()).unwrap()
+    }
+
+    #[test]
+    fn test_cadena_simulada() {
+        let fecha = FechaHora::new(Fecha::new(5, 3, 2024), 10, 0, 0);
+        let mut cadena = CadenaSimulada::new("Bitcoin", fecha.clone());
+        let primero = RetiroEnCadena::new("Bitcoin", 0, "44556677", cripto("0.01", "BTC"), fecha.clone());
+        // Con el mismo contenido da el mismo hash, con otro numero de retiro otro
+        assert_eq!(primero, RetiroEnCadena::new("Bitcoin", 0, "44556677", cripto("0.01", "BTC"), fecha.clone()));
+        let segundo = RetiroEnCadena::new("Bitcoin", 1, "44556677", cripto("0.01", "BTC"), fecha.clone());
+        assert_ne!(primero.hash, segundo.hash);
+        assert_eq!(primero.hash.len(), 64);
+        assert_ne!(primero.hash, RetiroEnCadena::new("Ethereum", 0, "44556677", cripto("0.01", "BTC"), fecha.clone()).hash);
+
+        cadena.agregar_retiro(primero.clone());
+        assert_eq!(cadena.verificar(&primero.hash, 2), Some(EstadoRetiro::Pendiente));
+        let bloque = cadena.minar(fecha.clone()).clone();
+        assert_eq!(bloque.hash_anterior, cadena.bloques[0].hash);
+        assert_eq!(cadena.verificar(&primero.hash, 2), Some(EstadoRetiro::EnBloque { altura: 1, confirmaciones: 1 }));
+        cadena.minar(fecha.clone());
+        assert_eq!(cadena.verificar(&primero.hash, 2), Some(EstadoRetiro::Confirmado { altura: 1, confirmaciones: 2 }));
+        assert_eq!(cadena.verificar(&segundo.hash, 2), None);
+        assert!(cadena.es_valida());
+
+        // Cambiar un retiro ya minado rompe la cadena
+        cadena.bloques[1].retiros[0].monto = cripto("1", "BTC");
+        assert!(!cadena.es_valida());
+        cadena.bloques[1].retiros[0] = primero;
+        assert!(cadena.es_valida());
+        cadena.bloques[1].fecha.sumar_segundos(1);
+        assert!(!cadena.es_valida());
+    }
+
+    #[test]
+    fn test_retirar_cripto_confirmaciones() {
+        let mut sistema = XYZ::new("test_retirar_cripto_confirmaciones", Rc::new(AlmacenamientoMemoria::new()));
+        sistema.crear_usuario("Bob", "Smith", "bob@example.com", "44556677", true);
+        sistema.add_criptomoneda(CriptoMoneda {
+            nombre: "Bitcoin".to_string(),
+            prefijo: "BTC".to_string(),
+            blockchains: vec![BlockChain { nombre: "Bitcoin".to_string(), prefijo: "BTC".to_string() }]
+        });
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
+        sistema.set_confirmaciones_retiro(3);
+        assert!(sistema.add_balance("44556677", "BTC", cripto("0.02", "BTC")).is_ok());
+
+        // Dos retiros iguales en el mismo momento tienen hashes distintos
+        assert!(sistema.retirar_cripto("44556677", cripto("0.01", "BTC"), "BTC", "Bitcoin").is_ok());
+        assert!(sistema.retirar_cripto("44556677", cripto("0.01", "BTC"), "BTC", "Bitcoin").is_ok());
+        let hashes: Vec<String> = sistema.transacciones.iter().map(|t| match &t.tipo {
+            TipoTransaccion::RetiroCripto { hash, .. } => hash.clone(),
+            _ => panic!("se esperaba un retiro"),
+        }).collect();
+        assert_ne!(hashes[0], hashes[1]);
+        assert_eq!(sistema.verificar_retiro(&hashes[0]), Some(EstadoRetiro::Pendiente));
+
+        for _ in 0..2 {
+            assert!(sistema.minar_bloque("Bitcoin").is_ok());
+        }
+        assert_eq!(sistema.verificar_retiro(&hashes[1]), Some(EstadoRetiro::EnBloque { altura: 1, confirmaciones: 2 }));
+        let hash_bloque = sistema.minar_bloque("Bitcoin").unwrap();
+        assert_eq!(sistema.verificar_retiro(&hashes[1]), Some(EstadoRetiro::Confirmado { altura: 1, confirmaciones: 3 }));
+        assert_eq!(sistema.cadenas["Bitcoin"].bloques[3].hash, hash_bloque);
+        assert!(sistema.cadenas["Bitcoin"].es_valida());
+        assert_eq!(sistema.verificar_retiro("Bitcoin42"), None);
+        assert_eq!(sistema.minar_bloque("Solana").unwrap_err().kind(), std::io::ErrorKind::NotFound);
+
+        // Un retiro que no se pudo guardar no llega a la cadena
+        sistema.set_comisiones(Comisiones::new().con_comision_red("Bitcoin", cripto("0.001", "BTC")));
+        assert!(sistema.add_balance("44556677", "BTC", cripto("0.01", "BTC")).is_ok());
+        assert!(sistema.retirar_cripto("44556677", cripto("0.01", "BTC"), "BTC", "Bitcoin").is_err());
+        assert_eq!(sistema.cadenas["Bitcoin"].retiros, 2);
+        assert!(sistema.cadenas["Bitcoin"].pendientes.is_empty());
+    }
+
+    #[test]
+    fn test_cadenas_persistentes() {
+        for limite_compactacion in [LIMITE_COMPACTACION, 1] {
+            let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+            let bitcoin = || CriptoMoneda {
+                nombre: "Bitcoin".to_string(),
+                prefijo: "BTC".to_string(),
+                blockchains: vec![BlockChain { nombre: "Bitcoin".to_string(), prefijo: "BTC".to_string() }]
+            };
+            let mut sistema = XYZ::new("test_cadenas_persistentes", almacenamiento.clone());
+            sistema.set_limite_compactacion(limite_compactacion);
+            sistema.crear_usuario("Bob", "Smith", "bob@example.com", "44556677", true);
+            sistema.add_criptomoneda(bitcoin());
+            sistema.add_cotizacion("BTC".to_string(), ars("50000"));
+            assert!(sistema.add_balance("44556677", "BTC", cripto("0.03", "BTC")).is_ok());
+
+            assert!(sistema.retirar_cripto("44556677", cripto("0.01", "BTC"), "BTC", "Bitcoin").is_ok());
+            let hash_bloque = sistema.minar_bloque("Bitcoin").unwrap();
+            assert!(sistema.retirar_cripto("44556677", cripto("0.01", "BTC"), "BTC", "Bitcoin").is_ok());
+            let hashes: Vec<String> = sistema.transacciones.iter().filter_map(|t| match &t.tipo {
+                TipoTransaccion::RetiroCripto { hash, .. } => Some(hash.clone()),
+                _ => None,
+            }).collect();
+
+            // Al cargar se recuperan los bloques minados, los retiros pendientes y el contador de retiros
+            let mut sistema = XYZ::new("test_cadenas_persistentes", almacenamiento.clone());
+            sistema.crear_usuario("Bob", "Smith", "bob@example.com", "44556677", true);
+            sistema.add_criptomoneda(bitcoin());
+            sistema.add_cotizacion("BTC".to_string(), ars("50000"));
+            assert_eq!(sistema.verificar_retiro(&hashes[0]), Some(EstadoRetiro::EnBloque { altura: 1, confirmaciones: 1 }));
+            assert_eq!(sistema.verificar_retiro(&hashes[1]), Some(EstadoRetiro::Pendiente));
+            assert_eq!(sistema.cadenas["Bitcoin"].bloques[1].hash, hash_bloque);
+            assert_eq!(sistema.cadenas["Bitcoin"].retiros, 2);
+            assert!(sistema.cadenas["Bitcoin"].es_valida());
+
+            assert!(sistema.retirar_cripto("44556677", cripto("0.01", "BTC"), "BTC", "Bitcoin").is_ok());
+            let TipoTransaccion::RetiroCripto { hash, .. } = &sistema.transacciones.last().unwrap().tipo else { panic!("se esperaba un retiro") };
+            assert!(!hashes.contains(hash));
+        }
+    }
+
+    #[test]
+    fn test_retirar_cripto_blockchain_no_soportada() {
+        let mut sistema = XYZ::new("test_retirar_cripto_blockchain_no_soportada", Rc::new(AlmacenamientoMemoria::new()));
+        sistema.crear_usuario("Bob", "Smith", "bob@example.com", "44556677", true);
+        sistema.add_criptomoneda(CriptoMoneda {
+            nombre: "Bitcoin".to_string(),
+            prefijo: "BTC".to_string(),
+            blockchains: vec![BlockChain { nombre: "Bitcoin".to_string(), prefijo: "BTC".to_string() }]
+        });
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
+        sistema.add_cotizacion("ETH".to_string(), ars("2500"));
+        assert!(sistema.add_balance("44556677", "BTC", cripto("0.02", "BTC")).is_ok());
+        assert!(sistema.add_balance("44556677", "ETH", cripto("1", "ETH")).is_ok());
+
+        let error = sistema.retirar_cripto("44556677", cripto("0.01", "BTC"), "BTC", "Ethereum").unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(sistema.retirar_cripto("44556677", cripto("0.5", "ETH"), "ETH", "Ethereum").unwrap_err().kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(sistema.get_balance("44556677", "BTC"), cripto("0.02", "BTC"));
+        assert!(sistema.transacciones.is_empty());
+        assert!(sistema.cadenas.is_empty());
+    }
+
+    #[test]
+    fn test_new_xyz_con_datos() {
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+        let mut sistema = XYZ::new("test_new_XYZ_con_datos", almacenamiento.clone());
+
+        assert_eq!(sistema.balances.len(), 0);
+        assert_eq!(sistema.transacciones.len(), 0);
 
-            let cripto = self.get_criptomoneda(criptomoneda).unwrap();
-            let hash = cripto.blockchains.iter()
-                .find(|b| b.nombre == blockchain)
-                .unwrap().withdraw();
+        sistema.crear_usuario( "Juan", "Garcia", "juan@example.com", "87654321", true);
+        assert!(sistema.add_balance("87654321", "ARS", ars("50000")).is_ok());
+        assert!(sistema.ingresar_dinero("87654321", ars("100")).is_ok());
+        assert_eq!(sistema.balances.len(), 1);
+        assert_eq!(sistema.transacciones.len(), 1);
 
-            self.crear_transaccion(TipoTransaccion::RetiroCripto { monto, criptomoneda: criptomoneda.to_string(), cotizacion, blockchain: blockchain.to_string(), hash }, dni_usuario)
-        } else {
-            Err(std::io::Error::new(std::io::ErrorKind::Other, "Ha ocurrido un error en la transaccion"))
-        }
+        let sistema = XYZ::new("test_new_XYZ_con_datos", almacenamiento);
+        assert_eq!(sistema.balances.len(), 1);
+        assert_eq!(sistema.transacciones.len(), 1);
     }
 
-    fn recibir_cripto(&mut self, dni_usuario: &str, monto: f64, criptomoneda: &str, blockchain: &str) -> std::io::Result<()>{
-        if self.verificar_identidad(dni_usuario) {
-            let cotizacion: f64 = self.get_cotizacion(&criptomoneda);
-            self.add_balance(dni_usuario, &criptomoneda, monto)?;
-            self.crear_transaccion(TipoTransaccion::RecepcionCripto { monto, criptomoneda: criptomoneda.to_string(), blockchain: blockchain.to_string(), cotizacion }, dni_usuario)
-        } else {
-            Err(std::io::Error::new(std::io::ErrorKind::Other, "Ha ocurrido un error en la transaccion"))
-        }
-    }
+    #[test]
+    fn test_cargar_xyz_corrupto() {
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+        almacenamiento.escribir("test_cargar_xyz_transacciones", "[{\"fecha\"").unwrap();
 
-    fn retirar_dinero(&mut self, dni_usuario: &str, monto: f64, medio: MedioRetiro) -> std::io::Result<()>{
-        if self.verificar_identidad(dni_usuario) && self.get_balance(dni_usuario, "fiat") >= monto {
-            self.remove_balance(dni_usuario, "fiat", monto)?;
-            self.crear_transaccion(TipoTransaccion::RetiroFiat { monto, medio }, dni_usuario)
-        } else {
-            Err(std::io::Error::new(std::io::ErrorKind::Other, "Ha ocurrido un error en la transaccion"))
-        
-        }
+        let resultado = XYZ::cargar("test_cargar_xyz", almacenamiento.clone(), &PoliticaCarga::default());
+        assert!(matches!(resultado, Err(ErrorCarga::Corrupto(_))));
+
+        let politica = PoliticaCarga { corrupto: AccionCarga::EmpezarVacio, ..PoliticaCarga::default() };
+        let sistema = XYZ::cargar("test_cargar_xyz", almacenamiento, &politica).unwrap();
+        assert_eq!(sistema.transacciones.len(), 0);
     }
-}
 
-impl GestorTransacciones for XYZ {
-    fn crear_transaccion(&mut self, tipo: TipoTransaccion, dni_usuario: &str) -> std::io::Result<()>{
-        self.transacciones.push(Transaccion { fecha: Fecha::now(), tipo, dni_usuario: dni_usuario.to_string() });
-        self.escribir_archivo_transacciones()
+    #[test]
+    fn test_log_operaciones() {
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+        let mut sistema = XYZ::new("test_log_operaciones", almacenamiento.clone());
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", true);
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
+        assert!(sistema.ingresar_dinero("87654321", ars("1000")).is_ok());
+        assert!(sistema.comprar_cripto("87654321", ars("500"), "BTC").is_ok());
+
+        // Cada operacion es una linea nueva, no se reescribe nada
+        assert_eq!(almacenamiento.leer_lineas("test_log_operaciones_operaciones").unwrap().len(), 2);
+        assert!(almacenamiento.leer("test_log_operaciones_estado").unwrap().is_none());
+        assert!(almacenamiento.leer("test_log_operaciones_balances").unwrap().is_none());
+
+        let mut sistema = XYZ::new("test_log_operaciones", almacenamiento.clone());
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", true);
+        assert_eq!(sistema.transacciones.len(), 2);
+        assert_eq!(sistema.ultima_operacion, 2);
+        assert_eq!(sistema.balances["87654321"]["ARS"], ars("500"));
+        assert_eq!(sistema.balances["87654321"]["BTC"], cripto("0.01", "BTC"));
     }
 
-    fn escribir_archivo_transacciones(&self) -> std::io::Result<()> {
-        let mut file = std::fs::File::create("test/".to_owned() + &self.file_name + "_transacciones.json")?;
-        let serialized = serde_json::to_string(&self.transacciones)?;
-        file.write_all(&serialized.as_bytes())?;
-        Ok(())
+    #[test]
+    fn test_compactar_log_operaciones() {
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+        let mut sistema = XYZ::new("test_compactar", almacenamiento.clone());
+        sistema.set_limite_compactacion(2);
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", true);
+        assert!(sistema.ingresar_dinero("87654321", ars("1000")).is_ok());
+        assert!(sistema.retirar_dinero("87654321", ars("100"), MedioRetiro::MercadoPago).is_ok());
+        assert!(sistema.ingresar_dinero("87654321", ars("50")).is_ok());
+        assert_eq!(almacenamiento.leer_lineas("test_compactar_operaciones").unwrap().len(), 1);
+        assert!(almacenamiento.leer("test_compactar_estado").unwrap().is_some());
+
+        let sistema = XYZ::new("test_compactar", almacenamiento.clone());
+        assert_eq!(sistema.transacciones.len(), 3);
+        assert_eq!(sistema.balances["87654321"]["ARS"], ars("950"));
+        assert_eq!(sistema.operaciones_sin_compactar, 1);
     }
-}
 
-impl GestorUsuarios for XYZ {
-    fn crear_usuario(&mut self, nombre: &str, apellido: &str, email: &str, dni: &str, identidad: bool) -> &Usuario {
-        let usuario = Usuario::new(nombre.to_string(), apellido.to_string(), email.to_string(), dni.to_string(), identidad);
-        self.usuarios.push(usuario);
-        self.balances.insert(dni.to_string(), HashMap::new());
-        self.usuarios.last().unwrap()
+    /// Almacenamiento en memoria que no puede guardar archivos, solo agregar lineas al log
+    #[derive(Default)]
+    struct AlmacenamientoSinFotos {
+        memoria: AlmacenamientoMemoria,
+        fallar: std::cell::Cell<bool>,
     }
 
-    fn verificar_identidad(&self, dni_usuario: &str) -> bool {
-        let usuario = self.get_usuario(dni_usuario);
-        match usuario {
-            Some(usuario) => usuario.get_identidad(),
-            None => false
+    impl Almacenamiento for AlmacenamientoSinFotos {
+        fn leer(&self, clave: &str) -> std::io::Result<Option<String>> {
+            self.memoria.leer(clave)
         }
-    }
 
-    fn get_usuario(&self, dni_usuario: &str) -> Option<&Usuario> {
-        self.usuarios.iter().find(|u| u.get_dni() == dni_usuario)
-    }
-}
+        fn leer_respaldo(&self, clave: &str) -> std::io::Result<Option<String>> {
+            self.memoria.leer_respaldo(clave)
+        }
 
-impl GestorBalances for XYZ {
-    fn get_balance(&self, dni_usuario: &str, moneda: &str) -> f64 {
-        if self.get_usuario(dni_usuario).is_some() {
-            self.balances.get(dni_usuario).unwrap().get(moneda).unwrap_or(&0.0).clone()
-        } else {
-            0.0
+        fn escribir(&self, clave: &str, contenido: &str) -> std::io::Result<()> {
+            if self.fallar.get() {
+                return Err(std::io::Error::other("disco lleno"));
+            }
+            self.memoria.escribir(clave, contenido)
         }
-    }
 
-    fn add_balance(&mut self, dni_usuario: &str, moneda: &str, monto: f64) -> std::io::Result<()> {
-        match self.get_usuario(dni_usuario) {
-            Some(_) => {
-                let user = self.balances.entry(dni_usuario.to_string()).or_insert(HashMap::new());
-                let current = user.get(moneda).unwrap_or(&0.0);
-                user.insert(moneda.to_string(), current + monto);
-                self.escribir_archivo_balance()
-            },
-            None => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Usuario no encontrado"))
+        fn agregar_linea(&self, clave: &str, linea: &str) -> std::io::Result<()> {
+            self.memoria.agregar_linea(clave, linea)
         }
-    }
 
-    fn remove_balance(&mut self, dni_usuario: &str, moneda: &str, monto: f64) -> std::io::Result<()>{
-        match self.get_usuario(dni_usuario) {
-            Some(_) => {
-                let user = self.balances.entry(dni_usuario.to_string()).or_insert(HashMap::new());
-                let current = user.get(moneda).unwrap_or(&0.0);
-                user.insert(moneda.to_string(), current - monto);
-                self.escribir_archivo_balance()
-            },
-            None => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Usuario no encontrado"))
+        fn leer_lineas(&self, clave: &str) -> std::io::Result<Vec<String>> {
+            self.memoria.leer_lineas(clave)
         }
-    }
 
-    fn escribir_archivo_balance(&self) -> std::io::Result<()> {
-        let mut file = std::fs::File::create("test/".to_owned() + &self.file_name + "_balances.json")?;
-        let serialized = serde_json::to_string(&self.balances)?;
-        file.write_all(&serialized.as_bytes())?;
-        Ok(())
+        fn vaciar_lineas(&self, clave: &str) -> std::io::Result<()> {
+            self.memoria.vaciar_lineas(clave)
+        }
     }
-}
 
-impl Estadisticas for XYZ {
-    fn cripto_mas_compras(&self) -> String {
-        self.transacciones.iter().filter(|t| match t.tipo {
-            TipoTransaccion::CompraCripto { .. } => true,
-            _ => false
-        }).fold(HashMap::new(), |mut acc, t| {
-            let criptomoneda = match &t.tipo {
-                TipoTransaccion::CompraCripto { criptomoneda, .. } => criptomoneda,
-                _ => ""
-            };
-            let entry = acc.entry(criptomoneda).or_insert(0);
-            *entry += 1;
-            acc
-        }).iter().max_by_key(|(_, &v)| v).map(|(k, _)| k.to_string()).unwrap_or("".to_string())
-    }
-
-    fn cripto_mas_ventas(&self) -> String {
-        self.transacciones.iter().filter(|t| match t.tipo {
-            TipoTransaccion::VentaCripto { .. } => true,
-            _ => false
-        }).fold(HashMap::new(), |mut acc, t| {
-            let criptomoneda = match &t.tipo {
-                TipoTransaccion::VentaCripto { criptomoneda, .. } => criptomoneda,
-                _ => ""
-            };
-            let entry = acc.entry(criptomoneda).or_insert(0);
-            *entry += 1;
-            acc
-        }).iter().max_by_key(|(_, &v)| v).map(|(k, _)| k.to_string()).unwrap_or("".to_string())
-    }
-
-    fn cripto_mas_volumen_venta(&self) -> String {
-        self.transacciones.iter().filter(|t| match t.tipo {
-            TipoTransaccion::VentaCripto { .. } => true,
-            _ => false
-        }).fold(HashMap::new(), |mut acc, t| {
-            let criptomoneda = match &t.tipo {
-                TipoTransaccion::VentaCripto { criptomoneda, .. } => criptomoneda,
-                _ => ""
-            };
-            let entry = acc.entry(criptomoneda).or_insert(0.0);
-            let monto = match t.tipo {
-                TipoTransaccion::VentaCripto { monto, .. } => monto,
-                _ => 0.0
-            };
-            *entry += monto;
-            acc
-        }).iter()
-        .max_by(|(_, &v1), (_, &v2)| v1.partial_cmp(&v2)
-        .unwrap_or(std::cmp::Ordering::Equal))
-        .map(|(k, _)| k.to_string())
-        .unwrap_or("".to_string())
-    }
-
-    fn cripto_mas_volumen_compras(&self) -> String {
-        self.transacciones.iter().filter(|t| match t.tipo {
-            TipoTransaccion::CompraCripto { .. } => true,
-            _ => false
-        }).fold(HashMap::new(), |mut acc, t| {
-            let criptomoneda = match &t.tipo {
-                TipoTransaccion::CompraCripto { criptomoneda, .. } => criptomoneda,
-                _ => ""
-            };
-            let entry = acc.entry(criptomoneda).or_insert(0.0);
-            let monto = match t.tipo {
-                TipoTransaccion::CompraCripto { monto, .. } => monto,
-                _ => 0.0
-            };
-            *entry += monto;
-            acc
-        }).iter()
-        .max_by(|(_, &v1), (_, &v2)| v1.partial_cmp(&v2)
-        .unwrap_or(std::cmp::Ordering::Equal))
-        .map(|(k, _)| k.to_string())
-        .unwrap_or("".to_string())
+    #[test]
+    fn test_compactacion_fallida() {
+        let almacenamiento = Rc::new(AlmacenamientoSinFotos::default());
+        let mut sistema = XYZ::new("test_compactacion_fallida", almacenamiento.clone());
+        sistema.set_limite_compactacion(1);
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", true);
+
+        // Si no se puede guardar la foto la operacion igual queda hecha y se reintenta despues
+        almacenamiento.fallar.set(true);
+        assert!(sistema.ingresar_dinero("87654321", ars("1000")).is_ok());
+        assert_eq!(sistema.balances["87654321"]["ARS"], ars("1000"));
+        assert_eq!(sistema.operaciones_sin_compactar, 1);
+        assert_eq!(almacenamiento.leer_lineas("test_compactacion_fallida_operaciones").unwrap().len(), 1);
+
+        almacenamiento.fallar.set(false);
+        assert!(sistema.ingresar_dinero("87654321", ars("500")).is_ok());
+        assert_eq!(sistema.operaciones_sin_compactar, 0);
+        assert!(almacenamiento.leer_lineas("test_compactacion_fallida_operaciones").unwrap().is_empty());
+
+        let sistema = XYZ::new("test_compactacion_fallida", almacenamiento);
+        assert_eq!(sistema.balances["87654321"]["ARS"], ars("1500"));
     }
-}
 
-impl XYZ {
-    fn new(file_name: &str) -> XYZ {
-        let balances = match std::fs::File::open("test/".to_owned() + file_name + "_balances.json") {
-            Ok(mut file) => {
-                let mut buf = String::new();
-                file.read_to_string(&mut buf).unwrap();
-                let suscripciones: HashMap<String, HashMap<String, f64>> = serde_json::from_str(&buf).unwrap();
-                suscripciones
-            },
-            Err(_) => HashMap::new()
-        };
+    #[test]
+    fn test_registrar_operacion_invalida() {
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+        let mut sistema = XYZ::new("test_registrar_operacion_invalida", almacenamiento.clone());
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", true);
+        assert!(sistema.ingresar_dinero("87654321", ars("1000")).is_ok());
+
+        // La cancelacion de una orden que no existe falla despues de aplicar el balance, no queda nada de la linea
+        let operaciones = vec![
+            Operacion::Balance { dni_usuario: "87654321".to_string(), moneda: "ARS".to_string(), monto: ars("500") },
+            Operacion::Transaccion(Transaccion { fecha: sistema.reloj.ahora(), tipo: TipoTransaccion::IngresoDinero { monto: ars("500") }, dni_usuario: "87654321".to_string() }),
+            Operacion::OrdenCancelada { id: 42 },
+        ];
+        assert!(sistema.registrar(operaciones).is_err());
+        assert_eq!(sistema.balances["87654321"]["ARS"], ars("1000"));
+        assert_eq!(sistema.transacciones.len(), 1);
+        assert_eq!(sistema.ultima_operacion, 1);
+        assert_eq!(almacenamiento.leer_lineas("test_registrar_operacion_invalida_operaciones").unwrap().len(), 1);
 
-        let transacciones = match std::fs::File::open("test/".to_owned() + file_name + "_transacciones.json") {
-            Ok(mut file) => {
-                let mut buf = String::new();
-                file.read_to_string(&mut buf).unwrap();
-                let transacciones: Vec<Transaccion> = serde_json::from_str(&buf).unwrap();
-                transacciones
-            },
-            Err(_) => Vec::new()
-        };
+        let sistema = XYZ::new("test_registrar_operacion_invalida", almacenamiento);
+        assert_eq!(sistema.balances["87654321"]["ARS"], ars("1000"));
+    }
 
-        XYZ {
-            file_name: file_name.to_string(),
-            usuarios: Vec::new(),
-            cotizaciones: HashMap::new(),
-            criptomonedas: Vec::new(),
-            transacciones,
-            balances
+    #[test]
+    fn test_compactacion_interrumpida() {
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+        let mut sistema = XYZ::new("test_compactacion_interrumpida", almacenamiento.clone());
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", true);
+        assert!(sistema.ingresar_dinero("87654321", ars("1000")).is_ok());
+        let lineas = almacenamiento.leer_lineas("test_compactacion_interrumpida_operaciones").unwrap();
+
+        // La foto se guardo pero el log no se llego a vaciar
+        sistema.compactar().unwrap();
+        for linea in lineas {
+            almacenamiento.agregar_linea("test_compactacion_interrumpida_operaciones", &linea).unwrap();
         }
-    }
 
-    fn add_cotizacion(&mut self, criptomoneda: String, cotizacion: f64) {
-        self.cotizaciones.insert(criptomoneda, cotizacion);
+        let sistema = XYZ::new("test_compactacion_interrumpida", almacenamiento);
+        assert_eq!(sistema.transacciones.len(), 1);
+        assert_eq!(sistema.balances["87654321"]["ARS"], ars("1000"));
     }
 
-    fn add_criptomoneda(&mut self, criptomoneda: CriptoMoneda) {
-        self.criptomonedas.push(criptomoneda);
+    #[test]
+    fn test_migrar_log_operaciones_v1() {
+        // Lineas guardadas sin sobre y con los montos como numeros de punto flotante
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+        let fecha = r#"{"version":1,"dia":6,"mes":3,"anio":2024,"hora":11,"minuto":30,"segundo":0,"offset_minutos":-180}"#;
+        almacenamiento.agregar_linea("test_migrar_log_operaciones", r#"{"numero":1,"operaciones":[{"Balance":{"dni_usuario":"87654321","moneda":"fiat","monto":1000.0}}]}"#).unwrap();
+        almacenamiento.agregar_linea("test_migrar_log_operaciones", &format!(
+            r#"{{"numero":2,"operaciones":[{{"Balance":{{"dni_usuario":"87654321","moneda":"fiat","monto":-100.0}}}},{{"Balance":{{"dni_usuario":"87654321","moneda":"BTC","monto":0.002}}}},{{"Transaccion":{{"fecha":{},"tipo":{{"CompraCripto":{{"monto":100.0,"criptomoneda":"BTC","cotizacion":50000.0}}}},"dni_usuario":"87654321"}}}}]}}"#,
+            fecha)).unwrap();
+
+        let mut sistema = XYZ::new("test_migrar_log", almacenamiento.clone());
+        assert_eq!(sistema.balances["87654321"]["ARS"], ars("900"));
+        assert_eq!(sistema.balances["87654321"]["BTC"], cripto("0.002", "BTC"));
+        assert!(matches!(&sistema.transacciones[0].tipo, TipoTransaccion::CompraCripto { monto, cotizacion, cantidad, .. }
+            if *monto == ars("100") && *cotizacion == ars("50000") && *cantidad == cripto("0.002", "BTC")));
+
+        // Las lineas nuevas se guardan con la version actual y conviven con las viejas
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", true);
+        assert!(sistema.ingresar_dinero("87654321", ars("0.50")).is_ok());
+        assert!(almacenamiento.leer_lineas("test_migrar_log_operaciones").unwrap()[2].starts_with(r#"{"version":5,"#));
+        let sistema = XYZ::new("test_migrar_log", almacenamiento);
+        assert_eq!(sistema.balances["87654321"]["ARS"], ars("900.50"));
     }
 
-    fn get_criptomoneda(&self, prefijo: &str) -> Option<&CriptoMoneda> {
-        self.criptomonedas.iter().find(|c| c.prefijo == prefijo)
-    }
+    #[test]
+    fn test_unidad_trabajo() {
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+        let mut sistema = XYZ::new("test_unidad_trabajo", almacenamiento.clone());
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", true);
+        assert!(sistema.add_balance("87654321", "ARS", ars("100")).is_ok());
+
+        let mut unidad = sistema.iniciar_unidad();
+        unidad.remove_balance("87654321", "ARS", ars("100"));
+        unidad.add_balance("87654321", "BTC", cripto("1", "BTC"));
+        unidad.crear_transaccion(TipoTransaccion::CompraCripto { monto: ars("100"), criptomoneda: "BTC".to_string(), cotizacion: ars("100"), cantidad: cripto("1", "BTC"), comision: ars("0") }, "87654321");
+        // Hasta que se confirma no cambia nada
+        assert_eq!(sistema.get_balance("87654321", "BTC"), cripto("0", "BTC"));
+        assert!(sistema.confirmar(unidad).is_ok());
+        assert_eq!(sistema.get_balance("87654321", "ARS"), ars("0"));
+        assert_eq!(sistema.get_balance("87654321", "BTC"), cripto("1", "BTC"));
+        assert_eq!(sistema.transacciones.len(), 1);
 
-    fn get_cotizacion(&self, criptomoneda: &str) -> f64 {
-        *self.cotizaciones.get(criptomoneda).unwrap_or(&0.0)
+        let sistema = XYZ::new("test_unidad_trabajo", almacenamiento);
+        assert_eq!(sistema.balances["87654321"]["BTC"], cripto("1", "BTC"));
+        assert_eq!(sistema.transacciones.len(), 1);
     }
-}
 
-impl Usuario {
-    fn new(nombre: String, apellido: String, email: String, dni: String, identidad: bool) -> Usuario {
-        Usuario {
-            nombre,
-            apellido,
-            email,
-            dni,
-            identidad
-        }
+    #[test]
+    fn test_unidad_trabajo_rechazada() {
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+        let mut sistema = XYZ::new("test_unidad_trabajo_rechazada", almacenamiento.clone());
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", true);
+        assert!(sistema.add_balance("87654321", "ARS", ars("100")).is_ok());
+
+        // El debito dejaria el balance en negativo, no se aplica ni la acreditacion
+        let mut unidad = sistema.iniciar_unidad();
+        unidad.add_balance("87654321", "BTC", cripto("1", "BTC"));
+        unidad.remove_balance("87654321", "ARS", ars("150"));
+        unidad.crear_transaccion(TipoTransaccion::CompraCripto { monto: ars("150"), criptomoneda: "BTC".to_string(), cotizacion: ars("150"), cantidad: cripto("1", "BTC"), comision: ars("0") }, "87654321");
+        assert!(sistema.confirmar(unidad).is_err());
+
+        // Un usuario inexistente invalida toda la unidad
+        let mut unidad = sistema.iniciar_unidad();
+        unidad.add_balance("87654321", "ARS", ars("10"));
+        unidad.add_balance("11111111", "ARS", ars("10"));
+        assert_eq!(sistema.confirmar(unidad).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+
+        assert_eq!(sistema.get_balance("87654321", "ARS"), ars("100"));
+        assert_eq!(sistema.get_balance("87654321", "BTC"), cripto("0", "BTC"));
+        assert_eq!(sistema.transacciones.len(), 0);
+        assert_eq!(almacenamiento.leer_lineas("test_unidad_trabajo_rechazada_operaciones").unwrap().len(), 1);
     }
 
-    fn get_dni(&self) -> &str {
-        &self.dni
-    }
+    #[test]
+    fn test_unidad_trabajo_falla_escritura() {
+        let directorio = std::env::temp_dir().join(format!("rust-unlp-unidad-trabajo-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directorio);
+        let archivos = AlmacenamientoArchivos::new(&directorio);
+        let mut sistema = XYZ::new("xyz", Rc::new(archivos.clone()));
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", true);
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
+        assert!(sistema.ingresar_dinero("87654321", ars("1000")).is_ok());
+
+        // Un directorio en lugar del log hace que falle la escritura de la compra
+        std::fs::remove_file(archivos.ruta_lineas("xyz_operaciones")).unwrap();
+        std::fs::create_dir(archivos.ruta_lineas("xyz_operaciones")).unwrap();
+        assert!(sistema.comprar_cripto("87654321", ars("500"), "BTC").is_err());
+        assert_eq!(sistema.get_balance("87654321", "ARS"), ars("1000"));
+        assert_eq!(sistema.get_balance("87654321", "BTC"), cripto("0", "BTC"));
+        assert_eq!(sistema.transacciones.len(), 1);
 
-    fn set_identidad(&mut self, identidad: bool) {
-        self.identidad = identidad;
+        std::fs::remove_dir_all(&directorio).unwrap();
     }
 
-    fn get_identidad(&self) -> bool {
-        self.identidad
+    #[test]
+    fn test_reporte_impositivo() {
+        let reloj = Rc::new(RelojManual::new(FechaHora::new(Fecha::new(10, 12, 2023), 10, 0, 0)));
+        let mut sistema = XYZ::new_con_reloj("test_reporte_impositivo", Rc::new(AlmacenamientoMemoria::new()), reloj.clone());
+        // Que la verificacion no venza en los dos años que dura la prueba
+        sistema.set_validez_kyc(3 * 365);
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", true);
+        sistema.add_criptomoneda(CriptoMoneda {
+            nombre: "Bitcoin".to_string(),
+            prefijo: "BTC".to_string(),
+            blockchains: vec![BlockChain { nombre: "Bitcoin".to_string(), prefijo: "BTC".to_string() }]
+        });
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
+        assert!(sistema.ingresar_dinero("87654321", ars("20000")).is_ok());
+        assert!(sistema.comprar_cripto("87654321", ars("5000"), "BTC").is_ok());
+
+        reloj.avanzar_segundos(36 * 24 * 3600);
+        sistema.add_cotizacion("BTC".to_string(), ars("60000"));
+        sistema.set_comisiones(Comisiones::new().con_comision_red("Bitcoin", cripto("0.001", "BTC")));
+        assert!(sistema.vender_cripto("87654321", cripto("0.05", "BTC"), "BTC").is_ok());
+        assert!(sistema.recibir_cripto("87654321", cripto("0.02", "BTC"), "BTC", "Bitcoin").is_ok());
+        assert!(sistema.retirar_cripto("87654321", cripto("0.01", "BTC"), "BTC", "Bitcoin").is_ok());
+        assert!(sistema.retirar_dinero("87654321", ars("1000"), MedioRetiro::MercadoPago).is_ok());
+        assert!(sistema.comprar_cripto("87654321", ars("600"), "BTC").is_ok());
+
+        // Lo del año siguiente no entra, ni siquiera la cotizacion para valuar las tenencias
+        reloj.avanzar_segundos(360 * 24 * 3600);
+        sistema.add_cotizacion("BTC".to_string(), ars("70000"));
+        assert!(sistema.vender_cripto("87654321", cripto("0.01", "BTC"), "BTC").is_ok());
+
+        let reporte = sistema.reporte_impositivo("87654321", 2023, &Moneda::ars()).unwrap();
+        assert_eq!(reporte.movimientos.len(), 2);
+        assert_eq!(reporte.totales.depositos, ars("20000"));
+        assert_eq!(reporte.tenencias, vec![TenenciaFinDeAnio { cantidad: cripto("0.1", "BTC"), costo: ars("5000"), valor: ars("5000") }]);
+
+        let reporte = sistema.reporte_impositivo("87654321", 2024, &Moneda::ars()).unwrap();
+        let tipos: Vec<TipoMovimiento> = reporte.movimientos.iter().map(|m| m.tipo).collect();
+        assert_eq!(tipos, vec![TipoMovimiento::Venta, TipoMovimiento::Deposito, TipoMovimiento::Retiro, TipoMovimiento::Retiro, TipoMovimiento::Compra]);
+        // El costo de lo vendido es el de la compra del año anterior
+        assert_eq!(reporte.movimientos[0].ganancia, Some(ars("500")));
+        assert_eq!(reporte.movimientos[2].comision, ars("60"));
+        assert_eq!(reporte.totales, TotalesImpositivos {
+            compras: ars("600"),
+            ventas: ars("3000"),
+            depositos: ars("1200"),
+            retiros: ars("1600"),
+            comisiones: ars("60"),
+            ganancia_realizada: ars("500"),
+        });
+        assert_eq!(reporte.tenencias, vec![TenenciaFinDeAnio { cantidad: cripto("0.069", "BTC"), costo: ars("3750"), valor: ars("4140") }]);
+
+        let csv = reporte.a_csv().unwrap();
+        let lineas: Vec<&str> = csv.lines().collect();
+        assert_eq!(lineas.len(), 13);
+        assert_eq!(lineas[0], "fecha,tipo,activo,cantidad,moneda,equivalente,comision,costo,ganancia");
+        assert!(lineas[1].ends_with(",venta,BTC,0.05000000,ARS,3000.00,0.00,,500.00"), "{}", lineas[1]);
+        assert_eq!(lineas[11], "31/12/2024,ganancia_realizada,ARS,,ARS,500.00,,,");
+        assert_eq!(lineas[12], "31/12/2024,tenencia,BTC,0.06900000,ARS,4140.00,,3750.00,");
+
+        let json: Value = serde_json::from_str(&reporte.a_json().unwrap()).unwrap();
+        assert_eq!(json["anio"], 2024);
+        assert_eq!(json["movimientos"][0]["ganancia"], "500.00");
+        assert_eq!(json["movimientos"][1]["ganancia"], Value::Null);
+        assert_eq!(json["totales"]["retiros"], "1600.00");
+        assert_eq!(json["tenencias"][0]["valor"], "4140.00");
+
+        let ruta = std::env::temp_dir().join(format!("rust-unlp-reporte-impositivo-{}.csv", std::process::id()));
+        assert!(reporte.exportar(&ruta, FormatoExportacion::Csv).is_ok());
+        assert_eq!(std::fs::read_to_string(&ruta).unwrap(), csv);
+        std::fs::remove_file(&ruta).unwrap();
+
+        // Sin movimientos igual informa las tenencias, y sin cotizacion a la fecha de cierre no se puede valuar
+        let reporte = sistema.reporte_impositivo("87654321", 2022, &Moneda::ars()).unwrap();
+        assert!(reporte.movimientos.is_empty() && reporte.tenencias.is_empty());
+        assert!(sistema.reporte_impositivo("87654321", 2024, &Moneda::usd()).is_err());
     }
-}
 
-impl BlockChain {
-    fn withdraw(&self) -> String {
-        self.nombre.clone() + thread_rng().gen_range(0..100).to_string().as_str()
+    #[test]
+    fn test_reporte_impositivo_anios_anteriores() {
+        let reloj = Rc::new(RelojManual::new(FechaHora::new(Fecha::new(10, 12, 2023), 10, 0, 0)));
+        let mut sistema = XYZ::new_con_reloj("test_reporte_impositivo_anios_anteriores", Rc::new(AlmacenamientoMemoria::new()), reloj.clone());
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", true);
+        // Los movimientos en dolares del año anterior no tienen cotizacion, pero no hacen falta para el costo
+        assert!(sistema.crear_transaccion(TipoTransaccion::IngresoDinero { monto: usd("100") }, "87654321").is_ok());
+        assert!(sistema.crear_transaccion(TipoTransaccion::CompraCripto { monto: ars("500"), criptomoneda: "BTC".to_string(), cotizacion: ars("50000"), cantidad: cripto("0.01", "BTC"), comision: usd("1") }, "87654321").is_ok());
+        assert!(sistema.crear_transaccion(TipoTransaccion::RetiroFiat { monto: usd("50"), medio: MedioRetiro::MercadoPago }, "87654321").is_ok());
+        assert!(sistema.reporte_impositivo("87654321", 2023, &Moneda::ars()).is_err());
+
+        reloj.avanzar_segundos(30 * 24 * 3600);
+        sistema.add_cotizacion("BTC".to_string(), ars("60000"));
+        assert!(sistema.crear_transaccion(TipoTransaccion::VentaCripto { monto: cripto("0.005", "BTC"), criptomoneda: "BTC".to_string(), cotizacion: ars("60000"), comision: ars("0") }, "87654321").is_ok());
+
+        let reporte = sistema.reporte_impositivo("87654321", 2024, &Moneda::ars()).unwrap();
+        assert_eq!(reporte.movimientos.len(), 1);
+        assert_eq!(reporte.totales.ganancia_realizada, ars("50"));
+        assert_eq!(reporte.tenencias, vec![TenenciaFinDeAnio { cantidad: cripto("0.005", "BTC"), costo: ars("250"), valor: ars("300") }]);
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
     #[test]
-    fn test_blockchain_withdraw() {
-        let blockchain = BlockChain { nombre: "Bitcoin".to_string(), prefijo: "BTC".to_string() };
-        let hash = blockchain.withdraw();
-
-        assert!(hash.starts_with("Bitcoin"));
+    fn test_conciliacion() {
+        let mut sistema = sistema_con_usuarios("test_conciliacion", Rc::new(AlmacenamientoMemoria::new()));
+        sistema.add_criptomoneda(CriptoMoneda {
+            nombre: "Bitcoin".to_string(),
+            prefijo: "BTC".to_string(),
+            blockchains: vec![BlockChain { nombre: "Bitcoin".to_string(), prefijo: "BTC".to_string() }]
+        });
+        sistema.set_comisiones(Comisiones::new().con_operacion(Porcentaje::new(1)).con_spread(Porcentaje::new(2)).con_comision_red("Bitcoin", cripto("0.0001", "BTC")));
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
+        assert!(sistema.conciliar().unwrap().es_consistente());
+
+        assert!(sistema.ingresar_dinero("11111111", ars("10000")).is_ok());
+        assert!(sistema.ingresar_dinero("33333333", ars("10000")).is_ok());
+        assert!(sistema.comprar_cripto("11111111", ars("5000"), "BTC").is_ok());
+        assert!(sistema.vender_cripto("11111111", cripto("0.01", "BTC"), "BTC").is_ok());
+        assert!(sistema.retirar_cripto("11111111", cripto("0.01", "BTC"), "BTC", "Bitcoin").is_ok());
+        assert!(sistema.recibir_cripto("22222222", cripto("0.05", "BTC"), "BTC", "Bitcoin").is_ok());
+        sistema.colocar_orden_limite("22222222", Lado::Venta, cripto("0.03", "BTC"), ars("40000")).unwrap();
+        sistema.colocar_orden_mercado("33333333", Lado::Compra, cripto("0.01", "BTC"), &Moneda::ars()).unwrap();
+        sistema.colocar_orden_limite("11111111", Lado::Compra, cripto("0.003", "BTC"), ars("30000")).unwrap();
+        assert!(sistema.retirar_dinero("22222222", ars("100"), MedioRetiro::MercadoPago).is_ok());
+        let reporte = sistema.conciliar().unwrap();
+        assert!(reporte.es_consistente(), "{}", reporte);
+
+        // Un balance que cambia sin transaccion no se puede explicar con el log
+        assert!(sistema.add_balance("33333333", "BTC", cripto("0.5", "BTC")).is_ok());
+        let reporte = sistema.conciliar().unwrap();
+        assert_eq!(reporte.discrepancias, vec![Discrepancia {
+            dni_usuario: "33333333".to_string(),
+            moneda: "BTC".to_string(),
+            esperado: cripto("0.01", "BTC"),
+            guardado: cripto("0.51", "BTC"),
+        }]);
+        assert_eq!(reporte.violaciones, vec![Violacion::CriptoNoConservada {
+            criptomoneda: "BTC".to_string(),
+            esperado: cripto("0.10705882", "BTC"),
+            total: cripto("0.60705882", "BTC"),
+        }]);
+        assert!(reporte.to_string().contains("33333333 BTC: guardado 0.51"), "{}", reporte);
+
+        // Un balance negativo guardado y una transaccion sin saldo
+        assert!(sistema.registrar(vec![Operacion::Balance { dni_usuario: "22222222".to_string(), moneda: "ARS".to_string(), monto: ars("-100000") }]).is_ok());
+        assert!(sistema.crear_transaccion(TipoTransaccion::RetiroFiat { monto: ars("20000"), medio: MedioRetiro::MercadoPago }, "11111111").is_ok());
+        let reporte = sistema.conciliar().unwrap();
+        assert_eq!(reporte.discrepancias.len(), 3);
+        assert!(reporte.violaciones.contains(&Violacion::SaldoNegativo { dni_usuario: "22222222".to_string(), moneda: "ARS".to_string(), balance: sistema.get_balance("22222222", "ARS") }));
+        assert!(reporte.violaciones.iter().any(|v| matches!(v, Violacion::SaldoNegativoEnLog { transaccion, dni_usuario, .. }
+            if *transaccion == sistema.transacciones.len() - 1 && dni_usuario == "11111111")));
     }
 
     #[test]
-    fn test_new_xyz_con_datos() {
-        let mut sistema = XYZ::new("test_new_XYZ_con_datos");
+    fn test_conciliar_archivos() {
+        let directorio = std::env::temp_dir().join(format!("rust-unlp-conciliacion-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directorio);
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoArchivos::new(&directorio));
+        let mut sistema = sistema_con_usuarios("xyz", almacenamiento.clone());
+        sistema.set_limite_compactacion(3);
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
+        assert!(sistema.ingresar_dinero("11111111", ars("10000")).is_ok());
+        assert!(sistema.comprar_cripto("11111111", ars("5000"), "BTC").is_ok());
+        sistema.colocar_orden_limite("11111111", Lado::Venta, cripto("0.05", "BTC"), ars("60000")).unwrap();
+        assert!(sistema.ingresar_dinero("22222222", ars("500")).is_ok());
+        assert!(XYZ::conciliar_archivos("xyz", almacenamiento.clone(), &PoliticaCarga::estricta()).unwrap().es_consistente());
+
+        assert!(sistema.remove_balance("22222222", "ARS", ars("200")).is_ok());
+        let reporte = XYZ::conciliar_archivos("xyz", almacenamiento.clone(), &PoliticaCarga::estricta()).unwrap();
+        assert_eq!(reporte.discrepancias.len(), 1);
+        assert_eq!(reporte.discrepancias[0].esperado, ars("500"));
+        assert!(XYZ::conciliar_archivos("otro", almacenamiento, &PoliticaCarga::estricta()).is_err());
+
+        std::fs::remove_dir_all(&directorio).unwrap();
+    }
 
-        assert_eq!(sistema.balances.len(), 0);
-        assert_eq!(sistema.transacciones.len(), 0);
+    #[test]
+    fn test_crear_transaccion_con_reloj() {
+        let reloj = Rc::new(RelojManual::new(FechaHora::new(Fecha::new(1, 3, 2024), 10, 0, 0)));
+        let mut sistema = XYZ::new_con_reloj("test_crear_transaccion_con_reloj", Rc::new(AlmacenamientoMemoria::new()), reloj.clone());
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "12345678", true);
 
-        sistema.crear_usuario( "Juan", "Garcia", "juan@example.com", "87654321", true);
-        assert!(sistema.add_balance("87654321", "fiat", 50000.0).is_ok());
-        assert!(sistema.ingresar_dinero("87654321", 100.0).is_ok());
-        assert_eq!(sistema.balances.len(), 1);
-        assert_eq!(sistema.transacciones.len(), 1);
+        assert!(sistema.ingresar_dinero("12345678", ars("100")).is_ok());
+        reloj.avanzar_segundos(30);
+        assert!(sistema.ingresar_dinero("12345678", ars("100")).is_ok());
 
-        let sistema = XYZ::new("test_new_XYZ_con_datos");
-        assert_eq!(sistema.balances.len(), 1);
-        assert_eq!(sistema.transacciones.len(), 1);
+        assert_eq!(sistema.transacciones[0].fecha, FechaHora::new(Fecha::new(1, 3, 2024), 10, 0, 0));
+        assert_eq!(sistema.transacciones[0].fecha.segundos_entre(&sistema.transacciones[1].fecha), 30);
     }
 
     #[test]
-    fn test_usuario_set_identidad() {
-        let mut usuario = Usuario::new("Jose".to_string(), "Maria".to_string(), "josemaria@test.com".to_string(), "12345678".to_string(), false); 
-
-        assert!(!usuario.get_identidad());
-        usuario.set_identidad(true);
-        assert!(usuario.get_identidad());
+    fn test_usuario_kyc() {
+        let mut usuario = Usuario::new("Jose".to_string(), "Maria".to_string(), "josemaria@test.com".to_string(), "12345678".to_string());
+        let fecha = FechaHora::new(Fecha::new(1, 3, 2024), 10, 0, 0);
+        let vence = FechaHora::new(Fecha::new(1, 3, 2025), 10, 0, 0);
+
+        assert_eq!(usuario.estado_kyc(&fecha), EstadoKyc::Pendiente);
+        assert_eq!(usuario.nivel(&fecha), NivelIdentidad::SinVerificar);
+        usuario.registrar_kyc(EstadoKyc::Aprobado { nivel: NivelIdentidad::Completo, vence: vence.clone() }, fecha.clone(), "Prueba");
+        assert_eq!(usuario.nivel(&fecha), NivelIdentidad::Completo);
+        assert_eq!(usuario.estado_kyc(&vence), EstadoKyc::Vencido { nivel: NivelIdentidad::Completo });
+        assert_eq!(usuario.nivel(&vence), NivelIdentidad::SinVerificar);
+        assert_eq!(usuario.historial_kyc.len(), 1);
+        assert_eq!(usuario.historial_kyc[0].desde, EstadoKyc::Pendiente);
     }
 
     #[test]
     fn test_add_criptomoneda() {
-        let mut sistema = XYZ::new("test_add_criptomoneda");
+        let mut sistema = XYZ::new("test_add_criptomoneda", Rc::new(AlmacenamientoMemoria::new()));
         let criptomoneda = CriptoMoneda {
             nombre: "Bitcoin".to_string(),
             prefijo: "BTC".to_string(),
@@ -448,7 +3285,7 @@ mod tests {
 
     #[test]
     fn test_get_criptomoneda() {
-        let mut sistema = XYZ::new("test_get_criptomoneda");
+        let mut sistema = XYZ::new("test_get_criptomoneda", Rc::new(AlmacenamientoMemoria::new()));
         let criptomoneda = CriptoMoneda {
             nombre: "Bitcoin".to_string(),
             prefijo: "BTC".to_string(),
@@ -465,174 +3302,634 @@ mod tests {
 
     #[test]
     fn test_ingresar_dinero() {
-        let mut sistema = XYZ::new("test_ingresar_dinero");
+        let mut sistema = XYZ::new("test_ingresar_dinero", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "12345678", true);
 
-        assert!(sistema.ingresar_dinero("12345678", 100.0).is_ok());
+        assert!(sistema.ingresar_dinero("12345678", ars("100")).is_ok());
 
-        assert_eq!(sistema.get_balance("12345678", "fiat"), 100.0);
+        assert_eq!(sistema.get_balance("12345678", "ARS"), ars("100"));
         assert_eq!(sistema.transacciones.len(), 1);
     }
 
+    #[test]
+    fn test_ingresar_moneda_no_soportada() {
+        let eur = Moneda::new("EUR", 2);
+        let mut sistema = XYZ::new("test_ingresar_moneda_no_soportada", Rc::new(AlmacenamientoMemoria::new()));
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "12345678", true);
+
+        let error = sistema.ingresar_dinero("12345678", Dinero::new(100, eur.clone())).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(sistema.ingresar_dinero("12345678", Dinero::new(100, Moneda::cripto("BTC"))).is_err());
+
+        sistema.add_moneda_fiat(eur.clone());
+        assert!(sistema.ingresar_dinero("12345678", Dinero::new(100, eur.clone())).is_ok());
+        assert!(sistema.ingresar_dinero("12345678", usd("10")).is_ok());
+        assert_eq!(sistema.get_balance("12345678", "EUR"), Dinero::new(100, eur));
+        assert_eq!(sistema.get_balance("12345678", "USD"), usd("10"));
+        assert_eq!(sistema.get_balance("12345678", "ARS"), ars("0"));
+    }
+
+    #[test]
+    fn test_balance_con_otra_moneda() {
+        let mut sistema = XYZ::new("test_balance_con_otra_moneda", Rc::new(AlmacenamientoMemoria::new()));
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "12345678", true);
+
+        let error = sistema.add_balance("12345678", "USD", ars("10")).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(sistema.get_balance("12345678", "USD"), usd("0"));
+    }
+
     #[test]
     fn test_ingresar_dinero_sin_identidad() {
-        let mut sistema = XYZ::new("test_ingresar_dinero_sin_identidad");
+        let mut sistema = XYZ::new("test_ingresar_dinero_sin_identidad", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "12345678", false);
 
-        assert!(sistema.ingresar_dinero("12345678", 100.0).is_err());
+        assert!(sistema.ingresar_dinero("12345678", ars("100")).is_err());
         assert_eq!(sistema.transacciones.len(), 0);
     }
 
     #[test]
     fn test_comprar_cripto() {
-        let mut sistema = XYZ::new("test_comprar_cripto");
+        let mut sistema = XYZ::new("test_comprar_cripto", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario( "Juan", "Garcia", "juan@example.com", "87654321", true);
 
-        assert!(sistema.add_balance("87654321", "fiat", 50000.0).is_ok());
-        sistema.add_cotizacion("BTC".to_string(), 50000.0);
+        assert!(sistema.add_balance("87654321", "ARS", ars("50000")).is_ok());
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
+
+        assert!(sistema.comprar_cripto("87654321", ars("50000"), "BTC").is_ok());
+
+        assert_eq!(sistema.get_balance("87654321", "ARS"), ars("0"));
+        assert_eq!(sistema.get_balance("87654321", "BTC"), cripto("1", "BTC"));
+        assert_eq!(sistema.transacciones.len(), 1);
+    }
+
+    #[test]
+    fn test_cotizacion_cruzada() {
+        let mut sistema = XYZ::new("test_cotizacion_cruzada", Rc::new(AlmacenamientoMemoria::new()));
+        sistema.add_cotizacion("BTC".to_string(), usd("50000"));
+        sistema.add_cotizacion("USD".to_string(), ars("1000"));
+
+        assert_eq!(sistema.get_cotizacion("BTC", &Moneda::usd()).unwrap(), usd("50000"));
+        assert_eq!(sistema.get_cotizacion("BTC", &Moneda::ars()).unwrap(), ars("50000000"));
+        // El par USD/ARS tambien se usa al reves
+        assert_eq!(sistema.get_cotizacion("ARS", &Moneda::usd()).unwrap(), usd("0"));
+        assert_eq!(sistema.convertir(&ars("2500"), &Moneda::usd(), Redondeo::Truncar).unwrap(), usd("2.5"));
+        assert_eq!(sistema.convertir(&ars("25000"), &Moneda::cripto("BTC"), Redondeo::Truncar).unwrap(), cripto("0.0005", "BTC"));
+        assert_eq!(sistema.convertir(&ars("10"), &Moneda::ars(), Redondeo::Truncar).unwrap(), ars("10"));
+
+        let error = ErrorCotizacion::SinCotizacion { base: "ETH".to_string(), cotizada: "ARS".to_string() };
+        assert_eq!(sistema.get_cotizacion("ETH", &Moneda::ars()).unwrap_err(), error);
+
+        // Una cotizacion nueva del mismo par reemplaza a la anterior
+        sistema.add_cotizacion("USD".to_string(), ars("1200"));
+        assert_eq!(sistema.get_cotizacion("BTC", &Moneda::ars()).unwrap(), ars("60000000"));
+    }
+
+    #[test]
+    fn test_cotizacion_en_fecha() {
+        let inicio = FechaHora::new(Fecha::new(5, 3, 2024), 10, 0, 0);
+        let reloj = Rc::new(RelojManual::new(inicio.clone()));
+        let mut sistema = XYZ::new_con_reloj("test_cotizacion_en_fecha", Rc::new(AlmacenamientoMemoria::new()), reloj.clone());
+        sistema.add_cotizacion("BTC".to_string(), usd("50000"));
+        sistema.add_cotizacion("USD".to_string(), ars("1000"));
+        reloj.avanzar_segundos(3600);
+        sistema.add_cotizacion("BTC".to_string(), usd("60000"));
+
+        let media_hora = FechaHora::new(Fecha::new(5, 3, 2024), 10, 30, 0);
+        let una_hora = FechaHora::new(Fecha::new(5, 3, 2024), 11, 0, 0);
+        assert_eq!(sistema.cotizacion_en("BTC", &Moneda::usd(), &inicio).unwrap(), usd("50000"));
+        assert_eq!(sistema.cotizacion_en("BTC", &Moneda::usd(), &media_hora).unwrap(), usd("50000"));
+        assert_eq!(sistema.cotizacion_en("BTC", &Moneda::usd(), &una_hora).unwrap(), usd("60000"));
+        assert_eq!(sistema.cotizacion_en("BTC", &Moneda::ars(), &media_hora).unwrap(), ars("50000000"));
+        assert_eq!(sistema.get_cotizacion("BTC", &Moneda::usd()).unwrap(), usd("60000"));
+        assert_eq!(sistema.cotizaciones.serie("BTC", "USD").len(), 2);
+
+        // Antes del primer precio no hay cotizacion
+        let antes = FechaHora::new(Fecha::new(5, 3, 2024), 9, 0, 0);
+        assert!(matches!(sistema.cotizacion_en("BTC", &Moneda::usd(), &antes), Err(ErrorCotizacion::SinCotizacion { .. })));
+    }
+
+    #[test]
+    fn test_operar_sin_cotizacion() {
+        let mut sistema = XYZ::new("test_operar_sin_cotizacion", Rc::new(AlmacenamientoMemoria::new()));
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", true);
+        assert!(sistema.ingresar_dinero("87654321", ars("1000")).is_ok());
+        assert!(sistema.add_balance("87654321", "DOGE", cripto("5", "DOGE")).is_ok());
+
+        assert_eq!(sistema.comprar_cripto("87654321", ars("100"), "DOGE").unwrap_err().kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(sistema.vender_cripto("87654321", cripto("1", "DOGE"), "DOGE").unwrap_err().kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(sistema.recibir_cripto("87654321", cripto("1", "DOGE"), "DOGE", "Dogecoin").unwrap_err().kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(sistema.get_balance("87654321", "ARS"), ars("1000"));
+        assert_eq!(sistema.get_balance("87654321", "DOGE"), cripto("5", "DOGE"));
+
+        // Un precio en cero no se puede usar para comprar
+        sistema.add_cotizacion("DOGE".to_string(), ars("0"));
+        let error = sistema.comprar_cripto("87654321", ars("100"), "DOGE").unwrap_err();
+        assert!(error.to_string().contains("cero"), "{}", error);
+        assert_eq!(sistema.transacciones.len(), 1);
+    }
+
+    #[test]
+    fn test_simulador_cotizaciones() {
+        let inicio = FechaHora::new(Fecha::new(5, 3, 2024), 10, 0, 0);
+        let simulador = || SimuladorCotizaciones::new(42, inicio.clone(), 60, Porcentaje::new(1))
+            .agregar_par("BTC", usd("50000"))
+            .agregar_par("USD", ars("1000"));
+        let mut a = simulador();
+        let mut b = simulador();
+
+        let mut hasta = inicio.clone();
+        hasta.sumar_minutos(10);
+        let serie = a.cotizaciones_hasta(&hasta).unwrap();
+        // Once pasos de un minuto, de las 10:00 a las 10:10 inclusive, para cada par
+        assert_eq!(serie.len(), 22);
+        assert_eq!(serie, b.cotizaciones_hasta(&hasta).unwrap());
+        assert_eq!(serie[0], Cotizacion { base: "BTC".to_string(), precio: usd("50000"), fecha: inicio.clone() });
+
+        let precios_btc: Vec<&Dinero> = serie.iter().filter(|c| c.base == "BTC").map(|c| &c.precio).collect();
+        for par in precios_btc.windows(2) {
+            let maximo = par[0].multiplicar_porcentaje(Porcentaje::new(1), Redondeo::Techo).unwrap();
+            assert!(par[1].restar(par[0]).unwrap().unidades().abs() <= maximo.unidades());
+        }
+        assert_ne!(precios_btc.first(), precios_btc.last());
+
+        // Cada cotizacion se entrega una sola vez
+        assert!(a.cotizaciones_hasta(&hasta).unwrap().is_empty());
+        hasta.sumar_minutos(1);
+        assert_eq!(a.cotizaciones_hasta(&hasta).unwrap().len(), 2);
+
+        let otra_semilla = SimuladorCotizaciones::new(7, inicio.clone(), 60, Porcentaje::new(1))
+            .agregar_par("BTC", usd("50000"))
+            .agregar_par("USD", ars("1000"))
+            .cotizaciones_hasta(&hasta).unwrap();
+        assert_ne!(otra_semilla[2..], serie[2..]);
+
+        let negativa = SimuladorCotizaciones::new(42, inicio.clone(), 60, Porcentaje::new(-1))
+            .agregar_par("BTC", usd("50000"))
+            .agregar_par("USD", ars("1000"))
+            .cotizaciones_hasta(&hasta).unwrap();
+        assert_eq!(negativa[..22], serie[..]);
+    }
+
+    #[test]
+    fn test_actualizar_cotizaciones() {
+        let reloj = Rc::new(RelojManual::new(FechaHora::new(Fecha::new(5, 3, 2024), 13, 0, 0)));
+        let mut sistema = XYZ::new_con_reloj("test_actualizar_cotizaciones", Rc::new(AlmacenamientoMemoria::new()), reloj.clone());
+        assert!(matches!(sistema.actualizar_cotizaciones(), Err(ErrorCotizacion::Fuente(_))));
+
+        let csv = "fecha,base,cotizada,precio\n\
+            2024-03-05T11:00:00-03:00,BTC,USD,51000.50\n\
+            2024-03-05T10:00:00-03:00,BTC,USD,50000\n\
+            2024-03-05T10:00:00-03:00,USD,ARS,1000\n";
+        let fuente = ReproduccionCsv::desde_texto(csv, &[Moneda::ars(), Moneda::usd()]).unwrap();
+        sistema.set_fuente_cotizaciones(Box::new(fuente));
+
+        // Las 13:00 UTC son las 10:00 en Argentina, todavia no llego la de las 11:00
+        assert_eq!(sistema.actualizar_cotizaciones().unwrap(), 2);
+        assert_eq!(sistema.get_cotizacion("BTC", &Moneda::ars()).unwrap(), ars("50000000"));
+        reloj.avanzar_segundos(3600);
+        assert_eq!(sistema.actualizar_cotizaciones().unwrap(), 1);
+        assert_eq!(sistema.actualizar_cotizaciones().unwrap(), 0);
+        assert_eq!(sistema.get_cotizacion("BTC", &Moneda::usd()).unwrap(), usd("51000.5"));
+
+        sistema.set_fuente_cotizaciones(Box::new(SimuladorCotizaciones::new(1, reloj.ahora(), 60, Porcentaje::new(2)).agregar_par("ETH", usd("2500"))));
+        assert_eq!(sistema.actualizar_cotizaciones().unwrap(), 1);
+        assert_eq!(sistema.get_cotizacion("ETH", &Moneda::usd()).unwrap(), usd("2500"));
+    }
+
+    #[test]
+    fn test_reproduccion_csv_invalida() {
+        let monedas = [Moneda::ars(), Moneda::usd()];
+        let invalidas = [
+            "fecha,base,cotizada,precio\n5/3/2024,BTC,USD,50000\n",
+            "fecha,base,cotizada,precio\n2024-03-05T10:00:00Z,BTC,USD,50000.001\n",
+            "fecha,base,cotizada,precio\n2024-03-05T10:00:00Z,BTC,USD\n",
+        ];
+        for csv in invalidas {
+            assert!(matches!(ReproduccionCsv::desde_texto(csv, &monedas), Err(ErrorCotizacion::Fuente(_))), "{}", csv);
+        }
+
+        let ruta = std::env::temp_dir().join(format!("rust-unlp-cotizaciones-{}.csv", std::process::id()));
+        assert!(ReproduccionCsv::abrir(&ruta, &monedas).is_err());
+        std::fs::write(&ruta, "fecha,base,cotizada,precio\n2024-03-05T10:00:00Z,BTC,ETH,20.5\n").unwrap();
+        let mut fuente = ReproduccionCsv::abrir(&ruta, &monedas).unwrap();
+        let cotizaciones = fuente.cotizaciones_hasta(&FechaHora::new(Fecha::new(5, 3, 2024), 10, 0, 0)).unwrap();
+        assert_eq!(cotizaciones[0].precio, cripto("20.5", "ETH"));
+        std::fs::remove_file(&ruta).unwrap();
+    }
+
+    #[test]
+    fn test_comprar_cripto_en_dolares() {
+        let mut sistema = XYZ::new("test_comprar_cripto_en_dolares", Rc::new(AlmacenamientoMemoria::new()));
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", true);
+        sistema.add_cotizacion("BTC".to_string(), usd("50000"));
+        sistema.add_cotizacion("USD".to_string(), ars("1000"));
+        assert!(sistema.ingresar_dinero("87654321", usd("100")).is_ok());
+        assert!(sistema.ingresar_dinero("87654321", ars("50000")).is_ok());
+
+        assert!(sistema.comprar_cripto("87654321", usd("50"), "BTC").is_ok());
+        assert_eq!(sistema.get_balance("87654321", "USD"), usd("50"));
+        assert_eq!(sistema.get_balance("87654321", "BTC"), cripto("0.001", "BTC"));
+        assert!(matches!(&sistema.transacciones[2].tipo, TipoTransaccion::CompraCripto { cotizacion, .. } if *cotizacion == usd("50000")));
+
+        // En pesos se usa la cotizacion cruzada BTC/USD * USD/ARS
+        assert!(sistema.comprar_cripto("87654321", ars("50000"), "BTC").is_ok());
+        assert_eq!(sistema.get_balance("87654321", "ARS"), ars("0"));
+        assert_eq!(sistema.get_balance("87654321", "BTC"), cripto("0.002", "BTC"));
+
+        // No alcanza el balance en dolares aunque sobre en otra moneda
+        assert!(sistema.comprar_cripto("87654321", usd("60"), "BTC").is_err());
+        assert!(sistema.comprar_cripto("87654321", usd("10"), "ETH").is_err());
+        assert_eq!(sistema.get_balance("87654321", "USD"), usd("50"));
+    }
+
+    #[test]
+    fn test_vender_cripto_en_dolares() {
+        let mut sistema = XYZ::new("test_vender_cripto_en_dolares", Rc::new(AlmacenamientoMemoria::new()));
+        sistema.crear_usuario("Alice", "Wonder", "alice@example.com", "11223344", true);
+        sistema.add_cotizacion("BTC".to_string(), usd("50000"));
+        sistema.add_cotizacion("USD".to_string(), ars("1000"));
+        assert!(sistema.add_balance("11223344", "BTC", cripto("0.02", "BTC")).is_ok());
+
+        assert!(sistema.vender_cripto_en("11223344", cripto("0.01", "BTC"), "BTC", &Moneda::usd()).is_ok());
+        assert!(sistema.vender_cripto("11223344", cripto("0.01", "BTC"), "BTC").is_ok());
+        assert_eq!(sistema.get_balance("11223344", "USD"), usd("500"));
+        assert_eq!(sistema.get_balance("11223344", "ARS"), ars("500000"));
+        assert_eq!(sistema.get_balance("11223344", "BTC"), cripto("0", "BTC"));
+    }
+
+    fn sistema_con_usuarios(nombre: &str, almacenamiento: Rc<dyn Almacenamiento>) -> XYZ {
+        let mut sistema = XYZ::new(nombre, almacenamiento);
+        for dni in ["11111111", "22222222", "33333333"] {
+            sistema.crear_usuario("Usuario", dni, "usuario@example.com", dni, true);
+        }
+        sistema
+    }
+
+    #[test]
+    fn test_orden_limite_sin_contraparte() {
+        let mut sistema = sistema_con_usuarios("test_orden_limite_sin_contraparte", Rc::new(AlmacenamientoMemoria::new()));
+        assert!(sistema.ingresar_dinero("11111111", usd("1000")).is_ok());
+
+        let id = sistema.colocar_orden_limite("11111111", Lado::Compra, cripto("0.01", "BTC"), usd("50000")).unwrap();
+        // Se reserva lo necesario para pagar la orden completa
+        assert_eq!(sistema.get_balance("11111111", "USD"), usd("500"));
+        let libro = sistema.get_libro("BTC", &Moneda::usd()).unwrap();
+        assert_eq!(libro.mejor_compra().unwrap().id, id);
+        assert!(libro.mejor_venta().is_none());
+        assert!(sistema.get_libro("BTC", &Moneda::ars()).is_none());
+
+        // No alcanza lo que queda para otra orden igual
+        assert!(sistema.colocar_orden_limite("11111111", Lado::Compra, cripto("0.02", "BTC"), usd("50000")).is_err());
+        assert!(sistema.colocar_orden_limite("11111111", Lado::Compra, usd("1"), usd("50000")).is_err());
+        assert!(sistema.colocar_orden_limite("11111111", Lado::Compra, cripto("0.01", "BTC"), usd("0")).is_err());
+        assert_eq!(sistema.get_libro("BTC", &Moneda::usd()).unwrap().compras.len(), 1);
+    }
+
+    #[test]
+    fn test_orden_limite_ejecucion_parcial() {
+        let mut sistema = sistema_con_usuarios("test_orden_limite_ejecucion_parcial", Rc::new(AlmacenamientoMemoria::new()));
+        assert!(sistema.add_balance("11111111", "BTC", cripto("0.02", "BTC")).is_ok());
+        assert!(sistema.ingresar_dinero("22222222", ars("1000")).is_ok());
+
+        let venta = sistema.colocar_orden_limite("11111111", Lado::Venta, cripto("0.02", "BTC"), ars("50000")).unwrap();
+        assert_eq!(sistema.get_balance("11111111", "BTC"), cripto("0", "BTC"));
+
+        // Se ejecuta al precio de la orden que estaba en el libro aunque la compra aceptara pagar mas
+        sistema.colocar_orden_limite("22222222", Lado::Compra, cripto("0.01", "BTC"), ars("51000")).unwrap();
+        assert_eq!(sistema.get_balance("22222222", "BTC"), cripto("0.01", "BTC"));
+        assert_eq!(sistema.get_balance("22222222", "ARS"), ars("500"));
+        assert_eq!(sistema.get_balance("11111111", "ARS"), ars("500"));
+
+        let libro = sistema.get_libro("BTC", &Moneda::ars()).unwrap();
+        assert!(libro.compras.is_empty());
+        assert_eq!(libro.ventas[0].id, venta);
+        assert_eq!(libro.ventas[0].cantidad, cripto("0.01", "BTC"));
+        assert_eq!(libro.ventas[0].reservado, cripto("0.01", "BTC"));
+
+        // Despues del ingreso, una compra y una venta por la ejecucion
+        assert_eq!(sistema.transacciones.len(), 3);
+        assert!(matches!(&sistema.transacciones[1].tipo, TipoTransaccion::CompraCripto { monto, cotizacion, .. }
+            if *monto == ars("500") && *cotizacion == ars("50000")));
+        assert_eq!(sistema.transacciones[1].dni_usuario, "22222222");
+        assert!(matches!(&sistema.transacciones[2].tipo, TipoTransaccion::VentaCripto { monto, cotizacion, .. }
+            if *monto == cripto("0.01", "BTC") && *cotizacion == ars("50000")));
+        assert_eq!(sistema.transacciones[2].dni_usuario, "11111111");
+    }
+
+    #[test]
+    fn test_orden_no_cruza_con_el_mismo_usuario() {
+        let mut sistema = sistema_con_usuarios("test_orden_no_cruza_con_el_mismo_usuario", Rc::new(AlmacenamientoMemoria::new()));
+        assert!(sistema.add_balance("11111111", "BTC", cripto("0.01", "BTC")).is_ok());
+        assert!(sistema.add_balance("22222222", "BTC", cripto("0.01", "BTC")).is_ok());
+        assert!(sistema.ingresar_dinero("11111111", ars("2000")).is_ok());
+
+        let propia = sistema.colocar_orden_limite("11111111", Lado::Venta, cripto("0.01", "BTC"), ars("50000")).unwrap();
+        let ajena = sistema.colocar_orden_limite("22222222", Lado::Venta, cripto("0.01", "BTC"), ars("51000")).unwrap();
+
+        // Se saltea la venta propia aunque tenga mejor precio y se ejecuta contra la del otro usuario
+        let (_, ejecutado) = sistema.colocar_orden("11111111", Lado::Compra, cripto("0.01", "BTC"), None, &Moneda::ars()).unwrap();
+        assert_eq!(ejecutado, cripto("0.01", "BTC"));
+        assert_eq!(sistema.get_balance("11111111", "ARS"), ars("1490"));
+        assert_eq!(sistema.get_balance("22222222", "ARS"), ars("510"));
+        let libro = sistema.get_libro("BTC", &Moneda::ars()).unwrap();
+        assert_eq!(libro.ventas.len(), 1);
+        assert_eq!(libro.ventas[0].id, propia);
+        assert!(libro.get_orden(ajena).is_none());
+
+        // Sin otras contrapartes la compra queda en el libro en lugar de ejecutarse contra si mismo
+        sistema.colocar_orden_limite("11111111", Lado::Compra, cripto("0.01", "BTC"), ars("50000")).unwrap();
+        let libro = sistema.get_libro("BTC", &Moneda::ars()).unwrap();
+        assert_eq!(libro.compras.len(), 1);
+        assert_eq!(libro.ventas[0].cantidad, cripto("0.01", "BTC"));
+        assert!(sistema.transacciones.iter().filter(|t| t.dni_usuario == "11111111").all(|t| !matches!(t.tipo, TipoTransaccion::VentaCripto { .. })));
+    }
+
+    #[test]
+    fn test_orden_limite_sin_cruce() {
+        let mut sistema = sistema_con_usuarios("test_orden_limite_sin_cruce", Rc::new(AlmacenamientoMemoria::new()));
+        assert!(sistema.add_balance("11111111", "BTC", cripto("0.01", "BTC")).is_ok());
+        assert!(sistema.ingresar_dinero("22222222", ars("1000")).is_ok());
+
+        sistema.colocar_orden_limite("11111111", Lado::Venta, cripto("0.01", "BTC"), ars("50000")).unwrap();
+        sistema.colocar_orden_limite("22222222", Lado::Compra, cripto("0.01", "BTC"), ars("49000")).unwrap();
+
+        let libro = sistema.get_libro("BTC", &Moneda::ars()).unwrap();
+        assert_eq!(libro.mejor_venta().unwrap().precio, ars("50000"));
+        assert_eq!(libro.mejor_compra().unwrap().precio, ars("49000"));
+        assert_eq!(sistema.get_balance("22222222", "ARS"), ars("510"));
+        assert_eq!(sistema.transacciones.len(), 1);
+    }
 
-        assert!(sistema.comprar_cripto("87654321", 50000.0, "BTC").is_ok());
+    #[test]
+    fn test_ordenes_prioridad_precio_tiempo() {
+        let mut sistema = sistema_con_usuarios("test_ordenes_prioridad_precio_tiempo", Rc::new(AlmacenamientoMemoria::new()));
+        for dni in ["11111111", "22222222", "33333333"] {
+            assert!(sistema.add_balance(dni, "BTC", cripto("0.01", "BTC")).is_ok());
+        }
+        sistema.colocar_orden_limite("11111111", Lado::Venta, cripto("0.01", "BTC"), ars("51000")).unwrap();
+        sistema.colocar_orden_limite("22222222", Lado::Venta, cripto("0.01", "BTC"), ars("50000")).unwrap();
+        sistema.colocar_orden_limite("33333333", Lado::Venta, cripto("0.01", "BTC"), ars("50000")).unwrap();
+        let libro = sistema.get_libro("BTC", &Moneda::ars()).unwrap();
+        let vendedores: Vec<&str> = libro.ventas.iter().map(|o| o.dni_usuario.as_str()).collect();
+        assert_eq!(vendedores, vec!["22222222", "33333333", "11111111"]);
+
+        sistema.crear_usuario("Comprador", "Test", "comprador@example.com", "44444444", true);
+        assert!(sistema.ingresar_dinero("44444444", ars("1000")).is_ok());
+
+        let ejecutado = sistema.colocar_orden_mercado("44444444", Lado::Compra, cripto("0.015", "BTC"), &Moneda::ars()).unwrap();
+        assert_eq!(ejecutado, cripto("0.015", "BTC"));
+        assert_eq!(sistema.get_balance("22222222", "ARS"), ars("500"));
+        assert_eq!(sistema.get_balance("33333333", "ARS"), ars("250"));
+        assert_eq!(sistema.get_balance("11111111", "ARS"), ars("0"));
+        assert_eq!(sistema.get_balance("44444444", "ARS"), ars("250"));
+        assert_eq!(sistema.get_balance("44444444", "BTC"), cripto("0.015", "BTC"));
+
+        // Las ordenes de mercado que no se llegan a ejecutar se descartan
+        let ejecutado = sistema.colocar_orden_mercado("44444444", Lado::Compra, cripto("1", "BTC"), &Moneda::ars());
+        assert!(ejecutado.is_err());
+        assert!(sistema.ingresar_dinero("44444444", ars("1000")).is_ok());
+        let ejecutado = sistema.colocar_orden_mercado("44444444", Lado::Compra, cripto("1", "BTC"), &Moneda::ars()).unwrap();
+        assert_eq!(ejecutado, cripto("0.015", "BTC"));
+        assert!(sistema.get_libro("BTC", &Moneda::ars()).unwrap().ventas.is_empty());
+        assert!(sistema.get_libro("BTC", &Moneda::ars()).unwrap().compras.is_empty());
+    }
 
-        assert_eq!(sistema.get_balance("87654321", "fiat"), 0.0);
-        assert_eq!(sistema.get_balance("87654321", "BTC"), 1.0);
+    #[test]
+    fn test_orden_mercado_sin_saldo() {
+        let mut sistema = sistema_con_usuarios("test_orden_mercado_sin_saldo", Rc::new(AlmacenamientoMemoria::new()));
+        assert!(sistema.add_balance("11111111", "BTC", cripto("0.02", "BTC")).is_ok());
+        assert!(sistema.ingresar_dinero("22222222", ars("100")).is_ok());
+        assert_eq!(sistema.colocar_orden_mercado("22222222", Lado::Compra, cripto("0.01", "BTC"), &Moneda::ars()).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+
+        sistema.colocar_orden_limite("11111111", Lado::Venta, cripto("0.02", "BTC"), ars("50000")).unwrap();
+        assert!(sistema.colocar_orden_mercado("22222222", Lado::Compra, cripto("0.01", "BTC"), &Moneda::ars()).is_err());
+
+        // No se ejecuto nada
+        assert_eq!(sistema.get_balance("22222222", "ARS"), ars("100"));
+        assert_eq!(sistema.get_libro("BTC", &Moneda::ars()).unwrap().ventas[0].cantidad, cripto("0.02", "BTC"));
         assert_eq!(sistema.transacciones.len(), 1);
     }
 
+    #[test]
+    fn test_orden_devuelve_sobrante_de_redondeo() {
+        let mut sistema = sistema_con_usuarios("test_orden_devuelve_sobrante_de_redondeo", Rc::new(AlmacenamientoMemoria::new()));
+        assert!(sistema.ingresar_dinero("11111111", ars("10")).is_ok());
+        assert!(sistema.add_balance("22222222", "BTC", cripto("0.003", "BTC")).is_ok());
+
+        // 0,003 * 333,33 = 0,99999, se reserva 1 peso
+        sistema.colocar_orden_limite("11111111", Lado::Compra, cripto("0.003", "BTC"), ars("333.33")).unwrap();
+        assert_eq!(sistema.get_balance("11111111", "ARS"), ars("9"));
+
+        sistema.colocar_orden_mercado("22222222", Lado::Venta, cripto("0.001", "BTC"), &Moneda::ars()).unwrap();
+        sistema.colocar_orden_mercado("22222222", Lado::Venta, cripto("0.002", "BTC"), &Moneda::ars()).unwrap();
+        assert_eq!(sistema.get_balance("22222222", "ARS"), ars("0.99"));
+        assert_eq!(sistema.get_balance("11111111", "ARS"), ars("9.01"));
+        assert_eq!(sistema.get_balance("11111111", "BTC"), cripto("0.003", "BTC"));
+    }
+
+    #[test]
+    fn test_cancelar_orden() {
+        let mut sistema = sistema_con_usuarios("test_cancelar_orden", Rc::new(AlmacenamientoMemoria::new()));
+        assert!(sistema.add_balance("11111111", "BTC", cripto("0.02", "BTC")).is_ok());
+        assert!(sistema.ingresar_dinero("22222222", ars("1000")).is_ok());
+        let id = sistema.colocar_orden_limite("11111111", Lado::Venta, cripto("0.02", "BTC"), ars("50000")).unwrap();
+        sistema.colocar_orden_mercado("22222222", Lado::Compra, cripto("0.005", "BTC"), &Moneda::ars()).unwrap();
+
+        // Solo el duenio puede cancelarla
+        assert_eq!(sistema.cancelar_orden("22222222", id).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+        assert!(sistema.cancelar_orden("11111111", id).is_ok());
+        assert_eq!(sistema.get_balance("11111111", "BTC"), cripto("0.015", "BTC"));
+        assert!(sistema.get_libro("BTC", &Moneda::ars()).unwrap().ventas.is_empty());
+        assert!(sistema.cancelar_orden("11111111", id).is_err());
+    }
+
+    #[test]
+    fn test_libro_ordenes_persistente() {
+        let almacenamiento: Rc<dyn Almacenamiento> = Rc::new(AlmacenamientoMemoria::new());
+        let mut sistema = sistema_con_usuarios("test_libro_ordenes_persistente", almacenamiento.clone());
+        sistema.set_limite_compactacion(4);
+        assert!(sistema.add_balance("11111111", "BTC", cripto("0.02", "BTC")).is_ok());
+        assert!(sistema.ingresar_dinero("22222222", ars("1000")).is_ok());
+        let venta = sistema.colocar_orden_limite("11111111", Lado::Venta, cripto("0.01", "BTC"), ars("50000")).unwrap();
+        let compra = sistema.colocar_orden_limite("22222222", Lado::Compra, cripto("0.01", "BTC"), ars("40000")).unwrap();
+        // Esta orden queda despues de la foto del estado, en el log
+        sistema.colocar_orden_limite("11111111", Lado::Venta, cripto("0.01", "BTC"), ars("50000")).unwrap();
+        sistema.colocar_orden_mercado("22222222", Lado::Compra, cripto("0.005", "BTC"), &Moneda::ars()).unwrap();
+
+        let mut sistema = sistema_con_usuarios("test_libro_ordenes_persistente", almacenamiento);
+        let libro = sistema.get_libro("BTC", &Moneda::ars()).unwrap();
+        assert_eq!(libro.ventas.len(), 2);
+        assert_eq!(libro.ventas[0].id, venta);
+        assert_eq!(libro.ventas[0].cantidad, cripto("0.005", "BTC"));
+        assert_eq!(libro.mejor_compra().unwrap().id, compra);
+
+        assert!(sistema.cancelar_orden("22222222", compra).is_ok());
+        assert_eq!(sistema.get_balance("22222222", "ARS"), ars("750"));
+    }
+
+    #[test]
+    fn test_estadisticas_con_ejecuciones() {
+        let mut sistema = sistema_con_usuarios("test_estadisticas_con_ejecuciones", Rc::new(AlmacenamientoMemoria::new()));
+        assert!(sistema.add_balance("11111111", "ETH", cripto("1", "ETH")).is_ok());
+        assert!(sistema.add_balance("11111111", "BTC", cripto("0.01", "BTC")).is_ok());
+        assert!(sistema.ingresar_dinero("22222222", ars("10000")).is_ok());
+        sistema.colocar_orden_limite("11111111", Lado::Venta, cripto("1", "ETH"), ars("2500")).unwrap();
+        sistema.colocar_orden_limite("11111111", Lado::Venta, cripto("0.01", "BTC"), ars("50000")).unwrap();
+
+        sistema.colocar_orden_mercado("22222222", Lado::Compra, cripto("0.5", "ETH"), &Moneda::ars()).unwrap();
+        sistema.colocar_orden_mercado("22222222", Lado::Compra, cripto("0.5", "ETH"), &Moneda::ars()).unwrap();
+        sistema.colocar_orden_mercado("22222222", Lado::Compra, cripto("0.01", "BTC"), &Moneda::ars()).unwrap();
+
+        assert_eq!(sistema.cripto_mas_compras(&Periodo::todo()).as_deref(), Some("ETH"));
+        assert_eq!(sistema.cripto_mas_ventas(&Periodo::todo()).as_deref(), Some("ETH"));
+        assert_eq!(sistema.cripto_mas_volumen_compras(&Periodo::todo()), Ok(Some("ETH".to_string())));
+
+        // Cada ejecucion genera una compra y una venta pero en la vela cuenta una sola vez
+        let velas = sistema.velas("ETH", &Moneda::ars(), &Periodo::todo(), Intervalo::Dia).unwrap();
+        assert_eq!(velas.len(), 1);
+        assert_eq!(velas[0].operaciones, 2);
+        assert_eq!(velas[0].volumen, cripto("1", "ETH"));
+        assert_eq!((velas[0].apertura.clone(), velas[0].cierre.clone()), (ars("2500"), ars("2500")));
+        let velas = sistema.velas("BTC", &Moneda::ars(), &Periodo::todo(), Intervalo::Dia).unwrap();
+        assert_eq!((velas[0].operaciones, velas[0].volumen.clone()), (1, cripto("0.01", "BTC")));
+    }
+
     #[test]
     fn test_comprar_cripto_sin_identidad() {
-        let mut sistema = XYZ::new("test_comprar_cripto_sin_identidad");
+        let mut sistema = XYZ::new("test_comprar_cripto_sin_identidad", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario( "Juan", "Garcia", "juan@example.com", "87654321", false);
 
-        assert!(sistema.add_balance("87654321", "fiat", 50000.0).is_ok());
-        sistema.add_cotizacion("BTC".to_string(), 50000.0);
+        assert!(sistema.add_balance("87654321", "ARS", ars("50000")).is_ok());
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
 
-        assert!(sistema.comprar_cripto("87654321", 50000.0, "BTC").is_err());
+        assert!(sistema.comprar_cripto("87654321", ars("50000"), "BTC").is_err());
 
-        assert_eq!(sistema.get_balance("87654321", "fiat"), 50000.0);
+        assert_eq!(sistema.get_balance("87654321", "ARS"), ars("50000"));
         assert_eq!(sistema.transacciones.len(), 0);
     }
 
     #[test]
     fn test_vender_cripto() {
-        let mut sistema = XYZ::new("test_vender_cripto");
+        let mut sistema = XYZ::new("test_vender_cripto", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario( "Alice", "Wonder", "alice@example.com", "11223344", true);
 
-        assert!(sistema.add_balance("11223344", "BTC", 0.02).is_ok());
-        sistema.add_cotizacion("BTC".to_string(), 50000.0);
+        assert!(sistema.add_balance("11223344", "BTC", cripto("0.02", "BTC")).is_ok());
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
 
-        assert_eq!(sistema.get_balance("11223344", "BTC"), 0.02);
+        assert_eq!(sistema.get_balance("11223344", "BTC"), cripto("0.02", "BTC"));
 
-        assert!(sistema.vender_cripto("11223344", 0.01, "BTC").is_ok());
+        assert!(sistema.vender_cripto("11223344", cripto("0.01", "BTC"), "BTC").is_ok());
 
-        assert_eq!(sistema.get_balance("11223344", "BTC"), 0.01);
-        assert_eq!(sistema.get_balance("11223344", "fiat"), 500.0);
+        assert_eq!(sistema.get_balance("11223344", "BTC"), cripto("0.01", "BTC"));
+        assert_eq!(sistema.get_balance("11223344", "ARS"), ars("500"));
         assert_eq!(sistema.transacciones.len(), 1);
     }
 
     #[test]
     fn test_vender_cripto_sin_identidad() {
-        let mut sistema = XYZ::new("test_vender_cripto_sin_identidad");
+        let mut sistema = XYZ::new("test_vender_cripto_sin_identidad", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario( "Alice", "Wonder", "alice@example.com", "11223344", false);
 
-        assert!(sistema.add_balance("11223344", "BTC", 0.02).is_ok());
-        sistema.add_cotizacion("BTC".to_string(), 50000.0);
+        assert!(sistema.add_balance("11223344", "BTC", cripto("0.02", "BTC")).is_ok());
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
 
-        assert_eq!(sistema.get_balance("11223344", "BTC"), 0.02);
+        assert_eq!(sistema.get_balance("11223344", "BTC"), cripto("0.02", "BTC"));
 
-        assert!(sistema.vender_cripto("11223344", 0.01, "BTC").is_err());
+        assert!(sistema.vender_cripto("11223344", cripto("0.01", "BTC"), "BTC").is_err());
 
-        assert_eq!(sistema.get_balance("11223344", "BTC"), 0.02);
+        assert_eq!(sistema.get_balance("11223344", "BTC"), cripto("0.02", "BTC"));
         assert_eq!(sistema.transacciones.len(), 0);
     }
 
     #[test]
     fn test_retirar_cripto() {
-        let mut sistema = XYZ::new("test_retirar_cripto");
+        let mut sistema = XYZ::new("test_retirar_cripto", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario( "Bob", "Smith", "bob@example.com", "44556677", true);
 
-        assert!(sistema.add_balance("44556677", "BTC", 0.02).is_ok());
+        assert!(sistema.add_balance("44556677", "BTC", cripto("0.02", "BTC")).is_ok());
         sistema.add_criptomoneda(CriptoMoneda {
             nombre: "Bitcoin".to_string(),
             prefijo: "BTC".to_string(),
             blockchains: vec![BlockChain { nombre: "Bitcoin".to_string(), prefijo: "BTC".to_string() }]
         });
-        sistema.add_cotizacion("BTC".to_string(), 50000.0);
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
 
-        assert!(sistema.retirar_cripto("44556677", 0.01, "BTC", "Bitcoin").is_ok());
-        assert_eq!(sistema.get_balance("44556677", "BTC"), 0.01);
+        assert!(sistema.retirar_cripto("44556677", cripto("0.01", "BTC"), "BTC", "Bitcoin").is_ok());
+        assert_eq!(sistema.get_balance("44556677", "BTC"), cripto("0.01", "BTC"));
         assert_eq!(sistema.transacciones.len(), 1);
     }
 
     #[test]
     fn test_retirar_cripto_sin_identidad() {
-        let mut sistema = XYZ::new("test_retirar_cripto_sin_identidad");
+        let mut sistema = XYZ::new("test_retirar_cripto_sin_identidad", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario( "Bob", "Smith", "bob@example.com", "44556677", false);
 
-        assert!(sistema.add_balance("44556677", "BTC", 0.02).is_ok());
+        assert!(sistema.add_balance("44556677", "BTC", cripto("0.02", "BTC")).is_ok());
         sistema.add_criptomoneda(CriptoMoneda {
             nombre: "Bitcoin".to_string(),
             prefijo: "BTC".to_string(),
             blockchains: vec![BlockChain { nombre: "Bitcoin".to_string(), prefijo: "BTC".to_string() }]
         });
-        sistema.add_cotizacion("BTC".to_string(), 50000.0);
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
 
-        assert!(sistema.retirar_cripto("44556677", 0.01, "BTC", "Bitcoin").is_err());
-        assert_eq!(sistema.get_balance("44556677", "BTC"), 0.02);
+        assert!(sistema.retirar_cripto("44556677", cripto("0.01", "BTC"), "BTC", "Bitcoin").is_err());
+        assert_eq!(sistema.get_balance("44556677", "BTC"), cripto("0.02", "BTC"));
         assert_eq!(sistema.transacciones.len(), 0);
     }
 
     #[test]
     fn test_recibir_cripto() {
-        let mut sistema = XYZ::new("test_recibir_cripto");
+        let mut sistema = XYZ::new("test_recibir_cripto", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario( "Carol", "Danvers", "carol@example.com", "55667788", true);
 
-        sistema.add_cotizacion("ETH".to_string(), 2500.0);
-        assert!(sistema.recibir_cripto("55667788", 2.0, "ETH", "Ethereum").is_ok());
+        sistema.add_cotizacion("ETH".to_string(), ars("2500"));
+        assert!(sistema.recibir_cripto("55667788", cripto("2", "ETH"), "ETH", "Ethereum").is_ok());
 
-        assert_eq!(sistema.get_balance("55667788", "ETH"), 2.0);
+        assert_eq!(sistema.get_balance("55667788", "ETH"), cripto("2", "ETH"));
         assert_eq!(sistema.transacciones.len(), 1);
     }
 
     #[test]
     fn test_recibir_cripto_sin_identidad() {
-        let mut sistema = XYZ::new("test_recibir_cripto_sin_identidad");
+        let mut sistema = XYZ::new("test_recibir_cripto_sin_identidad", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario( "Carol", "Danvers", "carol@example.com", "55667788", false);
 
-        sistema.add_cotizacion("ETH".to_string(), 2500.0);
-        assert!(sistema.recibir_cripto("55667788", 2.0, "ETH", "Ethereum").is_err());
+        sistema.add_cotizacion("ETH".to_string(), ars("2500"));
+        assert!(sistema.recibir_cripto("55667788", cripto("2", "ETH"), "ETH", "Ethereum").is_err());
 
         assert_eq!(sistema.transacciones.len(), 0);
     }
 
     #[test]
     fn test_retirar_dinero() {
-        let mut sistema = XYZ::new("test_retirar_dinero");
+        let mut sistema = XYZ::new("test_retirar_dinero", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario( "David", "Beckham", "david@example.com", "66554433", true);
 
-        assert!(sistema.add_balance("66554433", "fiat", 1000.0).is_ok());
+        assert!(sistema.add_balance("66554433", "ARS", ars("1000")).is_ok());
 
-        assert!(sistema.retirar_dinero("66554433", 500.0, MedioRetiro::MercadoPago).is_ok());
+        assert!(sistema.retirar_dinero("66554433", ars("500"), MedioRetiro::MercadoPago).is_ok());
 
-        assert_eq!(sistema.get_balance("66554433", "fiat"), 500.0);
+        assert_eq!(sistema.get_balance("66554433", "ARS"), ars("500"));
         assert_eq!(sistema.transacciones.len(), 1);
     }
 
     #[test]
     fn test_retirar_dinero_sin_identidad() {
-        let mut sistema = XYZ::new("test_retirar_dinero_sin_identidad");
+        let mut sistema = XYZ::new("test_retirar_dinero_sin_identidad", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario( "David", "Beckham", "david@example.com", "66554433", false);
 
-        assert!(sistema.add_balance("66554433", "fiat", 1000.0).is_ok());
+        assert!(sistema.add_balance("66554433", "ARS", ars("1000")).is_ok());
 
-        assert!(sistema.retirar_dinero("66554433", 500.0, MedioRetiro::MercadoPago).is_err());
+        assert!(sistema.retirar_dinero("66554433", ars("500"), MedioRetiro::MercadoPago).is_err());
 
-        assert_eq!(sistema.get_balance("66554433", "fiat"), 1000.0);
+        assert_eq!(sistema.get_balance("66554433", "ARS"), ars("1000"));
         assert_eq!(sistema.transacciones.len(), 0);
     }
 
     #[test]
     fn test_crear_usuario() {
-        let mut sistema = XYZ::new("test_crear_usuario");
+        let mut sistema = XYZ::new("test_crear_usuario", Rc::new(AlmacenamientoMemoria::new()));
 
         let usuario = sistema.crear_usuario("Eva", "Green", "eva@example.com", "99887766", false);
 
@@ -645,7 +3942,7 @@ mod tests {
 
     #[test]
     fn test_verificar_identidad() {
-        let mut sistema = XYZ::new("test_verificar_identidad");
+        let mut sistema = XYZ::new("test_verificar_identidad", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario( "Frank", "Ocean", "frank@example.com", "77665544", true);
 
         assert!(sistema.verificar_identidad("77665544"));
@@ -653,7 +3950,7 @@ mod tests {
 
     #[test]
     fn test_get_usuario() {
-        let mut sistema = XYZ::new("test_get_usuario");
+        let mut sistema = XYZ::new("test_get_usuario", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario("test", "test", "test@test.com", "55443322", false);
 
         let found_user = sistema.get_usuario("55443322");
@@ -664,80 +3961,441 @@ mod tests {
 
     #[test]
     fn test_get_balance() {
-        let mut sistema = XYZ::new("test_get_balance");
+        let mut sistema = XYZ::new("test_get_balance", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario( "Hank", "Moody", "hank@example.com", "33445566", true);
 
-        assert!(sistema.add_balance("33445566", "fiat", 2000.0).is_ok());
+        assert!(sistema.add_balance("33445566", "ARS", ars("2000")).is_ok());
 
-        assert_eq!(sistema.get_balance("33445566", "fiat"), 2000.0);
+        assert_eq!(sistema.get_balance("33445566", "ARS"), ars("2000"));
     }
 
     #[test]
     fn test_add_balance() {
-        let mut sistema = XYZ::new("test_add_balance");
+        let mut sistema = XYZ::new("test_add_balance", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario( "Hank", "Moody", "hank@example.com", "33445566", true);
 
-        assert!(sistema.add_balance("33445566", "fiat", 2000.0).is_ok());
+        assert!(sistema.add_balance("33445566", "ARS", ars("2000")).is_ok());
 
-        assert_eq!(sistema.get_balance("33445566", "fiat"), 2000.0);
+        assert_eq!(sistema.get_balance("33445566", "ARS"), ars("2000"));
     }
 
     #[test]
     fn test_remove_balance() {
-        let mut sistema = XYZ::new("test_remove_balance");
+        let mut sistema = XYZ::new("test_remove_balance", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario( "Hank", "Moody", "hank@example.com", "33445566", true);
 
-        assert!(sistema.add_balance("33445566", "fiat", 2000.0).is_ok());
-        assert!(sistema.remove_balance("33445566", "fiat", 1000.0).is_ok());
+        assert!(sistema.add_balance("33445566", "ARS", ars("2000")).is_ok());
+        assert!(sistema.remove_balance("33445566", "ARS", ars("1000")).is_ok());
 
-        assert_eq!(sistema.get_balance("33445566", "fiat"), 1000.0);
+        assert_eq!(sistema.get_balance("33445566", "ARS"), ars("1000"));
     }
 
     #[test]
     fn test_cripto_mas_compras() {
-        let mut sistema = XYZ::new("test_cripto_mas_compras");
+        let mut sistema = XYZ::new("test_cripto_mas_compras", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario( "Ivan", "Reitman", "ivan@example.com", "12344321", true);
 
-        assert!(sistema.crear_transaccion(TipoTransaccion::CompraCripto { monto: 500.0, criptomoneda: "BTC".to_string(), cotizacion: 50000.0 }, "12344321").is_ok());
-        assert!(sistema.crear_transaccion(TipoTransaccion::CompraCripto { monto: 300.0, criptomoneda: "ETH".to_string(), cotizacion: 2500.0 }, "12344321").is_ok());
-        assert!(sistema.crear_transaccion(TipoTransaccion::CompraCripto { monto: 200.0, criptomoneda: "BTC".to_string(), cotizacion: 50000.0 }, "12344321").is_ok());
+        assert!(sistema.crear_transaccion(TipoTransaccion::CompraCripto { monto: ars("500"), criptomoneda: "BTC".to_string(), cotizacion: ars("50000"), cantidad: cripto("0.01", "BTC"), comision: ars("0") }, "12344321").is_ok());
+        assert!(sistema.crear_transaccion(TipoTransaccion::CompraCripto { monto: ars("300"), criptomoneda: "ETH".to_string(), cotizacion: ars("2500"), cantidad: cripto("0.12", "ETH"), comision: ars("0") }, "12344321").is_ok());
+        assert!(sistema.crear_transaccion(TipoTransaccion::CompraCripto { monto: ars("200"), criptomoneda: "BTC".to_string(), cotizacion: ars("50000"), cantidad: cripto("0.004", "BTC"), comision: ars("0") }, "12344321").is_ok());
 
-        assert_eq!(sistema.cripto_mas_compras(), "BTC");
+        assert_eq!(sistema.cripto_mas_compras(&Periodo::todo()).as_deref(), Some("BTC"));
     }
 
     #[test]
     fn test_cripto_mas_ventas() {
-        let mut sistema = XYZ::new("test_cripto_mas_ventas");
+        let mut sistema = XYZ::new("test_cripto_mas_ventas", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario( "Jack", "Nicholson", "jack@example.com", "43211234", true);
 
-        assert!(sistema.crear_transaccion(TipoTransaccion::VentaCripto { monto: 0.01, criptomoneda: "BTC".to_string(), cotizacion: 50000.0 }, "43211234").is_ok());
-        assert!(sistema.crear_transaccion(TipoTransaccion::VentaCripto { monto: 0.02, criptomoneda: "ETH".to_string(), cotizacion: 2500.0 }, "43211234").is_ok());
-        assert!(sistema.crear_transaccion(TipoTransaccion::VentaCripto { monto: 0.01, criptomoneda: "BTC".to_string(), cotizacion: 50000.0 }, "43211234").is_ok());
+        assert!(sistema.crear_transaccion(TipoTransaccion::VentaCripto { monto: cripto("0.01", "BTC"), criptomoneda: "BTC".to_string(), cotizacion: ars("50000"), comision: ars("0") }, "43211234").is_ok());
+        assert!(sistema.crear_transaccion(TipoTransaccion::VentaCripto { monto: cripto("0.02", "ETH"), criptomoneda: "ETH".to_string(), cotizacion: ars("2500"), comision: ars("0") }, "43211234").is_ok());
+        assert!(sistema.crear_transaccion(TipoTransaccion::VentaCripto { monto: cripto("0.01", "BTC"), criptomoneda: "BTC".to_string(), cotizacion: ars("50000"), comision: ars("0") }, "43211234").is_ok());
 
-        assert_eq!(sistema.cripto_mas_ventas(), "BTC");
+        assert_eq!(sistema.cripto_mas_ventas(&Periodo::todo()).as_deref(), Some("BTC"));
     }
 
     #[test]
     fn test_cripto_mas_volumen_venta() {
-        let mut sistema = XYZ::new("test_cripto_mas_volumen_venta");
+        let mut sistema = XYZ::new("test_cripto_mas_volumen_venta", Rc::new(AlmacenamientoMemoria::new()));
         sistema.crear_usuario( "Jack", "Nicholson", "jack@example.com", "43211234", true);
 
-        assert!(sistema.crear_transaccion(TipoTransaccion::VentaCripto { monto: 0.01, criptomoneda: "BTC".to_string(), cotizacion: 50000.0 }, "43211234").is_ok());
-        assert!(sistema.crear_transaccion(TipoTransaccion::VentaCripto { monto: 0.02, criptomoneda: "ETH".to_string(), cotizacion: 2500.0 }, "43211234").is_ok());
-        assert!(sistema.crear_transaccion( TipoTransaccion::VentaCripto { monto: 0.03, criptomoneda: "BTC".to_string(), cotizacion: 50000.0 }, "43211234").is_ok());
+        assert!(sistema.crear_transaccion(TipoTransaccion::VentaCripto { monto: cripto("0.01", "BTC"), criptomoneda: "BTC".to_string(), cotizacion: ars("50000"), comision: ars("0") }, "43211234").is_ok());
+        assert!(sistema.crear_transaccion(TipoTransaccion::VentaCripto { monto: cripto("0.02", "ETH"), criptomoneda: "ETH".to_string(), cotizacion: ars("2500"), comision: ars("0") }, "43211234").is_ok());
+        assert!(sistema.crear_transaccion( TipoTransaccion::VentaCripto { monto: cripto("0.03", "BTC"), criptomoneda: "BTC".to_string(), cotizacion: ars("50000"), comision: ars("0") }, "43211234").is_ok());
 
-        assert_eq!(sistema.cripto_mas_volumen_venta(), "BTC");
+        assert_eq!(sistema.cripto_mas_volumen_venta(&Periodo::todo()).as_deref(), Some("BTC"));
     }
 
     #[test]
     fn test_cripto_mas_volumen_compras() {
-        let mut sistema = XYZ::new("test_cripto_mas_volumen_compras");
+        let reloj = Rc::new(RelojManual::new(FechaHora::new(Fecha::new(4, 3, 2024), 10, 0, 0)));
+        let mut sistema = XYZ::new_con_reloj("test_cripto_mas_volumen_compras", Rc::new(AlmacenamientoMemoria::new()), reloj.clone());
+        sistema.crear_usuario( "Jack", "Nicholson", "jack@example.com", "43211234", true);
+
+        assert!(sistema.crear_transaccion(TipoTransaccion::CompraCripto { monto: ars("500"), criptomoneda: "BTC".to_string(), cotizacion: ars("50000"), cantidad: cripto("0.01", "BTC"), comision: ars("0") }, "43211234").is_ok());
+        assert!(sistema.crear_transaccion(TipoTransaccion::CompraCripto { monto: ars("300"), criptomoneda: "ETH".to_string(), cotizacion: ars("2500"), cantidad: cripto("0.12", "ETH"), comision: ars("0") }, "43211234").is_ok());
+        assert!(sistema.crear_transaccion(TipoTransaccion::CompraCripto { monto: ars("700"), criptomoneda: "BTC".to_string(), cotizacion: ars("50000"), cantidad: cripto("0.014", "BTC"), comision: ars("0") }, "43211234").is_ok());
+
+        assert_eq!(sistema.cripto_mas_volumen_compras(&Periodo::todo()), Ok(Some("BTC".to_string())));
+
+        // La compra en dolares se valua a la cotizacion del dia en que se hizo, no a la actual
+        sistema.add_cotizacion("USD".to_string(), ars("1000"));
+        assert!(sistema.crear_transaccion(TipoTransaccion::CompraCripto { monto: usd("1"), criptomoneda: "ETH".to_string(), cotizacion: usd("2.5"), cantidad: cripto("0.4", "ETH"), comision: usd("0") }, "43211234").is_ok());
+        reloj.avanzar_segundos(3600);
+        sistema.add_cotizacion("USD".to_string(), ars("1"));
+        assert_eq!(sistema.cripto_mas_volumen_compras(&Periodo::todo()), Ok(Some("ETH".to_string())));
+    }
+
+    #[test]
+    fn test_cripto_mas_volumen_compras_sin_cotizacion() {
+        let reloj = Rc::new(RelojManual::new(FechaHora::new(Fecha::new(4, 3, 2024), 10, 0, 0)));
+        let mut sistema = XYZ::new_con_reloj("test_cripto_mas_volumen_compras_sin_cotizacion", Rc::new(AlmacenamientoMemoria::new()), reloj.clone());
         sistema.crear_usuario( "Jack", "Nicholson", "jack@example.com", "43211234", true);
+        assert!(sistema.crear_transaccion(TipoTransaccion::CompraCripto { monto: usd("1"), criptomoneda: "ETH".to_string(), cotizacion: usd("2.5"), cantidad: cripto("0.4", "ETH"), comision: usd("0") }, "43211234").is_ok());
+
+        // Una cotizacion posterior a la compra no sirve para valuarla, la compra no se deja de contar
+        reloj.avanzar_segundos(3600);
+        sistema.add_cotizacion("USD".to_string(), ars("1000"));
+        let error = ErrorCotizacion::SinCotizacion { base: "USD".to_string(), cotizada: "ARS".to_string() };
+        assert_eq!(sistema.cripto_mas_volumen_compras(&Periodo::todo()), Err(error.clone()));
+        assert_eq!(sistema.estadisticas_por_intervalo(&Periodo::todo(), Intervalo::Dia), Err(error));
+    }
+
+    #[test]
+    fn test_estadisticas_por_periodo() {
+        // El 4/3/2024 es lunes
+        let reloj = Rc::new(RelojManual::new(FechaHora::new(Fecha::new(4, 3, 2024), 10, 0, 0)));
+        let mut sistema = XYZ::new_con_reloj("test_estadisticas_por_periodo", Rc::new(AlmacenamientoMemoria::new()), reloj.clone());
+        sistema.crear_usuario("Jack", "Nicholson", "jack@example.com", "43211234", true);
+        assert!(sistema.cripto_mas_compras(&Periodo::todo()).is_none());
+        assert!(sistema.estadisticas_por_intervalo(&Periodo::todo(), Intervalo::Dia).unwrap().is_empty());
+
+        let compra = |criptomoneda: &str, cantidad: &str, precio: &str| TipoTransaccion::CompraCripto {
+            monto: cripto(cantidad, criptomoneda).valuar(&ars(precio), Redondeo::Truncar).unwrap(),
+            criptomoneda: criptomoneda.to_string(), cotizacion: ars(precio), cantidad: cripto(cantidad, criptomoneda), comision: ars("0")
+        };
+        let venta = |criptomoneda: &str, cantidad: &str, precio: &str| TipoTransaccion::VentaCripto {
+            monto: cripto(cantidad, criptomoneda), criptomoneda: criptomoneda.to_string(), cotizacion: ars(precio), comision: ars("0")
+        };
+        assert!(sistema.crear_transaccion(compra("BTC", "0.01", "50000"), "43211234").is_ok());
+        assert!(sistema.crear_transaccion(venta("ETH", "0.1", "2500"), "43211234").is_ok());
+        reloj.avanzar_segundos(2 * 24 * 3600);
+        assert!(sistema.crear_transaccion(compra("ETH", "0.1", "2600"), "43211234").is_ok());
+        assert!(sistema.crear_transaccion(compra("ETH", "0.2", "2400"), "43211234").is_ok());
+        assert!(sistema.crear_transaccion(venta("BTC", "0.01", "52000"), "43211234").is_ok());
+        sistema.add_cotizacion("USD".to_string(), ars("1000"));
+        assert!(sistema.crear_transaccion(TipoTransaccion::CompraCripto { monto: usd("2"), criptomoneda: "ETH".to_string(), cotizacion: usd("2"), cantidad: cripto("1", "ETH"), comision: usd("0") }, "43211234").is_ok());
+        reloj.avanzar_segundos(6 * 24 * 3600);
+        assert!(sistema.crear_transaccion(compra("BTC", "0.01", "51000"), "43211234").is_ok());
+        reloj.avanzar_segundos(21 * 24 * 3600);
+        assert!(sistema.crear_transaccion(compra("ETH", "0.1", "2700"), "43211234").is_ok());
+
+        assert_eq!(sistema.cripto_mas_compras(&Periodo::entre(Fecha::new(4, 3, 2024), Fecha::new(6, 3, 2024))).as_deref(), Some("ETH"));
+        assert_eq!(sistema.cripto_mas_compras(&Periodo::entre(Fecha::new(10, 3, 2024), Fecha::new(31, 3, 2024))).as_deref(), Some("BTC"));
+        assert!(sistema.cripto_mas_ventas(&Periodo::entre(Fecha::new(1, 1, 2024), Fecha::new(2, 1, 2024))).is_none());
+        // En un empate gana la de menor prefijo
+        assert_eq!(sistema.cripto_mas_ventas(&Periodo::todo()).as_deref(), Some("BTC"));
+        assert_eq!(sistema.cripto_mas_volumen_venta(&Periodo { desde: None, hasta: Some(Fecha::new(5, 3, 2024)) }).as_deref(), Some("ETH"));
+
+        let semanas = sistema.estadisticas_por_intervalo(&Periodo::todo(), Intervalo::Semana).unwrap();
+        let inicios: Vec<Fecha> = semanas.iter().map(|e| e.inicio.clone()).collect();
+        assert_eq!(inicios, vec![Fecha::new(4, 3, 2024), Fecha::new(11, 3, 2024), Fecha::new(1, 4, 2024)]);
+        assert_eq!(semanas[1], EstadisticasIntervalo {
+            inicio: Fecha::new(11, 3, 2024),
+            cripto_mas_compras: Some("BTC".to_string()),
+            cripto_mas_ventas: None,
+            cripto_mas_volumen_compras: Some("BTC".to_string()),
+            cripto_mas_volumen_venta: None,
+        });
+        let meses = sistema.estadisticas_por_intervalo(&Periodo::todo(), Intervalo::Mes).unwrap();
+        assert_eq!(meses.len(), 2);
+        assert_eq!(meses[0].inicio, Fecha::new(1, 3, 2024));
+        assert_eq!(meses[1].cripto_mas_compras.as_deref(), Some("ETH"));
+        let dias = sistema.estadisticas_por_intervalo(&Periodo::entre(Fecha::new(4, 3, 2024), Fecha::new(4, 3, 2024)), Intervalo::Dia).unwrap();
+        assert_eq!(dias.len(), 1);
+        assert_eq!(dias[0].cripto_mas_volumen_venta.as_deref(), Some("ETH"));
+
+        // La compra cotizada en dolares no entra en las velas en pesos
+        let velas = sistema.velas("ETH", &Moneda::ars(), &Periodo::todo(), Intervalo::Semana).unwrap();
+        assert_eq!(velas, vec![
+            Vela { inicio: Fecha::new(4, 3, 2024), apertura: ars("2500"), maximo: ars("2600"), minimo: ars("2400"), cierre: ars("2400"), volumen: cripto("0.4", "ETH"), operaciones: 3 },
+            Vela { inicio: Fecha::new(1, 4, 2024), apertura: ars("2700"), maximo: ars("2700"), minimo: ars("2700"), cierre: ars("2700"), volumen: cripto("0.1", "ETH"), operaciones: 1 },
+        ]);
+        let velas = sistema.velas("BTC", &Moneda::ars(), &Periodo::todo(), Intervalo::Mes).unwrap();
+        assert_eq!(velas.len(), 1);
+        assert_eq!((velas[0].apertura.clone(), velas[0].maximo.clone(), velas[0].cierre.clone()), (ars("50000"), ars("52000"), ars("51000")));
+        assert_eq!(sistema.velas("ETH", &Moneda::usd(), &Periodo::todo(), Intervalo::Dia).unwrap().len(), 1);
+        assert!(sistema.velas("SOL", &Moneda::ars(), &Periodo::todo(), Intervalo::Dia).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_comisiones_compra_venta() {
+        let mut sistema = XYZ::new("test_comisiones_compra_venta", Rc::new(AlmacenamientoMemoria::new()));
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", true);
+        sistema.set_comisiones(Comisiones::new().con_operacion(Porcentaje::new(1)).con_spread(Porcentaje::new(2)));
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
+        assert!(sistema.ingresar_dinero("87654321", ars("10000")).is_ok());
+
+        // Se compra a 51000 con lo que queda despues de la comision de 10,20
+        assert!(sistema.comprar_cripto("87654321", ars("1020"), "BTC").is_ok());
+        assert_eq!(sistema.get_balance("87654321", "ARS"), ars("8980"));
+        assert_eq!(sistema.get_balance("87654321", "BTC"), cripto("0.0198", "BTC"));
+        assert_eq!(sistema.get_comisiones_cobradas("ARS"), ars("10.20"));
+        assert!(matches!(&sistema.transacciones[1].tipo, TipoTransaccion::CompraCripto { cotizacion, comision, .. }
+            if *cotizacion == ars("51000") && *comision == ars("10.20")));
+
+        // Se vende a 49000, de los 970,20 obtenidos la comision es 9,702 y se redondea para arriba
+        assert!(sistema.vender_cripto("87654321", cripto("0.0198", "BTC"), "BTC").is_ok());
+        assert_eq!(sistema.get_balance("87654321", "ARS"), ars("9940.49"));
+        assert_eq!(sistema.get_comisiones_cobradas("ARS"), ars("19.91"));
+        assert!(matches!(&sistema.transacciones[2].tipo, TipoTransaccion::VentaCripto { cotizacion, comision, .. }
+            if *cotizacion == ars("49000") && *comision == ars("9.71")));
+
+        // Lo cobrado por la casa se conserva al volver a cargar
+        let sistema = XYZ::new("test_comisiones_compra_venta", sistema.almacenamiento.clone());
+        assert_eq!(sistema.get_comisiones_cobradas("ARS"), ars("19.91"));
+    }
+
+    #[test]
+    fn test_comision_red() {
+        let mut sistema = XYZ::new("test_comision_red", Rc::new(AlmacenamientoMemoria::new()));
+        sistema.crear_usuario("Bob", "Smith", "bob@example.com", "44556677", true);
+        sistema.add_criptomoneda(CriptoMoneda {
+            nombre: "Bitcoin".to_string(),
+            prefijo: "BTC".to_string(),
+            blockchains: vec![BlockChain { nombre: "Bitcoin".to_string(), prefijo: "BTC".to_string() }]
+        });
+        sistema.set_comisiones(Comisiones::new().con_comision_red("Bitcoin", cripto("0.0005", "BTC")));
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
+        assert!(sistema.add_balance("44556677", "BTC", cripto("0.02", "BTC")).is_ok());
+
+        assert!(sistema.retirar_cripto("44556677", cripto("0.01", "BTC"), "BTC", "Bitcoin").is_ok());
+        assert_eq!(sistema.get_balance("44556677", "BTC"), cripto("0.0095", "BTC"));
+        assert_eq!(sistema.get_comisiones_cobradas("BTC"), cripto("0.0005", "BTC"));
+        assert!(matches!(&sistema.transacciones[0].tipo, TipoTransaccion::RetiroCripto { comision, .. } if *comision == cripto("0.0005", "BTC")));
+
+        // Alcanza para el monto pero no para la comision
+        assert!(sistema.retirar_cripto("44556677", cripto("0.0095", "BTC"), "BTC", "Bitcoin").is_err());
+        assert_eq!(sistema.get_balance("44556677", "BTC"), cripto("0.0095", "BTC"));
+        assert_eq!(sistema.transacciones.len(), 1);
+    }
+
+    #[test]
+    fn test_limite_retiro_diario() {
+        let reloj = Rc::new(RelojManual::new(FechaHora::new(Fecha::new(5, 3, 2024), 10, 0, 0)));
+        let mut sistema = XYZ::new_con_reloj("test_limite_retiro_diario", Rc::new(AlmacenamientoMemoria::new()), reloj.clone());
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", true);
+        sistema.add_criptomoneda(CriptoMoneda {
+            nombre: "Bitcoin".to_string(),
+            prefijo: "BTC".to_string(),
+            blockchains: vec![BlockChain { nombre: "Bitcoin".to_string(), prefijo: "BTC".to_string() }]
+        });
+        sistema.set_limite_retiro_diario(NivelIdentidad::Basico, ars("1000"));
+        sistema.set_limite_retiro_diario(NivelIdentidad::Completo, ars("5000"));
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
+        sistema.add_cotizacion("USD".to_string(), ars("1000"));
+        assert!(sistema.ingresar_dinero("87654321", ars("10000")).is_ok());
+        assert!(sistema.ingresar_dinero("87654321", usd("10")).is_ok());
+        assert!(sistema.add_balance("87654321", "BTC", cripto("1", "BTC")).is_ok());
+
+        assert!(sistema.retirar_dinero("87654321", ars("600"), MedioRetiro::MercadoPago).is_ok());
+        let error = sistema.retirar_dinero("87654321", ars("500"), MedioRetiro::MercadoPago).unwrap_err();
+        assert!(error.to_string().contains("limite diario"), "{}", error);
+        assert!(sistema.retirar_dinero("87654321", ars("400"), MedioRetiro::TransferenciaBancaria).is_ok());
+        // Los retiros de criptomonedas cuentan por su valor en pesos
+        assert!(sistema.retirar_cripto("87654321", cripto("0.00002", "BTC"), "BTC", "Bitcoin").is_err());
+
+        assert!(sistema.enviar_documentos("87654321", NivelIdentidad::Completo, "Comprobante de domicilio").is_ok());
+        assert!(sistema.aprobar_kyc("87654321", "Documentacion correcta").is_ok());
+        assert!(sistema.retirar_cripto("87654321", cripto("0.00002", "BTC"), "BTC", "Bitcoin").is_ok());
+
+        // Al otro dia se vuelve a empezar, los dolares se cuentan a la cotizacion del momento del retiro
+        reloj.avanzar_segundos(24 * 3600);
+        assert!(sistema.retirar_dinero("87654321", ars("4000"), MedioRetiro::MercadoPago).is_ok());
+        assert!(sistema.retirar_dinero("87654321", usd("1"), MedioRetiro::MercadoPago).is_ok());
+        reloj.avanzar_segundos(60);
+        sistema.add_cotizacion("USD".to_string(), ars("1"));
+        assert!(sistema.retirar_dinero("87654321", ars("0.01"), MedioRetiro::MercadoPago).is_err());
+        assert_eq!(sistema.get_balance("87654321", "ARS"), ars("5000"));
+        assert_eq!(sistema.enviar_documentos("99999999", NivelIdentidad::Completo, "").unwrap_err(), ErrorKyc::UsuarioNoEncontrado("99999999".to_string()));
+    }
+
+    #[test]
+    fn test_kyc_flujo() {
+        let reloj = Rc::new(RelojManual::new(FechaHora::new(Fecha::new(5, 3, 2024), 10, 0, 0)));
+        let mut sistema = XYZ::new_con_reloj("test_kyc_flujo", Rc::new(AlmacenamientoMemoria::new()), reloj.clone());
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", false);
+        sistema.crear_usuario("Ana", "Lopez", "ana@example.com", "12345678", true);
+        assert_eq!(sistema.estado_kyc("87654321"), Some(EstadoKyc::Pendiente));
+        assert!(!sistema.verificar_identidad("87654321"));
+        assert!(sistema.verificar_identidad("12345678"));
+        assert!(sistema.estado_kyc("99999999").is_none());
+
+        // No se puede aprobar ni rechazar sin documentos
+        assert!(matches!(sistema.aprobar_kyc("87654321", ""), Err(ErrorKyc::TransicionInvalida { desde: EstadoKyc::Pendiente, .. })));
+        assert!(sistema.rechazar_kyc("87654321", "").is_err());
+        assert!(sistema.enviar_documentos("87654321", NivelIdentidad::SinVerificar, "").is_err());
+
+        assert!(sistema.enviar_documentos("87654321", NivelIdentidad::Basico, "DNI").is_ok());
+        assert!(sistema.enviar_documentos("87654321", NivelIdentidad::Basico, "DNI").is_err());
+        assert!(sistema.rechazar_kyc("87654321", "Foto ilegible").is_ok());
+        assert_eq!(sistema.estado_kyc("87654321"), Some(EstadoKyc::Rechazado));
+        assert!(sistema.enviar_documentos("87654321", NivelIdentidad::Basico, "DNI nuevo").is_ok());
+        reloj.avanzar_segundos(3600);
+        assert!(sistema.aprobar_kyc("87654321", "Documentacion correcta").is_ok());
+        assert!(sistema.verificar_identidad("87654321"));
+        let vence = FechaHora::new(Fecha::new(5, 3, 2025), 11, 0, 0);
+        assert_eq!(sistema.estado_kyc("87654321"), Some(EstadoKyc::Aprobado { nivel: NivelIdentidad::Basico, vence }));
+        // Solo se puede volver a enviar para subir de nivel
+        assert!(sistema.enviar_documentos("87654321", NivelIdentidad::Basico, "").is_err());
+
+        // Al vencer deja de estar verificado aunque no se registre el vencimiento
+        reloj.avanzar_segundos(366 * 24 * 3600);
+        assert!(!sistema.verificar_identidad("87654321"));
+        assert_eq!(sistema.estado_kyc("87654321"), Some(EstadoKyc::Vencido { nivel: NivelIdentidad::Basico }));
+        assert_eq!(sistema.actualizar_vencimientos(), 2);
+        assert_eq!(sistema.actualizar_vencimientos(), 0);
+        assert!(sistema.enviar_documentos("87654321", NivelIdentidad::Completo, "Renovacion").is_ok());
+
+        let motivos: Vec<&str> = sistema.historial_kyc("87654321").iter().map(|c| c.motivo.as_str()).collect();
+        assert_eq!(motivos, vec!["DNI", "Foto ilegible", "DNI nuevo", "Documentacion correcta", "Vencio la verificacion de identidad", "Renovacion"]);
+        let historial = sistema.historial_kyc("87654321");
+        assert_eq!(historial[1].desde, EstadoKyc::DocumentosEnviados { nivel: NivelIdentidad::Basico });
+        assert_eq!(historial[3].fecha, FechaHora::new(Fecha::new(5, 3, 2024), 11, 0, 0));
+        assert_eq!(sistema.historial_kyc("12345678")[0].motivo, "Identidad verificada al crear el usuario");
+        assert!(sistema.historial_kyc("99999999").is_empty());
+    }
+
+    #[test]
+    fn test_limites_por_nivel() {
+        let reloj = Rc::new(RelojManual::new(FechaHora::new(Fecha::new(5, 3, 2024), 10, 0, 0)));
+        let mut sistema = XYZ::new_con_reloj("test_limites_por_nivel", Rc::new(AlmacenamientoMemoria::new()), reloj.clone());
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", false);
+        sistema.add_criptomoneda(CriptoMoneda {
+            nombre: "Bitcoin".to_string(),
+            prefijo: "BTC".to_string(),
+            blockchains: vec![BlockChain { nombre: "Bitcoin".to_string(), prefijo: "BTC".to_string() }]
+        });
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
+        sistema.set_limites(NivelIdentidad::SinVerificar, LimitesNivel { ingreso_diario: Some(ars("100")), ..LimitesNivel::bloqueado() });
+        sistema.set_limites(NivelIdentidad::Basico, LimitesNivel { ingreso_diario: Some(ars("1000")), operacion: Some(ars("500")), retiro_diario: None });
+
+        // Sin verificar puede ingresar poco y no puede operar
+        assert!(sistema.ingresar_dinero("87654321", ars("100")).is_ok());
+        assert!(sistema.ingresar_dinero("87654321", ars("1")).is_err());
+        let error = sistema.comprar_cripto("87654321", ars("10"), "BTC").unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+        assert!(sistema.ingresar_dinero("99999999", ars("1")).is_err());
+
+        assert!(sistema.enviar_documentos("87654321", NivelIdentidad::Basico, "DNI").is_ok());
+        assert!(sistema.aprobar_kyc("87654321", "Documentacion correcta").is_ok());
+        // Lo ingresado antes de aprobar cuenta para el limite diario
+        assert!(sistema.ingresar_dinero("87654321", ars("900")).is_ok());
+        let error = sistema.recibir_cripto("87654321", cripto("0.00002", "BTC"), "BTC", "Bitcoin").unwrap_err();
+        assert!(error.to_string().contains("Se supera el limite diario de ingresos"), "{}", error);
+
+        assert!(sistema.comprar_cripto("87654321", ars("600"), "BTC").is_err());
+        assert!(sistema.comprar_cripto("87654321", ars("500"), "BTC").is_ok());
+        assert!(sistema.comprar_cripto("87654321", ars("500"), "BTC").is_ok());
+        assert!(sistema.vender_cripto("87654321", cripto("0.02", "BTC"), "BTC").is_err());
+        assert!(sistema.vender_cripto("87654321", cripto("0.01", "BTC"), "BTC").is_ok());
+
+        reloj.avanzar_segundos(24 * 3600);
+        assert!(sistema.recibir_cripto("87654321", cripto("0.02", "BTC"), "BTC", "Bitcoin").is_ok());
+        assert_eq!(sistema.transacciones.len(), 6);
+    }
+
+    #[test]
+    fn test_reporte_portafolio() {
+        let reloj = Rc::new(RelojManual::new(FechaHora::new(Fecha::new(5, 3, 2024), 10, 0, 0)));
+        let mut sistema = XYZ::new_con_reloj("test_reporte_portafolio", Rc::new(AlmacenamientoMemoria::new()), reloj.clone());
+        sistema.crear_usuario("Juan", "Garcia", "juan@example.com", "87654321", true);
+        sistema.add_criptomoneda(CriptoMoneda {
+            nombre: "Ethereum".to_string(),
+            prefijo: "ETH".to_string(),
+            blockchains: vec![BlockChain { nombre: "Ethereum".to_string(), prefijo: "ETH".to_string() }]
+        });
+        sistema.add_cotizacion("USD".to_string(), ars("1000"));
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
+        sistema.add_cotizacion("ETH".to_string(), ars("2000"));
+        assert!(sistema.ingresar_dinero("87654321", ars("10000")).is_ok());
+        assert!(sistema.comprar_cripto("87654321", ars("1000"), "BTC").is_ok());
+        assert!(sistema.recibir_cripto("87654321", cripto("2", "ETH"), "ETH", "Ethereum").is_ok());
+
+        reloj.avanzar_segundos(3600);
+        sistema.add_cotizacion("BTC".to_string(), ars("100000"));
+        assert!(sistema.comprar_cripto("87654321", ars("1000"), "BTC").is_ok());
+
+        reloj.avanzar_segundos(3600);
+        sistema.add_cotizacion("BTC".to_string(), ars("80000"));
+        sistema.add_cotizacion("ETH".to_string(), ars("2500"));
+        assert!(sistema.vender_cripto("87654321", cripto("0.015", "BTC"), "BTC").is_ok());
+        assert!(sistema.retirar_cripto("87654321", cripto("0.5", "ETH"), "ETH", "Ethereum").is_ok());
+
+        let reporte = sistema.reporte_portafolio("87654321", &Moneda::ars()).unwrap();
+        assert_eq!(reporte.posiciones.len(), 2);
+        let btc = &reporte.posiciones[0];
+        assert_eq!(btc.criptomoneda, "BTC");
+        assert_eq!(btc.cantidad, cripto("0.015", "BTC"));
+        assert_eq!(btc.valor_actual, ars("1200"));
+        // FIFO: se vendio 0,015 del lote de 0,02 que costo 1000, quedan 0,005 por 250 y 0,01 por 1000
+        assert_eq!(btc.fifo, ResultadoCosteo { costo: ars("1250"), realizado: ars("450"), no_realizado: ars("-50") });
+        // Costo promedio: 2000 por 0,03, lo vendido costo 1000
+        assert_eq!(btc.promedio, ResultadoCosteo { costo: ars("1000"), realizado: ars("200"), no_realizado: ars("200") });
+
+        // Lo recibido se costea a la cotizacion del momento y el retiro no realiza ganancias
+        let eth = &reporte.posiciones[1];
+        assert_eq!(eth.cantidad, cripto("1.5", "ETH"));
+        assert_eq!(eth.fifo, ResultadoCosteo { costo: ars("3000"), realizado: ars("0"), no_realizado: ars("750") });
+        assert_eq!(eth.promedio, eth.fifo);
+
+        assert_eq!(reporte.valor_total, ars("4950"));
+        assert_eq!(reporte.total_fifo, ResultadoCosteo { costo: ars("4250"), realizado: ars("450"), no_realizado: ars("700") });
+        assert_eq!(reporte.total_promedio.realizado, ars("200"));
+
+        // En dolares los costos se convierten con la cotizacion de la fecha de cada transaccion
+        reloj.avanzar_segundos(60);
+        sistema.add_cotizacion("USD".to_string(), ars("2000"));
+        let reporte = sistema.reporte_portafolio("87654321", &Moneda::usd()).unwrap();
+        assert_eq!(reporte.posiciones[0].fifo, ResultadoCosteo { costo: usd("1.25"), realizado: usd("0.45"), no_realizado: usd("-0.65") });
+        assert_eq!(reporte.posiciones[0].valor_actual, usd("0.6"));
+    }
 
-        assert!(sistema.crear_transaccion(TipoTransaccion::CompraCripto { monto: 500.0, criptomoneda: "BTC".to_string(), cotizacion: 50000.0 }, "43211234").is_ok());
-        assert!(sistema.crear_transaccion(TipoTransaccion::CompraCripto { monto: 300.0, criptomoneda: "ETH".to_string(), cotizacion: 2500.0 }, "43211234").is_ok());
-        assert!(sistema.crear_transaccion(TipoTransaccion::CompraCripto { monto: 700.0, criptomoneda: "BTC".to_string(), cotizacion: 50000.0 }, "43211234").is_ok());
+    #[test]
+    fn test_reporte_portafolio_sin_datos() {
+        let mut sistema = sistema_con_usuarios("test_reporte_portafolio_sin_datos", Rc::new(AlmacenamientoMemoria::new()));
+        let reporte = sistema.reporte_portafolio("99999999", &Moneda::ars()).unwrap();
+        assert!(reporte.posiciones.is_empty());
+        assert_eq!(reporte.valor_total, ars("0"));
+
+        // Las ejecuciones de ordenes guardan la cantidad exacta aunque el pago se redondee
+        assert!(sistema.add_balance("11111111", "ARS", ars("1")).is_ok());
+        assert!(sistema.add_balance("22222222", "BTC", cripto("0.00000003", "BTC")).is_ok());
+        assert!(sistema.colocar_orden_limite("22222222", Lado::Venta, cripto("0.00000003", "BTC"), ars("500000")).is_ok());
+        assert!(sistema.colocar_orden_mercado("11111111", Lado::Compra, cripto("0.00000003", "BTC"), &Moneda::ars()).is_ok());
+        assert!(matches!(sistema.reporte_portafolio("11111111", &Moneda::ars()), Err(ErrorCotizacion::SinCotizacion { .. })));
+        sistema.add_cotizacion("BTC".to_string(), ars("50000"));
+        let reporte = sistema.reporte_portafolio("11111111", &Moneda::ars()).unwrap();
+        assert_eq!(reporte.posiciones[0].cantidad, sistema.get_balance("11111111", "BTC"));
+        assert_eq!(reporte.posiciones[0].fifo.costo, ars("0.01"));
+
+        // El vendedor no tenia lotes con costo, todo lo cobrado es ganancia
+        let reporte = sistema.reporte_portafolio("22222222", &Moneda::ars()).unwrap();
+        assert_eq!(reporte.posiciones[0].fifo.realizado, ars("0.01"));
+        assert_eq!(reporte.posiciones[0].cantidad, cripto("0", "BTC"));
+    }
 
-        assert_eq!(sistema.cripto_mas_volumen_compras(), "BTC");
+    #[test]
+    fn test_cargar_fixtures() {
+        for (version, almacenamiento) in cargar_fixtures("practica5/ej6") {
+            let sistema = XYZ::cargar("fixture", almacenamiento, &PoliticaCarga::estricta())
+                .unwrap_or_else(|e| panic!("{}: {}", version, e));
+            assert_eq!(sistema.transacciones.len(), 2, "{}", version);
+            assert_eq!(sistema.balances["12345678"]["BTC"], cripto("0.002", "BTC"), "{}", version);
+            assert_eq!(sistema.balances["12345678"]["ARS"], ars("900"), "{}", version);
+            assert!(matches!(&sistema.transacciones[1].tipo, TipoTransaccion::CompraCripto { monto, .. } if *monto == ars("100")), "{}", version);
+            assert_eq!(Fecha::from(sistema.transacciones[1].fecha.clone()), Fecha::new(6, 3, 2024), "{}", version);
+            assert!(matches!(&sistema.transacciones[1].tipo, TipoTransaccion::CompraCripto { cantidad, .. } if *cantidad == cripto("0.002", "BTC")), "{}", version);
+        }
     }
-}
\ No newline at end of file
+}